@@ -0,0 +1,52 @@
+//! Generated mylang sources for the benchmarks. The text depends only on
+//! the size asked for, so numbers from different commits compare.
+
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The file sizes benchmarked, in units of about six lines each.
+pub const SIZES: &[(&str, usize)] = &[("small", 10), ("medium", 200), ("huge", 2_000)];
+
+/// Files in the workspace each size is indexed as, so every workspace
+/// holds about the same amount of source.
+pub const WORKSPACE_FILES: &[(&str, usize)] = &[("small", 200), ("medium", 10), ("huge", 1)];
+
+/// A file of `units` declarations, covering functions, lambdas, pipes,
+/// lists, conditionals, blocks and comments. It has no diagnostics.
+pub fn source(units: usize) -> String {
+    let mut text = String::new();
+    for n in 0..units {
+        text.push_str(&format!(
+            "// Unit {n}.\n\
+             let scale{n} x -> x * {n} + 1;\n\
+             let items{n} = [1, 2, 3, {n}] |> map(fn x -> scale{n}(x));\n\
+             let total{n} = fold(items{n}, 0, fn acc x -> acc + x);\n\
+             let label{n} = if total{n} > 100 {{ \"big\" }} else {{ str(total{n}) }};\n\
+             print({{ let shown = label{n}; shown }});\n"
+        ));
+    }
+    text
+}
+
+/// A fresh directory holding `files` copies of a file of `units`, for the
+/// workspace index to scan.
+pub fn workspace(name: &str, units: usize, files: usize) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("mylang-lsp-bench-{name}"));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    let text = source(units);
+    for n in 0..files {
+        fs::write(directory.join(format!("file{n}.mylang")), &text).unwrap();
+    }
+    directory
+}
+
+/// Bytes of source below `directory`.
+pub fn size_of(directory: &Path) -> u64 {
+    fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().metadata().unwrap().len())
+        .sum()
+}
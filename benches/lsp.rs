@@ -0,0 +1,94 @@
+//! End-to-end benchmarks, run with `cargo bench`. Requests go through the
+//! server over in-memory pipes, as the tests do, so the numbers include
+//! framing and JSON as well as the analysis. Each runs over the generated
+//! corpus in three sizes. Huge files take a good fraction of a second, so
+//! every group takes the fewest samples criterion allows.
+
+#[path = "../tests/support/mod.rs"]
+mod support;
+
+mod corpus;
+
+use std::fs;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use mylang_lsp::analysis::config::ServerConfig;
+use mylang_lsp::analysis::index::WorkspaceIndex;
+use serde_json::json;
+use support::Client;
+
+const URI: &str = "file:///bench.mylang";
+
+/// From sending `didOpen` to receiving the document's diagnostics, which
+/// takes a full analysis since reopening drops the cached one.
+fn did_open_to_diagnostics(c: &mut Criterion) {
+    let mut group = c.benchmark_group("didOpen to diagnostics");
+    group.sample_size(10);
+    for (name, units) in corpus::SIZES {
+        let text = corpus::source(*units);
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            let mut client = Client::initialized(json!({}));
+            b.iter(|| {
+                client.open(URI, &text);
+                let diagnostics = client.diagnostics(URI);
+                assert!(diagnostics.is_empty(), "{diagnostics:?}");
+            });
+        });
+    }
+    group.finish();
+}
+
+/// A completion request at the end of an open document, once its analysis
+/// is cached.
+fn completion(c: &mut Criterion) {
+    let mut group = c.benchmark_group("completion");
+    group.sample_size(10);
+    for (name, units) in corpus::SIZES {
+        let text = corpus::source(*units);
+        let line = text.lines().count();
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            let mut client = Client::initialized(json!({}));
+            client.open(URI, &text);
+            client.diagnostics(URI);
+            let params = json!({
+                "textDocument": { "uri": URI },
+                "position": { "line": line, "character": 0 }
+            });
+            b.iter(|| client.request("textDocument/completion", params.clone()));
+        });
+    }
+    group.finish();
+}
+
+/// Scanning a workspace and indexing every file in it, in bytes of source
+/// per second.
+fn workspace_indexing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("workspace indexing");
+    group.sample_size(10);
+    let config = ServerConfig::default();
+    for ((name, units), (_, files)) in corpus::SIZES.iter().zip(corpus::WORKSPACE_FILES) {
+        let directory = corpus::workspace(name, *units, *files);
+        group.throughput(Throughput::Bytes(corpus::size_of(&directory)));
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| {
+                let mut index = WorkspaceIndex::default();
+                index.scan(&directory, &config);
+                while index.is_scanning() {
+                    index.scan_next();
+                }
+                index
+            });
+        });
+        let _ = fs::remove_dir_all(&directory);
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    did_open_to_diagnostics,
+    completion,
+    workspace_indexing
+);
+criterion_main!(benches);
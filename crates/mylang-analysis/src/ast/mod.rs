@@ -0,0 +1,265 @@
+use super::lexer::Span;
+
+pub struct Program {
+    pub statements: Vec<Stmt>,
+    pub span: Span,
+}
+
+pub enum Stmt {
+    Let(LetStmt),
+    Expr(ExprStmt),
+}
+
+/// `let name params -> body;` defines a function, `let name = value;` binds
+/// a value.
+pub struct LetStmt {
+    pub name: Ident,
+    pub params: Vec<Ident>,
+    pub kind: LetKind,
+    pub value: Option<Expr>,
+    /// From `let` through the closing `;`, or the last token parsed.
+    pub span: Span,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LetKind {
+    Function,
+    Value,
+}
+
+pub struct ExprStmt {
+    pub expr: Expr,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug)]
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
+}
+
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+pub enum ExprKind {
+    Number(String),
+    String(String),
+    Bool(bool),
+    Name(Ident),
+    Unary {
+        op: UnaryOp,
+        operand: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    Call {
+        callee: Box<Expr>,
+        args: Vec<Expr>,
+    },
+    Field {
+        receiver: Box<Expr>,
+        name: Ident,
+    },
+    List(Vec<Expr>),
+    Set(Vec<Expr>),
+    Block(Block),
+    If {
+        condition: Box<Expr>,
+        then_branch: Block,
+        else_branch: Option<Box<Expr>>,
+    },
+    /// `fn params -> body`
+    Lambda {
+        params: Vec<Ident>,
+        body: Box<Expr>,
+    },
+    /// Stands in for an expression that failed to parse.
+    Error,
+}
+
+pub struct Block {
+    pub statements: Vec<Stmt>,
+    pub tail: Option<Box<Expr>>,
+    pub span: Span,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum UnaryOp {
+    Negate,
+    Not,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Pipe,
+}
+
+impl Stmt {
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Let(stmt) => stmt.span,
+            Stmt::Expr(stmt) => stmt.span,
+        }
+    }
+
+    /// The value of a `let`, or the expression of an expression statement.
+    pub fn expr(&self) -> Option<&Expr> {
+        match self {
+            Stmt::Let(stmt) => stmt.value.as_ref(),
+            Stmt::Expr(stmt) => Some(&stmt.expr),
+        }
+    }
+}
+
+impl Block {
+    /// The expressions of the block's statements, then its tail.
+    pub fn exprs(&self) -> impl Iterator<Item = &Expr> {
+        self.statements
+            .iter()
+            .filter_map(Stmt::expr)
+            .chain(self.tail.as_deref())
+    }
+}
+
+impl Expr {
+    /// The expressions directly inside this one, in source order.
+    pub fn children(&self) -> Vec<&Expr> {
+        match &self.kind {
+            ExprKind::Unary { operand, .. } => vec![operand],
+            ExprKind::Binary { left, right, .. } => vec![left, right],
+            ExprKind::Call { callee, args } => std::iter::once(&**callee).chain(args).collect(),
+            ExprKind::Field { receiver, .. } => vec![receiver],
+            ExprKind::List(items) | ExprKind::Set(items) => items.iter().collect(),
+            ExprKind::Block(block) => block.exprs().collect(),
+            ExprKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => std::iter::once(&**condition)
+                .chain(then_branch.exprs())
+                .chain(else_branch.as_deref())
+                .collect(),
+            ExprKind::Lambda { body, .. } => vec![body],
+            ExprKind::Number(_)
+            | ExprKind::String(_)
+            | ExprKind::Bool(_)
+            | ExprKind::Name(_)
+            | ExprKind::Error => Vec::new(),
+        }
+    }
+}
+
+impl Program {
+    /// An indented outline of the tree, one node per line with its byte
+    /// span, for debugging the parser.
+    pub fn dump(&self) -> String {
+        let mut out = String::new();
+        line(&mut out, 0, "Program", self.span);
+        for stmt in &self.statements {
+            dump_stmt(stmt, 1, &mut out);
+        }
+        out
+    }
+}
+
+fn line(out: &mut String, depth: usize, label: &str, span: Span) {
+    out.push_str(&format!(
+        "{}{} @{}..{}\n",
+        "  ".repeat(depth),
+        label,
+        span.start,
+        span.end
+    ));
+}
+
+fn dump_stmt(stmt: &Stmt, depth: usize, out: &mut String) {
+    match stmt {
+        Stmt::Let(stmt) => {
+            let label = match stmt.kind {
+                LetKind::Value => format!("Let {}", stmt.name.name),
+                LetKind::Function => {
+                    let params: Vec<&str> = stmt
+                        .params
+                        .iter()
+                        .map(|param| param.name.as_str())
+                        .collect();
+                    format!("LetFunction {}({})", stmt.name.name, params.join(", "))
+                }
+            };
+            line(out, depth, &label, stmt.span);
+            if let Some(value) = &stmt.value {
+                dump_expr(value, depth + 1, out);
+            }
+        }
+        Stmt::Expr(stmt) => {
+            line(out, depth, "ExprStmt", stmt.span);
+            dump_expr(&stmt.expr, depth + 1, out);
+        }
+    }
+}
+
+fn dump_block(block: &Block, depth: usize, out: &mut String) {
+    line(out, depth, "Block", block.span);
+    for stmt in &block.statements {
+        dump_stmt(stmt, depth + 1, out);
+    }
+    if let Some(tail) = &block.tail {
+        dump_expr(tail, depth + 1, out);
+    }
+}
+
+fn dump_expr(expr: &Expr, depth: usize, out: &mut String) {
+    let label = match &expr.kind {
+        ExprKind::Number(value) => format!("Number {value}"),
+        ExprKind::String(value) => format!("String {value:?}"),
+        ExprKind::Bool(value) => format!("Bool {value}"),
+        ExprKind::Name(name) => format!("Name {}", name.name),
+        ExprKind::Unary { op, .. } => format!("Unary {op:?}"),
+        ExprKind::Binary { op, .. } => format!("Binary {op:?}"),
+        ExprKind::Call { .. } => "Call".to_string(),
+        ExprKind::Field { name, .. } => format!("Field {}", name.name),
+        ExprKind::List(_) => "List".to_string(),
+        ExprKind::Set(_) => "Set".to_string(),
+        ExprKind::Block(block) => return dump_block(block, depth, out),
+        ExprKind::If { .. } => "If".to_string(),
+        ExprKind::Lambda { params, .. } => {
+            let params: Vec<&str> = params.iter().map(|param| param.name.as_str()).collect();
+            format!("Lambda({})", params.join(", "))
+        }
+        ExprKind::Error => "Error".to_string(),
+    };
+    line(out, depth, &label, expr.span);
+
+    if let ExprKind::If {
+        condition,
+        then_branch,
+        else_branch,
+    } = &expr.kind
+    {
+        dump_expr(condition, depth + 1, out);
+        dump_block(then_branch, depth + 1, out);
+        if let Some(else_branch) = else_branch {
+            dump_expr(else_branch, depth + 1, out);
+        }
+        return;
+    }
+    for child in expr.children() {
+        dump_expr(child, depth + 1, out);
+    }
+}
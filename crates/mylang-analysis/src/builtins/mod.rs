@@ -0,0 +1,245 @@
+use std::fmt;
+
+use serde::Deserialize;
+
+use super::types::Type;
+
+/// A function provided by the language runtime.
+pub struct Builtin {
+    pub name: &'static str,
+    pub params: &'static [&'static str],
+    /// What the first parameter accepts, which decides whether a value can
+    /// be piped into the builtin. `Unknown` accepts anything.
+    pub input: Type,
+    pub returns: Type,
+    pub doc: &'static str,
+}
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "print",
+        params: &["value"],
+        input: Type::Unknown,
+        returns: Type::Unknown,
+        doc: "Writes `value` to standard output and returns it.",
+    },
+    Builtin {
+        name: "str",
+        params: &["value"],
+        input: Type::Unknown,
+        returns: Type::String,
+        doc: "Converts `value` to its string representation.",
+    },
+    Builtin {
+        name: "len",
+        params: &["items"],
+        input: Type::Unknown,
+        returns: Type::Number,
+        doc: "The number of elements in a list or set, or characters in a string.",
+    },
+    Builtin {
+        name: "range",
+        params: &["start", "end"],
+        input: Type::Number,
+        returns: Type::List,
+        doc: "The list of numbers from `start` up to, but not including, `end`, at most a million of them.",
+    },
+    Builtin {
+        name: "map",
+        params: &["items", "f"],
+        input: Type::List,
+        returns: Type::List,
+        doc: "Applies `f` to every element of `items`.",
+    },
+    Builtin {
+        name: "filter",
+        params: &["items", "predicate"],
+        input: Type::List,
+        returns: Type::List,
+        doc: "The elements of `items` for which `predicate` returns true.",
+    },
+    Builtin {
+        name: "fold",
+        params: &["items", "initial", "f"],
+        input: Type::List,
+        returns: Type::Unknown,
+        doc: "Combines the elements of `items` from the left, starting with `initial`.",
+    },
+    Builtin {
+        name: "sum",
+        params: &["items"],
+        input: Type::List,
+        returns: Type::Number,
+        doc: "The sum of a list of numbers.",
+    },
+    Builtin {
+        name: "abs",
+        params: &["x"],
+        input: Type::Number,
+        returns: Type::Number,
+        doc: "The absolute value of `x`.",
+    },
+    Builtin {
+        name: "sqrt",
+        params: &["x"],
+        input: Type::Number,
+        returns: Type::Number,
+        doc: "The square root of `x`.",
+    },
+];
+
+/// A global the host environment defines, such as a function an embedding
+/// application injects at runtime. Declared in the configuration as a
+/// signature: `name`, `name: type` or `name(param: type, ...) -> type`,
+/// where every type is optional.
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+#[serde(try_from = "String")]
+#[non_exhaustive]
+pub struct Global {
+    pub name: String,
+    /// Parameter names and types, when the global is a function with a
+    /// known signature.
+    pub params: Option<Vec<(String, Type)>>,
+    /// The value's type, or what calling the function returns.
+    pub ty: Type,
+}
+
+impl Global {
+    pub fn parse(signature: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid signature '{signature}'");
+        let identifier = |name: &str| {
+            let name = name.trim();
+            let valid = name
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+            valid.then(|| name.to_string()).ok_or_else(invalid)
+        };
+        let ty = |name: Option<&str>| match name {
+            Some(name) => Type::from_name(name.trim())
+                .ok_or_else(|| format!("unknown type '{}' in '{signature}'", name.trim())),
+            None => Ok(Type::Unknown),
+        };
+
+        let Some((name, rest)) = signature.split_once('(') else {
+            let (name, value_type) = match signature.split_once(':') {
+                Some((name, value_type)) => (name, Some(value_type)),
+                None => (signature, None),
+            };
+            return Ok(Self {
+                name: identifier(name)?,
+                params: None,
+                ty: ty(value_type)?,
+            });
+        };
+        let (params, returns) = rest.split_once(')').ok_or_else(invalid)?;
+        let returns = match returns.trim() {
+            "" => None,
+            returns => Some(returns.strip_prefix("->").ok_or_else(invalid)?),
+        };
+        let params = params
+            .split(',')
+            .filter(|param| !param.trim().is_empty())
+            .map(|param| {
+                let (name, param_type) = match param.split_once(':') {
+                    Some((name, param_type)) => (name, Some(param_type)),
+                    None => (param, None),
+                };
+                Ok((identifier(name)?, ty(param_type)?))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self {
+            name: identifier(name)?,
+            params: Some(params),
+            ty: ty(returns)?,
+        })
+    }
+
+    /// What the first parameter accepts, as for [`Builtin::input`].
+    pub fn input(&self) -> Type {
+        self.params
+            .as_ref()
+            .and_then(|params| params.first())
+            .map_or(Type::Unknown, |(_, ty)| *ty)
+    }
+}
+
+impl TryFrom<String> for Global {
+    type Error = String;
+
+    fn try_from(signature: String) -> Result<Self, String> {
+        Self::parse(&signature)
+    }
+}
+
+/// Writes the signature back out, leaving out unknown types.
+impl fmt::Display for Global {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(params) = &self.params {
+            let params: Vec<String> = params
+                .iter()
+                .map(|(name, ty)| match ty {
+                    Type::Unknown => name.clone(),
+                    ty => format!("{name}: {}", ty.name()),
+                })
+                .collect();
+            write!(f, "({})", params.join(", "))?;
+        }
+        match (&self.params, self.ty) {
+            (_, Type::Unknown) => Ok(()),
+            (Some(_), ty) => write!(f, " -> {}", ty.name()),
+            (None, ty) => write!(f, ": {}", ty.name()),
+        }
+    }
+}
+
+/// A property readable with `.` on values of the `owner` type.
+pub struct Member {
+    pub owner: Type,
+    pub name: &'static str,
+    pub ty: Type,
+    pub doc: &'static str,
+}
+
+pub const MEMBERS: &[Member] = &[
+    Member {
+        owner: Type::String,
+        name: "length",
+        ty: Type::Number,
+        doc: "The number of characters in the string.",
+    },
+    Member {
+        owner: Type::List,
+        name: "length",
+        ty: Type::Number,
+        doc: "The number of elements in the list.",
+    },
+    Member {
+        owner: Type::List,
+        name: "first",
+        ty: Type::Unknown,
+        doc: "The first element of the list.",
+    },
+    Member {
+        owner: Type::List,
+        name: "last",
+        ty: Type::Unknown,
+        doc: "The last element of the list.",
+    },
+    Member {
+        owner: Type::Set,
+        name: "size",
+        ty: Type::Number,
+        doc: "The number of elements in the set.",
+    },
+];
+
+pub fn members(owner: Type) -> impl Iterator<Item = &'static Member> {
+    MEMBERS.iter().filter(move |member| member.owner == owner)
+}
+
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
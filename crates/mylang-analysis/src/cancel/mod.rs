@@ -0,0 +1,74 @@
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Steps the lexer, parser and resolver take between looks at their token.
+pub const CHECK_INTERVAL: u32 = 256;
+
+/// Tells an analysis running on one thread that another no longer wants
+/// its result. Clones share the flag.
+#[derive(Clone, Default, Debug)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// The analysis stopped early because its token was cancelled, and what it
+/// had built so far was thrown away.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the analysis was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Looks at a token every [`CHECK_INTERVAL`] steps of a loop, and keeps
+/// saying so once it has seen it cancelled.
+pub struct Checkpoint<'a> {
+    token: &'a CancellationToken,
+    steps: u32,
+    cancelled: bool,
+}
+
+impl<'a> Checkpoint<'a> {
+    pub fn new(token: &'a CancellationToken) -> Self {
+        Self {
+            token,
+            steps: 0,
+            cancelled: false,
+        }
+    }
+
+    /// Counts a step, and says whether the loop should stop.
+    pub fn step(&mut self) -> bool {
+        self.steps += 1;
+        if self.steps == CHECK_INTERVAL {
+            self.steps = 0;
+            self.cancelled = self.cancelled || self.token.is_cancelled();
+        }
+        self.cancelled
+    }
+
+    pub fn result(&self) -> Result<(), Cancelled> {
+        if self.cancelled {
+            Err(Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use serde::{Deserialize, Serialize, Serializer};
+
+use super::lexer::{Span, Token};
+use super::line_index::LineIndex;
+use super::messages::{self, Locale};
+
+/// Stable identifiers for each kind of problem, used to match diagnostics
+/// across runs and edits.
+pub mod codes {
+    pub const UNKNOWN_IDENTIFIER: &str = "unknown-identifier";
+    pub const DUPLICATE_IDENTIFIER: &str = "duplicate-identifier";
+    pub const EXPECTED_IDENTIFIER: &str = "expected-identifier";
+    pub const UNEXPECTED_TOKEN: &str = "unexpected-token";
+    pub const UNEXPECTED_END: &str = "unexpected-end";
+    pub const EXPECTED_TOKEN: &str = "expected-token";
+    pub const EXPECTED_EXPRESSION: &str = "expected-expression";
+    pub const MISSING_SEMICOLON: &str = "missing-semicolon";
+    pub const UNUSED_VARIABLE: &str = "unused-variable";
+    pub const WRONG_ARGUMENT_COUNT: &str = "wrong-argument-count";
+}
+
+/// The broad class a diagnostic belongs to, reported through `source` so
+/// editors can filter on it.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Category {
+    Syntax,
+    Names,
+    Types,
+    Lint,
+}
+
+impl Category {
+    pub fn of(code: &str) -> Self {
+        match code {
+            codes::UNKNOWN_IDENTIFIER | codes::DUPLICATE_IDENTIFIER => Category::Names,
+            codes::UNUSED_VARIABLE => Category::Lint,
+            codes::WRONG_ARGUMENT_COUNT => Category::Types,
+            _ => Category::Syntax,
+        }
+    }
+
+    pub fn source(self) -> &'static str {
+        match self {
+            Category::Syntax => "mylang(syntax)",
+            Category::Names => "mylang(names)",
+            Category::Types => "mylang(types)",
+            Category::Lint => "mylang(lint)",
+        }
+    }
+}
+
+/// Source for problems with the protocol messages themselves rather than
+/// with any document.
+pub const PROTOCOL_SOURCE: &str = "mylang";
+
+#[derive(Serialize, Clone)]
+pub struct Diagnostic {
+    pub range: Range,
+    pub severity: DiagnosticSeverity,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    pub message: Option<String>,
+    pub source: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<DiagnosticData>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<DiagnosticTag>,
+    /// Message parameters, kept so the message can be rendered again in
+    /// another locale.
+    #[serde(skip)]
+    pub args: Vec<(String, String)>,
+}
+
+/// Machine-readable payload carried in the LSP `data` field. Clients send it
+/// back unchanged with code action requests, so fixes can be built without
+/// analyzing the document again.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct DiagnosticData {
+    pub fixes: Vec<Fix>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Fix {
+    pub title: String,
+    pub edits: Vec<TextEdit>,
+    /// Safe to apply without asking, such as an obvious typo correction.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub preferred: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TextEdit {
+    pub range: Range,
+    #[serde(rename = "newText")]
+    pub new_text: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Position {
+    pub line: u32,
+    pub character: u32,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+#[repr(u8)]
+#[allow(dead_code)]
+pub enum DiagnosticSeverity {
+    Error = 1,
+    Warning = 2,
+    Information = 3,
+    Hint = 4,
+}
+
+impl DiagnosticSeverity {
+    /// The lowercase name used in command line output.
+    pub fn label(self) -> &'static str {
+        match self {
+            DiagnosticSeverity::Error => "error",
+            DiagnosticSeverity::Warning => "warning",
+            DiagnosticSeverity::Information => "info",
+            DiagnosticSeverity::Hint => "hint",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+#[repr(u8)]
+#[allow(dead_code)]
+pub enum DiagnosticTag {
+    /// Rendered faded out, for code that can be removed.
+    Unnecessary = 1,
+    Deprecated = 2,
+}
+
+impl Serialize for DiagnosticTag {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(*self as u8)
+    }
+}
+
+/// The protocol expects severities as their numeric value.
+impl Serialize for DiagnosticSeverity {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(*self as u8)
+    }
+}
+
+impl Diagnostic {
+    pub fn generate(index: &LineIndex, token: &Token, code: &str, args: &[(&str, &str)]) -> Self {
+        Self::spanning(index, token, token, code, args)
+    }
+
+    /// A diagnostic underlining everything from `first` through `last`, which
+    /// may span several tokens and lines, such as a whole statement.
+    pub fn spanning(
+        index: &LineIndex,
+        first: &Token,
+        last: &Token,
+        code: &str,
+        args: &[(&str, &str)],
+    ) -> Self {
+        Self::at(index, first.span.to(last.span), code, args)
+    }
+
+    /// A diagnostic underlining an arbitrary byte span, for problems found
+    /// after parsing when the tokens are no longer at hand.
+    pub fn at(index: &LineIndex, span: Span, code: &str, args: &[(&str, &str)]) -> Self {
+        Self {
+            range: index.range(span),
+            severity: DiagnosticSeverity::Error,
+            code: Some(code.to_string()),
+            message: Some(messages::render(Locale::English, code, args)),
+            source: Some(Category::of(code).source().to_string()),
+            data: None,
+            tags: Vec::new(),
+            args: args
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Re-renders the message from the catalog for `locale`.
+    pub fn localize(&mut self, locale: Locale) {
+        if let Some(code) = &self.code {
+            self.message = Some(messages::render(locale, code, &self.args));
+        }
+    }
+
+    /// Attaches a fix that replaces `range` with `new_text`.
+    pub fn with_fix(mut self, title: &str, range: Range, new_text: &str) -> Self {
+        let data = self.data.get_or_insert_with(DiagnosticData::default);
+        data.fixes.push(Fix {
+            title: title.to_string(),
+            edits: vec![TextEdit {
+                range,
+                new_text: new_text.to_string(),
+            }],
+            preferred: false,
+        });
+        self
+    }
+
+    /// Like `with_fix`, for the fix editors may apply automatically.
+    pub fn with_preferred_fix(self, title: &str, range: Range, new_text: &str) -> Self {
+        let mut diagnostic = self.with_fix(title, range, new_text);
+        if let Some(fix) = diagnostic
+            .data
+            .as_mut()
+            .and_then(|data| data.fixes.last_mut())
+        {
+            fix.preferred = true;
+        }
+        diagnostic
+    }
+}
+
+impl Default for Range {
+    fn default() -> Self {
+        let pos = Position {
+            line: 0,
+            character: 0,
+        };
+
+        Self {
+            start: pos.clone(),
+            end: pos,
+        }
+    }
+}
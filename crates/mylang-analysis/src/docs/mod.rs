@@ -0,0 +1,91 @@
+/// The `///` comment lines directly above the line containing `offset`,
+/// with the markers stripped.
+pub fn doc_comment(text: &str, offset: usize) -> Option<String> {
+    let line_start = text[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let mut lines: Vec<&str> = text[..line_start]
+        .lines()
+        .rev()
+        .map_while(|line| line.trim().strip_prefix("///"))
+        .map(|line| line.strip_prefix(' ').unwrap_or(line))
+        .collect();
+
+    if lines.is_empty() {
+        return None;
+    }
+    lines.reverse();
+    Some(lines.join("\n"))
+}
+
+/// Starts a doc comment line to mark the documented symbol as deprecated,
+/// optionally followed by what to use instead.
+pub const DEPRECATED_TAG: &str = "@deprecated";
+
+pub fn is_deprecated(doc: &str) -> bool {
+    doc.lines()
+        .any(|line| line.trim_start().starts_with(DEPRECATED_TAG))
+}
+
+/// Explanation and example for a keyword or operator.
+pub struct ConstructDoc {
+    pub lexeme: &'static str,
+    pub summary: &'static str,
+    pub example: &'static str,
+}
+
+pub const CONSTRUCTS: &[ConstructDoc] = &[
+    ConstructDoc {
+        lexeme: "let",
+        summary: "Binds a name. `let name = value;` binds a value, `let name params -> body;` \
+                  defines a function. Value bindings are visible after the statement, functions \
+                  also inside their own body.",
+        example: "let answer = 42;\nlet double x -> x * 2;",
+    },
+    ConstructDoc {
+        lexeme: "if",
+        summary: "Evaluates to the first block whose condition is true. Without an `else`, the \
+                  result is empty when the condition is false.",
+        example: "let sign = if n < 0 { -1 } else { 1 };",
+    },
+    ConstructDoc {
+        lexeme: "else",
+        summary: "The block evaluated when the preceding `if` condition is false. Chain further \
+                  conditions with `else if`.",
+        example: "if n < 0 { \"negative\" } else if n == 0 { \"zero\" } else { \"positive\" }",
+    },
+    ConstructDoc {
+        lexeme: "fn",
+        summary: "An anonymous function, written like a function definition without a name.",
+        example: "map([1, 2, 3], fn x -> x * 2)",
+    },
+    ConstructDoc {
+        lexeme: "true",
+        summary: "The boolean true value.",
+        example: "let done = true;",
+    },
+    ConstructDoc {
+        lexeme: "false",
+        summary: "The boolean false value.",
+        example: "let done = false;",
+    },
+    ConstructDoc {
+        lexeme: "|>",
+        summary: "Pipes the value on the left into the function on the right, so `x |> f` is \
+                  `f(x)`. Chains read in the order the steps run.",
+        example: "range(0, 10) |> sum |> str",
+    },
+    ConstructDoc {
+        lexeme: "->",
+        summary: "Separates a function's parameters from its body.",
+        example: "let add a b -> a + b;",
+    },
+    ConstructDoc {
+        lexeme: "^",
+        summary: "Raises the left operand to the power of the right one. Groups to the right, \
+                  so `2 ^ 3 ^ 2` is `2 ^ 9`.",
+        example: "let area = r ^ 2 * 3.14;",
+    },
+];
+
+pub fn construct(lexeme: &str) -> Option<&'static ConstructDoc> {
+    CONSTRUCTS.iter().find(|doc| doc.lexeme == lexeme)
+}
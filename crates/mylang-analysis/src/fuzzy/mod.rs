@@ -0,0 +1,121 @@
+/// Scores how well `pattern` matches `candidate` as a case-insensitive
+/// subsequence, or `None` when it does not match at all. Prefix matches
+/// rank above camel-hump matches, whose characters all start words or
+/// continue a run (`usrNm` against `userName`), which rank above scattered
+/// ones. Within a tier, word starts and runs score up and gaps score down.
+pub fn score(pattern: &str, candidate: &str) -> Option<u32> {
+    if pattern.is_empty() {
+        return Some(0);
+    }
+
+    let chars: Vec<char> = candidate.chars().collect();
+    let wanted: Vec<char> = pattern.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let mut points: i32 = 0;
+    let mut humps_only = true;
+    let mut next = 0;
+    let mut previous_match: Option<usize> = None;
+
+    for (k, &c) in wanted.iter().enumerate() {
+        let matches = |i: &usize| chars[*i].to_ascii_lowercase() == c;
+        // The rest of the pattern must still fit after whatever is chosen.
+        let rest_fits = |i: &usize| is_subsequence(&wanted[k + 1..], &chars[i + 1..]);
+        // Continue a run if possible, else jump to the next word start
+        // holding the character, else take the first occurrence.
+        let position = Some(next)
+            .filter(|i| previous_match.is_some() && *i < chars.len() && matches(i) && rest_fits(i))
+            .or_else(|| {
+                (next..chars.len())
+                    .find(|i| is_word_start(&chars, *i) && matches(i) && rest_fits(i))
+            })
+            .or_else(|| (next..chars.len()).find(matches))?;
+
+        let continues_run = previous_match.is_some_and(|p| position == p + 1);
+        if is_word_start(&chars, position) {
+            points += 10;
+        } else if continues_run {
+            points += 5;
+        } else {
+            humps_only = false;
+        }
+        points -= (position - next) as i32;
+        previous_match = Some(position);
+        next = position + 1;
+    }
+
+    let tier = if candidate
+        .to_ascii_lowercase()
+        .starts_with(&pattern.to_ascii_lowercase())
+    {
+        2
+    } else if humps_only {
+        1
+    } else {
+        0
+    };
+    // Among equal matches, shorter candidates are closer to what was typed.
+    let quality = 5000 + points * 10 - chars.len() as i32;
+    Some(tier * 10_000 + quality.clamp(0, 9_999) as u32)
+}
+
+/// The tier of a score: 2 for prefix, 1 for camel-hump and 0 for scattered
+/// matches.
+pub fn tier(score: u32) -> u32 {
+    score / 10_000
+}
+
+/// The number of single-character insertions, deletions, substitutions and
+/// swaps of neighbours that turn `a` into `b`.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let width = b.len() + 1;
+    let mut d = vec![0; (a.len() + 1) * width];
+    for (j, cell) in d.iter_mut().take(width).enumerate() {
+        *cell = j;
+    }
+    for i in 0..=a.len() {
+        d[i * width] = i;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (d[(i - 1) * width + j] + 1)
+                .min(d[i * width + j - 1] + 1)
+                .min(d[(i - 1) * width + j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(d[(i - 2) * width + j - 2] + 1);
+            }
+            d[i * width + j] = best;
+        }
+    }
+    d[a.len() * width + b.len()]
+}
+
+/// The candidate `name` was most likely a typo of, if any is close enough:
+/// one edit for names of three to five characters, two for longer ones,
+/// and none for shorter names, where almost anything would be close. Ties
+/// go to the earliest candidate.
+pub fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let allowed = (name.chars().count() / 3).min(2);
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| (1..=allowed).contains(distance))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn is_word_start(chars: &[char], i: usize) -> bool {
+    if i == 0 {
+        return true;
+    }
+    let (previous, current) = (chars[i - 1], chars[i]);
+    (previous == '_' && current != '_') || (previous.is_lowercase() && current.is_uppercase())
+}
+
+fn is_subsequence(pattern: &[char], chars: &[char]) -> bool {
+    let mut chars = chars.iter();
+    pattern
+        .iter()
+        .all(|wanted| chars.any(|c| c.to_ascii_lowercase() == *wanted))
+}
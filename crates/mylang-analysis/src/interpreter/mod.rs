@@ -0,0 +1,705 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use std::rc::Rc;
+
+use super::ast::{BinaryOp, Block, Expr, ExprKind, LetKind, Program, Stmt, UnaryOp};
+use super::builtins::{self, Builtin};
+use super::lexer::Span;
+
+/// Calls nested deeper than this fail instead of overflowing the stack.
+pub const MAX_CALL_DEPTH: usize = 1000;
+/// The longest list `range` builds.
+pub const MAX_RANGE_LENGTH: usize = 1_000_000;
+/// Stack for a thread that runs the interpreter, enough to reach
+/// `MAX_CALL_DEPTH` in unoptimized builds.
+pub const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+/// A runtime value. Functions borrow their bodies from the program they
+/// were declared in, which therefore has to outlive them.
+#[derive(Clone)]
+pub enum Value<'a> {
+    Number(f64),
+    String(String),
+    Bool(bool),
+    List(Vec<Value<'a>>),
+    /// Elements in insertion order, without duplicates.
+    Set(Vec<Value<'a>>),
+    Function(Rc<Function<'a>>),
+    Builtin(&'static Builtin),
+    /// What a block without a tail, or an `if` without `else` that did not
+    /// run, evaluates to.
+    Unit,
+}
+
+pub struct Function<'a> {
+    pub name: Option<String>,
+    pub params: Vec<String>,
+    pub body: &'a Expr,
+    env: Env<'a>,
+}
+
+/// A call in progress, the top level being the outermost.
+pub struct Frame<'a> {
+    /// `None` for the top level.
+    function: Option<Rc<Function<'a>>>,
+    /// The step about to run, or the call the frame is waiting on.
+    pub span: Span,
+    /// The innermost scope at the last step.
+    env: Env<'a>,
+}
+
+/// Stops the interpreter between steps, as a debugger does.
+pub trait Debugger<'a> {
+    /// Called before each statement, block tail and expression-bodied
+    /// function runs, with the innermost frame last. An error ends the run.
+    fn pause(&mut self, frames: &[Frame<'a>]) -> Result<(), RuntimeError>;
+}
+
+#[derive(Debug)]
+pub struct RuntimeError {
+    pub message: String,
+    pub span: Span,
+}
+
+type Env<'a> = Rc<Scope<'a>>;
+
+#[derive(Default)]
+struct Scope<'a> {
+    values: RefCell<HashMap<String, Value<'a>>>,
+    parent: Option<Env<'a>>,
+}
+
+impl<'a> Scope<'a> {
+    fn child(parent: &Env<'a>) -> Env<'a> {
+        Rc::new(Scope {
+            values: RefCell::default(),
+            parent: Some(parent.clone()),
+        })
+    }
+
+    fn get(&self, name: &str) -> Option<Value<'a>> {
+        match self.values.borrow().get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.parent.as_ref()?.get(name),
+        }
+    }
+
+    fn define(&self, name: &str, value: Value<'a>) {
+        self.values.borrow_mut().insert(name.to_string(), value);
+    }
+}
+
+/// Evaluates programs by walking their syntax tree. Top-level bindings
+/// persist across `run` calls, so a session can be fed one program at a
+/// time.
+pub struct Interpreter<'a, 'o> {
+    globals: Env<'a>,
+    /// Where `print` writes.
+    output: &'o mut dyn Write,
+    frames: Vec<Frame<'a>>,
+    debugger: Option<&'o mut dyn Debugger<'a>>,
+}
+
+impl<'a, 'o> Interpreter<'a, 'o> {
+    pub fn new(output: &'o mut dyn Write) -> Self {
+        let globals: Env<'a> = Rc::default();
+        Self {
+            frames: vec![Frame {
+                function: None,
+                span: Span::new(0, 0),
+                env: globals.clone(),
+            }],
+            globals,
+            output,
+            debugger: None,
+        }
+    }
+
+    /// Lets `debugger` stop the run between steps.
+    pub fn with_debugger(mut self, debugger: &'o mut dyn Debugger<'a>) -> Self {
+        self.debugger = Some(debugger);
+        self
+    }
+
+    /// Runs the statements of `program` and returns the value of the last
+    /// one, or unit when that is a `let`.
+    pub fn run(&mut self, program: &'a Program) -> Result<Value<'a>, RuntimeError> {
+        self.run_statements(&program.statements)
+    }
+
+    /// Runs top-level `statements` as `run` does.
+    pub fn run_statements(&mut self, statements: &'a [Stmt]) -> Result<Value<'a>, RuntimeError> {
+        let globals = self.globals.clone();
+        let mut last = Value::Unit;
+        for stmt in statements {
+            last = self.statement(stmt, &globals)?;
+        }
+        Ok(last)
+    }
+
+    /// Calls the top-level function `name` with `args`.
+    pub fn call(&mut self, name: &str, args: Vec<Value<'a>>) -> Result<Value<'a>, RuntimeError> {
+        let span = Span::new(0, 0);
+        let Some(function) = self.globals.get(name) else {
+            return Err(error(span, format!("There is no function named '{name}'")));
+        };
+        self.apply(&function, args, span)
+    }
+
+    /// The value bound to `name` at the top level.
+    pub fn global(&self, name: &str) -> Option<Value<'a>> {
+        self.globals.get(name)
+    }
+
+    /// Gives the debugger, if there is one, the chance to stop before `span`
+    /// runs in `env`.
+    fn step(&mut self, span: Span, env: &Env<'a>) -> Result<(), RuntimeError> {
+        let Some(debugger) = self.debugger.as_deref_mut() else {
+            return Ok(());
+        };
+        if let Some(frame) = self.frames.last_mut() {
+            frame.span = span;
+            frame.env = env.clone();
+        }
+        debugger.pause(&self.frames)
+    }
+
+    fn statement(&mut self, stmt: &'a Stmt, env: &Env<'a>) -> Result<Value<'a>, RuntimeError> {
+        self.step(stmt.span(), env)?;
+        match stmt {
+            Stmt::Let(stmt) => {
+                let Some(value) = &stmt.value else {
+                    return Err(error(stmt.span, "Incomplete 'let' statement".to_string()));
+                };
+                let value = match stmt.kind {
+                    // The function sees the scope it is defined in, itself
+                    // included, so it can recurse.
+                    LetKind::Function => Value::Function(Rc::new(Function {
+                        name: Some(stmt.name.name.clone()),
+                        params: stmt.params.iter().map(|p| p.name.clone()).collect(),
+                        body: value,
+                        env: env.clone(),
+                    })),
+                    LetKind::Value => self.expr(value, env)?,
+                };
+                env.define(&stmt.name.name, value);
+                Ok(Value::Unit)
+            }
+            Stmt::Expr(stmt) => self.expr(&stmt.expr, env),
+        }
+    }
+
+    fn block(&mut self, block: &'a Block, env: &Env<'a>) -> Result<Value<'a>, RuntimeError> {
+        let env = Scope::child(env);
+        for stmt in &block.statements {
+            self.statement(stmt, &env)?;
+        }
+        match &block.tail {
+            Some(tail) => {
+                self.step(tail.span, &env)?;
+                self.expr(tail, &env)
+            }
+            None => Ok(Value::Unit),
+        }
+    }
+
+    fn expr(&mut self, expr: &'a Expr, env: &Env<'a>) -> Result<Value<'a>, RuntimeError> {
+        match &expr.kind {
+            ExprKind::Number(text) => text
+                .parse()
+                .map(Value::Number)
+                .map_err(|_| error(expr.span, format!("Invalid number '{text}'"))),
+            ExprKind::String(text) => Ok(Value::String(unescape(text))),
+            ExprKind::Bool(value) => Ok(Value::Bool(*value)),
+            ExprKind::Name(name) => env
+                .get(&name.name)
+                .or_else(|| builtins::lookup(&name.name).map(Value::Builtin))
+                .ok_or_else(|| error(name.span, format!("Unknown identifier: {}", name.name))),
+            ExprKind::Unary { op, operand } => {
+                let value = self.expr(operand, env)?;
+                match (op, value) {
+                    (UnaryOp::Negate, Value::Number(n)) => Ok(Value::Number(-n)),
+                    (UnaryOp::Not, Value::Bool(b)) => Ok(Value::Bool(!b)),
+                    (UnaryOp::Negate, value) => Err(type_error(expr.span, "negate", &value)),
+                    (UnaryOp::Not, value) => Err(type_error(expr.span, "apply '!' to", &value)),
+                }
+            }
+            ExprKind::Binary {
+                op: BinaryOp::Pipe,
+                left,
+                right,
+            } => {
+                let piped = self.expr(left, env)?;
+                // `x |> f(a)` passes `x` ahead of `a`; `x |> f` calls `f`
+                // with `x` alone.
+                let (callee, mut args) = match &right.kind {
+                    ExprKind::Call { callee, args } => {
+                        let callee = self.expr(callee, env)?;
+                        (callee, self.exprs(args, env)?)
+                    }
+                    _ => (self.expr(right, env)?, Vec::new()),
+                };
+                args.insert(0, piped);
+                self.apply(&callee, args, right.span)
+            }
+            ExprKind::Binary { op, left, right } => {
+                let left = self.expr(left, env)?;
+                let right = self.expr(right, env)?;
+                binary(*op, left, right, expr.span)
+            }
+            ExprKind::Call { callee, args } => {
+                let function = self.expr(callee, env)?;
+                let args = self.exprs(args, env)?;
+                self.apply(&function, args, expr.span)
+            }
+            ExprKind::Field { receiver, name } => {
+                let value = self.expr(receiver, env)?;
+                field(value, &name.name, name.span)
+            }
+            ExprKind::List(items) => Ok(Value::List(self.exprs(items, env)?)),
+            ExprKind::Set(items) => {
+                let mut set = Vec::new();
+                for item in self.exprs(items, env)? {
+                    if !set.contains(&item) {
+                        set.push(item);
+                    }
+                }
+                Ok(Value::Set(set))
+            }
+            ExprKind::Block(block) => self.block(block, env),
+            ExprKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => match self.expr(condition, env)? {
+                Value::Bool(true) => self.block(then_branch, env),
+                Value::Bool(false) => match else_branch {
+                    Some(branch) => self.expr(branch, env),
+                    None => Ok(Value::Unit),
+                },
+                value => Err(error(
+                    condition.span,
+                    format!("The condition is a {}, not a bool", value.type_name()),
+                )),
+            },
+            ExprKind::Lambda { params, body } => Ok(Value::Function(Rc::new(Function {
+                name: None,
+                params: params.iter().map(|p| p.name.clone()).collect(),
+                body,
+                env: env.clone(),
+            }))),
+            ExprKind::Error => Err(error(
+                expr.span,
+                "Cannot run code with syntax errors".to_string(),
+            )),
+        }
+    }
+
+    fn exprs(&mut self, exprs: &'a [Expr], env: &Env<'a>) -> Result<Vec<Value<'a>>, RuntimeError> {
+        exprs.iter().map(|expr| self.expr(expr, env)).collect()
+    }
+
+    fn apply(
+        &mut self,
+        function: &Value<'a>,
+        args: Vec<Value<'a>>,
+        span: Span,
+    ) -> Result<Value<'a>, RuntimeError> {
+        match function {
+            Value::Function(function) => {
+                expect_arity(function.label(), function.params.len(), args.len(), span)?;
+                if self.frames.len() > MAX_CALL_DEPTH {
+                    return Err(error(
+                        span,
+                        format!("Calls are nested more than {MAX_CALL_DEPTH} deep"),
+                    ));
+                }
+                let env = Scope::child(&function.env);
+                for (param, arg) in function.params.iter().zip(args) {
+                    env.define(param, arg);
+                }
+                if let Some(caller) = self.frames.last_mut() {
+                    caller.span = span;
+                }
+                self.frames.push(Frame {
+                    function: Some(function.clone()),
+                    span: function.body.span,
+                    env: env.clone(),
+                });
+                // Block bodies stop at their own statements.
+                let result = match function.body.kind {
+                    ExprKind::Block(_) => Ok(()),
+                    _ => self.step(function.body.span, &env),
+                }
+                .and_then(|()| self.expr(function.body, &env));
+                self.frames.pop();
+                result
+            }
+            Value::Builtin(builtin) => {
+                expect_arity(builtin.name, builtin.params.len(), args.len(), span)?;
+                self.builtin(builtin, args, span)
+            }
+            value => Err(error(
+                span,
+                format!("A {} cannot be called", value.type_name()),
+            )),
+        }
+    }
+
+    fn builtin(
+        &mut self,
+        builtin: &Builtin,
+        mut args: Vec<Value<'a>>,
+        span: Span,
+    ) -> Result<Value<'a>, RuntimeError> {
+        let wrong = |value: &Value| {
+            error(
+                span,
+                format!("{} does not accept a {}", builtin.name, value.type_name()),
+            )
+        };
+        match (builtin.name, args.as_mut_slice()) {
+            ("print", [value]) => {
+                let _ = writeln!(self.output, "{value}");
+                Ok(value.clone())
+            }
+            ("str", [value]) => Ok(Value::String(value.to_string())),
+            ("len", [value]) => match value {
+                Value::String(text) => Ok(Value::Number(text.chars().count() as f64)),
+                Value::List(items) | Value::Set(items) => Ok(Value::Number(items.len() as f64)),
+                value => Err(wrong(value)),
+            },
+            ("range", [Value::Number(start), Value::Number(end)]) => {
+                if !start.is_finite() || !end.is_finite() {
+                    return Err(error(
+                        span,
+                        "range does not accept a bound that is not finite".to_string(),
+                    ));
+                }
+                if *end - *start > MAX_RANGE_LENGTH as f64 {
+                    return Err(error(
+                        span,
+                        format!("range cannot make more than {MAX_RANGE_LENGTH} numbers"),
+                    ));
+                }
+                let mut items = Vec::new();
+                let mut n = *start;
+                while n < *end {
+                    items.push(Value::Number(n));
+                    n = *start + items.len() as f64;
+                }
+                Ok(Value::List(items))
+            }
+            ("range", [start, end]) => Err(wrong(if start.is_number() { end } else { start })),
+            ("map", [items, f]) => {
+                let items = elements(items).ok_or_else(|| wrong(items))?;
+                let f = f.clone();
+                let mapped = items
+                    .into_iter()
+                    .map(|item| self.apply(&f, vec![item], span))
+                    .collect::<Result<_, _>>()?;
+                Ok(Value::List(mapped))
+            }
+            ("filter", [items, predicate]) => {
+                let items = elements(items).ok_or_else(|| wrong(items))?;
+                let predicate = predicate.clone();
+                let mut kept = Vec::new();
+                for item in items {
+                    match self.apply(&predicate, vec![item.clone()], span)? {
+                        Value::Bool(true) => kept.push(item),
+                        Value::Bool(false) => {}
+                        value => {
+                            return Err(error(
+                                span,
+                                format!(
+                                    "The predicate returned a {}, not a bool",
+                                    value.type_name()
+                                ),
+                            ));
+                        }
+                    }
+                }
+                Ok(Value::List(kept))
+            }
+            ("fold", [items, initial, f]) => {
+                let items = elements(items).ok_or_else(|| wrong(items))?;
+                let f = f.clone();
+                let mut acc = initial.clone();
+                for item in items {
+                    acc = self.apply(&f, vec![acc, item], span)?;
+                }
+                Ok(acc)
+            }
+            ("sum", [items]) => {
+                let mut total = 0.0;
+                for item in elements(items).ok_or_else(|| wrong(items))? {
+                    match item {
+                        Value::Number(n) => total += n,
+                        item => return Err(wrong(&item)),
+                    }
+                }
+                Ok(Value::Number(total))
+            }
+            ("abs", [Value::Number(x)]) => Ok(Value::Number(x.abs())),
+            ("sqrt", [Value::Number(x)]) => Ok(Value::Number(x.sqrt())),
+            (_, [value, ..]) => Err(wrong(value)),
+            _ => Err(error(span, format!("{} is not implemented", builtin.name))),
+        }
+    }
+}
+
+impl<'a> Frame<'a> {
+    /// The function's name, or `<top level>`.
+    pub fn name(&self) -> &str {
+        match &self.function {
+            Some(function) => function.name.as_deref().unwrap_or("<lambda>"),
+            None => "<top level>",
+        }
+    }
+
+    /// The bindings visible in the frame other than the top-level ones, by
+    /// name. Inner bindings hide outer ones with the same name.
+    pub fn locals(&self) -> Vec<(String, Value<'a>)> {
+        let mut locals: Vec<(String, Value<'a>)> = Vec::new();
+        let mut scope = &self.env;
+        while let Some(parent) = &scope.parent {
+            for (name, value) in scope.values.borrow().iter() {
+                if !locals.iter().any(|(local, _)| local == name) {
+                    locals.push((name.clone(), value.clone()));
+                }
+            }
+            scope = parent;
+        }
+        locals.sort_by(|a, b| a.0.cmp(&b.0));
+        locals
+    }
+
+    /// The top-level bindings, by name.
+    pub fn globals(&self) -> Vec<(String, Value<'a>)> {
+        let mut scope = &self.env;
+        while let Some(parent) = &scope.parent {
+            scope = parent;
+        }
+        let mut globals: Vec<_> = scope
+            .values
+            .borrow()
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        globals.sort_by(|a, b| a.0.cmp(&b.0));
+        globals
+    }
+}
+
+impl Function<'_> {
+    fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or("The function")
+    }
+}
+
+impl Value<'_> {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Bool(_) => "bool",
+            Value::List(_) => "list",
+            Value::Set(_) => "set",
+            Value::Function(_) | Value::Builtin(_) => "fn",
+            Value::Unit => "unit",
+        }
+    }
+
+    fn is_number(&self) -> bool {
+        matches!(self, Value::Number(_))
+    }
+
+    /// How the value is written inside a list, where strings are quoted.
+    pub fn nested(&self) -> String {
+        match self {
+            Value::String(text) => format!("{text:?}"),
+            value => value.to_string(),
+        }
+    }
+}
+
+impl PartialEq for Value<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::List(a), Value::List(b)) => a == b,
+            (Value::Set(a), Value::Set(b)) => {
+                a.len() == b.len() && a.iter().all(|item| b.contains(item))
+            }
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            (Value::Builtin(a), Value::Builtin(b)) => a.name == b.name,
+            (Value::Unit, Value::Unit) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let join = |items: &[Value]| {
+            items
+                .iter()
+                .map(Value::nested)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self {
+            // Whole numbers print without a fractional part.
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+            Value::Number(n) => write!(f, "{n}"),
+            Value::String(text) => write!(f, "{text}"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::List(items) => write!(f, "[{}]", join(items)),
+            Value::Set(items) => write!(f, "{{{}}}", join(items)),
+            Value::Function(function) => match &function.name {
+                Some(name) => write!(f, "<fn {name}>"),
+                None => write!(f, "<fn>"),
+            },
+            Value::Builtin(builtin) => write!(f, "<builtin {}>", builtin.name),
+            Value::Unit => write!(f, "()"),
+        }
+    }
+}
+
+fn binary<'a>(
+    op: BinaryOp,
+    left: Value<'a>,
+    right: Value<'a>,
+    span: Span,
+) -> Result<Value<'a>, RuntimeError> {
+    use Value::{Bool, List, Number, String};
+
+    let mismatch = |left: &Value, right: &Value| {
+        error(
+            span,
+            format!(
+                "Cannot apply '{}' to a {} and a {}",
+                symbol(op),
+                left.type_name(),
+                right.type_name()
+            ),
+        )
+    };
+    Ok(match (op, left, right) {
+        (BinaryOp::Equal, left, right) => Bool(left == right),
+        (BinaryOp::NotEqual, left, right) => Bool(left != right),
+        (BinaryOp::Add, Number(a), Number(b)) => Number(a + b),
+        (BinaryOp::Add, String(a), String(b)) => String(a + &b),
+        (BinaryOp::Add, List(mut a), List(b)) => {
+            a.extend(b);
+            List(a)
+        }
+        (BinaryOp::Subtract, Number(a), Number(b)) => Number(a - b),
+        (BinaryOp::Multiply, Number(a), Number(b)) => Number(a * b),
+        (BinaryOp::Divide, Number(_), Number(0.0)) => {
+            return Err(error(span, "Division by zero".to_string()));
+        }
+        (BinaryOp::Divide, Number(a), Number(b)) => Number(a / b),
+        (BinaryOp::Power, Number(a), Number(b)) => Number(a.powf(b)),
+        (BinaryOp::Less, Number(a), Number(b)) => Bool(a < b),
+        (BinaryOp::LessEqual, Number(a), Number(b)) => Bool(a <= b),
+        (BinaryOp::Greater, Number(a), Number(b)) => Bool(a > b),
+        (BinaryOp::GreaterEqual, Number(a), Number(b)) => Bool(a >= b),
+        (BinaryOp::Less, String(a), String(b)) => Bool(a < b),
+        (BinaryOp::LessEqual, String(a), String(b)) => Bool(a <= b),
+        (BinaryOp::Greater, String(a), String(b)) => Bool(a > b),
+        (BinaryOp::GreaterEqual, String(a), String(b)) => Bool(a >= b),
+        (_, left, right) => return Err(mismatch(&left, &right)),
+    })
+}
+
+fn symbol(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Add => "+",
+        BinaryOp::Subtract => "-",
+        BinaryOp::Multiply => "*",
+        BinaryOp::Divide => "/",
+        BinaryOp::Power => "^",
+        BinaryOp::Equal => "==",
+        BinaryOp::NotEqual => "!=",
+        BinaryOp::Less => "<",
+        BinaryOp::LessEqual => "<=",
+        BinaryOp::Greater => ">",
+        BinaryOp::GreaterEqual => ">=",
+        BinaryOp::Pipe => "|>",
+    }
+}
+
+/// The members listed in `builtins::MEMBERS`.
+fn field<'a>(value: Value<'a>, name: &str, span: Span) -> Result<Value<'a>, RuntimeError> {
+    let missing = |value: &Value| {
+        error(
+            span,
+            format!("A {} has no field '{}'", value.type_name(), name),
+        )
+    };
+    match (&value, name) {
+        (Value::String(text), "length") => Ok(Value::Number(text.chars().count() as f64)),
+        (Value::List(items), "length") | (Value::Set(items), "size") => {
+            Ok(Value::Number(items.len() as f64))
+        }
+        (Value::List(items), "first") => items
+            .first()
+            .cloned()
+            .ok_or_else(|| error(span, "The list is empty".to_string())),
+        (Value::List(items), "last") => items
+            .last()
+            .cloned()
+            .ok_or_else(|| error(span, "The list is empty".to_string())),
+        _ => Err(missing(&value)),
+    }
+}
+
+fn elements<'a>(value: &Value<'a>) -> Option<Vec<Value<'a>>> {
+    match value {
+        Value::List(items) | Value::Set(items) => Some(items.clone()),
+        _ => None,
+    }
+}
+
+fn expect_arity(name: &str, expected: usize, got: usize, span: Span) -> Result<(), RuntimeError> {
+    if expected == got {
+        return Ok(());
+    }
+    let plural = if expected == 1 { "" } else { "s" };
+    Err(error(
+        span,
+        format!("{name} expects {expected} argument{plural}, got {got}"),
+    ))
+}
+
+fn type_error(span: Span, action: &str, value: &Value) -> RuntimeError {
+    error(span, format!("Cannot {} a {}", action, value.type_name()))
+}
+
+fn error(span: Span, message: String) -> RuntimeError {
+    RuntimeError { message, span }
+}
+
+/// The text of a string literal with its escape sequences resolved.
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
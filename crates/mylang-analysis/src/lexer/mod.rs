@@ -0,0 +1,260 @@
+use super::cancel::{CancellationToken, Cancelled, Checkpoint};
+
+/// A token owns none of its text, which is the span of the source it was
+/// lexed from, so a document's tokens take a single allocation.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Token {
+    pub token_type: TokenType,
+    pub span: Span,
+}
+
+impl Token {
+    /// The token's text in `source`, the text it was lexed from.
+    pub fn lexeme<'a>(&self, source: &'a str) -> &'a str {
+        &source[self.span.start..self.span.end]
+    }
+}
+
+/// Byte offsets into the source, end exclusive.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TokenType {
+    PLUS,
+    MINUS,
+    SLASH,
+    STAR,
+    CARET,
+    LeftParen,
+    RightParen,
+    LeftBracket,
+    RightBracket,
+    LeftBrace,
+    RightBrace,
+
+    ARROW,
+    PIPE,
+    COMMA,
+    DOT,
+    COLON,
+    SEMICOLON,
+
+    EQUAL,
+    BANG,
+    GREATER,
+    LESS,
+    EqualEqual,
+    BangEqual,
+    LessEqual,
+    GreaterEqual,
+
+    IDENTIFIER,
+    STRING,
+    NUMBER,
+
+    TRUE,
+    FALSE,
+    IF,
+    ELSE,
+    LET,
+    FN,
+
+    EOF,
+}
+
+/// Operators and punctuation with their token types. Where one starts
+/// another, the longer one comes first so it wins.
+pub const SYMBOLS: &[(&str, TokenType)] = &[
+    ("->", TokenType::ARROW),
+    ("|>", TokenType::PIPE),
+    ("==", TokenType::EqualEqual),
+    ("!=", TokenType::BangEqual),
+    (">=", TokenType::GreaterEqual),
+    ("<=", TokenType::LessEqual),
+    ("+", TokenType::PLUS),
+    ("-", TokenType::MINUS),
+    ("*", TokenType::STAR),
+    ("/", TokenType::SLASH),
+    ("^", TokenType::CARET),
+    ("(", TokenType::LeftParen),
+    (")", TokenType::RightParen),
+    ("{", TokenType::LeftBrace),
+    ("}", TokenType::RightBrace),
+    ("[", TokenType::LeftBracket),
+    ("]", TokenType::RightBracket),
+    ("|", TokenType::ARROW),
+    (",", TokenType::COMMA),
+    (".", TokenType::DOT),
+    (":", TokenType::COLON),
+    (";", TokenType::SEMICOLON),
+    ("=", TokenType::EQUAL),
+    ("!", TokenType::BANG),
+    (">", TokenType::GREATER),
+    ("<", TokenType::LESS),
+];
+
+/// Reserved words with their token types.
+pub const KEYWORDS: &[(&str, TokenType)] = &[
+    ("true", TokenType::TRUE),
+    ("false", TokenType::FALSE),
+    ("if", TokenType::IF),
+    ("else", TokenType::ELSE),
+    ("let", TokenType::LET),
+    ("fn", TokenType::FN),
+];
+
+/// Starts a comment that runs to the end of the line.
+pub const LINE_COMMENT: &str = "//";
+
+pub fn lex(source: &str) -> Vec<Token> {
+    lex_cancellable(source, &CancellationToken::new())
+        .unwrap_or_else(|Cancelled| unreachable!("a new token is never cancelled"))
+}
+
+/// Like [`lex`], giving up once `cancel` is cancelled.
+pub fn lex_cancellable(source: &str, cancel: &CancellationToken) -> Result<Vec<Token>, Cancelled> {
+    let mut tokens = Vec::new();
+    let mut current = 0;
+    let mut checkpoint = Checkpoint::new(cancel);
+
+    while current < source.len() && !checkpoint.step() {
+        let start = current;
+        let rest = &source[current..];
+        let c = rest.chars().next().unwrap();
+
+        if rest.starts_with(LINE_COMMENT) {
+            current += rest.find('\n').unwrap_or(rest.len());
+            continue;
+        }
+        if let Some((symbol, token_type)) =
+            SYMBOLS.iter().find(|(symbol, _)| rest.starts_with(symbol))
+        {
+            current += symbol.len();
+            tokens.push(Token {
+                token_type: *token_type,
+                span: Span::new(start, current),
+            });
+            continue;
+        }
+
+        match c {
+            '0'..='9' => {
+                add_number_token(source, &mut tokens, start, &mut current);
+                continue;
+            }
+            '"' => {
+                add_string_token(source, &mut tokens, start, &mut current);
+                continue;
+            }
+            'a'..='z' | 'A'..='Z' | '_' => {
+                add_identifier_token(source, &mut tokens, start, &mut current);
+                continue;
+            }
+            ' ' | '\r' | '\t' | '\n' => {
+                // Ignore whitespace
+            }
+            _ => {
+                tracing::debug!("skipping unexpected character {:?} at {}", c, start);
+            }
+        }
+
+        current += c.len_utf8();
+    }
+
+    tokens.push(Token {
+        token_type: TokenType::EOF,
+        span: Span::new(source.len(), source.len()),
+    });
+    checkpoint.result()?;
+    Ok(tokens)
+}
+
+fn add_number_token(source: &str, tokens: &mut Vec<Token>, start: usize, current: &mut usize) {
+    while *current < source.len() && source.as_bytes()[*current].is_ascii_digit() {
+        *current += 1;
+    }
+    tokens.push(Token {
+        token_type: TokenType::NUMBER,
+        span: Span::new(start, *current),
+    });
+}
+
+fn add_string_token(source: &str, tokens: &mut Vec<Token>, start: usize, current: &mut usize) {
+    let bytes = source.as_bytes();
+
+    *current += 1; // Skip the opening quote
+    while *current < source.len() && bytes[*current] != b'"' {
+        if bytes[*current] == b'\\' {
+            *current += 1;
+        }
+        *current += 1;
+    }
+    // Skip the closing quote, if the string was terminated at all
+    *current = (*current + 1).min(source.len());
+    tokens.push(Token {
+        token_type: TokenType::STRING,
+        span: Span::new(start, *current),
+    });
+}
+
+/// The token type of a reserved word.
+pub fn keyword(lexeme: &str) -> Option<TokenType> {
+    KEYWORDS
+        .iter()
+        .find(|(keyword, _)| *keyword == lexeme)
+        .map(|(_, token_type)| *token_type)
+}
+
+fn add_identifier_token(source: &str, tokens: &mut Vec<Token>, start: usize, current: &mut usize) {
+    while let Some(c) = source[*current..].chars().next() {
+        if !(c.is_alphanumeric() || c == '_') {
+            break;
+        }
+        *current += c.len_utf8();
+    }
+    let token_type = keyword(&source[start..*current]).unwrap_or(TokenType::IDENTIFIER);
+    tokens.push(Token {
+        token_type,
+        span: Span::new(start, *current),
+    });
+}
+
+/// The `//` comments `lex` skipped over, found in the gaps between `tokens`.
+pub fn comments(source: &str, tokens: &[Token]) -> Vec<Span> {
+    let mut gaps = Vec::with_capacity(tokens.len() + 1);
+    let mut end = 0;
+    for token in tokens {
+        gaps.push(end..token.span.start.max(end));
+        end = token.span.end;
+    }
+    gaps.push(end..source.len().max(end));
+
+    let mut comments = Vec::new();
+    for gap in gaps {
+        let mut current = gap.start;
+        while let Some(found) = source[current..gap.end].find(LINE_COMMENT) {
+            let start = current + found;
+            let end = source[start..gap.end]
+                .find('\n')
+                .map_or(gap.end, |i| start + i);
+            comments.push(Span::new(start, end));
+            current = end;
+        }
+    }
+    comments
+}
@@ -0,0 +1,126 @@
+//! The mylang analyzer, for tools that want diagnostics and symbols
+//! without speaking the Language Server Protocol:
+//!
+//! ```
+//! use mylang_analysis::{Analysis, Config};
+//!
+//! let mut analysis = Analysis::new(Config::default());
+//! analysis.set_file_text("file:///main.mylang", "let x = 1;\nprint(y);\n");
+//! let diagnostics = analysis.diagnostics("file:///main.mylang").unwrap();
+//! assert_eq!(diagnostics.len(), 2);
+//! let symbols = analysis.symbols("file:///main.mylang").unwrap();
+//! assert_eq!(symbols[0].name, "x");
+//! ```
+//!
+//! The items exported from the crate root follow semantic versioning,
+//! including the types their fields use, such as [`Diagnostic`] and
+//! [`SymbolKind`]. The modules beneath it are what the language server is
+//! built from and may change in any release.
+
+pub mod ast;
+pub mod builtins;
+pub mod cancel;
+pub mod diagnostics;
+pub mod docs;
+pub mod fuzzy;
+pub mod interpreter;
+pub mod lexer;
+pub mod line_index;
+pub mod messages;
+pub mod parser;
+pub mod resolver;
+pub mod types;
+pub mod workspace;
+
+pub use builtins::Global;
+pub use cancel::{CancellationToken, Cancelled};
+pub use diagnostics::{
+    Diagnostic, DiagnosticData, DiagnosticSeverity, DiagnosticTag, Fix, Position, Range, TextEdit,
+};
+pub use messages::Locale;
+pub use resolver::SymbolKind;
+pub use types::Type;
+pub use workspace::{Analysis, Config, Profile, SymbolInfo};
+
+use std::time::{Duration, Instant};
+
+use ast::Program;
+use lexer::Token;
+use line_index::LineIndex;
+use resolver::SymbolTable;
+
+/// The result of running the analysis pipeline over one version of a document.
+pub struct FileAnalysis {
+    pub tokens: Vec<Token>,
+    pub program: Program,
+    pub symbols: SymbolTable,
+    pub diagnostics: Vec<Diagnostic>,
+    pub token_count: usize,
+    pub duration: Duration,
+    pub timings: Timings,
+}
+
+/// How long each stage of an analysis took. All zero where there is no
+/// clock.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Timings {
+    pub lex: Duration,
+    pub parse: Duration,
+    pub resolve: Duration,
+}
+
+pub fn analyze(text: &str) -> FileAnalysis {
+    analyze_with(text, &[])
+}
+
+/// Like [`analyze`], with `globals` the host environment defines declared
+/// next to the builtins.
+pub fn analyze_with(text: &str, globals: &[Global]) -> FileAnalysis {
+    analyze_cancellable(text, globals, &CancellationToken::new())
+        .unwrap_or_else(|Cancelled| unreachable!("a new token is never cancelled"))
+}
+
+/// Like [`analyze_with`], giving up part way through the lexer, parser or
+/// resolver once `cancel` is cancelled.
+pub fn analyze_cancellable(
+    text: &str,
+    globals: &[Global],
+    cancel: &CancellationToken,
+) -> Result<FileAnalysis, Cancelled> {
+    // There is no clock to read on `wasm32-unknown-unknown`.
+    let now = || (!cfg!(target_arch = "wasm32")).then(Instant::now);
+    let between = |from: Option<Instant>, to: Option<Instant>| match (from, to) {
+        (Some(from), Some(to)) => to - from,
+        _ => Duration::ZERO,
+    };
+
+    let start = now();
+    let index = LineIndex::new(text);
+    let tokens = lexer::lex_cancellable(text, cancel)?;
+    let lexed = now();
+    let (program, mut diagnostics) = parser::parse_cancellable(&tokens, &index, cancel)?;
+    let parsed = now();
+    let (symbols, name_diagnostics) =
+        resolver::resolve_cancellable(&program, &index, globals, cancel)?;
+    let resolved = now();
+    diagnostics.extend(name_diagnostics);
+
+    Ok(FileAnalysis {
+        token_count: tokens.len(),
+        tokens,
+        program,
+        symbols,
+        diagnostics,
+        duration: between(start, resolved),
+        timings: Timings {
+            lex: between(start, lexed),
+            parse: between(lexed, parsed),
+            resolve: between(parsed, resolved),
+        },
+    })
+}
+
+/// Runs the full analysis pipeline over a document's text.
+pub fn document_diagnostics(text: &str) -> Vec<Diagnostic> {
+    analyze(text).diagnostics
+}
@@ -0,0 +1,84 @@
+use super::diagnostics::{Position, Range};
+use super::lexer::Span;
+
+/// Maps byte offsets in a document to LSP positions and back. Lines are
+/// 0-based and characters are counted in UTF-16 code units, as the protocol
+/// requires by default.
+pub struct LineIndex<'a> {
+    text: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(text: &'a str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(
+            text.bytes()
+                .enumerate()
+                .filter(|(_, b)| *b == b'\n')
+                .map(|(i, _)| i + 1),
+        );
+        Self { text, line_starts }
+    }
+
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.text.len());
+        let line = self.line_starts.partition_point(|start| *start <= offset) - 1;
+        let line_start = self.line_starts[line];
+        let character = self.text[line_start..offset]
+            .chars()
+            .map(char::len_utf16)
+            .sum::<usize>();
+
+        Position {
+            line: line as u32,
+            character: character as u32,
+        }
+    }
+
+    pub fn range(&self, span: Span) -> Range {
+        Range {
+            start: self.position(span.start),
+            end: self.position(span.end),
+        }
+    }
+
+    /// The byte offset of a position, clamped to the end of its line.
+    pub fn offset(&self, position: &Position) -> usize {
+        let Some(line_start) = self.line_starts.get(position.line as usize) else {
+            return self.text.len();
+        };
+        let line_end = self
+            .line_starts
+            .get(position.line as usize + 1)
+            .map(|next| next - 1)
+            .unwrap_or(self.text.len());
+
+        let mut units = 0;
+        for (i, c) in self.text[*line_start..line_end].char_indices() {
+            if units >= position.character as usize {
+                return line_start + i;
+            }
+            units += c.len_utf16();
+        }
+        line_end
+    }
+
+    /// The whole text the index was built over.
+    pub fn text(&self) -> &'a str {
+        self.text
+    }
+
+    /// The text of a 0-based line, without its line terminator.
+    pub fn line_text(&self, line: u32) -> &'a str {
+        let Some(start) = self.line_starts.get(line as usize) else {
+            return "";
+        };
+        let end = self
+            .line_starts
+            .get(line as usize + 1)
+            .copied()
+            .unwrap_or(self.text.len());
+        self.text[*start..end].trim_end_matches(['\n', '\r'])
+    }
+}
@@ -0,0 +1,96 @@
+use super::diagnostics::codes;
+
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub enum Locale {
+    #[default]
+    English,
+    German,
+}
+
+impl Locale {
+    /// Accepts BCP 47 style tags such as `de`, `de-AT` or `de_CH`; anything
+    /// without a catalog falls back to English.
+    pub fn from_tag(tag: &str) -> Self {
+        let language = tag.split(['-', '_']).next().unwrap_or("");
+        match language.to_ascii_lowercase().as_str() {
+            "de" => Locale::German,
+            _ => Locale::English,
+        }
+    }
+}
+
+const ENGLISH: &[(&str, &str)] = &[
+    (codes::UNKNOWN_IDENTIFIER, "Unknown identifier: {name}"),
+    (codes::DUPLICATE_IDENTIFIER, "Duplicate identifier: {name}"),
+    (
+        codes::EXPECTED_IDENTIFIER,
+        "Expected identifier after 'let', found: {found}",
+    ),
+    (codes::UNEXPECTED_TOKEN, "Unexpected token: {token}"),
+    (codes::UNEXPECTED_END, "Unexpected end of input"),
+    (codes::EXPECTED_TOKEN, "Expected {expected}, found: {found}"),
+    (
+        codes::EXPECTED_EXPRESSION,
+        "Expected an expression, found: {found}",
+    ),
+    (codes::MISSING_SEMICOLON, "Expected ';' after statement"),
+    (codes::UNUSED_VARIABLE, "'{name}' is never used"),
+    (
+        codes::WRONG_ARGUMENT_COUNT,
+        "Wrong number of arguments to '{name}': expected {expected}, found: {found}",
+    ),
+];
+
+const GERMAN: &[(&str, &str)] = &[
+    (codes::UNKNOWN_IDENTIFIER, "Unbekannter Bezeichner: {name}"),
+    (codes::DUPLICATE_IDENTIFIER, "Doppelter Bezeichner: {name}"),
+    (
+        codes::EXPECTED_IDENTIFIER,
+        "Bezeichner nach 'let' erwartet, gefunden: {found}",
+    ),
+    (codes::UNEXPECTED_TOKEN, "Unerwartetes Token: {token}"),
+    (codes::UNEXPECTED_END, "Unerwartetes Ende der Eingabe"),
+    (
+        codes::EXPECTED_TOKEN,
+        "{expected} erwartet, gefunden: {found}",
+    ),
+    (
+        codes::EXPECTED_EXPRESSION,
+        "Ausdruck erwartet, gefunden: {found}",
+    ),
+    (codes::MISSING_SEMICOLON, "';' nach Anweisung erwartet"),
+    (codes::UNUSED_VARIABLE, "'{name}' wird nie verwendet"),
+    (
+        codes::WRONG_ARGUMENT_COUNT,
+        "Falsche Anzahl an Argumenten für '{name}': {expected} erwartet, gefunden: {found}",
+    ),
+];
+
+fn catalog(locale: Locale) -> &'static [(&'static str, &'static str)] {
+    match locale {
+        Locale::English => ENGLISH,
+        Locale::German => GERMAN,
+    }
+}
+
+fn template(locale: Locale, code: &str) -> Option<&'static str> {
+    catalog(locale)
+        .iter()
+        .chain(ENGLISH)
+        .find(|(key, _)| *key == code)
+        .map(|(_, template)| *template)
+}
+
+/// Renders the message for `code`, replacing each `{name}` placeholder with
+/// the matching argument. Unknown codes render as the code itself.
+pub fn render<K: AsRef<str>, V: AsRef<str>>(locale: Locale, code: &str, args: &[(K, V)]) -> String {
+    let Some(template) = template(locale, code) else {
+        return code.to_string();
+    };
+
+    let mut message = template.to_string();
+    for (name, value) in args {
+        message = message.replace(&format!("{{{}}}", name.as_ref()), value.as_ref());
+    }
+    message
+}
@@ -0,0 +1,623 @@
+use super::ast::{
+    BinaryOp, Block, Expr, ExprKind, ExprStmt, Ident, LetKind, LetStmt, Program, Stmt, UnaryOp,
+};
+use super::cancel::{CancellationToken, Cancelled, Checkpoint};
+use super::diagnostics::{Diagnostic, codes};
+use super::lexer::{Span, Token, TokenType};
+use super::line_index::LineIndex;
+
+/// Left-associative binary operators by level, loosest first. `^` binds
+/// tighter than all of them and is right associative, and prefix `-` and
+/// `!` bind tighter still.
+pub const BINARY_OPERATORS: &[&[(TokenType, BinaryOp)]] = &[
+    &[(TokenType::PIPE, BinaryOp::Pipe)],
+    &[
+        (TokenType::EqualEqual, BinaryOp::Equal),
+        (TokenType::BangEqual, BinaryOp::NotEqual),
+        (TokenType::LESS, BinaryOp::Less),
+        (TokenType::LessEqual, BinaryOp::LessEqual),
+        (TokenType::GREATER, BinaryOp::Greater),
+        (TokenType::GreaterEqual, BinaryOp::GreaterEqual),
+    ],
+    &[
+        (TokenType::PLUS, BinaryOp::Add),
+        (TokenType::MINUS, BinaryOp::Subtract),
+    ],
+    &[
+        (TokenType::STAR, BinaryOp::Multiply),
+        (TokenType::SLASH, BinaryOp::Divide),
+    ],
+];
+
+/// Parses a token stream into a program. Syntax errors are reported as
+/// diagnostics and the parser recovers at the next statement boundary, so
+/// the tree always covers as much of the document as possible.
+pub fn parse(tokens: &[Token], index: &LineIndex) -> (Program, Vec<Diagnostic>) {
+    parse_cancellable(tokens, index, &CancellationToken::new())
+        .unwrap_or_else(|Cancelled| unreachable!("a new token is never cancelled"))
+}
+
+/// Like [`parse`], giving up once `cancel` is cancelled.
+pub fn parse_cancellable(
+    tokens: &[Token],
+    index: &LineIndex,
+    cancel: &CancellationToken,
+) -> Result<(Program, Vec<Diagnostic>), Cancelled> {
+    let mut parser = Parser {
+        tokens,
+        current: 0,
+        index,
+        diagnostics: Vec::new(),
+        checkpoint: Checkpoint::new(cancel),
+    };
+
+    let mut statements = Vec::new();
+    while !parser.at_end() {
+        if let Some(stmt) = parser.statement() {
+            statements.push(stmt);
+        }
+    }
+
+    let end = tokens.last().map(|token| token.span.end).unwrap_or(0);
+    let program = Program {
+        statements,
+        span: Span::new(0, end),
+    };
+    parser.checkpoint.result()?;
+    Ok((program, parser.diagnostics))
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    current: usize,
+    index: &'a LineIndex<'a>,
+    diagnostics: Vec<Diagnostic>,
+    checkpoint: Checkpoint<'a>,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> &'a Token {
+        &self.tokens[self.current.min(self.tokens.len() - 1)]
+    }
+
+    fn previous(&self) -> &'a Token {
+        &self.tokens[self.current.saturating_sub(1)]
+    }
+
+    fn lexeme(&self, token: &Token) -> &'a str {
+        token.lexeme(self.index.text())
+    }
+
+    fn at_end(&self) -> bool {
+        self.peek().token_type == TokenType::EOF
+    }
+
+    fn check(&self, token_type: TokenType) -> bool {
+        self.peek().token_type == token_type
+    }
+
+    fn advance(&mut self) -> &'a Token {
+        let token = self.peek();
+        if !self.at_end() {
+            self.current += 1;
+        }
+        // Once cancelled, skip to the end, which every loop stops at.
+        if self.checkpoint.step() {
+            self.current = self.tokens.len() - 1;
+        }
+        token
+    }
+
+    fn matches(&mut self, token_type: TokenType) -> bool {
+        if self.check(token_type) {
+            self.advance();
+            return true;
+        }
+        false
+    }
+
+    fn error(&mut self, token: &Token, code: &str, args: &[(&str, &str)]) {
+        self.diagnostics
+            .push(Diagnostic::generate(self.index, token, code, args));
+    }
+
+    /// Reports the token that was found where `expected` should have been.
+    /// `expected` is shown as is, so token text comes quoted: `"')'"`.
+    fn expected(&mut self, expected: &str) {
+        let token = self.peek();
+        if token.token_type == TokenType::EOF {
+            self.error(token, codes::UNEXPECTED_END, &[]);
+        } else {
+            self.error(
+                token,
+                codes::EXPECTED_TOKEN,
+                &[("expected", expected), ("found", self.lexeme(token))],
+            );
+        }
+    }
+
+    fn expect(&mut self, token_type: TokenType, expected: &str) -> bool {
+        if self.matches(token_type) {
+            return true;
+        }
+        self.expected(expected);
+        false
+    }
+
+    /// Skips ahead to a point where a new statement can start.
+    fn synchronize(&mut self) {
+        while !self.at_end() {
+            match self.peek().token_type {
+                TokenType::SEMICOLON => {
+                    self.advance();
+                    return;
+                }
+                TokenType::RightBrace | TokenType::LET => return,
+                _ => {
+                    self.advance();
+                }
+            }
+        }
+    }
+
+    fn statement(&mut self) -> Option<Stmt> {
+        if self.check(TokenType::LET) {
+            return self.let_statement().map(Stmt::Let);
+        }
+
+        let start = self.current;
+        let reported = self.diagnostics.len();
+        let expr = self.expression();
+        if !self.end_statement(reported) {
+            self.synchronize();
+        }
+        if self.current == start {
+            // Nothing could be parsed here; never loop on the same token.
+            self.advance();
+            self.synchronize();
+        }
+
+        let span = expr.span.to(self.previous().span);
+        Some(Stmt::Expr(ExprStmt { expr, span }))
+    }
+
+    /// Consumes the `;` ending a statement, reporting it when missing unless
+    /// the statement already produced errors past `reported`.
+    fn end_statement(&mut self, reported: usize) -> bool {
+        if self.matches(TokenType::SEMICOLON) {
+            return true;
+        }
+        if self.diagnostics.len() > reported {
+            return false;
+        }
+
+        let last = self.previous();
+        if self.at_end() {
+            self.error(self.peek(), codes::UNEXPECTED_END, &[]);
+        } else {
+            self.error(last, codes::MISSING_SEMICOLON, &[]);
+        }
+        self.offer_semicolon(last);
+        false
+    }
+
+    /// Attaches a fix inserting the missing `;` right after `last` to the
+    /// diagnostic just reported.
+    fn offer_semicolon(&mut self, last: &Token) {
+        let at = self.index.range(Span::new(last.span.end, last.span.end));
+        if let Some(diagnostic) = self.diagnostics.pop() {
+            self.diagnostics
+                .push(diagnostic.with_preferred_fix("Insert ';'", at, ";"));
+        }
+    }
+
+    fn let_statement(&mut self) -> Option<LetStmt> {
+        let let_token = self.advance();
+
+        if !self.check(TokenType::IDENTIFIER) {
+            let found = self.peek();
+            if found.token_type == TokenType::EOF {
+                self.error(let_token, codes::UNEXPECTED_END, &[]);
+            } else {
+                self.error(
+                    found,
+                    codes::EXPECTED_IDENTIFIER,
+                    &[("found", self.lexeme(found))],
+                );
+            }
+            self.synchronize();
+            return None;
+        }
+        let name = self.ident();
+
+        let mut params = Vec::new();
+        while self.check(TokenType::IDENTIFIER) {
+            params.push(self.ident());
+        }
+
+        let kind = if self.matches(TokenType::ARROW) {
+            LetKind::Function
+        } else if self.check(TokenType::EQUAL) && params.is_empty() {
+            self.advance();
+            LetKind::Value
+        } else {
+            let found = self.peek();
+            if found.token_type == TokenType::EOF {
+                self.error(let_token, codes::UNEXPECTED_END, &[]);
+            } else {
+                self.error(
+                    found,
+                    codes::UNEXPECTED_TOKEN,
+                    &[("token", self.lexeme(found))],
+                );
+            }
+            self.synchronize();
+            return Some(LetStmt {
+                span: let_token.span.to(self.previous().span),
+                name,
+                params,
+                kind: LetKind::Function,
+                value: None,
+            });
+        };
+
+        let reported = self.diagnostics.len();
+        let value = self.expression();
+        if self.matches(TokenType::SEMICOLON) {
+            // Complete statement.
+        } else if self.diagnostics.len() > reported {
+            // Already reported where the expression broke off.
+            self.synchronize();
+        } else if self.at_end() {
+            let last = self.previous();
+            self.diagnostics.push(Diagnostic::spanning(
+                self.index,
+                let_token,
+                last,
+                codes::UNEXPECTED_END,
+                &[],
+            ));
+            self.offer_semicolon(last);
+        } else {
+            let last = self.previous();
+            self.error(last, codes::MISSING_SEMICOLON, &[]);
+            self.offer_semicolon(last);
+            self.synchronize();
+        }
+
+        Some(LetStmt {
+            span: let_token.span.to(self.previous().span),
+            name,
+            params,
+            kind,
+            value: Some(value),
+        })
+    }
+
+    fn ident(&mut self) -> Ident {
+        let token = self.advance();
+        Ident {
+            name: self.lexeme(token).to_string(),
+            span: token.span,
+        }
+    }
+
+    fn expression(&mut self) -> Expr {
+        self.binary(0)
+    }
+
+    /// Parses operators at `level` of `BINARY_OPERATORS` and tighter.
+    fn binary(&mut self, level: usize) -> Expr {
+        let Some(ops) = BINARY_OPERATORS.get(level) else {
+            return self.power();
+        };
+        let mut left = self.binary(level + 1);
+        while let Some((_, op)) = ops.iter().find(|(token_type, _)| self.check(*token_type)) {
+            self.advance();
+            let right = self.binary(level + 1);
+            left = Expr {
+                span: left.span.to(right.span),
+                kind: ExprKind::Binary {
+                    op: *op,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                },
+            };
+        }
+        left
+    }
+
+    /// `^` is right associative.
+    fn power(&mut self) -> Expr {
+        let base = self.unary();
+        if !self.matches(TokenType::CARET) {
+            return base;
+        }
+        let exponent = self.power();
+        Expr {
+            span: base.span.to(exponent.span),
+            kind: ExprKind::Binary {
+                op: BinaryOp::Power,
+                left: Box::new(base),
+                right: Box::new(exponent),
+            },
+        }
+    }
+
+    fn unary(&mut self) -> Expr {
+        let op = match self.peek().token_type {
+            TokenType::MINUS => UnaryOp::Negate,
+            TokenType::BANG => UnaryOp::Not,
+            _ => return self.postfix(),
+        };
+        let op_token = self.advance();
+        let operand = self.unary();
+        Expr {
+            span: op_token.span.to(operand.span),
+            kind: ExprKind::Unary {
+                op,
+                operand: Box::new(operand),
+            },
+        }
+    }
+
+    fn postfix(&mut self) -> Expr {
+        let mut expr = self.primary();
+        loop {
+            if self.matches(TokenType::LeftParen) {
+                let args = self.list_items(TokenType::RightParen, "')'");
+                expr = Expr {
+                    span: expr.span.to(self.previous().span),
+                    kind: ExprKind::Call {
+                        callee: Box::new(expr),
+                        args,
+                    },
+                };
+            } else if self.matches(TokenType::DOT) {
+                if !self.check(TokenType::IDENTIFIER) {
+                    self.expected("field name");
+                    return expr;
+                }
+                let name = self.ident();
+                expr = Expr {
+                    span: expr.span.to(name.span),
+                    kind: ExprKind::Field {
+                        receiver: Box::new(expr),
+                        name,
+                    },
+                };
+            } else {
+                return expr;
+            }
+        }
+    }
+
+    /// Comma separated expressions up to and including `close`.
+    fn list_items(&mut self, close: TokenType, expected: &str) -> Vec<Expr> {
+        let mut items = Vec::new();
+        if self.matches(close) {
+            return items;
+        }
+        loop {
+            items.push(self.expression());
+            if !self.matches(TokenType::COMMA) {
+                break;
+            }
+        }
+        self.expect(close, expected);
+        items
+    }
+
+    fn primary(&mut self) -> Expr {
+        let token = self.peek();
+        let kind = match token.token_type {
+            TokenType::NUMBER => ExprKind::Number(self.lexeme(token).to_string()),
+            TokenType::STRING => {
+                let content = self.lexeme(token).trim_start_matches('"');
+                let content = content.strip_suffix('"').unwrap_or(content);
+                ExprKind::String(content.to_string())
+            }
+            TokenType::TRUE => ExprKind::Bool(true),
+            TokenType::FALSE => ExprKind::Bool(false),
+            TokenType::IDENTIFIER => {
+                let name = self.ident();
+                return Expr {
+                    span: name.span,
+                    kind: ExprKind::Name(name),
+                };
+            }
+            TokenType::LeftParen => {
+                self.advance();
+                let inner = self.expression();
+                self.expect(TokenType::RightParen, "')'");
+                return Expr {
+                    span: token.span.to(self.previous().span),
+                    kind: inner.kind,
+                };
+            }
+            TokenType::LeftBracket => {
+                self.advance();
+                let items = self.list_items(TokenType::RightBracket, "']'");
+                return Expr {
+                    span: token.span.to(self.previous().span),
+                    kind: ExprKind::List(items),
+                };
+            }
+            TokenType::LeftBrace => return self.brace(),
+            TokenType::IF => return self.if_expression(),
+            TokenType::FN => return self.lambda(),
+            TokenType::EOF => {
+                self.error(token, codes::UNEXPECTED_END, &[]);
+                return Expr {
+                    span: token.span,
+                    kind: ExprKind::Error,
+                };
+            }
+            _ => {
+                self.error(
+                    token,
+                    codes::EXPECTED_EXPRESSION,
+                    &[("found", self.lexeme(token))],
+                );
+                // Leave closing delimiters for whoever opened them, and the
+                // next statement for the statement loop.
+                if !matches!(
+                    token.token_type,
+                    TokenType::LET
+                        | TokenType::SEMICOLON
+                        | TokenType::RightBrace
+                        | TokenType::RightParen
+                        | TokenType::RightBracket
+                ) {
+                    self.advance();
+                }
+                return Expr {
+                    span: token.span,
+                    kind: ExprKind::Error,
+                };
+            }
+        };
+
+        self.advance();
+        Expr {
+            span: token.span,
+            kind,
+        }
+    }
+
+    /// `{ a, b }` is a set literal; anything else in braces is a block.
+    fn brace(&mut self) -> Expr {
+        let open = self.advance();
+        let mut statements = Vec::new();
+
+        if !self.check(TokenType::LET) && !self.check(TokenType::RightBrace) {
+            let reported = self.diagnostics.len();
+            let first = self.expression();
+            if self.matches(TokenType::COMMA) {
+                let mut items = vec![first];
+                items.extend(self.list_items(TokenType::RightBrace, "'}'"));
+                return Expr {
+                    span: open.span.to(self.previous().span),
+                    kind: ExprKind::Set(items),
+                };
+            }
+
+            if self.check(TokenType::RightBrace) {
+                let block = self.finish_block(open, statements, Some(first));
+                return Expr {
+                    span: block.span,
+                    kind: ExprKind::Block(block),
+                };
+            }
+            if !self.end_statement(reported) {
+                self.synchronize();
+            }
+            let span = first.span.to(self.previous().span);
+            statements.push(Stmt::Expr(ExprStmt { expr: first, span }));
+        }
+
+        let block = self.block_body(open, statements);
+        Expr {
+            span: block.span,
+            kind: ExprKind::Block(block),
+        }
+    }
+
+    fn block(&mut self) -> Block {
+        if !self.check(TokenType::LeftBrace) {
+            self.expected("'{'");
+            let span = self.peek().span;
+            return Block {
+                statements: Vec::new(),
+                tail: None,
+                span: Span::new(span.start, span.start),
+            };
+        }
+        let open = self.advance();
+        self.block_body(open, Vec::new())
+    }
+
+    fn block_body(&mut self, open: &Token, mut statements: Vec<Stmt>) -> Block {
+        while !self.check(TokenType::RightBrace) && !self.at_end() {
+            if self.check(TokenType::LET) {
+                if let Some(stmt) = self.let_statement() {
+                    statements.push(Stmt::Let(stmt));
+                }
+                continue;
+            }
+
+            let start = self.current;
+            let reported = self.diagnostics.len();
+            let expr = self.expression();
+            if self.check(TokenType::RightBrace) {
+                return self.finish_block(open, statements, Some(expr));
+            }
+            if !self.end_statement(reported) {
+                self.synchronize();
+            }
+            if self.current == start {
+                self.advance();
+            }
+            let span = expr.span.to(self.previous().span);
+            statements.push(Stmt::Expr(ExprStmt { expr, span }));
+        }
+        self.finish_block(open, statements, None)
+    }
+
+    fn finish_block(&mut self, open: &Token, statements: Vec<Stmt>, tail: Option<Expr>) -> Block {
+        self.expect(TokenType::RightBrace, "'}'");
+        Block {
+            statements,
+            tail: tail.map(Box::new),
+            span: open.span.to(self.previous().span),
+        }
+    }
+
+    fn if_expression(&mut self) -> Expr {
+        let if_token = self.advance();
+        let condition = self.expression();
+        let then_branch = self.block();
+
+        let else_branch = if self.matches(TokenType::ELSE) {
+            let branch = if self.check(TokenType::IF) {
+                self.if_expression()
+            } else {
+                let block = self.block();
+                Expr {
+                    span: block.span,
+                    kind: ExprKind::Block(block),
+                }
+            };
+            Some(Box::new(branch))
+        } else {
+            None
+        };
+
+        Expr {
+            span: if_token.span.to(self.previous().span),
+            kind: ExprKind::If {
+                condition: Box::new(condition),
+                then_branch,
+                else_branch,
+            },
+        }
+    }
+
+    fn lambda(&mut self) -> Expr {
+        let fn_token = self.advance();
+        let mut params = Vec::new();
+        while self.check(TokenType::IDENTIFIER) {
+            params.push(self.ident());
+        }
+        self.expect(TokenType::ARROW, "'->'");
+        let body = self.expression();
+
+        Expr {
+            span: fn_token.span.to(body.span),
+            kind: ExprKind::Lambda {
+                params,
+                body: Box::new(body),
+            },
+        }
+    }
+}
@@ -0,0 +1,703 @@
+use std::collections::{HashMap, HashSet};
+
+use super::ast::{
+    BinaryOp, Block, Expr, ExprKind, Ident, LetKind, LetStmt, Program, Stmt, UnaryOp,
+};
+use super::builtins::{self, BUILTINS, Global};
+use super::cancel::{CancellationToken, Cancelled, Checkpoint};
+use super::diagnostics::{Diagnostic, DiagnosticSeverity, DiagnosticTag, codes};
+use super::fuzzy;
+use super::lexer::Span;
+use super::line_index::LineIndex;
+use super::types::Type;
+
+pub type ScopeId = usize;
+pub type SymbolId = usize;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SymbolKind {
+    Function,
+    Variable,
+    Parameter,
+    Builtin,
+}
+
+impl SymbolKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            SymbolKind::Function => "function",
+            SymbolKind::Variable => "variable",
+            SymbolKind::Parameter => "parameter",
+            SymbolKind::Builtin => "builtin",
+        }
+    }
+}
+
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub scope: ScopeId,
+    /// The declaring identifier; builtins have none.
+    pub span: Option<Span>,
+    /// The `let` statement that introduced the symbol, if any.
+    pub statement: Option<Span>,
+    /// Parameter names, for functions and builtins.
+    pub params: Vec<String>,
+    pub ty: Type,
+    /// The declared signature, for globals the host environment defines.
+    pub signature: Option<String>,
+    /// Offset from which the name can be referenced. Functions are visible
+    /// inside their own body, values only after their statement.
+    pub visible_from: usize,
+}
+
+pub struct Scope {
+    pub parent: Option<ScopeId>,
+    pub span: Span,
+    pub symbols: Vec<SymbolId>,
+}
+
+/// A use of a name, and the symbol it resolved to if any.
+pub struct Reference {
+    pub span: Span,
+    pub symbol: Option<SymbolId>,
+}
+
+/// Scopes and symbols of one document. Scope 0 holds the builtins and
+/// scope 1 the top level of the file; every other scope is nested in those.
+pub struct SymbolTable {
+    pub scopes: Vec<Scope>,
+    pub symbols: Vec<Symbol>,
+    pub references: Vec<Reference>,
+    /// The inferred type of every expression, innermost first.
+    pub expression_types: Vec<(Span, Type)>,
+}
+
+pub const BUILTIN_SCOPE: ScopeId = 0;
+pub const FILE_SCOPE: ScopeId = 1;
+
+impl SymbolTable {
+    /// The innermost scope containing `offset`.
+    pub fn scope_at(&self, offset: usize) -> ScopeId {
+        let mut found = FILE_SCOPE;
+        // Children are always created after their parents, so the last match
+        // is the most deeply nested one.
+        for (id, scope) in self.scopes.iter().enumerate().skip(FILE_SCOPE + 1) {
+            if scope.span.start <= offset && offset <= scope.span.end {
+                found = id;
+            }
+        }
+        found
+    }
+
+    /// Every symbol that can be referenced at `offset`, innermost first.
+    /// Shadowed names only appear once.
+    pub fn visible_at(&self, offset: usize) -> Vec<SymbolId> {
+        self.visible_in(self.scope_at(offset), offset)
+    }
+
+    /// Like `visible_at`, starting from a known `scope`.
+    pub fn visible_in(&self, scope: ScopeId, offset: usize) -> Vec<SymbolId> {
+        let mut visible: Vec<SymbolId> = Vec::new();
+        let mut scope = Some(scope);
+
+        while let Some(id) = scope {
+            for symbol_id in self.scopes[id].symbols.iter().rev() {
+                let symbol = &self.symbols[*symbol_id];
+                if symbol.visible_from <= offset
+                    && !visible
+                        .iter()
+                        .any(|other| self.symbols[*other].name == symbol.name)
+                {
+                    visible.push(*symbol_id);
+                }
+            }
+            scope = self.scopes[id].parent;
+        }
+        visible
+    }
+
+    /// The symbol named by the identifier at `offset`, whether a use or the
+    /// declaration itself, with that identifier's span. A cursor just past
+    /// the end of a name still counts as on it.
+    pub fn symbol_at(&self, offset: usize) -> Option<(SymbolId, Span)> {
+        let contains = |span: &Span| span.start <= offset && offset <= span.end;
+        let reference = self
+            .references
+            .iter()
+            .find(|reference| contains(&reference.span))
+            .and_then(|reference| Some((reference.symbol?, reference.span)));
+        reference.or_else(|| {
+            self.symbols
+                .iter()
+                .enumerate()
+                .find_map(|(id, symbol)| symbol.span.filter(contains).map(|span| (id, span)))
+        })
+    }
+
+    /// Every use of `symbol`, in source order.
+    pub fn uses_of(&self, symbol: SymbolId) -> Vec<Span> {
+        let mut spans: Vec<Span> = self
+            .references
+            .iter()
+            .filter(|reference| reference.symbol == Some(symbol))
+            .map(|reference| reference.span)
+            .collect();
+        spans.sort_by_key(|span| span.start);
+        spans
+    }
+
+    /// How many scopes lie between `from` and its ancestor `to`, if `to`
+    /// encloses `from` at all.
+    pub fn scope_distance(&self, from: ScopeId, to: ScopeId) -> Option<usize> {
+        let mut distance = 0;
+        let mut scope = Some(from);
+        while let Some(id) = scope {
+            if id == to {
+                return Some(distance);
+            }
+            distance += 1;
+            scope = self.scopes[id].parent;
+        }
+        None
+    }
+
+    /// The type of the outermost expression ending exactly at `offset`.
+    pub fn type_ending_at(&self, offset: usize) -> Option<Type> {
+        self.expression_types
+            .iter()
+            .filter(|(span, _)| span.end == offset)
+            .min_by_key(|(span, _)| span.start)
+            .map(|(_, ty)| *ty)
+    }
+
+    /// The symbol `name` refers to when used at `offset`.
+    pub fn resolve_at(&self, offset: usize, name: &str) -> Option<SymbolId> {
+        self.lookup(self.scope_at(offset), name, offset)
+    }
+
+    fn lookup(&self, scope: ScopeId, name: &str, offset: usize) -> Option<SymbolId> {
+        let mut scope = Some(scope);
+        while let Some(id) = scope {
+            let found = self.scopes[id].symbols.iter().rev().find(|symbol_id| {
+                let symbol = &self.symbols[**symbol_id];
+                symbol.name == name && symbol.visible_from <= offset
+            });
+            if let Some(symbol_id) = found {
+                return Some(*symbol_id);
+            }
+            scope = self.scopes[id].parent;
+        }
+        None
+    }
+}
+
+/// Builds the symbol table for a program and reports names that cannot be
+/// resolved or are declared twice in the same scope, and calls with the
+/// wrong number of arguments. `globals` are declared next to the builtins.
+pub fn resolve(
+    program: &Program,
+    index: &LineIndex,
+    globals: &[Global],
+) -> (SymbolTable, Vec<Diagnostic>) {
+    resolve_cancellable(program, index, globals, &CancellationToken::new())
+        .unwrap_or_else(|Cancelled| unreachable!("a new token is never cancelled"))
+}
+
+/// Like [`resolve`], giving up once `cancel` is cancelled.
+pub fn resolve_cancellable(
+    program: &Program,
+    index: &LineIndex,
+    globals: &[Global],
+    cancel: &CancellationToken,
+) -> Result<(SymbolTable, Vec<Diagnostic>), Cancelled> {
+    let mut resolver = Resolver {
+        table: SymbolTable {
+            scopes: Vec::new(),
+            symbols: Vec::new(),
+            references: Vec::new(),
+            expression_types: Vec::new(),
+        },
+        index,
+        globals,
+        diagnostics: Vec::new(),
+        statements: Vec::new(),
+        first_uses: HashMap::new(),
+        piped: None,
+        checkpoint: Checkpoint::new(cancel),
+    };
+
+    let builtins = resolver.push_scope(None, program.span);
+    for builtin in BUILTINS {
+        resolver.table.symbols.push(Symbol {
+            name: builtin.name.to_string(),
+            kind: SymbolKind::Builtin,
+            scope: builtins,
+            span: None,
+            statement: None,
+            params: builtin
+                .params
+                .iter()
+                .map(|param| param.to_string())
+                .collect(),
+            ty: Type::Function,
+            signature: None,
+            visible_from: 0,
+        });
+        let id = resolver.table.symbols.len() - 1;
+        resolver.table.scopes[builtins].symbols.push(id);
+    }
+    for global in globals {
+        let declared = resolver.table.scopes[builtins]
+            .symbols
+            .iter()
+            .any(|id| resolver.table.symbols[*id].name == global.name);
+        if declared {
+            continue;
+        }
+        let params = global.params.as_ref();
+        resolver.table.symbols.push(Symbol {
+            name: global.name.clone(),
+            kind: SymbolKind::Builtin,
+            scope: builtins,
+            span: None,
+            statement: None,
+            params: params
+                .into_iter()
+                .flatten()
+                .map(|(name, _)| name.clone())
+                .collect(),
+            ty: if params.is_some() {
+                Type::Function
+            } else {
+                global.ty
+            },
+            signature: Some(global.to_string()),
+            visible_from: 0,
+        });
+        let id = resolver.table.symbols.len() - 1;
+        resolver.table.scopes[builtins].symbols.push(id);
+    }
+
+    let file = resolver.push_scope(Some(builtins), program.span);
+    for stmt in &program.statements {
+        resolver.statement(file, stmt);
+    }
+    resolver.checkpoint.result()?;
+    resolver.report_unused();
+
+    Ok((resolver.table, resolver.diagnostics))
+}
+
+struct Resolver<'a> {
+    table: SymbolTable,
+    index: &'a LineIndex<'a>,
+    globals: &'a [Global],
+    diagnostics: Vec<Diagnostic>,
+    /// The statements being resolved, innermost last, with the scope of the
+    /// statement list each belongs to. A block's tail counts as a statement.
+    statements: Vec<(ScopeId, Span)>,
+    /// For each unknown name, the statement in each list before which a
+    /// declaration would cover its first use.
+    first_uses: HashMap<(String, ScopeId), Span>,
+    /// The call on the right of the `|>` being resolved, which gets the
+    /// piped value as an extra first argument.
+    piped: Option<Span>,
+    checkpoint: Checkpoint<'a>,
+}
+
+impl Resolver<'_> {
+    fn push_scope(&mut self, parent: Option<ScopeId>, span: Span) -> ScopeId {
+        self.table.scopes.push(Scope {
+            parent,
+            span,
+            symbols: Vec::new(),
+        });
+        self.table.scopes.len() - 1
+    }
+
+    fn declare(
+        &mut self,
+        scope: ScopeId,
+        name: &Ident,
+        kind: SymbolKind,
+        visible_from: usize,
+    ) -> SymbolId {
+        let duplicate = self.table.scopes[scope]
+            .symbols
+            .iter()
+            .any(|id| self.table.symbols[*id].name == name.name);
+        if duplicate {
+            let mut diagnostic = Diagnostic::at(
+                self.index,
+                name.span,
+                codes::DUPLICATE_IDENTIFIER,
+                &[("name", &name.name)],
+            );
+            if kind == SymbolKind::Parameter {
+                diagnostic = diagnostic.with_fix(
+                    &format!("Remove duplicate parameter '{}'", name.name),
+                    self.index.range(name.span),
+                    "",
+                );
+            }
+            self.diagnostics.push(diagnostic);
+        }
+
+        self.table.symbols.push(Symbol {
+            name: name.name.clone(),
+            kind,
+            scope,
+            span: Some(name.span),
+            statement: None,
+            params: Vec::new(),
+            ty: Type::Unknown,
+            signature: None,
+            visible_from,
+        });
+        let id = self.table.symbols.len() - 1;
+        self.table.scopes[scope].symbols.push(id);
+        id
+    }
+
+    /// Warns about `let` bindings nothing refers to. Names starting with an
+    /// underscore are unused on purpose.
+    fn report_unused(&mut self) {
+        let used: HashSet<SymbolId> = self
+            .table
+            .references
+            .iter()
+            .filter_map(|reference| reference.symbol)
+            .collect();
+
+        for (id, symbol) in self.table.symbols.iter().enumerate() {
+            let (Some(span), Some(statement)) = (symbol.span, symbol.statement) else {
+                continue;
+            };
+            if !matches!(symbol.kind, SymbolKind::Variable | SymbolKind::Function)
+                || used.contains(&id)
+                || symbol.name.starts_with('_')
+            {
+                continue;
+            }
+
+            let mut diagnostic = Diagnostic::at(
+                self.index,
+                span,
+                codes::UNUSED_VARIABLE,
+                &[("name", &symbol.name)],
+            )
+            .with_fix(
+                &format!("Remove unused binding '{}'", symbol.name),
+                self.index.range(whole_lines(self.index.text(), statement)),
+                "",
+            )
+            .with_fix(
+                &format!("Rename to '_{}'", symbol.name),
+                self.index.range(Span::new(span.start, span.start)),
+                "_",
+            );
+            diagnostic.severity = DiagnosticSeverity::Warning;
+            diagnostic.tags = vec![DiagnosticTag::Unnecessary];
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    /// Reports `name` as unknown, offering to correct it to a visible name
+    /// it looks like a typo of, or to declare it just before the statement
+    /// holding its first use in the current statement list.
+    fn unknown_identifier(&mut self, scope: ScopeId, name: &Ident) -> Diagnostic {
+        let mut diagnostic = Diagnostic::at(
+            self.index,
+            name.span,
+            codes::UNKNOWN_IDENTIFIER,
+            &[("name", &name.name)],
+        );
+        let visible = self.table.visible_in(scope, name.span.start);
+        let candidates = visible
+            .iter()
+            .map(|id| self.table.symbols[*id].name.as_str());
+        if let Some(suggestion) = fuzzy::closest(&name.name, candidates) {
+            diagnostic = diagnostic.with_preferred_fix(
+                &format!("Change to '{}'", suggestion),
+                self.index.range(name.span),
+                suggestion,
+            );
+        }
+
+        let Some(&(list, statement)) = self.statements.last() else {
+            return diagnostic;
+        };
+        let before = *self
+            .first_uses
+            .entry((name.name.clone(), list))
+            .or_insert(statement);
+
+        let text = self.index.text();
+        let line_start = text[..before.start].rfind('\n').map_or(0, |i| i + 1);
+        let indent = &text[line_start..before.start];
+        let (at, declaration) = if indent.trim().is_empty() {
+            (line_start, format!("{}let {} = 0;\n", indent, name.name))
+        } else {
+            (before.start, format!("let {} = 0; ", name.name))
+        };
+        diagnostic.with_fix(
+            &format!("Declare '{}'", name.name),
+            self.index.range(Span::new(at, at)),
+            &declaration,
+        )
+    }
+
+    fn statement(&mut self, scope: ScopeId, stmt: &Stmt) {
+        self.statements.push((scope, stmt.span()));
+        match stmt {
+            Stmt::Let(stmt) => self.let_statement(scope, stmt),
+            Stmt::Expr(stmt) => {
+                self.expression(scope, &stmt.expr);
+            }
+        }
+        self.statements.pop();
+    }
+
+    fn let_statement(&mut self, scope: ScopeId, stmt: &LetStmt) {
+        match stmt.kind {
+            LetKind::Function => {
+                let id = self.declare(scope, &stmt.name, SymbolKind::Function, stmt.span.start);
+                let symbol = &mut self.table.symbols[id];
+                symbol.statement = Some(stmt.span);
+                symbol.params = stmt.params.iter().map(|param| param.name.clone()).collect();
+                symbol.ty = Type::Function;
+
+                let body_end = stmt
+                    .value
+                    .as_ref()
+                    .map(|value| value.span.end)
+                    .unwrap_or(stmt.span.end);
+                let body = self.push_scope(Some(scope), Span::new(stmt.name.span.end, body_end));
+                for param in &stmt.params {
+                    self.declare(body, param, SymbolKind::Parameter, param.span.start);
+                }
+                if let Some(value) = &stmt.value {
+                    self.expression(body, value);
+                }
+            }
+            LetKind::Value => {
+                let ty = match &stmt.value {
+                    Some(value) => self.expression(scope, value),
+                    None => Type::Unknown,
+                };
+                let id = self.declare(scope, &stmt.name, SymbolKind::Variable, stmt.span.end);
+                let symbol = &mut self.table.symbols[id];
+                symbol.statement = Some(stmt.span);
+                symbol.ty = ty;
+            }
+        }
+    }
+
+    fn block(&mut self, scope: ScopeId, block: &Block) -> Type {
+        let inner = self.push_scope(Some(scope), block.span);
+        for stmt in &block.statements {
+            self.statement(inner, stmt);
+        }
+        let Some(tail) = &block.tail else {
+            return Type::Unknown;
+        };
+        self.statements.push((inner, tail.span));
+        let ty = self.expression(inner, tail);
+        self.statements.pop();
+        ty
+    }
+
+    /// Resolves the names in `expr` and infers its type.
+    fn expression(&mut self, scope: ScopeId, expr: &Expr) -> Type {
+        // Once cancelled, the rest of the tree is skipped.
+        if self.checkpoint.step() {
+            return Type::Unknown;
+        }
+        let ty = self.infer(scope, expr);
+        self.table.expression_types.push((expr.span, ty));
+        ty
+    }
+
+    fn infer(&mut self, scope: ScopeId, expr: &Expr) -> Type {
+        match &expr.kind {
+            ExprKind::Number(_) => Type::Number,
+            ExprKind::String(_) => Type::String,
+            ExprKind::Bool(_) => Type::Bool,
+            ExprKind::Error => Type::Unknown,
+            ExprKind::Name(name) => {
+                let symbol = self.table.lookup(scope, &name.name, name.span.start);
+                if symbol.is_none() {
+                    let diagnostic = self.unknown_identifier(scope, name);
+                    self.diagnostics.push(diagnostic);
+                }
+                self.table.references.push(Reference {
+                    span: name.span,
+                    symbol,
+                });
+                symbol.map_or(Type::Unknown, |id| self.table.symbols[id].ty)
+            }
+            ExprKind::Unary { op, operand } => {
+                self.expression(scope, operand);
+                match op {
+                    UnaryOp::Negate => Type::Number,
+                    UnaryOp::Not => Type::Bool,
+                }
+            }
+            ExprKind::Binary { op, left, right } => {
+                let left_type = self.expression(scope, left);
+                if *op == BinaryOp::Pipe {
+                    self.piped = Some(right.span);
+                }
+                let right_type = self.expression(scope, right);
+                if *op == BinaryOp::Pipe {
+                    self.piped = None;
+                    if matches!(right.kind, ExprKind::Name(_)) {
+                        self.check_arity(right, expr.span, 1);
+                    }
+                }
+                match op {
+                    BinaryOp::Add if left_type == Type::String || right_type == Type::String => {
+                        Type::String
+                    }
+                    BinaryOp::Add
+                    | BinaryOp::Subtract
+                    | BinaryOp::Multiply
+                    | BinaryOp::Divide
+                    | BinaryOp::Power => Type::Number,
+                    BinaryOp::Equal
+                    | BinaryOp::NotEqual
+                    | BinaryOp::Less
+                    | BinaryOp::LessEqual
+                    | BinaryOp::Greater
+                    | BinaryOp::GreaterEqual => Type::Bool,
+                    BinaryOp::Pipe => self.return_type(right),
+                }
+            }
+            ExprKind::Call { callee, args } => {
+                let piped = self.piped.take() == Some(expr.span);
+                self.expression(scope, callee);
+                for arg in args {
+                    self.expression(scope, arg);
+                }
+                self.check_arity(callee, expr.span, args.len() + usize::from(piped));
+                self.return_type(callee)
+            }
+            ExprKind::Field { receiver, name } => {
+                let receiver_type = self.expression(scope, receiver);
+                builtins::members(receiver_type)
+                    .find(|member| member.name == name.name)
+                    .map_or(Type::Unknown, |member| member.ty)
+            }
+            ExprKind::List(items) | ExprKind::Set(items) => {
+                for item in items {
+                    self.expression(scope, item);
+                }
+                if matches!(expr.kind, ExprKind::List(_)) {
+                    Type::List
+                } else {
+                    Type::Set
+                }
+            }
+            ExprKind::Block(block) => self.block(scope, block),
+            ExprKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(scope, condition);
+                let then_type = self.block(scope, then_branch);
+                match else_branch {
+                    Some(else_branch) => then_type.join(self.expression(scope, else_branch)),
+                    None => Type::Unknown,
+                }
+            }
+            ExprKind::Lambda { params, body } => {
+                let inner = self.push_scope(Some(scope), expr.span);
+                for param in params {
+                    self.declare(inner, param, SymbolKind::Parameter, param.span.start);
+                }
+                self.expression(inner, body);
+                Type::Function
+            }
+        }
+    }
+
+    /// The builtin or global `callee` names, if it names one.
+    fn callee_builtin(&self, callee: &Expr) -> Option<&Symbol> {
+        let ExprKind::Name(name) = &callee.kind else {
+            return None;
+        };
+        let symbol = self
+            .table
+            .references
+            .iter()
+            .rev()
+            .find(|reference| reference.span == name.span)
+            .and_then(|reference| reference.symbol)?;
+        let symbol = &self.table.symbols[symbol];
+        (symbol.kind == SymbolKind::Builtin).then_some(symbol)
+    }
+
+    fn global(&self, name: &str) -> Option<&Global> {
+        self.globals.iter().find(|global| global.name == name)
+    }
+
+    /// The type returned by calling `callee`, known only for builtins and
+    /// globals with a signature.
+    fn return_type(&self, callee: &Expr) -> Type {
+        let Some(symbol) = self.callee_builtin(callee) else {
+            return Type::Unknown;
+        };
+        match builtins::lookup(&symbol.name) {
+            Some(builtin) => builtin.returns,
+            None => self
+                .global(&symbol.name)
+                .filter(|global| global.params.is_some())
+                .map_or(Type::Unknown, |global| global.ty),
+        }
+    }
+
+    /// Reports a call to a builtin, or a global with a signature, with
+    /// `found` arguments when it takes a different number.
+    fn check_arity(&mut self, callee: &Expr, call: Span, found: usize) {
+        let Some(symbol) = self.callee_builtin(callee) else {
+            return;
+        };
+        let expected = match builtins::lookup(&symbol.name) {
+            Some(builtin) => builtin.params.len(),
+            None => match self.global(&symbol.name).and_then(|g| g.params.as_ref()) {
+                Some(params) => params.len(),
+                None => return,
+            },
+        };
+        if expected != found {
+            let diagnostic = Diagnostic::at(
+                self.index,
+                call,
+                codes::WRONG_ARGUMENT_COUNT,
+                &[
+                    ("name", &symbol.name),
+                    ("expected", &expected.to_string()),
+                    ("found", &found.to_string()),
+                ],
+            );
+            self.diagnostics.push(diagnostic);
+        }
+    }
+}
+
+/// Widens `span` to the lines it sits on when nothing else shares them, so
+/// deleting it leaves no blank line behind.
+pub fn whole_lines(text: &str, span: Span) -> Span {
+    let line_start = text[..span.start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = text[span.end..]
+        .find('\n')
+        .map_or(text.len(), |i| span.end + i + 1);
+    if text[line_start..span.start].trim().is_empty() && text[span.end..line_end].trim().is_empty()
+    {
+        Span::new(line_start, line_end)
+    } else {
+        span
+    }
+}
@@ -0,0 +1,47 @@
+/// The shape of a value as far as it can be inferred without running the
+/// program. Anything the inference cannot see through is `Unknown`.
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub enum Type {
+    #[default]
+    Unknown,
+    Number,
+    String,
+    Bool,
+    List,
+    Set,
+    Function,
+}
+
+impl Type {
+    pub fn name(self) -> &'static str {
+        match self {
+            Type::Unknown => "unknown",
+            Type::Number => "number",
+            Type::String => "string",
+            Type::Bool => "bool",
+            Type::List => "list",
+            Type::Set => "set",
+            Type::Function => "fn",
+        }
+    }
+
+    /// The type `name` stands for, as written in signatures.
+    pub fn from_name(name: &str) -> Option<Type> {
+        [
+            Type::Unknown,
+            Type::Number,
+            Type::String,
+            Type::Bool,
+            Type::List,
+            Type::Set,
+            Type::Function,
+        ]
+        .into_iter()
+        .find(|ty| ty.name() == name)
+    }
+
+    /// The type two branches agree on, if any.
+    pub fn join(self, other: Type) -> Type {
+        if self == other { self } else { Type::Unknown }
+    }
+}
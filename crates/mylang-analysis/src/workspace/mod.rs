@@ -0,0 +1,145 @@
+use std::cell::OnceCell;
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use super::builtins::Global;
+use super::diagnostics::{Category, Diagnostic, DiagnosticSeverity, Range};
+use super::line_index::LineIndex;
+use super::messages::Locale;
+use super::resolver::SymbolKind;
+use super::types::Type;
+use super::{FileAnalysis, analyze_with};
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Profile {
+    #[default]
+    Default,
+    /// For scratch files and half-written code: keeps highlighting and
+    /// navigation, but turns name resolution errors into hints and drops
+    /// lints.
+    Loose,
+}
+
+/// The settings that change what the analysis reports. Start from
+/// `Config::default()`; fields may be added in minor releases.
+#[derive(Clone, Default, Debug)]
+#[non_exhaustive]
+pub struct Config {
+    pub profile: Profile,
+    /// The language diagnostic messages are written in.
+    pub locale: Locale,
+    /// Names the host environment defines, which are not reported as
+    /// unknown.
+    pub globals: Vec<Global>,
+}
+
+impl Config {
+    /// Applies the profile and locale to diagnostics from `analyze`.
+    pub fn adjust(&self, mut diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        if self.locale != Locale::English {
+            for diagnostic in &mut diagnostics {
+                diagnostic.localize(self.locale);
+            }
+        }
+
+        if self.profile == Profile::Loose {
+            let category = |diagnostic: &Diagnostic| diagnostic.code.as_deref().map(Category::of);
+            diagnostics.retain(|diagnostic| category(diagnostic) != Some(Category::Lint));
+            for diagnostic in &mut diagnostics {
+                if category(diagnostic) == Some(Category::Names) {
+                    diagnostic.severity = DiagnosticSeverity::Hint;
+                }
+            }
+        }
+        diagnostics
+    }
+}
+
+/// A name a file declares.
+#[derive(Clone)]
+#[non_exhaustive]
+pub struct SymbolInfo {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub ty: Type,
+    /// The declaring identifier.
+    pub range: Range,
+    /// Parameter names, for functions.
+    pub params: Vec<String>,
+}
+
+struct File {
+    text: String,
+    analysis: OnceCell<FileAnalysis>,
+}
+
+/// The analyzer for tools that do not speak the protocol: hand it file
+/// texts by URI and query the results. Files are analyzed on first query
+/// and again after their text changes.
+#[derive(Default)]
+pub struct Analysis {
+    config: Config,
+    files: HashMap<String, File>,
+}
+
+impl Analysis {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            files: HashMap::new(),
+        }
+    }
+
+    /// Adds the file at `uri`, or replaces its text.
+    pub fn set_file_text(&mut self, uri: impl Into<String>, text: impl Into<String>) {
+        let file = File {
+            text: text.into(),
+            analysis: OnceCell::new(),
+        };
+        self.files.insert(uri.into(), file);
+    }
+
+    pub fn remove_file(&mut self, uri: &str) {
+        self.files.remove(uri);
+    }
+
+    /// The diagnostics for the file at `uri`, or `None` if it was never
+    /// set.
+    pub fn diagnostics(&self, uri: &str) -> Option<Vec<Diagnostic>> {
+        let (_, analysis) = self.file(uri)?;
+        Some(self.config.adjust(analysis.diagnostics.clone()))
+    }
+
+    /// The names the file at `uri` declares, in source order, or `None` if
+    /// it was never set.
+    pub fn symbols(&self, uri: &str) -> Option<Vec<SymbolInfo>> {
+        let (text, analysis) = self.file(uri)?;
+        let index = LineIndex::new(text);
+        let mut symbols: Vec<SymbolInfo> = analysis
+            .symbols
+            .symbols
+            .iter()
+            .filter_map(|symbol| {
+                Some(SymbolInfo {
+                    name: symbol.name.clone(),
+                    kind: symbol.kind,
+                    ty: symbol.ty,
+                    range: index.range(symbol.span?),
+                    params: symbol.params.clone(),
+                })
+            })
+            .collect();
+        symbols.sort_by_key(|symbol| (symbol.range.start.line, symbol.range.start.character));
+        Some(symbols)
+    }
+
+    fn file(&self, uri: &str) -> Option<(&str, &FileAnalysis)> {
+        let file = self.files.get(uri)?;
+        let analysis = file
+            .analysis
+            .get_or_init(|| analyze_with(&file.text, &self.config.globals));
+        Some((&file.text, analysis))
+    }
+}
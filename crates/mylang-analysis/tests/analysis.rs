@@ -0,0 +1,94 @@
+//! The analyzer as tools use it, through the items at the crate root.
+
+use mylang_analysis::{Analysis, Config, DiagnosticSeverity, Locale, Profile, SymbolKind, Type};
+
+const URI: &str = "file:///main.mylang";
+
+#[test]
+fn files_that_were_never_set_have_no_results() {
+    let analysis = Analysis::new(Config::default());
+    assert!(analysis.diagnostics(URI).is_none());
+    assert!(analysis.symbols(URI).is_none());
+}
+
+#[test]
+fn setting_a_file_again_replaces_its_text() {
+    let mut analysis = Analysis::new(Config::default());
+    analysis.set_file_text(URI, "print(y);\n");
+    assert_eq!(analysis.diagnostics(URI).unwrap().len(), 1);
+
+    analysis.set_file_text(URI, "let y = 1;\nprint(y);\n");
+    assert!(analysis.diagnostics(URI).unwrap().is_empty());
+}
+
+#[test]
+fn removed_files_are_forgotten() {
+    let mut analysis = Analysis::new(Config::default());
+    analysis.set_file_text(URI, "let y = 1;\n");
+    analysis.set_file_text("file:///other.mylang", "let z = 2;\n");
+    analysis.remove_file(URI);
+    assert!(analysis.symbols(URI).is_none());
+    assert!(analysis.symbols("file:///other.mylang").is_some());
+}
+
+#[test]
+fn symbols_come_in_source_order() {
+    let mut analysis = Analysis::new(Config::default());
+    analysis.set_file_text(
+        URI,
+        "let double x -> x * 2;\nlet n = 1;\nprint(double(n));\n",
+    );
+    let symbols = analysis.symbols(URI).unwrap();
+    let summary: Vec<(&str, SymbolKind, u32, u32)> = symbols
+        .iter()
+        .map(|symbol| {
+            (
+                symbol.name.as_str(),
+                symbol.kind,
+                symbol.range.start.line,
+                symbol.range.start.character,
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("double", SymbolKind::Function, 0, 4),
+            ("x", SymbolKind::Parameter, 0, 11),
+            ("n", SymbolKind::Variable, 1, 4),
+        ]
+    );
+    assert_eq!(symbols[0].ty, Type::Function);
+    assert_eq!(symbols[0].params, ["x"]);
+    assert_eq!(symbols[2].ty, Type::Number);
+}
+
+#[test]
+fn messages_follow_the_configured_locale() {
+    let mut config = Config::default();
+    config.locale = Locale::German;
+    let mut analysis = Analysis::new(config);
+    analysis.set_file_text(URI, "print(missing);\n");
+    let diagnostics = analysis.diagnostics(URI).unwrap();
+    assert_eq!(
+        diagnostics[0].message.as_deref(),
+        Some("Unbekannter Bezeichner: missing")
+    );
+}
+
+#[test]
+fn the_loose_profile_drops_lints_and_softens_names() {
+    let text = "let unused = 1;\nprint(missing);\n";
+    let mut strict = Analysis::new(Config::default());
+    strict.set_file_text(URI, text);
+    assert_eq!(strict.diagnostics(URI).unwrap().len(), 2);
+
+    let mut config = Config::default();
+    config.profile = Profile::Loose;
+    let mut loose = Analysis::new(config);
+    loose.set_file_text(URI, text);
+    let diagnostics = loose.diagnostics(URI).unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].code.as_deref(), Some("unknown-identifier"));
+    assert!(diagnostics[0].severity == DiagnosticSeverity::Hint);
+}
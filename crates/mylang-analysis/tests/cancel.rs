@@ -0,0 +1,41 @@
+//! Cancelling the stages of an analysis part way through.
+
+use mylang_analysis::lexer::lex_cancellable;
+use mylang_analysis::{CancellationToken, Cancelled, analyze, analyze_cancellable};
+
+/// Enough statements that every stage passes several checkpoints.
+fn source() -> String {
+    (0..1_000).map(|n| format!("let x{n} = {n};\n")).collect()
+}
+
+fn cancelled() -> CancellationToken {
+    let token = CancellationToken::new();
+    token.cancel();
+    token
+}
+
+#[test]
+fn a_cancelled_token_stops_the_lexer() {
+    assert!(matches!(
+        lex_cancellable(&source(), &cancelled()),
+        Err(Cancelled)
+    ));
+}
+
+#[test]
+fn a_cancelled_token_stops_the_analysis() {
+    assert!(matches!(
+        analyze_cancellable(&source(), &[], &cancelled()),
+        Err(Cancelled)
+    ));
+}
+
+#[test]
+fn an_uncancelled_analysis_matches_the_plain_one() {
+    let text = source();
+    let analysis = analyze_cancellable(&text, &[], &CancellationToken::new()).unwrap();
+    let plain = analyze(&text);
+    assert_eq!(analysis.tokens.len(), plain.tokens.len());
+    assert_eq!(analysis.symbols.symbols.len(), plain.symbols.symbols.len());
+    assert_eq!(analysis.diagnostics.len(), plain.diagnostics.len());
+}
@@ -0,0 +1,80 @@
+//! The builtins as programs call them.
+
+use mylang_analysis::analyze;
+use mylang_analysis::interpreter::{Interpreter, MAX_RANGE_LENGTH};
+
+/// Runs `source` and returns what it printed followed by its last value, or
+/// the message of the runtime error that stopped it.
+fn eval(source: &str) -> Result<String, String> {
+    let analysis = analyze(source);
+    let mut output = Vec::new();
+    let mut interpreter = Interpreter::new(&mut output);
+    let value = interpreter
+        .run(&analysis.program)
+        .map(|value| value.to_string())
+        .map_err(|e| e.message)?;
+    Ok(String::from_utf8(output).unwrap() + &value)
+}
+
+#[test]
+fn print_writes_its_argument_and_returns_it() {
+    assert_eq!(eval("print(\"hi\");").unwrap(), "hi\nhi");
+}
+
+#[test]
+fn str_and_len_describe_values() {
+    assert_eq!(eval("str([1, \"a\"]);").unwrap(), "[1, \"a\"]");
+    assert_eq!(eval("len(\"héllo\");").unwrap(), "5");
+    assert_eq!(eval("len([1, 2, 3]);").unwrap(), "3");
+    assert!(eval("len(1);").is_err());
+}
+
+#[test]
+fn range_counts_up_to_its_end() {
+    assert_eq!(eval("range(1, 4);").unwrap(), "[1, 2, 3]");
+    assert_eq!(eval("range(4, 1);").unwrap(), "[]");
+}
+
+#[test]
+fn range_rejects_bounds_that_are_not_finite() {
+    let message = eval("range(0, 10 ^ 400);").unwrap_err();
+    assert!(message.contains("not finite"), "{message}");
+    let message = eval("range(sqrt(-1), 3);").unwrap_err();
+    assert!(message.contains("not finite"), "{message}");
+}
+
+#[test]
+fn range_rejects_lists_past_the_cap() {
+    let message = eval(&format!("range(0, {});", MAX_RANGE_LENGTH + 1)).unwrap_err();
+    assert!(message.contains(&MAX_RANGE_LENGTH.to_string()), "{message}");
+    // Past 2^53 adding one no longer changes a number, which must not
+    // keep the range from ending.
+    assert!(eval("range(2 ^ 60, 2 ^ 60 + 512);").is_ok());
+    assert_eq!(
+        eval(&format!("len(range(0, {MAX_RANGE_LENGTH}));")).unwrap(),
+        MAX_RANGE_LENGTH.to_string()
+    );
+}
+
+#[test]
+fn map_filter_and_fold_call_their_function() {
+    assert_eq!(eval("map([1, 2], fn x -> x * 2);").unwrap(), "[2, 4]");
+    assert_eq!(
+        eval("filter(range(0, 6), fn x -> x > 3);").unwrap(),
+        "[4, 5]"
+    );
+    assert_eq!(
+        eval("fold([1, 2, 3], 10, fn acc x -> acc + x);").unwrap(),
+        "16"
+    );
+    let message = eval("filter([1], fn x -> x);").unwrap_err();
+    assert!(message.contains("not a bool"), "{message}");
+}
+
+#[test]
+fn sum_abs_and_sqrt_do_arithmetic() {
+    assert_eq!(eval("sum([1, 2, 3]);").unwrap(), "6");
+    assert_eq!(eval("abs(-3);").unwrap(), "3");
+    assert_eq!(eval("sqrt(9);").unwrap(), "3");
+    assert!(eval("sum([1, \"a\"]);").is_err());
+}
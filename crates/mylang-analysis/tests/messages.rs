@@ -0,0 +1,30 @@
+//! The text of diagnostic messages, in each locale.
+
+use mylang_analysis::Diagnostic;
+use mylang_analysis::diagnostics::codes;
+use mylang_analysis::document_diagnostics;
+use mylang_analysis::messages::Locale;
+
+#[test]
+fn expected_tokens_are_quoted_once() {
+    let mut diagnostics = document_diagnostics("print(1;\nlet f = fn x 1;\n");
+    let messages = |diagnostics: &[Diagnostic]| -> Vec<String> {
+        diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.code.as_deref() == Some(codes::EXPECTED_TOKEN))
+            .map(|diagnostic| diagnostic.message.clone().unwrap())
+            .collect()
+    };
+    assert_eq!(
+        messages(&diagnostics),
+        ["Expected ')', found: ;", "Expected '->', found: 1"]
+    );
+
+    for diagnostic in &mut diagnostics {
+        diagnostic.localize(Locale::German);
+    }
+    assert_eq!(
+        messages(&diagnostics),
+        ["')' erwartet, gefunden: ;", "'->' erwartet, gefunden: 1"]
+    );
+}
@@ -0,0 +1,261 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::ast::ExprKind;
+use super::baseline::Baseline;
+use super::config::ServerConfig;
+use super::diagnostics::{Diagnostic, DiagnosticData, Fix, Range, TextEdit, codes};
+use super::documents::{Document, DocumentStore};
+use super::lexer::Span;
+use super::line_index::LineIndex;
+use super::pull::TextDocumentIdentifier;
+use super::{refactor, reported_diagnostics};
+
+pub const FIX_ALL: &str = "source.fixAll";
+
+/// A refactoring offered for the selection, and why it may not apply.
+struct Refactoring {
+    title: &'static str,
+    kind: &'static str,
+    offer: fn(&Document, Span) -> Option<Value>,
+    unavailable: &'static str,
+}
+
+const REFACTORINGS: &[Refactoring] = &[
+    Refactoring {
+        title: "Extract into variable",
+        kind: "refactor.extract",
+        offer: refactor::extract_variable,
+        unavailable: "Select a whole expression whose names mean the same before its statement",
+    },
+    Refactoring {
+        title: "Extract into function",
+        kind: "refactor.extract",
+        offer: refactor::extract_function,
+        unavailable: "Select whole statements that leave at most one binding in use after them",
+    },
+    Refactoring {
+        title: "Inline variable",
+        kind: "refactor.inline",
+        offer: refactor::inline_variable,
+        unavailable: "Place the cursor on a variable used at most three times",
+    },
+    Refactoring {
+        title: "Convert to pipeline",
+        kind: "refactor.rewrite",
+        offer: refactor::calls_to_pipeline,
+        unavailable: "Place the cursor on at least two nested single-argument calls",
+    },
+    Refactoring {
+        title: "Convert to nested calls",
+        kind: "refactor.rewrite",
+        offer: refactor::pipeline_to_calls,
+        unavailable: "Place the cursor on a pipeline",
+    },
+];
+
+#[derive(Deserialize)]
+pub struct CodeActionParams {
+    #[serde(rename = "textDocument")]
+    pub text_document: TextDocumentIdentifier,
+    pub range: Range,
+    pub context: CodeActionContext,
+}
+
+#[derive(Deserialize)]
+pub struct CodeActionContext {
+    /// Kept as raw values so they can be echoed back exactly as received.
+    pub diagnostics: Vec<Value>,
+    /// Kinds the client asked for; every kind when absent.
+    #[serde(default)]
+    pub only: Option<Vec<String>>,
+}
+
+/// Turns the fixes stored in each diagnostic's `data` into quick fixes, and
+/// offers the refactorings that apply to the selected range and a fix-all
+/// for the whole document. When the client asked for refactorings by kind
+/// and can show disabled actions, the ones that do not apply are listed
+/// with the reason.
+pub fn code_actions(
+    documents: &DocumentStore,
+    config: &ServerConfig,
+    baseline: &Baseline,
+    params: CodeActionParams,
+    disabled_support: bool,
+) -> Value {
+    let uri = params.text_document.uri;
+    let only = params.context.only;
+    let wanted = |kind: &str| {
+        only.as_ref().is_none_or(|only| {
+            only.iter()
+                .any(|requested| kind == requested || kind.starts_with(&format!("{requested}.")))
+        })
+    };
+    let mut actions = Vec::new();
+
+    let diagnostics = if wanted("quickfix") {
+        params.context.diagnostics
+    } else {
+        Vec::new()
+    };
+    for diagnostic in diagnostics {
+        let Some(data) = diagnostic
+            .get("data")
+            .and_then(|data| serde_json::from_value::<DiagnosticData>(data.clone()).ok())
+        else {
+            continue;
+        };
+
+        for fix in data.fixes {
+            let mut action = json!({
+                "title": fix.title,
+                "kind": "quickfix",
+                "diagnostics": [diagnostic],
+                "edit": {
+                    "changes": { uri.clone(): fix.edits }
+                }
+            });
+            if fix.preferred {
+                action["isPreferred"] = json!(true);
+            }
+            actions.push(action);
+        }
+    }
+
+    if let Some(document) = documents.get(&uri) {
+        let index = LineIndex::new(&document.text);
+        let selection = Span::new(
+            index.offset(&params.range.start),
+            index.offset(&params.range.end),
+        );
+        for refactoring in REFACTORINGS
+            .iter()
+            .filter(|refactoring| wanted(refactoring.kind))
+        {
+            match (refactoring.offer)(document, selection) {
+                Some(action) => actions.push(action),
+                None if disabled_support && only.is_some() => actions.push(json!({
+                    "title": refactoring.title,
+                    "kind": refactoring.kind,
+                    "disabled": { "reason": refactoring.unavailable }
+                })),
+                None => {}
+            }
+        }
+        if wanted(FIX_ALL) {
+            let edits = fix_all(document, &reported_diagnostics(config, baseline, document));
+            if !edits.is_empty() {
+                actions.push(json!({
+                    "title": "Fix all auto-fixable problems",
+                    "kind": FIX_ALL,
+                    "edit": {
+                        "changes": { uri.clone(): edits }
+                    }
+                }));
+            }
+        }
+    }
+
+    json!(actions)
+}
+
+/// The edits of every preferred fix among `diagnostics`, sorted by
+/// position.
+pub fn fix_all(document: &Document, diagnostics: &[Diagnostic]) -> Vec<TextEdit> {
+    combine_fixes(document, diagnostics, |_, fix| fix.preferred)
+}
+
+/// The edits of every fix that is safe to apply unattended: the preferred
+/// ones, plus dealing with unused bindings. Used by the `fix` subcommand,
+/// where the result is reviewed as a whole rather than fix by fix. A binding
+/// is only removed when computing its value calls nothing; otherwise it is
+/// renamed so the call still happens.
+pub fn safe_fixes(document: &Document, diagnostics: &[Diagnostic]) -> Vec<TextEdit> {
+    combine_fixes(document, diagnostics, |diagnostic, fix| {
+        if fix.preferred {
+            return true;
+        }
+        if diagnostic.code.as_deref() != Some(codes::UNUSED_VARIABLE) {
+            return false;
+        }
+        let removes = fix.edits.iter().all(|edit| edit.new_text.is_empty());
+        let renames = fix.edits.iter().all(|edit| edit.new_text == "_");
+        let removable = removable(document, diagnostic);
+        (removes && removable) || (renames && !removable)
+    })
+}
+
+/// Whether the unused binding `diagnostic` reports can go without taking
+/// anything else with it: its value, or a function's body, is a literal or
+/// a name.
+fn removable(document: &Document, diagnostic: &Diagnostic) -> bool {
+    let analysis = document.analysis();
+    let start = LineIndex::new(&document.text).offset(&diagnostic.range.start);
+    let Some(statement) = analysis
+        .symbols
+        .symbols
+        .iter()
+        .find(|symbol| symbol.span.is_some_and(|span| span.start == start))
+        .and_then(|symbol| symbol.statement)
+    else {
+        return false;
+    };
+    refactor::let_statement(&analysis.program.statements, statement)
+        .and_then(|statement| statement.value.as_ref())
+        .is_some_and(|value| {
+            matches!(
+                value.kind,
+                ExprKind::Number(_) | ExprKind::String(_) | ExprKind::Bool(_) | ExprKind::Name(_)
+            )
+        })
+}
+
+/// The edits of the fixes `accept` picks among `diagnostics`, sorted by
+/// position. Fixes are taken in order of where they start, and one whose
+/// edits would overlap an already accepted edit is dropped whole, so the
+/// result does not depend on the order diagnostics were reported in.
+fn combine_fixes(
+    document: &Document,
+    diagnostics: &[Diagnostic],
+    accept: impl Fn(&Diagnostic, &Fix) -> bool,
+) -> Vec<TextEdit> {
+    let index = LineIndex::new(&document.text);
+    let span = |edit: &TextEdit| {
+        Span::new(
+            index.offset(&edit.range.start),
+            index.offset(&edit.range.end),
+        )
+    };
+
+    let mut fixes: Vec<(Vec<Span>, &str, &Vec<TextEdit>)> = diagnostics
+        .iter()
+        .filter_map(|diagnostic| Some((diagnostic, diagnostic.data.as_ref()?)))
+        .flat_map(|(diagnostic, data)| data.fixes.iter().map(move |fix| (diagnostic, fix)))
+        .filter(|(diagnostic, fix)| accept(diagnostic, fix) && !fix.edits.is_empty())
+        .map(|(_, fix)| fix)
+        .map(|fix| {
+            let mut spans: Vec<Span> = fix.edits.iter().map(span).collect();
+            spans.sort_by_key(|span| (span.start, span.end));
+            (spans, fix.title.as_str(), &fix.edits)
+        })
+        .collect();
+    fixes.sort_by(|(a, a_title, _), (b, b_title, _)| {
+        (a[0].start, a[0].end, a_title).cmp(&(b[0].start, b[0].end, b_title))
+    });
+
+    // Two edits at the same offset are a conflict too: which goes first
+    // would change the result.
+    let overlaps = |a: &Span, b: &Span| a.start == b.start || (a.start < b.end && b.start < a.end);
+    let mut accepted: Vec<(Span, TextEdit)> = Vec::new();
+    for (spans, _, edits) in fixes {
+        if spans
+            .iter()
+            .any(|span| accepted.iter().any(|(taken, _)| overlaps(span, taken)))
+        {
+            continue;
+        }
+        accepted.extend(edits.iter().map(|edit| (span(edit), edit.clone())));
+    }
+    accepted.sort_by_key(|(span, _)| (span.start, span.end));
+    accepted.into_iter().map(|(_, edit)| edit).collect()
+}
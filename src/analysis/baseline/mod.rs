@@ -0,0 +1,189 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::diagnostics::Diagnostic;
+use super::line_index::LineIndex;
+
+pub const DEFAULT_BASELINE_FILE: &str = "mylang-baseline.json";
+
+/// A recorded set of known diagnostics. Problems listed here are suppressed,
+/// so only issues introduced after the baseline was taken are reported.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Baseline {
+    entries: Vec<BaselineEntry>,
+    /// Directory the entry paths are relative to.
+    #[serde(skip)]
+    root: PathBuf,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+struct BaselineEntry {
+    file: String,
+    code: String,
+    line: u32,
+    /// The trimmed source line the diagnostic started on. Matching on this
+    /// instead of the line number keeps entries valid when code above moves.
+    snippet: String,
+}
+
+impl Baseline {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            entries: Vec::new(),
+            root,
+        }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let mut baseline: Baseline = serde_json::from_str(&content)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        // A bare file name is relative to the current directory.
+        baseline.root = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .map_or_else(|| PathBuf::from("."), Path::to_path_buf);
+        Ok(baseline)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let content = serde_json::to_string_pretty(self).unwrap();
+        fs::write(path, content + "\n")
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn record(&mut self, path: &Path, text: &str, diagnostics: &[Diagnostic]) {
+        let file = self.relative(path);
+        let index = LineIndex::new(text);
+        for diagnostic in diagnostics {
+            if let Some(code) = &diagnostic.code {
+                let line = diagnostic.range.start.line;
+                self.entries.push(BaselineEntry {
+                    file: file.clone(),
+                    code: code.clone(),
+                    line,
+                    snippet: index.line_text(line).trim().to_string(),
+                });
+            }
+        }
+    }
+
+    /// Removes the diagnostics that are covered by the baseline. Each entry
+    /// suppresses at most one diagnostic, preferring the one closest to the
+    /// recorded line, so a second copy of a known problem still surfaces.
+    pub fn filter(&self, path: &Path, text: &str, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        if self.entries.is_empty() {
+            return diagnostics;
+        }
+
+        let file = self.relative(path);
+        let index = LineIndex::new(text);
+        let mut remaining: Vec<&BaselineEntry> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.file == file)
+            .collect();
+
+        diagnostics
+            .into_iter()
+            .filter(|diagnostic| {
+                let Some(code) = &diagnostic.code else {
+                    return true;
+                };
+                let line = diagnostic.range.start.line;
+                let snippet = index.line_text(line).trim();
+
+                let matched = remaining
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, entry)| entry.code == *code && entry.snippet == snippet)
+                    .min_by_key(|(_, entry)| entry.line.abs_diff(line))
+                    .map(|(index, _)| index);
+
+                match matched {
+                    Some(index) => {
+                        remaining.remove(index);
+                        false
+                    }
+                    None => true,
+                }
+            })
+            .collect()
+    }
+
+    fn relative(&self, path: &Path) -> String {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let root = fs::canonicalize(&self.root).unwrap_or_else(|_| self.root.clone());
+        let relative = path.strip_prefix(&root).unwrap_or(&path);
+        relative.to_string_lossy().replace('\\', "/")
+    }
+}
+
+/// Turns a filesystem path into a `file://` uri, the inverse of
+/// [`uri_to_path`]. Bytes other than letters, digits, `-._~`, `/` and the
+/// drive colon are percent-encoded.
+pub fn path_to_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    if encoded.starts_with('/') {
+        format!("file://{}", encoded)
+    } else {
+        format!("file:///{}", encoded)
+    }
+}
+
+/// Turns a `file://` uri into a filesystem path, decoding every
+/// percent-encoded byte.
+pub fn uri_to_path(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    let path = percent_decode(path);
+    // `file:///C:/...` on Windows carries a leading slash before the drive.
+    match path.as_bytes() {
+        [b'/', _, b':', ..] => PathBuf::from(&path[1..]),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// `text` with each `%XX` replaced by the byte it stands for. A `%` not
+/// followed by two hex digits is kept as is.
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let byte = bytes
+            .get(i + 1..i + 3)
+            .filter(|hex| bytes[i] == b'%' && hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match byte {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
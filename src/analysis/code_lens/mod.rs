@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use super::commands::{self, RUN};
+use super::documents::DocumentStore;
+use super::line_index::LineIndex;
+use super::pull::TextDocumentIdentifier;
+use super::resolver::{FILE_SCOPE, SymbolId, SymbolKind};
+
+/// Client-side command that opens the references view, called with the
+/// document uri, the declaration's position and the locations to list.
+pub const SHOW_REFERENCES: &str = "mylang.showReferences";
+
+#[derive(Deserialize)]
+pub struct CodeLensParams {
+    #[serde(rename = "textDocument")]
+    pub text_document: TextDocumentIdentifier,
+}
+
+/// Carried in each lens's `data` so `codeLens/resolve` can find the symbol
+/// again.
+#[derive(Serialize, Deserialize)]
+struct CodeLensData {
+    uri: String,
+    version: u32,
+    symbol: SymbolId,
+}
+
+/// Handles `textDocument/codeLens`, placing an unresolved reference count
+/// above every top-level function and binding. Counting is left to
+/// `codeLens/resolve`, so only the lenses on screen pay for it. `main` and
+/// test functions also get a Run lens.
+pub fn code_lens(documents: &DocumentStore, params: CodeLensParams) -> Value {
+    let Some(document) = documents.get(&params.text_document.uri) else {
+        return Value::Null;
+    };
+
+    let index = LineIndex::new(&document.text);
+    let symbols = &document.analysis().symbols;
+    let mut lenses = Vec::new();
+    for (id, symbol) in symbols.symbols.iter().enumerate() {
+        let Some(span) = symbol.span else {
+            continue;
+        };
+        if symbol.scope != FILE_SCOPE
+            || !matches!(symbol.kind, SymbolKind::Function | SymbolKind::Variable)
+        {
+            continue;
+        }
+        let range = index.range(span);
+        if symbol.kind == SymbolKind::Function && commands::is_entry_point(&symbol.name) {
+            lenses.push(json!({
+                "range": range,
+                "command": {
+                    "title": "Run",
+                    "command": RUN,
+                    "arguments": [document.uri, symbol.name]
+                }
+            }));
+        }
+        lenses.push(json!({
+            "range": range,
+            "data": CodeLensData {
+                uri: document.uri.clone(),
+                version: document.version,
+                symbol: id,
+            }
+        }));
+    }
+    json!(lenses)
+}
+
+/// Handles `codeLens/resolve`, counting the uses of the lens's symbol.
+/// Lenses from an outdated version are returned unchanged.
+pub fn resolve(documents: &DocumentStore, mut lens: Value) -> Value {
+    let Some(data) = lens
+        .get("data")
+        .and_then(|data| serde_json::from_value::<CodeLensData>(data.clone()).ok())
+    else {
+        return lens;
+    };
+    let Some(document) = documents.get(&data.uri) else {
+        return lens;
+    };
+    if document.version != data.version {
+        return lens;
+    }
+    let symbols = &document.analysis().symbols;
+    let Some(span) = symbols
+        .symbols
+        .get(data.symbol)
+        .and_then(|symbol| symbol.span)
+    else {
+        return lens;
+    };
+
+    let index = LineIndex::new(&document.text);
+    let uses = symbols.uses_of(data.symbol);
+    let title = match uses.len() {
+        1 => "1 reference".to_string(),
+        count => format!("{count} references"),
+    };
+    let locations: Vec<Value> = uses
+        .into_iter()
+        .map(|span| json!({ "uri": document.uri, "range": index.range(span) }))
+        .collect();
+    lens["command"] = json!({
+        "title": title,
+        "command": SHOW_REFERENCES,
+        "arguments": [document.uri, index.position(span.start), locations]
+    });
+    lens
+}
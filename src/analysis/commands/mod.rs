@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use std::env;
+use std::io::Read;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::actions;
+use super::baseline::{Baseline, uri_to_path};
+use super::config::ServerConfig;
+use super::documents::DocumentStore;
+use super::{Event, reported_diagnostics};
+
+/// Asks the client to apply a workspace edit, with a label and the edit as
+/// arguments.
+pub const APPLY_FIX: &str = "mylang.applyFix";
+/// Applies every preferred fix in the document whose uri is the argument.
+pub const FIX_ALL: &str = "mylang.fixAll";
+/// Runs a function of a file, with the document uri and function name as
+/// arguments.
+pub const RUN: &str = "mylang.run";
+/// Runs the `main` function of the document whose uri is the argument.
+pub const RUN_MAIN: &str = "mylang.runMain";
+/// Returns an outline of the parsed document whose uri is the argument.
+pub const SHOW_SYNTAX_TREE: &str = "mylang.showSyntaxTree";
+
+/// A command the server carries out through `workspace/executeCommand`.
+pub struct ServerCommand {
+    pub id: &'static str,
+    pub run: fn(&mut CommandContext, &[Value]) -> Result<Value, String>,
+}
+
+/// Every command handled through `workspace/executeCommand`.
+pub const COMMANDS: &[ServerCommand] = &[
+    ServerCommand {
+        id: APPLY_FIX,
+        run: apply_fix,
+    },
+    ServerCommand {
+        id: FIX_ALL,
+        run: fix_all,
+    },
+    ServerCommand { id: RUN, run },
+    ServerCommand {
+        id: RUN_MAIN,
+        run: run_main,
+    },
+    ServerCommand {
+        id: SHOW_SYNTAX_TREE,
+        run: show_syntax_tree,
+    },
+];
+
+/// What a command can see and touch while it runs.
+pub struct CommandContext<'a> {
+    pub documents: &'a DocumentStore,
+    pub config: &'a ServerConfig,
+    pub baseline: &'a Baseline,
+    /// Lets commands that outlive the request report back later.
+    pub events: Option<Sender<Event>>,
+    /// The id of the `workspace/executeCommand` request.
+    pub id: &'a Value,
+    /// The runs still going, which a started run joins.
+    pub runs: &'a Runs,
+    /// Messages to send after the response, such as `workspace/applyEdit`.
+    pub outgoing: Vec<Value>,
+}
+
+/// `MessageType` values from the protocol.
+const ERROR: u8 = 1;
+const INFO: u8 = 3;
+
+/// Numbers the `workspace/applyEdit` requests the server sends.
+static NEXT_EDIT: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Deserialize)]
+pub struct ExecuteCommandParams {
+    pub command: String,
+    #[serde(default)]
+    pub arguments: Vec<Value>,
+}
+
+/// The ids to advertise in `executeCommandProvider`.
+pub fn ids() -> Vec<&'static str> {
+    COMMANDS.iter().map(|command| command.id).collect()
+}
+
+/// Whether a top-level function named `name` gets a Run lens.
+pub fn is_entry_point(name: &str) -> bool {
+    name == "main" || name.starts_with("test_")
+}
+
+/// Handles `workspace/executeCommand` by dispatching to the registered
+/// command.
+pub fn execute_command(
+    context: &mut CommandContext,
+    params: ExecuteCommandParams,
+) -> Result<Value, String> {
+    let command = COMMANDS
+        .iter()
+        .find(|command| command.id == params.command)
+        .ok_or_else(|| format!("Unknown command '{}'", params.command))?;
+    (command.run)(context, &params.arguments)
+}
+
+fn string_argument<'a>(
+    arguments: &'a [Value],
+    at: usize,
+    command: &str,
+) -> Result<&'a str, String> {
+    arguments
+        .get(at)
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("{command} expects a string as argument {}", at + 1))
+}
+
+fn apply_fix(context: &mut CommandContext, arguments: &[Value]) -> Result<Value, String> {
+    let label = string_argument(arguments, 0, APPLY_FIX)?;
+    let Some(edit) = arguments.get(1).filter(|edit| edit.is_object()) else {
+        return Err(format!(
+            "{APPLY_FIX} expects a workspace edit as argument 2"
+        ));
+    };
+    request_edit(context, label, edit.clone());
+    Ok(Value::Null)
+}
+
+fn fix_all(context: &mut CommandContext, arguments: &[Value]) -> Result<Value, String> {
+    let uri = string_argument(arguments, 0, FIX_ALL)?;
+    let document = context
+        .documents
+        .get(uri)
+        .ok_or_else(|| format!("{uri} is not open"))?;
+    let diagnostics = reported_diagnostics(context.config, context.baseline, document);
+    let edits = actions::fix_all(document, &diagnostics);
+    if !edits.is_empty() {
+        request_edit(
+            context,
+            "Fix all auto-fixable problems",
+            json!({ "changes": { uri: edits } }),
+        );
+    }
+    Ok(Value::Null)
+}
+
+fn request_edit(context: &mut CommandContext, label: &str, edit: Value) {
+    context.outgoing.push(json!({
+        "jsonrpc": "2.0",
+        "id": format!("mylang/applyEdit/{}", NEXT_EDIT.fetch_add(1, Ordering::Relaxed)),
+        "method": "workspace/applyEdit",
+        "params": {
+            "label": label,
+            "edit": edit
+        }
+    }));
+}
+
+fn run_main(context: &mut CommandContext, arguments: &[Value]) -> Result<Value, String> {
+    let uri = string_argument(arguments, 0, RUN_MAIN)?;
+    run(context, &[json!(uri), json!("main")])
+}
+
+/// The runs the Run lens started and that are still going, by the id of
+/// the request that started them, so `$/cancelRequest` and `shutdown` can
+/// stop them.
+#[derive(Clone, Default)]
+pub struct Runs(Arc<Mutex<HashMap<String, Child>>>);
+
+impl Runs {
+    /// Kills the run the request `id` started, if it is still going.
+    pub fn cancel(&self, id: &Value) {
+        let child = self.0.lock().unwrap().remove(&id.to_string());
+        if let Some(child) = child {
+            kill(child);
+        }
+    }
+
+    /// Kills every run still going.
+    pub fn cancel_all(&self) {
+        let children: Vec<Child> = self
+            .0
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|(_, child)| child)
+            .collect();
+        children.into_iter().for_each(kill);
+    }
+}
+
+fn kill(mut child: Child) {
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Runs happen in a child process and report through `window/logMessage`,
+/// so the request returns as soon as the run has started. The configured
+/// runner is used when there is one, and the server's own `run` subcommand
+/// otherwise; either way the file on disk is what runs.
+fn run(context: &mut CommandContext, arguments: &[Value]) -> Result<Value, String> {
+    let uri = string_argument(arguments, 0, RUN)?;
+    let name = string_argument(arguments, 1, RUN)?;
+    let Some(events) = context.events.clone() else {
+        return Ok(Value::Null);
+    };
+
+    let path = uri_to_path(uri);
+    let label = format!("{} {}", path.display(), name);
+    let mut command = match context.config.runner.split_first() {
+        Some((program, args)) => {
+            let mut command = Command::new(program);
+            command.args(args);
+            command
+        }
+        None => {
+            let program = env::current_exe()
+                .map_err(|e| format!("Could not find the server executable: {e}"))?;
+            let mut command = Command::new(program);
+            command.arg("run");
+            command
+        }
+    };
+    let mut child = command
+        .arg(&path)
+        .arg(name)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Could not start the runner: {e}"))?;
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    let key = context.id.to_string();
+    context.runs.0.lock().unwrap().insert(key.clone(), child);
+
+    let runs = context.runs.clone();
+    thread::spawn(move || {
+        let log = |kind: u8, message: String| {
+            let notification = json!({
+                "jsonrpc": "2.0",
+                "method": "window/logMessage",
+                "params": { "type": kind, "message": message }
+            });
+            let _ = events.send(Event::Outgoing(notification.to_string()));
+        };
+
+        log(INFO, format!("Running {label}"));
+        // Read on its own thread, so neither pipe fills while the other is
+        // being read.
+        let stderr = thread::spawn(move || read_all(stderr));
+        let stdout = read_all(stdout);
+        let stderr = stderr.join().unwrap_or_default();
+        if !stdout.trim().is_empty() {
+            log(INFO, stdout.trim_end().to_string());
+        }
+        if !stderr.trim().is_empty() {
+            log(ERROR, stderr.trim_end().to_string());
+        }
+
+        // A cancelled run has already been killed and taken out.
+        let child = runs.0.lock().unwrap().remove(&key);
+        match child.map(|mut child| child.wait()) {
+            None => log(INFO, format!("{label} was cancelled")),
+            Some(Ok(status)) if status.success() => log(INFO, format!("{label} finished")),
+            Some(Ok(status)) => log(ERROR, format!("{label} failed: {status}")),
+            Some(Err(e)) => log(ERROR, format!("{label} failed: {e}")),
+        }
+    });
+    Ok(Value::Null)
+}
+
+fn read_all(pipe: Option<impl Read>) -> String {
+    let mut bytes = Vec::new();
+    if let Some(mut pipe) = pipe {
+        let _ = pipe.read_to_end(&mut bytes);
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn show_syntax_tree(context: &mut CommandContext, arguments: &[Value]) -> Result<Value, String> {
+    let uri = string_argument(arguments, 0, SHOW_SYNTAX_TREE)?;
+    let document = context
+        .documents
+        .get(uri)
+        .ok_or_else(|| format!("{uri} is not open"))?;
+    Ok(json!(document.analysis().program.dump()))
+}
@@ -0,0 +1,390 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use super::builtins;
+use super::diagnostics::Position;
+use super::docs::doc_comment;
+use super::documents::{Document, DocumentStore};
+use super::fuzzy;
+use super::lexer::{Token, TokenType};
+use super::line_index::LineIndex;
+use super::pull::TextDocumentIdentifier;
+use super::resolver::{
+    BUILTIN_SCOPE, FILE_SCOPE, ScopeId, Symbol, SymbolId, SymbolKind, SymbolTable,
+};
+use super::types::Type;
+
+/// `CompletionItemKind` values from the protocol.
+const FUNCTION_KIND: u8 = 3;
+const FIELD_KIND: u8 = 5;
+const VARIABLE_KIND: u8 = 6;
+const KEYWORD_KIND: u8 = 14;
+
+/// Proximity of file-level names, keywords and builtins, in that order;
+/// locals rank by scope distance ahead of all of them.
+const FILE_PROXIMITY: u32 = 1_000;
+const KEYWORD_PROXIMITY: u32 = 1_001;
+const BUILTIN_PROXIMITY: u32 = 1_002;
+
+/// Keywords that can start a statement.
+const STATEMENT_KEYWORDS: &[&str] = &["let", "if", "fn", "true", "false"];
+/// Keywords that can start an expression.
+const EXPRESSION_KEYWORDS: &[&str] = &["if", "fn", "true", "false"];
+
+#[derive(Deserialize)]
+pub struct CompletionParams {
+    #[serde(rename = "textDocument")]
+    pub text_document: TextDocumentIdentifier,
+    pub position: Position,
+    pub context: Option<CompletionContext>,
+}
+
+#[derive(Deserialize)]
+pub struct CompletionContext {
+    #[serde(rename = "triggerCharacter")]
+    pub trigger_character: Option<String>,
+}
+
+/// Characters that open the completion list without an explicit request.
+/// `>` is only meaningful as the end of `|>`.
+pub const TRIGGER_CHARACTERS: &[&str] = &[".", ">"];
+
+/// Carried in each item's `data` so `completionItem/resolve` can find the
+/// symbol again.
+#[derive(Serialize, Deserialize)]
+struct CompletionData {
+    uri: String,
+    version: u32,
+    symbol: SymbolId,
+}
+
+/// Handles `textDocument/completion`, offering every name visible at the
+/// cursor: bindings, parameters, functions and builtins, followed by the
+/// keywords that fit there. After `.` only the receiver's members are
+/// offered, and after `|>` only functions the piped value can be passed to.
+pub fn completion(documents: &DocumentStore, params: CompletionParams) -> Value {
+    let Some(document) = documents.get(&params.text_document.uri) else {
+        return Value::Null;
+    };
+
+    let offset = LineIndex::new(&document.text).offset(&params.position);
+    let analysis = document.analysis();
+    let symbols = &analysis.symbols;
+    let before = tokens_before(&analysis.tokens, offset);
+    // The type of whatever precedes the `.` or `|>` before the cursor.
+    let operand_type = || match before.len() {
+        0 | 1 => Type::Unknown,
+        n => symbols
+            .type_ending_at(before[n - 2].span.end)
+            .unwrap_or_default(),
+    };
+
+    let items = match before.last().map(|token| token.token_type) {
+        Some(TokenType::DOT) => member_items(operand_type()),
+        Some(TokenType::PIPE) => {
+            let piped = operand_type();
+            let mut items = symbol_items(document, offset);
+            items.retain(|(_, item)| accepts(item, piped));
+            items
+        }
+        _ if is_trigger(&params, ">") => Vec::new(),
+        _ => {
+            let mut items = symbol_items(document, offset);
+            items.extend(keywords(&before).into_iter().map(|keyword| {
+                let item = json!({
+                    "label": keyword,
+                    "kind": KEYWORD_KIND,
+                    "detail": "keyword"
+                });
+                (KEYWORD_PROXIMITY, item)
+            }));
+            items
+        }
+    };
+
+    let prefix = typed_prefix(&document.text, &analysis.tokens, offset);
+    json!({
+        // Matches are filtered here, so the list changes as the word grows.
+        "isIncomplete": !prefix.is_empty(),
+        "items": rank(prefix, items)
+    })
+}
+
+/// The part of the word under the cursor that has already been typed.
+fn typed_prefix<'a>(text: &'a str, tokens: &[Token], offset: usize) -> &'a str {
+    tokens
+        .iter()
+        .find(|token| token.span.end == offset && is_word(token.token_type))
+        .map_or("", |token| &text[token.span.start..offset])
+}
+
+/// Drops items that do not fuzzy match `prefix` and orders the rest through
+/// `sortText`: by match tier, then by proximity, then by match quality.
+/// `filterText` is set to the prefix so the editor does not filter out
+/// matches it would not have found itself.
+fn rank(prefix: &str, items: Vec<(u32, Value)>) -> Vec<Value> {
+    let mut scored: Vec<(u32, u32, Value)> = items
+        .into_iter()
+        .filter_map(|(proximity, item)| {
+            let score = fuzzy::score(prefix, item["label"].as_str()?)?;
+            Some((score, proximity, item))
+        })
+        .collect();
+    scored.sort_by(|(a, a_proximity, first), (b, b_proximity, second)| {
+        fuzzy::tier(*b)
+            .cmp(&fuzzy::tier(*a))
+            .then(a_proximity.cmp(b_proximity))
+            .then(b.cmp(a))
+            .then_with(|| first["label"].as_str().cmp(&second["label"].as_str()))
+    });
+
+    scored
+        .into_iter()
+        .enumerate()
+        .map(|(rank, (_, _, mut item))| {
+            item["sortText"] = json!(format!("{:05}", rank));
+            if !prefix.is_empty() {
+                item["filterText"] = json!(prefix);
+            }
+            item
+        })
+        .collect()
+}
+
+fn is_trigger(params: &CompletionParams, character: &str) -> bool {
+    params
+        .context
+        .as_ref()
+        .and_then(|context| context.trigger_character.as_deref())
+        == Some(character)
+}
+
+/// Completion items for the names visible at `offset`, each paired with
+/// its proximity.
+fn symbol_items(document: &Document, offset: usize) -> Vec<(u32, Value)> {
+    let symbols = &document.analysis().symbols;
+    let scope = symbols.scope_at(offset);
+    symbols
+        .visible_at(offset)
+        .into_iter()
+        .map(|id| {
+            let symbol = &symbols.symbols[id];
+            let mut item = symbol_item(symbol);
+            item["data"] = json!(CompletionData {
+                uri: document.uri.clone(),
+                version: document.version,
+                symbol: id,
+            });
+            (proximity(symbols, scope, symbol), item)
+        })
+        .collect()
+}
+
+/// How far a symbol is from the cursor's scope; lower is closer. Locals
+/// count the scopes between them and the cursor, and everything at file
+/// level ranks before the builtins.
+fn proximity(symbols: &SymbolTable, from: ScopeId, symbol: &Symbol) -> u32 {
+    match symbol.scope {
+        BUILTIN_SCOPE => BUILTIN_PROXIMITY,
+        FILE_SCOPE => FILE_PROXIMITY,
+        scope => symbols.scope_distance(from, scope).unwrap_or(0) as u32,
+    }
+}
+
+fn member_items(owner: Type) -> Vec<(u32, Value)> {
+    builtins::members(owner)
+        .map(|member| {
+            let item = json!({
+                "label": member.name,
+                "kind": FIELD_KIND,
+                "detail": member.ty.name(),
+                "documentation": {
+                    "kind": "markdown",
+                    "value": member.doc
+                }
+            });
+            (0, item)
+        })
+        .collect()
+}
+
+/// Whether the completion `item` names a function that takes a value of
+/// type `piped` as its first argument.
+fn accepts(item: &Value, piped: Type) -> bool {
+    if item["kind"] != json!(FUNCTION_KIND) && item["detail"] != json!(Type::Function.name()) {
+        return false;
+    }
+    let input = item["label"]
+        .as_str()
+        .and_then(builtins::lookup)
+        .map_or(Type::Unknown, |builtin| builtin.input);
+    input == Type::Unknown || piped == Type::Unknown || input == piped
+}
+
+/// Handles `completionItem/resolve`, adding the documentation left out of
+/// the initial list. Items from an outdated version are returned unchanged.
+pub fn resolve(documents: &DocumentStore, mut item: Value) -> Value {
+    let Some(data) = item
+        .get("data")
+        .and_then(|data| serde_json::from_value::<CompletionData>(data.clone()).ok())
+    else {
+        return item;
+    };
+    let Some(document) = documents.get(&data.uri) else {
+        return item;
+    };
+    if document.version != data.version {
+        return item;
+    }
+    let Some(symbol) = document.analysis().symbols.symbols.get(data.symbol) else {
+        return item;
+    };
+
+    if let Some(documentation) = documentation(&document.text, symbol) {
+        item["documentation"] = json!({
+            "kind": "markdown",
+            "value": documentation
+        });
+    }
+    item
+}
+
+/// The cheap parts of an item; documentation is filled in on resolve.
+fn symbol_item(symbol: &Symbol) -> Value {
+    let mut item = json!({ "label": symbol.name });
+
+    let kind = match symbol.kind {
+        SymbolKind::Builtin if !matches!(symbol.ty, Type::Function | Type::Unknown) => {
+            VARIABLE_KIND
+        }
+        SymbolKind::Function | SymbolKind::Builtin => FUNCTION_KIND,
+        SymbolKind::Variable | SymbolKind::Parameter => VARIABLE_KIND,
+    };
+    item["kind"] = json!(kind);
+
+    if let Some(detail) = detail(symbol) {
+        item["detail"] = json!(detail);
+    }
+
+    item
+}
+
+/// The builtin's description, the signature a global was declared with, or
+/// the doc comment above the declaration.
+pub fn documentation(text: &str, symbol: &Symbol) -> Option<String> {
+    match symbol.kind {
+        SymbolKind::Builtin => match &symbol.signature {
+            Some(signature) => Some(format!(
+                "Provided by the host environment as `{signature}`."
+            )),
+            None => builtins::lookup(&symbol.name).map(|builtin| builtin.doc.to_string()),
+        },
+        _ => symbol
+            .statement
+            .and_then(|statement| doc_comment(text, statement.start)),
+    }
+}
+
+/// The signature of a function, written like a lambda, or the inferred type
+/// of a value.
+pub fn detail(symbol: &Symbol) -> Option<String> {
+    match symbol.kind {
+        SymbolKind::Function | SymbolKind::Builtin
+            if symbol.kind == SymbolKind::Function || symbol.ty == Type::Function =>
+        {
+            let mut signature = String::from("fn");
+            for param in &symbol.params {
+                signature.push(' ');
+                signature.push_str(param);
+            }
+            Some(signature)
+        }
+        _ if symbol.ty == Type::Unknown => None,
+        _ => Some(symbol.ty.name().to_string()),
+    }
+}
+
+/// The tokens before the word under the cursor.
+fn tokens_before(tokens: &[Token], offset: usize) -> Vec<&Token> {
+    let mut before: Vec<&Token> = tokens
+        .iter()
+        .filter(|token| token.token_type != TokenType::EOF && token.span.end <= offset)
+        .collect();
+
+    // A word that ends at the cursor is the one being completed.
+    if before
+        .last()
+        .is_some_and(|token| token.span.end == offset && is_word(token.token_type))
+    {
+        before.pop();
+    }
+    before
+}
+
+/// The keywords that can be typed after `before`, judged by its last token.
+fn keywords(before: &[&Token]) -> Vec<&'static str> {
+    let Some(previous) = before.last() else {
+        return STATEMENT_KEYWORDS.to_vec();
+    };
+
+    match previous.token_type {
+        TokenType::SEMICOLON | TokenType::LeftBrace => STATEMENT_KEYWORDS.to_vec(),
+        TokenType::RightBrace if closes_if_branch(before) => vec!["else"],
+        TokenType::ELSE => vec!["if"],
+        // Operands, names being declared and closed blocks cannot be
+        // followed by the start of a new expression.
+        TokenType::IDENTIFIER
+        | TokenType::NUMBER
+        | TokenType::STRING
+        | TokenType::TRUE
+        | TokenType::FALSE
+        | TokenType::RightParen
+        | TokenType::RightBracket
+        | TokenType::RightBrace
+        | TokenType::LET
+        | TokenType::FN => Vec::new(),
+        _ => EXPRESSION_KEYWORDS.to_vec(),
+    }
+}
+
+fn is_word(token_type: TokenType) -> bool {
+    matches!(
+        token_type,
+        TokenType::IDENTIFIER
+            | TokenType::LET
+            | TokenType::IF
+            | TokenType::ELSE
+            | TokenType::FN
+            | TokenType::TRUE
+            | TokenType::FALSE
+    )
+}
+
+/// Whether the `}` ending `tokens` closes the block of an `if`, by walking
+/// back over the block and its condition to the keyword.
+fn closes_if_branch(tokens: &[&Token]) -> bool {
+    let mut depth = 0;
+    let mut in_condition = false;
+
+    for token in tokens.iter().rev() {
+        match token.token_type {
+            TokenType::RightBrace | TokenType::RightParen | TokenType::RightBracket => depth += 1,
+            TokenType::LeftBrace | TokenType::LeftParen | TokenType::LeftBracket => {
+                if depth == 0 {
+                    return false;
+                }
+                depth -= 1;
+                if depth == 0 && !in_condition {
+                    // The block just walked over; the condition comes next.
+                    in_condition = true;
+                }
+            }
+            TokenType::IF if depth == 0 => return in_condition,
+            TokenType::SEMICOLON | TokenType::LET | TokenType::ELSE if depth == 0 => {
+                return false;
+            }
+            _ => {}
+        }
+    }
+    false
+}
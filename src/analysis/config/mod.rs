@@ -0,0 +1,491 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::builtins::Global;
+use super::diagnostics::{Diagnostic, DiagnosticSeverity};
+use super::format::FormattingOptions;
+use super::messages::Locale;
+use super::workspace::{self, Profile};
+
+/// Project settings, read from the workspace root. Settings the client
+/// sends take precedence over it.
+pub const CONFIG_FILE: &str = "mylang.json";
+
+/// Project settings in TOML, found by searching upward from the workspace
+/// root. `mylang.json` and the client's settings take precedence over it.
+/// The user's own settings use the same format.
+pub const WORKSPACE_CONFIG_FILE: &str = "mylang-lsp.toml";
+
+/// Resolved settings for one analysis run.
+#[derive(Clone, Default)]
+pub struct ServerConfig {
+    pub profile: Profile,
+    pub locale: Locale,
+    pub format: FormattingOptions,
+    /// Program and leading arguments that run a function; the file path
+    /// and function name are appended. Empty runs it with the built-in
+    /// interpreter.
+    pub runner: Vec<String>,
+    /// Severity overrides by diagnostic code; `None` turns the code off.
+    pub severities: HashMap<String, Option<DiagnosticSeverity>>,
+    /// Names the host environment defines, which are not reported as
+    /// unknown.
+    pub globals: Arc<[Global]>,
+    /// Globs for files that get no diagnostics and are not indexed.
+    pub exclude: Vec<String>,
+    /// The directory `exclude` globs are relative to.
+    pub exclude_root: Option<PathBuf>,
+    /// Features turned on or off by capability name, such as
+    /// `semanticTokens`. Everything is on unless turned off.
+    pub features: HashMap<String, bool>,
+    /// Whether every response is followed by a log message saying where
+    /// the time went.
+    pub profiling: bool,
+}
+
+/// The settings clients may send, either as `initializationOptions` or under
+/// the `mylang` section of `workspace/didChangeConfiguration`.
+#[derive(Deserialize, Default, Clone)]
+pub struct ConfigUpdate {
+    pub profile: Option<Profile>,
+    /// Language tag for diagnostic messages, such as `de`.
+    pub locale: Option<String>,
+    /// Formatting options that win over the ones the editor sends.
+    pub format: Option<FormattingOptions>,
+    /// Command used by the Run lens, such as `["mylang", "run"]`.
+    pub runner: Option<Vec<String>>,
+    /// Lints turned on or off by code, such as `unused-variable = false`.
+    pub lints: Option<HashMap<String, bool>>,
+    /// Severities by code, such as `unknown-identifier = "warning"`.
+    pub severities: Option<HashMap<String, Level>>,
+    /// Globals an embedding host defines, as signatures such as
+    /// `"fetch(url: string) -> string"` or just names.
+    pub builtins: Option<Vec<Global>>,
+    /// Globs for files to leave alone, such as `vendor/**`.
+    pub exclude: Option<Vec<String>>,
+    /// Features turned on or off by the capability that advertises them,
+    /// without its `Provider` suffix, such as `inlayHint = false`.
+    pub features: Option<HashMap<String, bool>>,
+    /// Logs the time each request took, split into the analysis stages.
+    pub profiling: Option<bool>,
+}
+
+/// A severity as written in the configuration.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Off,
+    Error,
+    Warning,
+    #[serde(alias = "info")]
+    Information,
+    Hint,
+}
+
+impl Level {
+    fn severity(self) -> Option<DiagnosticSeverity> {
+        match self {
+            Level::Off => None,
+            Level::Error => Some(DiagnosticSeverity::Error),
+            Level::Warning => Some(DiagnosticSeverity::Warning),
+            Level::Information => Some(DiagnosticSeverity::Information),
+            Level::Hint => Some(DiagnosticSeverity::Hint),
+        }
+    }
+}
+
+impl ConfigUpdate {
+    /// Reads a `mylang.json`, describing what is wrong with it otherwise.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        serde_json::from_str(&content).map_err(|e| format!("invalid {}: {}", path.display(), e))
+    }
+
+    /// Reads a `mylang-lsp.toml`, describing what is wrong with it
+    /// otherwise.
+    pub fn load_toml(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        toml::from_str(&content).map_err(|e| format!("invalid {}: {}", path.display(), e))
+    }
+
+    /// Adds `newer` on top, keeping the settings it leaves out.
+    pub fn merge(&mut self, newer: ConfigUpdate) {
+        self.profile = newer.profile.or(self.profile);
+        self.locale = newer.locale.or(self.locale.take());
+        self.format = match (newer.format, self.format.take()) {
+            (Some(newer), Some(older)) => Some(newer.or(older)),
+            (newer, older) => newer.or(older),
+        };
+        self.runner = newer.runner.or(self.runner.take());
+        self.lints = merge_maps(self.lints.take(), newer.lints);
+        self.severities = merge_maps(self.severities.take(), newer.severities);
+        self.builtins = newer.builtins.or(self.builtins.take());
+        self.exclude = newer.exclude.or(self.exclude.take());
+        self.features = merge_maps(self.features.take(), newer.features);
+        self.profiling = newer.profiling.or(self.profiling);
+    }
+}
+
+fn merge_maps<V>(
+    older: Option<HashMap<String, V>>,
+    newer: Option<HashMap<String, V>>,
+) -> Option<HashMap<String, V>> {
+    match (older, newer) {
+        (Some(mut older), Some(newer)) => {
+            older.extend(newer);
+            Some(older)
+        }
+        (older, newer) => newer.or(older),
+    }
+}
+
+/// The first `mylang-lsp.toml` in `root` or a directory above it.
+pub fn find_workspace_config(root: &Path) -> Option<PathBuf> {
+    root.ancestors()
+        .map(|directory| directory.join(WORKSPACE_CONFIG_FILE))
+        .find(|path| path.is_file())
+}
+
+/// Points at the user's config file, replacing the usual location.
+pub const CONFIG_ENV: &str = "MYLANG_LSP_CONFIG";
+
+/// Variables that set what the config files can, for editors that launch
+/// the server without a way to pass it settings. Lists are separated by
+/// commas, and features are turned off with a leading `-`, as in
+/// `MYLANG_LSP_FEATURES=-semanticTokens,-inlayHint`.
+///
+/// `MYLANG_LSP_CONFIG` is read by [`user_config_file`], since it decides
+/// where a layer comes from. The log level and file, `MYLANG_LSP_LOG` and
+/// `MYLANG_LSP_LOG_FILE`, are read in `src/logging` before the server
+/// starts, as logging has to be up before any configuration is loaded.
+const ENV_SETTINGS: &[(&str, EnvSetting)] = &[
+    ("MYLANG_LSP_PROFILE", |update, value| {
+        let profile = serde_json::from_value(serde_json::Value::String(value.to_string()))
+            .map_err(|_| format!("unknown profile '{value}'"))?;
+        update.profile = Some(profile);
+        Ok(())
+    }),
+    ("MYLANG_LSP_LOCALE", |update, value| {
+        update.locale = Some(value.to_string());
+        Ok(())
+    }),
+    ("MYLANG_LSP_BUILTINS", |update, value| {
+        let globals = split_list(value)
+            .map(Global::parse)
+            .collect::<Result<_, _>>()?;
+        update.builtins = Some(globals);
+        Ok(())
+    }),
+    ("MYLANG_LSP_EXCLUDE", |update, value| {
+        update.exclude = Some(split_list(value).map(str::to_string).collect());
+        Ok(())
+    }),
+    ("MYLANG_LSP_FEATURES", |update, value| {
+        let features = split_list(value).map(|feature| match feature.strip_prefix('-') {
+            Some(feature) => (feature.to_string(), false),
+            None => (feature.to_string(), true),
+        });
+        update.features = Some(features.collect());
+        Ok(())
+    }),
+    ("MYLANG_LSP_PROFILING", |update, value| {
+        let profiling = match value {
+            "1" | "true" => true,
+            "0" | "false" => false,
+            _ => return Err(format!("expected true or false, found '{value}'")),
+        };
+        update.profiling = Some(profiling);
+        Ok(())
+    }),
+];
+
+type EnvSetting = fn(&mut ConfigUpdate, &str) -> Result<(), String>;
+
+/// Splits at the commas outside parentheses, so signatures keep theirs.
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    let mut depth = 0;
+    value
+        .split(move |c| {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            c == ',' && depth == 0
+        })
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+impl ConfigUpdate {
+    /// The settings given by `MYLANG_LSP_*` variables, with a message for
+    /// each variable that could not be used.
+    pub fn from_env() -> (Self, Vec<String>) {
+        let mut update = Self::default();
+        let mut problems = Vec::new();
+        for (name, set) in ENV_SETTINGS {
+            if let Ok(value) = env::var(name)
+                && let Err(problem) = set(&mut update, &value)
+            {
+                problems.push(format!("invalid {name}: {problem}"));
+            }
+        }
+        (update, problems)
+    }
+}
+
+/// The user's own `mylang-lsp.toml`, for settings shared by every
+/// workspace: the file `MYLANG_LSP_CONFIG` names, or else the one in
+/// `$XDG_CONFIG_HOME/mylang-lsp`, `~/.config/mylang-lsp` or, on Windows,
+/// `%APPDATA%\mylang-lsp`.
+pub fn user_config_file() -> Option<PathBuf> {
+    if let Some(path) = env::var_os(CONFIG_ENV) {
+        return Some(PathBuf::from(path));
+    }
+    let directory = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var_os("APPDATA")
+                .filter(|_| cfg!(windows))
+                .map(PathBuf::from)
+        })
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(directory.join("mylang-lsp").join(WORKSPACE_CONFIG_FILE))
+}
+
+/// Where settings come from. Each layer keeps only what its source set,
+/// so one can be replaced and the whole resolved again. From lowest
+/// precedence to highest: the defaults, `user`, `workspace`,
+/// `environment`, `initialization` and `pushed`.
+#[derive(Default)]
+pub struct ConfigLayers {
+    /// The editor's display language, used unless a layer sets a locale.
+    pub client_locale: Option<String>,
+    /// The user's `mylang-lsp.toml`.
+    pub user: ConfigUpdate,
+    /// The workspace's `mylang-lsp.toml`, with its `mylang.json` on top.
+    pub workspace: ConfigUpdate,
+    /// The directory `exclude` globs are relative to: the workspace's
+    /// `mylang-lsp.toml`, or else the workspace root.
+    pub workspace_dir: Option<PathBuf>,
+    /// The `MYLANG_LSP_*` variables.
+    pub environment: ConfigUpdate,
+    /// The client's `initializationOptions`.
+    pub initialization: ConfigUpdate,
+    /// Every `workspace/didChangeConfiguration` since, newest winning.
+    pub pushed: ConfigUpdate,
+}
+
+impl ConfigLayers {
+    /// Reads the user, workspace and environment layers again. Problems
+    /// with them are returned as messages for the user; a TOML file that
+    /// cannot be read leaves its layer empty, and a `mylang.json` that
+    /// cannot is left out.
+    pub fn load_files(&mut self, root: Option<&Path>) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut load = |path: &Path| {
+            ConfigUpdate::load_toml(path).unwrap_or_else(|problem| {
+                problems.push(problem);
+                ConfigUpdate::default()
+            })
+        };
+
+        // A file named by the variable has to exist; the usual one may not.
+        self.user = user_config_file()
+            .filter(|path| env::var_os(CONFIG_ENV).is_some() || path.is_file())
+            .map(|path| load(&path))
+            .unwrap_or_default();
+
+        self.workspace = ConfigUpdate::default();
+        self.workspace_dir = root.map(Path::to_path_buf);
+        if let Some(root) = root {
+            if let Some(path) = find_workspace_config(root) {
+                self.workspace = load(&path);
+                self.workspace_dir = path.parent().map(Path::to_path_buf);
+            }
+            let path = root.join(CONFIG_FILE);
+            if path.is_file() {
+                match ConfigUpdate::load(&path) {
+                    Ok(update) => self.workspace.merge(update),
+                    Err(problem) => problems.push(problem),
+                }
+            }
+        }
+
+        let (environment, environment_problems) = ConfigUpdate::from_env();
+        self.environment = environment;
+        problems.extend(environment_problems);
+        problems
+    }
+
+    /// The settings all layers add up to.
+    pub fn resolve(&self) -> ServerConfig {
+        let mut config = ServerConfig {
+            exclude_root: self.workspace_dir.clone(),
+            ..Default::default()
+        };
+        config.apply(ConfigUpdate {
+            locale: self.client_locale.clone(),
+            ..Default::default()
+        });
+        for layer in [
+            &self.user,
+            &self.workspace,
+            &self.environment,
+            &self.initialization,
+            &self.pushed,
+        ] {
+            config.apply(layer.clone());
+        }
+        config
+    }
+}
+
+impl ServerConfig {
+    pub fn apply(&mut self, update: ConfigUpdate) {
+        if let Some(profile) = update.profile {
+            self.profile = profile;
+        }
+        if let Some(locale) = update.locale {
+            self.locale = Locale::from_tag(&locale);
+        }
+        if let Some(format) = update.format {
+            self.format = format.or(self.format.clone());
+        }
+        if let Some(runner) = update.runner {
+            self.runner = runner;
+        }
+        for (code, on) in update.lints.into_iter().flatten() {
+            if on {
+                // Back to the default severity, unless one was configured.
+                if self.severities.get(&code) == Some(&None) {
+                    self.severities.remove(&code);
+                }
+            } else {
+                self.severities.insert(code, None);
+            }
+        }
+        for (code, level) in update.severities.into_iter().flatten() {
+            self.severities.insert(code, level.severity());
+        }
+        if let Some(builtins) = update.builtins {
+            self.globals = builtins.into();
+        }
+        if let Some(exclude) = update.exclude {
+            self.exclude = exclude;
+        }
+        self.features.extend(update.features.into_iter().flatten());
+        if let Some(profiling) = update.profiling {
+            self.profiling = profiling;
+        }
+    }
+
+    /// The part of the settings the analysis itself uses.
+    pub fn analysis(&self) -> workspace::Config {
+        let mut config = workspace::Config::default();
+        config.profile = self.profile;
+        config.locale = self.locale;
+        config.globals = self.globals.to_vec();
+        config
+    }
+
+    pub fn adjust(&self, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        self.analysis()
+            .adjust(diagnostics)
+            .into_iter()
+            .filter_map(|mut diagnostic| {
+                match diagnostic
+                    .code
+                    .as_ref()
+                    .and_then(|code| self.severities.get(code))
+                {
+                    Some(None) => return None,
+                    Some(Some(severity)) => diagnostic.severity = *severity,
+                    None => {}
+                }
+                Some(diagnostic)
+            })
+            .collect()
+    }
+
+    pub fn enabled(&self, feature: &str) -> bool {
+        self.features.get(feature) != Some(&false)
+    }
+
+    /// Whether an `exclude` glob matches `path`, or a directory it is in.
+    /// A glob without a slash matches a file or directory name anywhere.
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        if self.exclude.is_empty() {
+            return false;
+        }
+        let relative = match &self.exclude_root {
+            Some(root) => path.strip_prefix(root).unwrap_or(path),
+            None => path,
+        };
+        let components: Vec<String> = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        let components: Vec<&str> = components.iter().map(String::as_str).collect();
+
+        self.exclude.iter().any(|glob| {
+            let glob = glob.trim_start_matches("./").trim_end_matches('/');
+            if glob.contains('/') {
+                let parts: Vec<&str> = glob.split('/').collect();
+                (1..=components.len()).any(|len| matches_path(&parts, &components[..len]))
+            } else {
+                components.iter().any(|name| matches_name(glob, name))
+            }
+        })
+    }
+}
+
+/// Matches path components against glob parts, where `**` stands for any
+/// number of components.
+fn matches_path(parts: &[&str], components: &[&str]) -> bool {
+    match parts.split_first() {
+        None => components.is_empty(),
+        Some((&"**", rest)) => {
+            (0..=components.len()).any(|skip| matches_path(rest, &components[skip..]))
+        }
+        Some((part, rest)) => components
+            .split_first()
+            .is_some_and(|(name, others)| matches_name(part, name) && matches_path(rest, others)),
+    }
+}
+
+/// Matches one name against a glob part, where `*` stands for any run of
+/// characters and `?` for one.
+fn matches_name(glob: &str, name: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut g, mut n) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        match glob.get(g) {
+            Some('*') => {
+                backtrack = Some((g, n));
+                g += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                g += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    g = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    glob[g..].iter().all(|&c| c == '*')
+}
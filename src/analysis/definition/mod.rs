@@ -0,0 +1,39 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::diagnostics::Position;
+use super::documents::DocumentStore;
+use super::line_index::LineIndex;
+use super::pull::TextDocumentIdentifier;
+
+#[derive(Deserialize)]
+pub struct DefinitionParams {
+    #[serde(rename = "textDocument")]
+    pub text_document: TextDocumentIdentifier,
+    pub position: Position,
+}
+
+/// Handles `textDocument/definition`, jumping to the identifier that
+/// declares the symbol under the cursor. The language has no imports, so
+/// every definition lives in the same document; builtins have none.
+pub fn definition(documents: &DocumentStore, params: DefinitionParams) -> Value {
+    let Some(document) = documents.get(&params.text_document.uri) else {
+        return Value::Null;
+    };
+
+    let index = LineIndex::new(&document.text);
+    let offset = index.offset(&params.position);
+    let symbols = &document.analysis().symbols;
+
+    let Some(span) = symbols
+        .symbol_at(offset)
+        .and_then(|(id, _)| symbols.symbols[id].span)
+    else {
+        return Value::Null;
+    };
+
+    json!({
+        "uri": document.uri,
+        "range": index.range(span)
+    })
+}
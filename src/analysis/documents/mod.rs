@@ -0,0 +1,129 @@
+use std::cell::{Cell, OnceCell};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::builtins::Global;
+use super::cancel::{CancellationToken, Cancelled};
+use super::{FileAnalysis, analyze_cancellable, analyze_with};
+
+pub struct Document {
+    pub uri: String,
+    pub version: u32,
+    /// Shared rather than copied by whatever needs the text past the
+    /// message being handled, such as a run of the document.
+    pub text: Arc<str>,
+    /// Globals the host environment defines, from the configuration.
+    globals: Arc<[Global]>,
+    analysis: OnceCell<FileAnalysis>,
+    /// Lookups served from the cached analysis, across all versions.
+    pub cache_hits: Cell<u64>,
+    /// Lookups that had to run the analysis, across all versions.
+    pub cache_misses: Cell<u64>,
+}
+
+impl Document {
+    pub fn new(uri: String, version: u32, text: impl Into<Arc<str>>) -> Self {
+        Self {
+            uri,
+            version,
+            text: text.into(),
+            globals: Arc::default(),
+            analysis: OnceCell::new(),
+            cache_hits: Cell::new(0),
+            cache_misses: Cell::new(0),
+        }
+    }
+
+    /// Analyzes the document with `globals` declared.
+    pub fn with_globals(mut self, globals: Arc<[Global]>) -> Self {
+        self.set_globals(globals);
+        self
+    }
+
+    pub fn globals(&self) -> &Arc<[Global]> {
+        &self.globals
+    }
+
+    /// Declares `globals`, dropping the cached analysis if they changed.
+    pub fn set_globals(&mut self, globals: Arc<[Global]>) {
+        if self.globals != globals {
+            self.globals = globals;
+            self.analysis = OnceCell::new();
+        }
+    }
+
+    /// The analysis of the current text, computed on first use and reused
+    /// until the document changes.
+    pub fn analysis(&self) -> &FileAnalysis {
+        if let Some(analysis) = self.analysis.get() {
+            self.cache_hits.set(self.cache_hits.get() + 1);
+            return analysis;
+        }
+
+        self.cache_misses.set(self.cache_misses.get() + 1);
+        self.analysis
+            .get_or_init(|| analyze_with(&self.text, &self.globals))
+    }
+
+    /// Like [`Document::analysis`], giving up once `cancel` is cancelled.
+    /// Nothing is cached then, so the next lookup starts over.
+    pub fn try_analysis(&self, cancel: &CancellationToken) -> Result<&FileAnalysis, Cancelled> {
+        if let Some(analysis) = self.analysis.get() {
+            self.cache_hits.set(self.cache_hits.get() + 1);
+            return Ok(analysis);
+        }
+
+        self.cache_misses.set(self.cache_misses.get() + 1);
+        let analysis = analyze_cancellable(&self.text, &self.globals, cancel)?;
+        Ok(self.analysis.get_or_init(|| analysis))
+    }
+
+    /// The analysis if one has been computed, without computing it or
+    /// counting the lookup.
+    pub fn cached_analysis(&self) -> Option<&FileAnalysis> {
+        self.analysis.get()
+    }
+}
+
+#[derive(Default)]
+pub struct DocumentStore {
+    documents: HashMap<String, Document>,
+    globals: Arc<[Global]>,
+}
+
+impl DocumentStore {
+    pub fn open(&mut self, uri: String, version: u32, text: impl Into<Arc<str>>) {
+        let (cache_hits, cache_misses) = match self.documents.get(&uri) {
+            Some(previous) => (previous.cache_hits.get(), previous.cache_misses.get()),
+            None => (0, 0),
+        };
+
+        let document = Document::new(uri.clone(), version, text).with_globals(self.globals.clone());
+        document.cache_hits.set(cache_hits);
+        document.cache_misses.set(cache_misses);
+        self.documents.insert(uri, document);
+    }
+
+    /// Declares `globals` in every document, now and when opened later.
+    pub fn set_globals(&mut self, globals: Arc<[Global]>) {
+        for document in self.documents.values_mut() {
+            document.set_globals(globals.clone());
+        }
+        self.globals = globals;
+    }
+
+    pub fn remove(&mut self, uri: &str) -> Option<Document> {
+        self.documents.remove(uri)
+    }
+
+    pub fn get(&self, uri: &str) -> Option<&Document> {
+        self.documents.get(uri)
+    }
+
+    /// Documents sorted by uri, so workspace-wide results come back in a stable order.
+    pub fn iter(&self) -> Vec<&Document> {
+        let mut documents: Vec<&Document> = self.documents.values().collect();
+        documents.sort_by(|a, b| a.uri.cmp(&b.uri));
+        documents
+    }
+}
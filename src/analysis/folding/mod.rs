@@ -0,0 +1,133 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::ast::{BinaryOp, Expr, ExprKind, Stmt};
+use super::documents::DocumentStore;
+use super::lexer::{self, Span, TokenType};
+use super::line_index::LineIndex;
+use super::pull::TextDocumentIdentifier;
+
+#[derive(Deserialize)]
+pub struct FoldingRangeParams {
+    #[serde(rename = "textDocument")]
+    pub text_document: TextDocumentIdentifier,
+}
+
+/// Handles `textDocument/foldingRange`: multi-line blocks and brackets,
+/// pipelines split over lines, runs of comment lines, and the lines between
+/// `// region` and `// endregion`.
+pub fn folding_ranges(documents: &DocumentStore, params: FoldingRangeParams) -> Value {
+    let Some(document) = documents.get(&params.text_document.uri) else {
+        return Value::Null;
+    };
+
+    let text = &document.text;
+    let index = LineIndex::new(text);
+    let line = |offset: usize| index.position(offset).line;
+    let analysis = document.analysis();
+    let mut ranges = Vec::new();
+
+    // Brackets fold up to the line before the closing one, which stays
+    // visible.
+    let mut open = Vec::new();
+    for token in &analysis.tokens {
+        match token.token_type {
+            TokenType::LeftBrace | TokenType::LeftParen | TokenType::LeftBracket => {
+                open.push(line(token.span.start));
+            }
+            TokenType::RightBrace | TokenType::RightParen | TokenType::RightBracket => {
+                if let Some(start) = open.pop() {
+                    let end = line(token.span.start);
+                    if end > start + 1 {
+                        ranges.push(json!({ "startLine": start, "endLine": end - 1 }));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut pipelines = Vec::new();
+    for expr in analysis.program.statements.iter().filter_map(Stmt::expr) {
+        collect_pipelines(expr, &mut pipelines);
+    }
+    for pipeline in pipelines {
+        let (start, end) = (line(pipeline.start), line(pipeline.end));
+        if end > start {
+            ranges.push(json!({ "startLine": start, "endLine": end }));
+        }
+    }
+
+    let mut block: Option<(u32, u32)> = None;
+    let mut regions = Vec::new();
+    for comment in lexer::comments(text, &analysis.tokens) {
+        let comment_line = line(comment.start);
+        let line_start = text[..comment.start].rfind('\n').map_or(0, |i| i + 1);
+        if !text[line_start..comment.start].trim().is_empty() {
+            continue;
+        }
+
+        let body = text[comment.start + 2..comment.end].trim();
+        if body.starts_with("region") {
+            regions.push(comment_line);
+            continue;
+        }
+        if body.starts_with("endregion") {
+            if let Some(start) = regions.pop() {
+                ranges.push(json!({
+                    "startLine": start,
+                    "endLine": comment_line,
+                    "kind": "region"
+                }));
+            }
+            continue;
+        }
+
+        block = match block {
+            Some((start, end)) if end + 1 == comment_line => Some((start, comment_line)),
+            finished => {
+                push_comment_block(&mut ranges, finished);
+                Some((comment_line, comment_line))
+            }
+        };
+    }
+    push_comment_block(&mut ranges, block);
+
+    json!(ranges)
+}
+
+fn push_comment_block(ranges: &mut Vec<Value>, block: Option<(u32, u32)>) {
+    if let Some((start, end)) = block
+        && end > start
+    {
+        ranges.push(json!({ "startLine": start, "endLine": end, "kind": "comment" }));
+    }
+}
+
+/// The spans of every `|>` chain, each chain counted once however many
+/// stages it has.
+fn collect_pipelines(expr: &Expr, out: &mut Vec<Span>) {
+    let mut stages = Vec::new();
+    let mut operand = expr;
+    while let ExprKind::Binary {
+        op: BinaryOp::Pipe,
+        left,
+        right,
+    } = &operand.kind
+    {
+        stages.push(&**right);
+        operand = left;
+    }
+    if stages.is_empty() {
+        for child in expr.children() {
+            collect_pipelines(child, out);
+        }
+        return;
+    }
+
+    out.push(expr.span);
+    collect_pipelines(operand, out);
+    for stage in stages {
+        collect_pipelines(stage, out);
+    }
+}
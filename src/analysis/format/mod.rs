@@ -0,0 +1,748 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::ast::{BinaryOp, Block, Expr, ExprKind, Stmt};
+use super::config::ServerConfig;
+use super::diagnostics::{Position, Range};
+use super::documents::{Document, DocumentStore};
+use super::lexer::{self, Span, Token, TokenType};
+use super::line_index::LineIndex;
+use super::parser;
+use super::pull::TextDocumentIdentifier;
+
+/// Above this many line pairs the diff gives up on finding the smallest set
+/// of edits and replaces the changed region in one go.
+pub const MAX_DIFF_CELLS: usize = 1_000_000;
+
+/// The protocol's `FormattingOptions`, also accepted under `format` in the
+/// configuration, where any option set overrides what the editor sends.
+#[derive(Deserialize, Clone, Default, Debug)]
+pub struct FormattingOptions {
+    #[serde(rename = "tabSize")]
+    pub tab_size: Option<u32>,
+    #[serde(rename = "insertSpaces")]
+    pub insert_spaces: Option<bool>,
+    #[serde(rename = "trimTrailingWhitespace")]
+    pub trim_trailing_whitespace: Option<bool>,
+    #[serde(rename = "insertFinalNewline")]
+    pub insert_final_newline: Option<bool>,
+    #[serde(rename = "trimFinalNewlines")]
+    pub trim_final_newlines: Option<bool>,
+    /// How many `|>` a pipeline may have before each stage gets its own
+    /// line. Not part of the protocol, but clients may send extra options.
+    #[serde(rename = "pipelineThreshold")]
+    pub pipeline_threshold: Option<u32>,
+}
+
+impl FormattingOptions {
+    /// These options, falling back to `other` for any left unset.
+    pub fn or(self, other: FormattingOptions) -> Self {
+        Self {
+            tab_size: self.tab_size.or(other.tab_size),
+            insert_spaces: self.insert_spaces.or(other.insert_spaces),
+            trim_trailing_whitespace: self
+                .trim_trailing_whitespace
+                .or(other.trim_trailing_whitespace),
+            insert_final_newline: self.insert_final_newline.or(other.insert_final_newline),
+            trim_final_newlines: self.trim_final_newlines.or(other.trim_final_newlines),
+            pipeline_threshold: self.pipeline_threshold.or(other.pipeline_threshold),
+        }
+    }
+}
+
+/// Resolved formatting options. Anything neither the project nor the
+/// editor decides follows the canonical style.
+pub struct Style {
+    indent: String,
+    trim_trailing_whitespace: bool,
+    insert_final_newline: bool,
+    trim_final_newlines: bool,
+    pipeline_threshold: usize,
+}
+
+impl Style {
+    /// The style for a request made with `options`, after the configured
+    /// overrides.
+    pub fn new(config: &ServerConfig, options: FormattingOptions) -> Self {
+        let options = config.format.clone().or(options);
+        let indent = if options.insert_spaces.unwrap_or(true) {
+            " ".repeat(options.tab_size.unwrap_or(4) as usize)
+        } else {
+            "\t".to_string()
+        };
+        Self {
+            indent,
+            trim_trailing_whitespace: options.trim_trailing_whitespace.unwrap_or(true),
+            insert_final_newline: options.insert_final_newline.unwrap_or(true),
+            trim_final_newlines: options.trim_final_newlines.unwrap_or(true),
+            pipeline_threshold: options.pipeline_threshold.unwrap_or(3) as usize,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DocumentFormattingParams {
+    #[serde(rename = "textDocument")]
+    pub text_document: TextDocumentIdentifier,
+    #[serde(default)]
+    pub options: FormattingOptions,
+}
+
+/// Handles `textDocument/formatting`. Documents containing text the lexer
+/// cannot make sense of are left alone, since formatting around it could
+/// lose it.
+pub fn formatting(
+    documents: &DocumentStore,
+    config: &ServerConfig,
+    params: DocumentFormattingParams,
+) -> Value {
+    let Some(document) = documents.get(&params.text_document.uri) else {
+        return Value::Null;
+    };
+
+    let style = Style::new(config, params.options);
+    match format(&document.text, &style) {
+        Some(formatted) => json!(edits(&document.text, &formatted)),
+        None => Value::Null,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DocumentRangeFormattingParams {
+    #[serde(rename = "textDocument")]
+    pub text_document: TextDocumentIdentifier,
+    pub range: Range,
+    #[serde(default)]
+    pub options: FormattingOptions,
+}
+
+/// Handles `textDocument/rangeFormatting` by formatting the smallest run of
+/// whole statements covering the selection, then keeping only the edits
+/// that touch the selection itself.
+pub fn range_formatting(
+    documents: &DocumentStore,
+    config: &ServerConfig,
+    params: DocumentRangeFormattingParams,
+) -> Value {
+    let Some(document) = documents.get(&params.text_document.uri) else {
+        return Value::Null;
+    };
+    let text = &document.text;
+    let index = LineIndex::new(text);
+    let start = index.offset(&params.range.start);
+    let end = index.offset(&params.range.end);
+    let style = Style::new(config, params.options);
+    let Some(replacements) = format_statements(document, start, end, &style) else {
+        return Value::Null;
+    };
+
+    let selection_start = text[..start].rfind('\n').map_or(0, |i| i + 1);
+    let edits: Vec<Value> = replacements
+        .into_iter()
+        .filter(|(span, _)| span.start <= end && selection_start <= span.end)
+        .map(|(span, new_text)| {
+            json!({
+                "range": index.range(span),
+                "newText": new_text
+            })
+        })
+        .collect();
+    json!(edits)
+}
+
+/// The characters that trigger `textDocument/onTypeFormatting`.
+pub const ON_TYPE_TRIGGERS: [&str; 2] = [";", "}"];
+
+#[derive(Deserialize)]
+pub struct DocumentOnTypeFormattingParams {
+    #[serde(rename = "textDocument")]
+    pub text_document: TextDocumentIdentifier,
+    pub position: Position,
+    pub ch: String,
+    #[serde(default)]
+    pub options: FormattingOptions,
+}
+
+/// Handles `textDocument/onTypeFormatting`: once a `;` ends a statement or a
+/// `}` closes a block, lays out the statement it completes.
+pub fn on_type_formatting(
+    documents: &DocumentStore,
+    config: &ServerConfig,
+    params: DocumentOnTypeFormattingParams,
+) -> Value {
+    let Some(document) = documents.get(&params.text_document.uri) else {
+        return Value::Null;
+    };
+    let index = LineIndex::new(&document.text);
+    let offset = index.offset(&params.position);
+    let Some(typed) = offset.checked_sub(params.ch.len()) else {
+        return json!([]);
+    };
+
+    // Typing inside a string or comment completes nothing.
+    let completes = document.analysis().tokens.iter().any(|token| {
+        token.span.start == typed
+            && token.lexeme(&document.text) == params.ch
+            && matches!(
+                token.token_type,
+                TokenType::SEMICOLON | TokenType::RightBrace
+            )
+    });
+    if !completes {
+        return json!([]);
+    }
+
+    let style = Style::new(config, params.options);
+    let Some(replacements) = format_statements(document, typed, typed, &style) else {
+        return Value::Null;
+    };
+    let edits: Vec<Value> = replacements
+        .into_iter()
+        .map(|(span, new_text)| {
+            json!({
+                "range": index.range(span),
+                "newText": new_text
+            })
+        })
+        .collect();
+    json!(edits)
+}
+
+/// Lays out the smallest run of whole statements covering `start..end`,
+/// along with the indentation before them and any comment trailing them.
+/// `None` when the text there cannot be formatted.
+fn format_statements(
+    document: &Document,
+    start: usize,
+    end: usize,
+    style: &Style,
+) -> Option<Vec<(Span, String)>> {
+    let text = &document.text;
+    let analysis = document.analysis();
+    let Some(statements) = enclosing_statements(&analysis.program.statements, None, start, end)
+    else {
+        return Some(Vec::new());
+    };
+
+    let line_start = text[..statements.start].rfind('\n').map_or(0, |i| i + 1);
+    let indented = text[line_start..statements.start].trim().is_empty();
+    let from = if indented {
+        line_start
+    } else {
+        statements.start
+    };
+    let line_end = text[statements.end..]
+        .find('\n')
+        .map_or(text.len(), |i| statements.end + i);
+    let rest = text[statements.end..line_end].trim();
+    let to = if rest.is_empty() || rest.starts_with("//") {
+        line_end
+    } else {
+        statements.end
+    };
+
+    let depth = analysis
+        .tokens
+        .iter()
+        .take_while(|token| token.span.start < statements.start)
+        .fold(0usize, |depth, token| {
+            if is_open(token.token_type) {
+                depth + 1
+            } else if is_close(token.token_type) {
+                depth.saturating_sub(1)
+            } else {
+                depth
+            }
+        });
+    let formatted = layout(&text[from..to], depth, indented, style)?;
+    Some(replacements(text, Span::new(from, to), &formatted))
+}
+
+/// The span of the statements overlapping `start..end`, descending into a
+/// block when a single statement's block holds the whole range.
+fn enclosing_statements(
+    statements: &[Stmt],
+    tail: Option<&Expr>,
+    start: usize,
+    end: usize,
+) -> Option<Span> {
+    let overlapping: Vec<(Span, Option<&Expr>)> = statements
+        .iter()
+        .map(|stmt| match stmt {
+            Stmt::Let(stmt) => (stmt.span, stmt.value.as_ref()),
+            Stmt::Expr(stmt) => (stmt.span, Some(&stmt.expr)),
+        })
+        .chain(tail.map(|tail| (tail.span, Some(tail))))
+        .filter(|(span, _)| span.start <= end && start <= span.end)
+        .collect();
+
+    let (first, expr) = *overlapping.first()?;
+    let (last, _) = *overlapping.last()?;
+    if overlapping.len() == 1
+        && let Some(block) = expr.and_then(|expr| block_around(expr, start, end))
+        && let Some(inner) =
+            enclosing_statements(&block.statements, block.tail.as_deref(), start, end)
+    {
+        return Some(inner);
+    }
+    Some(first.to(last))
+}
+
+/// The outermost block inside `expr` whose braces enclose `start..end`.
+fn block_around(expr: &Expr, start: usize, end: usize) -> Option<&Block> {
+    if start < expr.span.start || expr.span.end < end {
+        return None;
+    }
+    let inside = |block: &Block| block.span.start < start && end < block.span.end;
+    match &expr.kind {
+        ExprKind::Block(block) => inside(block).then_some(block),
+        ExprKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => block_around(condition, start, end)
+            .or_else(|| inside(then_branch).then_some(then_branch))
+            .or_else(|| {
+                else_branch
+                    .as_deref()
+                    .and_then(|branch| block_around(branch, start, end))
+            }),
+        ExprKind::Unary { operand, .. } => block_around(operand, start, end),
+        ExprKind::Binary { left, right, .. } => {
+            block_around(left, start, end).or_else(|| block_around(right, start, end))
+        }
+        ExprKind::Call { callee, args } => block_around(callee, start, end)
+            .or_else(|| args.iter().find_map(|arg| block_around(arg, start, end))),
+        ExprKind::Field { receiver, .. } => block_around(receiver, start, end),
+        ExprKind::List(items) | ExprKind::Set(items) => {
+            items.iter().find_map(|item| block_around(item, start, end))
+        }
+        ExprKind::Lambda { body, .. } => block_around(body, start, end),
+        _ => None,
+    }
+}
+
+/// What sits between two tokens: the lexer drops it, the formatter keeps
+/// the comments and uses the line breaks to decide the layout.
+#[derive(Clone, Copy, PartialEq)]
+enum Trivia<'a> {
+    Newline,
+    Comment(&'a str),
+}
+
+fn trivia(gap: &str) -> Option<Vec<Trivia<'_>>> {
+    let mut items = Vec::new();
+    let mut rest = gap;
+    while let Some(c) = rest.chars().next() {
+        if c == '\n' {
+            items.push(Trivia::Newline);
+            rest = &rest[1..];
+        } else if matches!(c, ' ' | '\r' | '\t') {
+            rest = &rest[1..];
+        } else if rest.starts_with("//") {
+            let end = rest.find('\n').unwrap_or(rest.len());
+            items.push(Trivia::Comment(rest[..end].trim_end_matches('\r')));
+            rest = &rest[end..];
+        } else {
+            return None;
+        }
+    }
+    Some(items)
+}
+
+/// Lays `text` out in the canonical style: one statement per line, blocks
+/// indented one level, single spaces around binary operators and none
+/// inside brackets or before `,` and `;`. Line breaks the author chose are
+/// kept, though runs of blank lines collapse to one.
+pub fn format(text: &str, style: &Style) -> Option<String> {
+    let mut out = layout(text, 0, false, style)?;
+    if out.is_empty() {
+        return Some(out);
+    }
+
+    let trailing = text[text.trim_end().len()..].matches('\n').count();
+    let mut newlines = if style.trim_final_newlines {
+        trailing.min(1)
+    } else {
+        trailing
+    };
+    if style.insert_final_newline {
+        newlines = newlines.max(1);
+    }
+    out.extend(std::iter::repeat_n('\n', newlines));
+    Some(out)
+}
+
+/// Formats `text` as if it sat `depth` brackets deep, indenting its first
+/// line only if asked to, and without ending it with a newline.
+fn layout(text: &str, depth: usize, indent_first: bool, style: &Style) -> Option<String> {
+    let lexed = lexer::lex(text);
+    let tokens: Vec<&Token> = lexed
+        .iter()
+        .filter(|token| token.token_type != TokenType::EOF)
+        .collect();
+
+    let mut gaps = Vec::with_capacity(tokens.len() + 1);
+    let mut end = 0;
+    for token in &tokens {
+        gaps.push(trivia(&text[end..token.span.start])?);
+        end = token.span.end;
+    }
+    gaps.push(trivia(&text[end..])?);
+
+    let broken = broken_blocks(&tokens, &gaps);
+
+    // Pipeline stages go one per line, the `|>` lined up under the first
+    // operand, once the author breaks the pipeline or it grows long.
+    let (program, _) = parser::parse(&lexed, &LineIndex::new(text));
+    let mut aligned = vec![None; tokens.len()];
+    let mut columns: Vec<Option<String>> = vec![None; tokens.len()];
+    for pipeline in pipelines(&program.statements, &tokens) {
+        let stacked = pipeline.operators.len() >= style.pipeline_threshold
+            || pipeline.operators.iter().any(|&i| {
+                gaps[i].contains(&Trivia::Newline) || gaps[i + 1].contains(&Trivia::Newline)
+            });
+        if stacked {
+            for &i in &pipeline.operators {
+                aligned[i] = Some(pipeline.first);
+            }
+            columns[pipeline.first] = Some(String::new());
+        }
+    }
+
+    let mut out = if indent_first {
+        style.indent.repeat(depth)
+    } else {
+        String::new()
+    };
+    let mut depth = depth;
+
+    for (i, gap) in gaps.iter().enumerate() {
+        let token = tokens.get(i).copied();
+        let prev = i.checked_sub(1).map(|p| tokens[p].token_type);
+        let opened = prev.is_some_and(is_open);
+
+        let mut newlines = 0;
+        let mut after_comment = false;
+        for item in gap {
+            match item {
+                Trivia::Newline => newlines += 1,
+                Trivia::Comment(comment) => {
+                    if newlines == 0 && !out.is_empty() {
+                        out.push(' ');
+                    } else {
+                        let indent = depth + usize::from(continues(prev, None));
+                        let blank = newlines > 1 && (!opened || after_comment);
+                        start_line(&mut out, blank, indent, style);
+                    }
+                    out.push_str(comment);
+                    newlines = 0;
+                    after_comment = true;
+                }
+            }
+        }
+
+        let Some(token) = token else {
+            break;
+        };
+        let kind = token.token_type;
+        let closing = is_close(kind);
+        if closing {
+            depth = depth.saturating_sub(1);
+        }
+
+        let joins_else = kind == TokenType::ELSE && prev == Some(TokenType::RightBrace);
+        let forced = after_comment
+            || prev == Some(TokenType::SEMICOLON)
+            || (prev == Some(TokenType::LeftBrace) && broken[i - 1])
+            || (kind == TokenType::RightBrace && broken[i]);
+
+        if let Some(first) = aligned[i] {
+            start_line(&mut out, false, 0, style);
+            out.push_str(columns[first].as_deref().unwrap_or_default());
+        } else if forced || (newlines > 0 && !joins_else) {
+            let indent = depth + usize::from(continues(prev, Some(kind)));
+            let blank = newlines > 1 && !closing && (!opened || after_comment);
+            start_line(&mut out, blank, indent, style);
+        } else if i > 0 && space_before(&tokens, i) {
+            out.push(' ');
+        }
+        if let Some(column) = &mut columns[i] {
+            let line = &out[out.rfind('\n').map_or(0, |n| n + 1)..];
+            let indent = line.len() - line.trim_start_matches([' ', '\t']).len();
+            *column = line[..indent].to_string() + &" ".repeat(line[indent..].chars().count());
+        }
+        out.push_str(token.lexeme(text));
+
+        if is_open(kind) {
+            depth += 1;
+        }
+    }
+
+    if style.trim_trailing_whitespace {
+        let trimmed = out.trim_end().len();
+        out.truncate(trimmed);
+    }
+    Some(out)
+}
+
+/// A chain of `|>`, by token index: where its first operand starts, and
+/// each operator.
+struct Pipeline {
+    first: usize,
+    operators: Vec<usize>,
+}
+
+fn pipelines(statements: &[Stmt], tokens: &[&Token]) -> Vec<Pipeline> {
+    fn collect(expr: &Expr, tokens: &[&Token], out: &mut Vec<Pipeline>) {
+        let mut stages = Vec::new();
+        let mut operand = expr;
+        while let ExprKind::Binary {
+            op: BinaryOp::Pipe,
+            left,
+            right,
+        } = &operand.kind
+        {
+            stages.push(&**right);
+            operand = left;
+        }
+        if stages.is_empty() {
+            for child in expr.children() {
+                collect(child, tokens, out);
+            }
+            return;
+        }
+
+        stages.reverse();
+        let index = |offset: usize| {
+            tokens
+                .binary_search_by_key(&offset, |token| token.span.start)
+                .ok()
+        };
+        let operators = stages
+            .iter()
+            .filter_map(|stage| index(stage.span.start)?.checked_sub(1))
+            .filter(|&i| tokens[i].token_type == TokenType::PIPE)
+            .collect();
+        if let Some(first) = index(operand.span.start) {
+            out.push(Pipeline { first, operators });
+        }
+
+        collect(operand, tokens, out);
+        for stage in stages {
+            collect(stage, tokens, out);
+        }
+    }
+
+    let mut out = Vec::new();
+    for expr in statements.iter().filter_map(Stmt::expr) {
+        collect(expr, tokens, &mut out);
+    }
+    out
+}
+
+/// Marks the braces whose contents go on their own lines: those the author
+/// already broke over several lines, and those holding statements.
+fn broken_blocks(tokens: &[&Token], gaps: &[Vec<Trivia>]) -> Vec<bool> {
+    let mut broken = vec![false; tokens.len()];
+    let mut open = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if is_open(token.token_type) {
+            open.push(i);
+        } else if is_close(token.token_type)
+            && let Some(start) = open.pop()
+            && tokens[start].token_type == TokenType::LeftBrace
+            && token.token_type == TokenType::RightBrace
+        {
+            let multiline = gaps[start + 1..=i]
+                .iter()
+                .any(|gap| gap.contains(&Trivia::Newline))
+                || tokens[start + 1..i]
+                    .iter()
+                    .any(|token| token.token_type == TokenType::SEMICOLON);
+            broken[start] = multiline;
+            broken[i] = multiline;
+        }
+    }
+    broken
+}
+
+fn start_line(out: &mut String, blank: bool, indent: usize, style: &Style) {
+    if style.trim_trailing_whitespace {
+        let trimmed = out.trim_end_matches([' ', '\t']).len();
+        out.truncate(trimmed);
+    }
+    if !out.is_empty() {
+        out.push('\n');
+        if blank {
+            out.push('\n');
+        }
+    }
+    for _ in 0..indent {
+        out.push_str(&style.indent);
+    }
+}
+
+/// Whether a line starting after `prev` carries on an unfinished statement
+/// or expression, and so is indented one level further.
+fn continues(prev: Option<TokenType>, next: Option<TokenType>) -> bool {
+    let Some(prev) = prev else {
+        return false;
+    };
+    !next.is_some_and(is_close)
+        && !matches!(
+            prev,
+            TokenType::SEMICOLON
+                | TokenType::COMMA
+                | TokenType::LeftBrace
+                | TokenType::LeftParen
+                | TokenType::LeftBracket
+        )
+}
+
+fn space_before(tokens: &[&Token], i: usize) -> bool {
+    let kind = tokens[i].token_type;
+    let prev = tokens[i - 1].token_type;
+    match (prev, kind) {
+        (
+            _,
+            TokenType::COMMA
+            | TokenType::SEMICOLON
+            | TokenType::DOT
+            | TokenType::RightParen
+            | TokenType::RightBracket,
+        ) => false,
+        (TokenType::LeftParen | TokenType::LeftBracket | TokenType::DOT, _) => false,
+        (TokenType::LeftBrace, TokenType::RightBrace) => false,
+        (TokenType::MINUS | TokenType::BANG, _) if is_unary(tokens, i - 1) => false,
+        (TokenType::IDENTIFIER | TokenType::RightParen, TokenType::LeftParen) => false,
+        _ => true,
+    }
+}
+
+/// Whether the `-` or `!` at `i` applies to what follows rather than
+/// combining it with what came before.
+fn is_unary(tokens: &[&Token], i: usize) -> bool {
+    tokens[i].token_type == TokenType::BANG
+        || i == 0
+        || !matches!(
+            tokens[i - 1].token_type,
+            TokenType::IDENTIFIER
+                | TokenType::NUMBER
+                | TokenType::STRING
+                | TokenType::TRUE
+                | TokenType::FALSE
+                | TokenType::RightParen
+                | TokenType::RightBracket
+                | TokenType::RightBrace
+        )
+}
+
+fn is_open(kind: TokenType) -> bool {
+    matches!(
+        kind,
+        TokenType::LeftBrace | TokenType::LeftParen | TokenType::LeftBracket
+    )
+}
+
+fn is_close(kind: TokenType) -> bool {
+    matches!(
+        kind,
+        TokenType::RightBrace | TokenType::RightParen | TokenType::RightBracket
+    )
+}
+
+/// The `TextEdit`s turning `original` into `formatted`, one per run of
+/// changed lines, so the client keeps cursors and folds in the lines that
+/// stay the same.
+pub fn edits(original: &str, formatted: &str) -> Vec<Value> {
+    let index = LineIndex::new(original);
+    replacements(original, Span::new(0, original.len()), formatted)
+        .into_iter()
+        .map(|(span, new_text)| {
+            json!({
+                "range": index.range(span),
+                "newText": new_text
+            })
+        })
+        .collect()
+}
+
+/// The spans of `text` within `span` to replace, and what to replace them
+/// with, for that part of `text` to read `formatted`.
+fn replacements(text: &str, span: Span, formatted: &str) -> Vec<(Span, String)> {
+    let old: Vec<&str> = text[span.start..span.end].split_inclusive('\n').collect();
+    let new: Vec<&str> = formatted.split_inclusive('\n').collect();
+
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_changed = &old[prefix..old.len() - suffix];
+    let new_changed = &new[prefix..new.len() - suffix];
+
+    let hunks = if old_changed.len() * new_changed.len() <= MAX_DIFF_CELLS {
+        diff(old_changed, new_changed)
+    } else {
+        vec![(0..old_changed.len(), 0..new_changed.len())]
+    };
+
+    let mut line_starts = Vec::with_capacity(old.len() + 1);
+    let mut offset = span.start;
+    line_starts.push(offset);
+    for line in &old {
+        offset += line.len();
+        line_starts.push(offset);
+    }
+
+    hunks
+        .into_iter()
+        .filter(|(old_lines, new_lines)| !old_lines.is_empty() || !new_lines.is_empty())
+        .map(|(old_lines, new_lines)| {
+            let start = line_starts[prefix + old_lines.start];
+            let end = line_starts[prefix + old_lines.end];
+            (Span::new(start, end), new_changed[new_lines].concat())
+        })
+        .collect()
+}
+
+pub type Hunk = (std::ops::Range<usize>, std::ops::Range<usize>);
+
+/// Line ranges of `old` to replace with line ranges of `new`, following a
+/// longest common subsequence of lines.
+pub fn diff(old: &[&str], new: &[&str]) -> Vec<Hunk> {
+    let width = new.len() + 1;
+    let mut common = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i * width + j] = if old[i] == new[j] {
+                common[(i + 1) * width + j + 1] + 1
+            } else {
+                common[(i + 1) * width + j].max(common[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut hunks = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let mut start = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            hunks.push((start.0..i, start.1..j));
+            i += 1;
+            j += 1;
+            start = (i, j);
+        } else if j == new.len()
+            || (i < old.len() && common[(i + 1) * width + j] >= common[i * width + j + 1])
+        {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    hunks.push((start.0..i, start.1..j));
+    hunks
+}
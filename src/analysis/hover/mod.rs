@@ -0,0 +1,152 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::completion::{detail, documentation};
+use super::diagnostics::Position;
+use super::docs::{self, ConstructDoc};
+use super::documents::DocumentStore;
+use super::line_index::LineIndex;
+use super::pull::TextDocumentIdentifier;
+use super::resolver::{Symbol, SymbolKind};
+
+#[derive(Deserialize)]
+pub struct HoverParams {
+    #[serde(rename = "textDocument")]
+    pub text_document: TextDocumentIdentifier,
+    pub position: Position,
+}
+
+/// Handles `textDocument/hover`. Identifiers show the symbol's signature,
+/// kind, the statement that defines it and its doc comment; keywords and
+/// operators explain the construct. Clients that cannot render Markdown
+/// get the same content as plain text.
+pub fn hover(documents: &DocumentStore, params: HoverParams, markdown: bool) -> Value {
+    let Some(document) = documents.get(&params.text_document.uri) else {
+        return Value::Null;
+    };
+
+    let index = LineIndex::new(&document.text);
+    let offset = index.offset(&params.position);
+    let analysis = document.analysis();
+    let symbols = &analysis.symbols;
+
+    let (sections, span) = match symbols.symbol_at(offset) {
+        Some((id, span)) => (contents(&document.text, &symbols.symbols[id]), span),
+        None => {
+            let Some((doc, span)) = analysis
+                .tokens
+                .iter()
+                .find(|token| token.span.start <= offset && offset < token.span.end)
+                .and_then(|token| {
+                    Some((docs::construct(token.lexeme(&document.text))?, token.span))
+                })
+            else {
+                return Value::Null;
+            };
+            (construct_contents(doc), span)
+        }
+    };
+
+    let contents = if markdown {
+        json!({
+            "kind": "markdown",
+            "value": sections.iter().map(Section::markdown).collect::<Vec<_>>().join("\n\n")
+        })
+    } else {
+        json!({
+            "kind": "plaintext",
+            "value": sections
+                .iter()
+                .filter_map(Section::plaintext)
+                .collect::<Vec<_>>()
+                .join("\n\n")
+        })
+    };
+
+    json!({
+        "contents": contents,
+        "range": index.range(span)
+    })
+}
+
+/// A part of the hover body, rendered for the client's content format.
+enum Section {
+    Code(String),
+    Text(String),
+    Rule,
+}
+
+impl Section {
+    fn markdown(&self) -> String {
+        match self {
+            Section::Code(code) => format!("```mylang\n{}\n```", code),
+            Section::Text(text) => text.clone(),
+            Section::Rule => "---".to_string(),
+        }
+    }
+
+    fn plaintext(&self) -> Option<String> {
+        match self {
+            Section::Code(code) => Some(code.clone()),
+            Section::Text(text) => Some(text.replace('`', "")),
+            Section::Rule => None,
+        }
+    }
+}
+
+/// The hover for `symbol` as Markdown, for consumers outside a live
+/// session such as the LSIF dump.
+pub fn symbol_markdown(text: &str, symbol: &Symbol) -> String {
+    contents(text, symbol)
+        .iter()
+        .map(Section::markdown)
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn contents(text: &str, symbol: &Symbol) -> Vec<Section> {
+    let mut sections = vec![
+        Section::Code(signature(symbol)),
+        Section::Text(format!("({})", symbol.kind.name())),
+    ];
+
+    if let Some(statement) = symbol.statement {
+        sections.push(Section::Code(snippet(
+            &text[statement.start..statement.end],
+        )));
+    }
+
+    if let Some(documentation) = documentation(text, symbol) {
+        sections.push(Section::Rule);
+        sections.push(Section::Text(documentation));
+    }
+    sections
+}
+
+/// The line shown in the code block at the top: the function written like
+/// a lambda with its name, or the value and its inferred type.
+fn signature(symbol: &Symbol) -> String {
+    match (symbol.kind, detail(symbol)) {
+        (SymbolKind::Function | SymbolKind::Builtin, Some(detail)) if detail.starts_with("fn") => {
+            detail.replacen("fn", &format!("fn {}", symbol.name), 1)
+        }
+        (_, Some(detail)) => format!("{}: {}", symbol.name, detail),
+        (_, None) => symbol.name.clone(),
+    }
+}
+
+fn construct_contents(doc: &ConstructDoc) -> Vec<Section> {
+    vec![
+        Section::Code(doc.lexeme.to_string()),
+        Section::Text(doc.summary.to_string()),
+        Section::Code(doc.example.to_string()),
+    ]
+}
+
+/// The first line of a statement, marking anything cut off.
+fn snippet(statement: &str) -> String {
+    match statement.split_once('\n') {
+        Some((first, _)) => format!("{} …", first.trim_end()),
+        None => statement.to_string(),
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::cancel::CancellationToken;
+
+/// The work the main loop is doing, shared with the thread that reads the
+/// client's messages. That thread sees a message as soon as it arrives, so
+/// it can cancel work that a later message makes pointless: a
+/// `$/cancelRequest` for the request being handled, or an edit to the
+/// document being analyzed.
+#[derive(Default)]
+pub struct InFlight {
+    current: Mutex<Option<Work>>,
+    /// Ids of the requests the client cancelled, as JSON, kept until the
+    /// main loop handles the `$/cancelRequest` itself. By then the request,
+    /// which came first, has been answered.
+    cancelled: Mutex<HashSet<String>>,
+}
+
+struct Work {
+    id: Option<String>,
+    uri: Option<String>,
+    token: CancellationToken,
+}
+
+/// Work the main loop has started, which ends when this is dropped.
+pub struct Running<'a> {
+    in_flight: &'a InFlight,
+    pub token: CancellationToken,
+}
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        *self.in_flight.current.lock().unwrap() = None;
+    }
+}
+
+/// The parts of a message that decide what it cancels.
+#[derive(Deserialize)]
+struct Message {
+    method: Option<String>,
+    params: Option<Params>,
+}
+
+#[derive(Deserialize)]
+struct Params {
+    id: Option<Value>,
+    #[serde(rename = "textDocument")]
+    text_document: Option<DocumentIdentifier>,
+}
+
+#[derive(Deserialize)]
+struct DocumentIdentifier {
+    uri: String,
+}
+
+impl InFlight {
+    /// Starts handling the request `id`, or a notification when there is
+    /// none, about the document `uri`. A request the client has already
+    /// cancelled starts out cancelled.
+    pub fn begin(&self, id: Option<&Value>, uri: Option<&str>) -> Running<'_> {
+        let id = id.filter(|id| !id.is_null()).map(Value::to_string);
+        let token = CancellationToken::new();
+        if id
+            .as_ref()
+            .is_some_and(|id| self.cancelled.lock().unwrap().contains(id))
+        {
+            token.cancel();
+        }
+        *self.current.lock().unwrap() = Some(Work {
+            id,
+            uri: uri.map(str::to_string),
+            token: token.clone(),
+        });
+        Running {
+            in_flight: self,
+            token,
+        }
+    }
+
+    /// Whether the client cancelled the request `id`.
+    pub fn is_cancelled_request(&self, id: &Value) -> bool {
+        self.cancelled.lock().unwrap().contains(&id.to_string())
+    }
+
+    /// Drops the record of the client cancelling `id`.
+    pub fn forget(&self, id: &Value) {
+        self.cancelled.lock().unwrap().remove(&id.to_string());
+    }
+
+    /// Looks at a message on its way to the main loop, cancelling the
+    /// current work if the message makes it pointless.
+    pub fn observe(&self, message: &str) {
+        let Ok(Message {
+            method: Some(method),
+            params: Some(params),
+        }) = serde_json::from_str(message)
+        else {
+            return;
+        };
+        let current = self.current.lock().unwrap();
+
+        match method.as_str() {
+            "$/cancelRequest" => {
+                let Some(id) = params.id.filter(|id| !id.is_null()) else {
+                    return;
+                };
+                let id = id.to_string();
+                if let Some(work) = current
+                    .as_ref()
+                    .filter(|work| work.id.as_ref() == Some(&id))
+                {
+                    tracing::debug!(id, "cancelling the request being handled");
+                    work.token.cancel();
+                }
+                self.cancelled.lock().unwrap().insert(id);
+            }
+            "textDocument/didChange" | "textDocument/didClose" => {
+                let Some(document) = params.text_document else {
+                    return;
+                };
+                if let Some(work) = current
+                    .as_ref()
+                    .filter(|work| work.uri.as_ref() == Some(&document.uri))
+                {
+                    tracing::debug!(uri = document.uri, "cancelling work on a changed document");
+                    work.token.cancel();
+                }
+            }
+            _ => {}
+        }
+    }
+}
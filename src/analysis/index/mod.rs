@@ -0,0 +1,158 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::analyze;
+use super::baseline::{path_to_uri, uri_to_path};
+use super::config::ServerConfig;
+use super::diagnostics::Range;
+use super::line_index::LineIndex;
+use super::memory::HeapSize;
+use super::resolver::{SymbolKind, SymbolTable};
+
+pub const SOURCE_EXTENSION: &str = "mylang";
+
+/// Expands the given paths into the mylang source files they contain,
+/// descending into directories.
+pub fn collect_files(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for path in paths {
+        collect_into(path, &mut files);
+    }
+    files.sort();
+    files
+}
+
+fn collect_into(path: &Path, files: &mut Vec<PathBuf>) {
+    if path.is_dir() {
+        let Ok(entries) = fs::read_dir(path) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() || path.extension().is_some_and(|ext| ext == SOURCE_EXTENSION) {
+                collect_into(&path, files);
+            }
+        }
+    } else {
+        files.push(path.to_path_buf());
+    }
+}
+
+/// A declaration recorded for workspace-wide lookups.
+pub struct IndexedSymbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub range: Range,
+    /// The function the declaration is nested in, if any.
+    pub container: Option<String>,
+}
+
+/// Declarations of every source file in the workspace, keyed by uri. Open
+/// documents are kept current from their edits, everything else is read
+/// from disk.
+#[derive(Default)]
+pub struct WorkspaceIndex {
+    files: BTreeMap<String, Vec<IndexedSymbol>>,
+    /// Files a scan found that have not been read yet.
+    pending: VecDeque<PathBuf>,
+}
+
+impl WorkspaceIndex {
+    /// Queues every source file below `root` that is not excluded, to be
+    /// indexed one at a time by [`WorkspaceIndex::scan_next`].
+    pub fn scan(&mut self, root: &Path, config: &ServerConfig) {
+        let files = collect_files(&[root.to_path_buf()]);
+        self.pending
+            .extend(files.into_iter().filter(|path| !config.is_excluded(path)));
+    }
+
+    pub fn is_scanning(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Indexes the next file the scan found, unless it has been indexed
+    /// since from something newer, such as an open document.
+    pub fn scan_next(&mut self) {
+        let Some(path) = self.pending.pop_front() else {
+            return;
+        };
+        let uri = path_to_uri(&path);
+        if self.files.contains_key(&uri) {
+            return;
+        }
+        if let Ok(text) = fs::read_to_string(&path) {
+            self.update(uri, &text);
+        }
+    }
+
+    /// Re-reads a file from disk, dropping it if it can no longer be read.
+    pub fn refresh(&mut self, uri: &str) {
+        match fs::read_to_string(uri_to_path(uri)) {
+            Ok(text) => self.update(uri.to_string(), &text),
+            Err(_) => self.remove(uri),
+        }
+    }
+
+    pub fn update(&mut self, uri: String, text: &str) {
+        let analysis = analyze(text);
+        self.set(uri, text, &analysis.symbols);
+    }
+
+    /// Records the declarations of an already analyzed document.
+    pub fn set(&mut self, uri: String, text: &str, symbols: &SymbolTable) {
+        self.files.insert(uri, declarations(text, symbols));
+    }
+
+    /// Drops a file, or every file below it if `uri` is a folder.
+    pub fn remove(&mut self, uri: &str) {
+        let folder = format!("{}/", uri.trim_end_matches('/'));
+        self.files
+            .retain(|indexed, _| indexed != uri && !indexed.starts_with(&folder));
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = (&String, &Vec<IndexedSymbol>)> {
+        self.files.iter()
+    }
+}
+
+fn declarations(text: &str, symbols: &SymbolTable) -> Vec<IndexedSymbol> {
+    let index = LineIndex::new(text);
+    symbols
+        .symbols
+        .iter()
+        .filter(|symbol| matches!(symbol.kind, SymbolKind::Function | SymbolKind::Variable))
+        .filter_map(|symbol| {
+            let span = symbol.span?;
+            let container = symbols
+                .symbols
+                .iter()
+                .filter(|other| other.kind == SymbolKind::Function)
+                .filter(|other| {
+                    other.statement.is_some_and(|statement| {
+                        statement.start < span.start && span.end <= statement.end
+                    })
+                })
+                .max_by_key(|other| other.statement.map(|statement| statement.start))
+                .map(|other| other.name.clone());
+            Some(IndexedSymbol {
+                name: symbol.name.clone(),
+                kind: symbol.kind,
+                range: index.range(span),
+                container,
+            })
+        })
+        .collect()
+}
+
+impl HeapSize for WorkspaceIndex {
+    fn heap_size(&self) -> usize {
+        self.files.heap_size()
+    }
+}
+
+impl HeapSize for IndexedSymbol {
+    fn heap_size(&self) -> usize {
+        self.name.heap_size() + self.container.heap_size()
+    }
+}
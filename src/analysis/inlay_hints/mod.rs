@@ -0,0 +1,107 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::ast::{BinaryOp, Expr, ExprKind, Stmt};
+use super::diagnostics::Range;
+use super::documents::DocumentStore;
+use super::lexer::Span;
+use super::line_index::LineIndex;
+use super::pull::TextDocumentIdentifier;
+use super::resolver::SymbolTable;
+
+/// `InlayHintKind.Parameter` from the protocol.
+const PARAMETER: u8 = 2;
+
+#[derive(Deserialize)]
+pub struct InlayHintParams {
+    #[serde(rename = "textDocument")]
+    pub text_document: TextDocumentIdentifier,
+    pub range: Range,
+}
+
+/// Handles `textDocument/inlayHint`, labelling each call argument in the
+/// range with the parameter it binds to, from the builtin signatures or
+/// the function's declaration. Arguments that are already a name equal to
+/// the parameter's are left alone.
+pub fn inlay_hints(documents: &DocumentStore, params: InlayHintParams) -> Value {
+    let Some(document) = documents.get(&params.text_document.uri) else {
+        return Value::Null;
+    };
+
+    let index = LineIndex::new(&document.text);
+    let range = Span::new(
+        index.offset(&params.range.start),
+        index.offset(&params.range.end),
+    );
+    let analysis = document.analysis();
+    let mut hints = Vec::new();
+    for expr in analysis.program.statements.iter().filter_map(Stmt::expr) {
+        argument_names(&analysis.symbols, expr, range, &mut hints);
+    }
+
+    json!(
+        hints
+            .into_iter()
+            .map(|(offset, name)| json!({
+                "position": index.position(offset),
+                "label": format!("{name}:"),
+                "kind": PARAMETER,
+                "paddingRight": true
+            }))
+            .collect::<Vec<_>>()
+    )
+}
+
+fn argument_names<'a>(
+    symbols: &'a SymbolTable,
+    expr: &Expr,
+    range: Span,
+    hints: &mut Vec<(usize, &'a str)>,
+) {
+    if expr.span.end < range.start || range.end < expr.span.start {
+        return;
+    }
+
+    match &expr.kind {
+        // Which parameter a piped value fills is up to the callee, so the
+        // arguments written out cannot be matched to names reliably.
+        ExprKind::Binary {
+            op: BinaryOp::Pipe,
+            left,
+            right,
+        } => {
+            argument_names(symbols, left, range, hints);
+            if let ExprKind::Call { callee, args } = &right.kind {
+                argument_names(symbols, callee, range, hints);
+                for arg in args {
+                    argument_names(symbols, arg, range, hints);
+                }
+            } else {
+                argument_names(symbols, right, range, hints);
+            }
+            return;
+        }
+        ExprKind::Call { callee, args } => {
+            if let ExprKind::Name(name) = &callee.kind
+                && let Some(symbol) = symbols
+                    .references
+                    .iter()
+                    .find(|reference| reference.span == name.span)
+                    .and_then(|reference| reference.symbol)
+            {
+                for (arg, param) in args.iter().zip(&symbols.symbols[symbol].params) {
+                    let same_name =
+                        matches!(&arg.kind, ExprKind::Name(name) if name.name == *param);
+                    if !same_name && range.start <= arg.span.start && arg.span.start <= range.end {
+                        hints.push((arg.span.start, param.as_str()));
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+
+    for child in expr.children() {
+        argument_names(symbols, child, range, hints);
+    }
+}
@@ -0,0 +1,278 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::diagnostics::Position;
+use super::documents::{Document, DocumentStore};
+use super::lexer::{Span, Token, TokenType};
+use super::line_index::LineIndex;
+use super::pull::TextDocumentIdentifier;
+
+#[derive(Deserialize)]
+pub struct InlineCompletionParams {
+    #[serde(rename = "textDocument")]
+    pub text_document: TextDocumentIdentifier,
+    pub position: Position,
+}
+
+/// Where ghost text is being asked for.
+pub struct InlineContext<'a> {
+    pub document: &'a Document,
+    pub offset: usize,
+}
+
+/// Text to show after the cursor, replacing `replace` when it is set.
+pub struct Suggestion {
+    pub text: String,
+    pub replace: Option<Span>,
+}
+
+/// A source of inline completions. Providers are asked in order and all of
+/// their suggestions are offered.
+pub struct InlineCompletionProvider {
+    pub name: &'static str,
+    pub suggest: fn(&InlineContext) -> Vec<Suggestion>,
+}
+
+/// Every provider consulted by `textDocument/inlineCompletion`.
+pub const PROVIDERS: &[InlineCompletionProvider] = &[
+    InlineCompletionProvider {
+        name: "pipeline stage",
+        suggest: pipeline_stage,
+    },
+    InlineCompletionProvider {
+        name: "close block",
+        suggest: close_block,
+    },
+];
+
+/// Handles `textDocument/inlineCompletion` by collecting the suggestions
+/// of every provider.
+pub fn inline_completion(documents: &DocumentStore, params: InlineCompletionParams) -> Value {
+    let Some(document) = documents.get(&params.text_document.uri) else {
+        return Value::Null;
+    };
+
+    let index = LineIndex::new(&document.text);
+    let context = InlineContext {
+        document,
+        offset: index.offset(&params.position),
+    };
+    let items: Vec<Value> = PROVIDERS
+        .iter()
+        .flat_map(|provider| (provider.suggest)(&context))
+        .map(|suggestion| {
+            let mut item = json!({ "insertText": suggestion.text });
+            if let Some(span) = suggestion.replace {
+                item["range"] = json!(index.range(span));
+            }
+            item
+        })
+        .collect();
+    json!({ "items": items })
+}
+
+/// After `|>`, offers the stage that most often follows the same stage
+/// elsewhere in the document: with `xs |> sort |> unique` written once,
+/// `ys |> sort |>` suggests `unique`. A partly typed stage narrows the
+/// choice to stages starting with it.
+fn pipeline_stage(context: &InlineContext) -> Vec<Suggestion> {
+    let text = &context.document.text;
+    let tokens = &context.document.analysis().tokens;
+    let mut before = tokens
+        .iter()
+        .take_while(|token| token.token_type != TokenType::EOF && token.span.end <= context.offset)
+        .count();
+    let typed = match before.checked_sub(1).map(|at| &tokens[at]) {
+        Some(token)
+            if token.token_type == TokenType::IDENTIFIER && token.span.end == context.offset =>
+        {
+            before -= 1;
+            Some(token.span)
+        }
+        _ => None,
+    };
+    let Some(pipe) = before.checked_sub(1) else {
+        return Vec::new();
+    };
+    if tokens[pipe].token_type != TokenType::PIPE {
+        return Vec::new();
+    }
+    let Some(previous) = stage_head(text, tokens, pipe) else {
+        return Vec::new();
+    };
+    let prefix = typed.map_or("", |span| &text[span.start..span.end]);
+
+    // How often each stage follows `previous`, in order of first use.
+    let mut seen: Vec<(&str, usize)> = Vec::new();
+    for (at, token) in tokens.iter().enumerate() {
+        if at == pipe || token.token_type != TokenType::PIPE {
+            continue;
+        }
+        if stage_head(text, tokens, at) != Some(previous) {
+            continue;
+        }
+        let Some(stage) = stage_after(text, tokens, at) else {
+            continue;
+        };
+        if stage.len() <= prefix.len() || !stage.starts_with(prefix) {
+            continue;
+        }
+        match seen.iter_mut().find(|(known, _)| *known == stage) {
+            Some((_, count)) => *count += 1,
+            None => seen.push((stage, 1)),
+        }
+    }
+    let Some((stage, _)) = seen.iter().rev().max_by_key(|(_, count)| *count) else {
+        return Vec::new();
+    };
+
+    let separator = if typed.is_none() && !text[..context.offset].ends_with(char::is_whitespace) {
+        " "
+    } else {
+        ""
+    };
+    vec![Suggestion {
+        text: format!("{separator}{stage}"),
+        replace: typed,
+    }]
+}
+
+/// The name a pipeline stage ending just before the `|>` at `pipe` starts
+/// with, which is the function it calls or the value it starts from.
+fn stage_head<'a>(text: &'a str, tokens: &[Token], pipe: usize) -> Option<&'a str> {
+    let mut depth = 0;
+    let mut head = None;
+    for token in tokens[..pipe].iter().rev() {
+        match token.token_type {
+            TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace => depth += 1,
+            TokenType::LeftParen | TokenType::LeftBracket | TokenType::LeftBrace if depth == 0 => {
+                break;
+            }
+            TokenType::LeftParen | TokenType::LeftBracket | TokenType::LeftBrace => depth -= 1,
+            _ if depth > 0 => {}
+            TokenType::PIPE
+            | TokenType::SEMICOLON
+            | TokenType::EQUAL
+            | TokenType::ARROW
+            | TokenType::COMMA => break,
+            TokenType::IDENTIFIER => head = Some(token.span),
+            _ => {}
+        }
+    }
+    head.map(|span| &text[span.start..span.end])
+}
+
+/// The source of the stage right after the `|>` at `pipe`, up to the next
+/// `|>` or the end of the expression.
+fn stage_after<'a>(text: &'a str, tokens: &[Token], pipe: usize) -> Option<&'a str> {
+    let mut depth = 0;
+    let mut end = None;
+    for token in &tokens[pipe + 1..] {
+        match token.token_type {
+            TokenType::LeftParen | TokenType::LeftBracket | TokenType::LeftBrace => depth += 1,
+            TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace
+                if depth == 0 =>
+            {
+                break;
+            }
+            TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace => depth -= 1,
+            _ if depth > 0 => {}
+            TokenType::PIPE | TokenType::SEMICOLON | TokenType::COMMA | TokenType::EOF => break,
+            _ => {}
+        }
+        end = Some(token.span.end);
+    }
+    let start = tokens.get(pipe + 1)?.span.start;
+    end.map(|end| &text[start..end])
+}
+
+/// At the end of a line, offers the brackets and braces left open before
+/// the cursor that nothing after it closes, innermost first. Braces go on
+/// their own line at the indentation of the line that opened them, with
+/// the `;` that ends a `let` when the block is its body.
+fn close_block(context: &InlineContext) -> Vec<Suggestion> {
+    let text = &context.document.text;
+    let tokens = &context.document.analysis().tokens;
+    let line_start = text[..context.offset].rfind('\n').map_or(0, |at| at + 1);
+    let line_end = text[context.offset..]
+        .find('\n')
+        .map_or(text.len(), |at| context.offset + at);
+    if !text[context.offset..line_end].trim().is_empty() {
+        return Vec::new();
+    }
+
+    let mut open: Vec<usize> = Vec::new();
+    let mut after = false;
+    for (at, token) in tokens.iter().enumerate() {
+        after |= token.span.start >= context.offset;
+        match token.token_type {
+            TokenType::LeftParen | TokenType::LeftBracket | TokenType::LeftBrace if !after => {
+                open.push(at);
+            }
+            TokenType::LeftParen | TokenType::LeftBracket | TokenType::LeftBrace => {
+                // Opened after the cursor; its closer is not ours to match.
+                open.push(usize::MAX);
+            }
+            TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace => {
+                open.pop();
+            }
+            _ => {}
+        }
+    }
+    open.retain(|at| *at != usize::MAX);
+    if open.is_empty() {
+        return Vec::new();
+    }
+
+    let blank_line = text[line_start..context.offset].trim().is_empty();
+    let mut closing = String::new();
+    for &at in open.iter().rev() {
+        let opener = &tokens[at];
+        match opener.token_type {
+            TokenType::LeftParen => closing.push(')'),
+            TokenType::LeftBracket => closing.push(']'),
+            _ => {
+                if !(closing.is_empty() && blank_line) {
+                    closing.push('\n');
+                }
+                closing.push_str(indentation(text, opener.span.start));
+                closing.push('}');
+                if ends_let(tokens, at) {
+                    closing.push(';');
+                }
+            }
+        }
+    }
+
+    let replace = blank_line.then(|| Span::new(line_start, context.offset));
+    vec![Suggestion {
+        text: closing,
+        replace,
+    }]
+}
+
+/// Whether the `{` at `brace` opens the body of a `let`, so its statement
+/// ends right after the matching `}`.
+fn ends_let(tokens: &[Token], brace: usize) -> bool {
+    let mut depth = 0;
+    for token in tokens[..brace].iter().rev() {
+        match token.token_type {
+            TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace => depth += 1,
+            TokenType::LeftParen | TokenType::LeftBracket | TokenType::LeftBrace if depth == 0 => {
+                return false;
+            }
+            TokenType::LeftParen | TokenType::LeftBracket | TokenType::LeftBrace => depth -= 1,
+            TokenType::SEMICOLON if depth == 0 => return false,
+            TokenType::LET if depth == 0 => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// The whitespace the line containing `offset` starts with.
+fn indentation(text: &str, offset: usize) -> &str {
+    let start = text[..offset].rfind('\n').map_or(0, |at| at + 1);
+    let line = &text[start..];
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
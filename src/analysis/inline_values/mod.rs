@@ -0,0 +1,104 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::ast::{Expr, ExprKind, Stmt};
+use super::diagnostics::Range;
+use super::documents::DocumentStore;
+use super::lexer::Span;
+use super::line_index::LineIndex;
+use super::pull::TextDocumentIdentifier;
+use super::resolver::{SymbolKind, SymbolTable};
+
+#[derive(Deserialize)]
+pub struct InlineValueParams {
+    #[serde(rename = "textDocument")]
+    pub text_document: TextDocumentIdentifier,
+    pub range: Range,
+    pub context: InlineValueContext,
+}
+
+#[derive(Deserialize)]
+pub struct InlineValueContext {
+    #[serde(rename = "stoppedLocation")]
+    pub stopped_location: Range,
+}
+
+/// Handles `textDocument/inlineValue`. Up to where execution stopped, every
+/// variable or parameter in the range that is still in scope there is
+/// looked up by name, and every field read is handed to the debugger to
+/// evaluate.
+pub fn inline_values(documents: &DocumentStore, params: InlineValueParams) -> Value {
+    let Some(document) = documents.get(&params.text_document.uri) else {
+        return Value::Null;
+    };
+
+    let index = LineIndex::new(&document.text);
+    let stopped = index.offset(&params.context.stopped_location.end);
+    let range = Span::new(
+        index.offset(&params.range.start),
+        index.offset(&params.range.end).min(stopped),
+    );
+    let analysis = document.analysis();
+    let symbols = &analysis.symbols;
+    let in_range = |span: Span| range.start <= span.start && span.end <= range.end;
+
+    let mut values: Vec<(Span, Value)> = Vec::new();
+    for (id, symbol) in symbols.symbols.iter().enumerate() {
+        if !matches!(symbol.kind, SymbolKind::Variable | SymbolKind::Parameter)
+            || symbols.resolve_at(stopped, &symbol.name) != Some(id)
+        {
+            continue;
+        }
+        for span in symbol.span.into_iter().chain(symbols.uses_of(id)) {
+            if in_range(span) {
+                values.push((
+                    span,
+                    json!({
+                        "range": index.range(span),
+                        "variableName": symbol.name,
+                        "caseSensitiveLookup": true
+                    }),
+                ));
+            }
+        }
+    }
+
+    let mut fields = Vec::new();
+    for expr in analysis.program.statements.iter().filter_map(Stmt::expr) {
+        field_reads(symbols, expr, &mut fields);
+    }
+    for span in fields.into_iter().filter(|span| in_range(*span)) {
+        values.push((
+            span,
+            json!({
+                "range": index.range(span),
+                "expression": &document.text[span.start..span.end]
+            }),
+        ));
+    }
+
+    values.sort_by_key(|(span, _)| (span.start, span.end));
+    json!(
+        values
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect::<Vec<_>>()
+    )
+}
+
+/// Spans of `receiver.field` reads whose receiver is a plain name, which a
+/// debugger can evaluate without side effects.
+fn field_reads(symbols: &SymbolTable, expr: &Expr, out: &mut Vec<Span>) {
+    if let ExprKind::Field { receiver, .. } = &expr.kind
+        && let ExprKind::Name(name) = &receiver.kind
+        && symbols
+            .references
+            .iter()
+            .any(|reference| reference.span == name.span && reference.symbol.is_some())
+    {
+        out.push(expr.span);
+    }
+    for child in expr.children() {
+        field_reads(symbols, child, out);
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value, json};
+
+use super::analyze;
+use super::baseline::path_to_uri;
+use super::hover::symbol_markdown;
+use super::lexer::Span;
+use super::line_index::LineIndex;
+use super::moniker;
+use super::resolver::SymbolId;
+
+/// The LSIF format version written by `dump`.
+pub const LSIF_VERSION: &str = "0.6.0";
+
+/// Builds an LSIF index of `files` below `root`: one JSON object per line,
+/// vertices before the edges that point at them. Every declaration and use
+/// gets a range pointing at its symbol's result set, which carries the
+/// definition, references, hover and, for top-level and builtin names, a
+/// moniker.
+pub fn dump(root: &Path, files: &[(PathBuf, String)]) -> Vec<Value> {
+    let mut out = Emitter::default();
+    out.vertex(
+        "metaData",
+        json!({
+            "version": LSIF_VERSION,
+            "projectRoot": path_to_uri(root),
+            "positionEncoding": "utf-16",
+            "toolInfo": {
+                "name": env!("CARGO_PKG_NAME"),
+                "version": env!("CARGO_PKG_VERSION")
+            }
+        }),
+    );
+    let project = out.vertex("project", json!({ "kind": moniker::SCHEME }));
+
+    let mut documents = Vec::new();
+    for (path, text) in files {
+        let uri = path_to_uri(path);
+        let document = out.vertex(
+            "document",
+            json!({ "uri": uri, "languageId": moniker::SCHEME }),
+        );
+        documents.push(document);
+
+        let analysis = analyze(text);
+        let symbols = &analysis.symbols;
+        let index = LineIndex::new(text);
+
+        // Declaration and use ranges of every symbol with at least one.
+        let mut occurrences: HashMap<SymbolId, (Option<u64>, Vec<u64>)> = HashMap::new();
+        let mut ranges = Vec::new();
+        for (id, symbol) in symbols.symbols.iter().enumerate() {
+            if let Some(span) = symbol.span {
+                let range = out.vertex("range", range_fields(&index, span));
+                ranges.push(range);
+                occurrences.entry(id).or_default().0 = Some(range);
+            }
+        }
+        for reference in &symbols.references {
+            let Some(id) = reference.symbol else {
+                continue;
+            };
+            let range = out.vertex("range", range_fields(&index, reference.span));
+            ranges.push(range);
+            occurrences.entry(id).or_default().1.push(range);
+        }
+
+        let mut ids: Vec<&SymbolId> = occurrences.keys().collect();
+        ids.sort();
+        for id in ids {
+            let (declaration, uses) = &occurrences[id];
+            let symbol = &symbols.symbols[*id];
+            let result_set = out.vertex("resultSet", json!({}));
+            for range in declaration.iter().chain(uses) {
+                out.edge("next", *range, json!({ "inV": result_set }));
+            }
+
+            if let Some(declaration) = declaration {
+                let definition = out.vertex("definitionResult", json!({}));
+                out.edge(
+                    "textDocument/definition",
+                    result_set,
+                    json!({ "inV": definition }),
+                );
+                out.edge(
+                    "item",
+                    definition,
+                    json!({ "inVs": [declaration], "document": document }),
+                );
+            }
+
+            let references = out.vertex("referenceResult", json!({}));
+            out.edge(
+                "textDocument/references",
+                result_set,
+                json!({ "inV": references }),
+            );
+            if let Some(declaration) = declaration {
+                out.edge(
+                    "item",
+                    references,
+                    json!({
+                        "inVs": [declaration],
+                        "document": document,
+                        "property": "definitions"
+                    }),
+                );
+            }
+            if !uses.is_empty() {
+                out.edge(
+                    "item",
+                    references,
+                    json!({ "inVs": uses, "document": document, "property": "references" }),
+                );
+            }
+
+            let hover = out.vertex(
+                "hoverResult",
+                json!({
+                    "result": {
+                        "contents": {
+                            "kind": "markdown",
+                            "value": symbol_markdown(text, symbol)
+                        }
+                    }
+                }),
+            );
+            out.edge("textDocument/hover", result_set, json!({ "inV": hover }));
+
+            let moniker = moniker::describe(symbols, *id, Some(root), &uri);
+            if moniker["kind"] != "local" {
+                let Value::Object(fields) = moniker else {
+                    continue;
+                };
+                let moniker = out.vertex("moniker", Value::Object(fields));
+                out.edge("moniker", result_set, json!({ "inV": moniker }));
+            }
+        }
+
+        if !ranges.is_empty() {
+            out.edge("contains", document, json!({ "inVs": ranges }));
+        }
+    }
+    if !documents.is_empty() {
+        out.edge("contains", project, json!({ "inVs": documents }));
+    }
+    out.lines
+}
+
+fn range_fields(index: &LineIndex, span: Span) -> Value {
+    let range = index.range(span);
+    json!({ "start": range.start, "end": range.end })
+}
+
+/// Numbers vertices and edges in the order they are written.
+#[derive(Default)]
+struct Emitter {
+    next: u64,
+    lines: Vec<Value>,
+}
+
+impl Emitter {
+    fn vertex(&mut self, label: &str, fields: Value) -> u64 {
+        self.emit("vertex", label, fields)
+    }
+
+    fn edge(&mut self, label: &str, out_v: u64, fields: Value) -> u64 {
+        let mut fields = fields;
+        fields["outV"] = json!(out_v);
+        self.emit("edge", label, fields)
+    }
+
+    fn emit(&mut self, kind: &str, label: &str, fields: Value) -> u64 {
+        self.next += 1;
+        let mut line = Map::new();
+        line.insert("id".to_string(), json!(self.next));
+        line.insert("type".to_string(), json!(kind));
+        line.insert("label".to_string(), json!(label));
+        if let Value::Object(fields) = fields {
+            line.extend(fields);
+        }
+        self.lines.push(Value::Object(line));
+        self.next
+    }
+}
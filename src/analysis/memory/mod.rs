@@ -0,0 +1,264 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::mem::size_of;
+use std::sync::Arc;
+
+use serde_json::{Value, json};
+
+use super::ServerState;
+use super::ast::{Block, Expr, ExprKind, ExprStmt, Ident, LetStmt, Program, Stmt};
+use super::diagnostics::Range;
+use super::documents::Document;
+use super::lexer::{Span, Token};
+use super::resolver::{Reference, Scope, Symbol, SymbolTable};
+use super::types::Type;
+
+/// Bytes a value owns on the heap, not counting itself. Estimates: an
+/// allocator's overhead and a hash map's control bytes are left out.
+pub trait HeapSize {
+    fn heap_size(&self) -> usize;
+}
+
+macro_rules! no_heap {
+    ($($ty:ty),*) => {
+        $(impl HeapSize for $ty {
+            fn heap_size(&self) -> usize {
+                0
+            }
+        })*
+    };
+}
+
+no_heap!(u8, u32, u64, usize, Span, Token, Type, Range, Reference);
+
+impl HeapSize for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+/// The counts on the front of the allocation are included; that it may
+/// be shared is not taken into account.
+impl HeapSize for Arc<str> {
+    fn heap_size(&self) -> usize {
+        2 * size_of::<usize>() + self.len()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * size_of::<T>() + self.iter().map(T::heap_size).sum::<usize>()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Box<T> {
+    fn heap_size(&self) -> usize {
+        size_of::<T>() + T::heap_size(self)
+    }
+}
+
+impl<T: HeapSize> HeapSize for Option<T> {
+    fn heap_size(&self) -> usize {
+        self.as_ref().map_or(0, T::heap_size)
+    }
+}
+
+impl<A: HeapSize, B: HeapSize> HeapSize for (A, B) {
+    fn heap_size(&self) -> usize {
+        self.0.heap_size() + self.1.heap_size()
+    }
+}
+
+impl<A: HeapSize, B: HeapSize, C: HeapSize> HeapSize for (A, B, C) {
+    fn heap_size(&self) -> usize {
+        self.0.heap_size() + self.1.heap_size() + self.2.heap_size()
+    }
+}
+
+impl<K: HeapSize, V: HeapSize> HeapSize for HashMap<K, V> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * size_of::<(K, V)>()
+            + self
+                .iter()
+                .map(|(key, value)| key.heap_size() + value.heap_size())
+                .sum::<usize>()
+    }
+}
+
+impl<K: HeapSize, V: HeapSize> HeapSize for BTreeMap<K, V> {
+    fn heap_size(&self) -> usize {
+        self.iter()
+            .map(|(key, value)| size_of::<(K, V)>() + key.heap_size() + value.heap_size())
+            .sum()
+    }
+}
+
+impl HeapSize for Program {
+    fn heap_size(&self) -> usize {
+        self.statements.heap_size()
+    }
+}
+
+impl HeapSize for Stmt {
+    fn heap_size(&self) -> usize {
+        match self {
+            Stmt::Let(stmt) => stmt.heap_size(),
+            Stmt::Expr(stmt) => stmt.heap_size(),
+        }
+    }
+}
+
+impl HeapSize for LetStmt {
+    fn heap_size(&self) -> usize {
+        self.name.heap_size() + self.params.heap_size() + self.value.heap_size()
+    }
+}
+
+impl HeapSize for ExprStmt {
+    fn heap_size(&self) -> usize {
+        self.expr.heap_size()
+    }
+}
+
+impl HeapSize for Ident {
+    fn heap_size(&self) -> usize {
+        self.name.heap_size()
+    }
+}
+
+impl HeapSize for Block {
+    fn heap_size(&self) -> usize {
+        self.statements.heap_size() + self.tail.heap_size()
+    }
+}
+
+impl HeapSize for Expr {
+    fn heap_size(&self) -> usize {
+        match &self.kind {
+            ExprKind::Number(text) | ExprKind::String(text) => text.heap_size(),
+            ExprKind::Name(ident) => ident.heap_size(),
+            ExprKind::Unary { operand, .. } => operand.heap_size(),
+            ExprKind::Binary { left, right, .. } => left.heap_size() + right.heap_size(),
+            ExprKind::Call { callee, args } => callee.heap_size() + args.heap_size(),
+            ExprKind::Field { receiver, name } => receiver.heap_size() + name.heap_size(),
+            ExprKind::List(items) | ExprKind::Set(items) => items.heap_size(),
+            ExprKind::Block(block) => block.heap_size(),
+            ExprKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => condition.heap_size() + then_branch.heap_size() + else_branch.heap_size(),
+            ExprKind::Lambda { params, body } => params.heap_size() + body.heap_size(),
+            ExprKind::Bool(_) | ExprKind::Error => 0,
+        }
+    }
+}
+
+impl HeapSize for SymbolTable {
+    fn heap_size(&self) -> usize {
+        self.scopes.heap_size()
+            + self.symbols.heap_size()
+            + self.references.heap_size()
+            + self.expression_types.heap_size()
+    }
+}
+
+impl HeapSize for Scope {
+    fn heap_size(&self) -> usize {
+        self.symbols.heap_size()
+    }
+}
+
+impl HeapSize for Symbol {
+    fn heap_size(&self) -> usize {
+        self.name.heap_size() + self.params.heap_size() + self.signature.heap_size()
+    }
+}
+
+impl HeapSize for Document {
+    fn heap_size(&self) -> usize {
+        self.uri.heap_size() + DocumentUsage::of(self).total()
+    }
+}
+
+/// Handles `mylang/memoryUsage`, estimating the bytes each of the server's
+/// caches holds, in total and for each open document, largest first.
+pub fn memory_usage(state: &ServerState) -> Value {
+    let documents: Vec<DocumentUsage> = state
+        .documents
+        .iter()
+        .into_iter()
+        .map(DocumentUsage::of)
+        .collect();
+    let sum = |part: fn(&DocumentUsage) -> usize| documents.iter().map(part).sum::<usize>();
+
+    let parts = [
+        ("documentTexts", sum(|usage| usage.text)),
+        ("tokens", sum(|usage| usage.tokens)),
+        ("syntaxTrees", sum(|usage| usage.syntax_tree)),
+        ("symbolTables", sum(|usage| usage.symbols)),
+        ("semanticTokens", state.semantic_tokens.heap_size()),
+        ("workspaceIndex", state.index.heap_size()),
+        ("notebooks", state.notebooks.heap_size()),
+    ];
+    let mut result = json!({
+        "documentCount": documents.len(),
+        "total": parts.iter().map(|(_, bytes)| bytes).sum::<usize>(),
+        "residentBytes": resident_bytes(),
+    });
+    for (name, bytes) in parts {
+        result[name] = json!(bytes);
+    }
+
+    let mut documents = documents;
+    documents.sort_by_key(|usage| std::cmp::Reverse(usage.total()));
+    result["documents"] = documents
+        .iter()
+        .map(|usage| {
+            json!({
+                "uri": usage.uri,
+                "total": usage.total(),
+                "text": usage.text,
+                "tokens": usage.tokens,
+                "syntaxTree": usage.syntax_tree,
+                "symbols": usage.symbols
+            })
+        })
+        .collect();
+    result
+}
+
+struct DocumentUsage<'a> {
+    uri: &'a str,
+    text: usize,
+    tokens: usize,
+    syntax_tree: usize,
+    symbols: usize,
+}
+
+impl<'a> DocumentUsage<'a> {
+    /// Counts only an analysis that is already cached, so the report does
+    /// not allocate what it measures.
+    fn of(document: &'a Document) -> Self {
+        let analysis = document.cached_analysis();
+        Self {
+            uri: &document.uri,
+            text: document.text.heap_size(),
+            tokens: analysis.map_or(0, |analysis| analysis.tokens.heap_size()),
+            syntax_tree: analysis.map_or(0, |analysis| analysis.program.heap_size()),
+            symbols: analysis.map_or(0, |analysis| analysis.symbols.heap_size()),
+        }
+    }
+
+    fn total(&self) -> usize {
+        self.text + self.tokens + self.syntax_tree + self.symbols
+    }
+}
+
+/// The process's resident set size, where the platform reports it.
+fn resident_bytes() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
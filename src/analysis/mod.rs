@@ -1,8 +1,55 @@
-pub mod diagnostics;
-pub mod lexer;
-use diagnostics::{Diagnostic, DiagnosticSeverity, Range};
-use lexer::TokenType;
-use std::collections::HashSet;
+pub mod actions;
+pub mod baseline;
+pub mod code_lens;
+pub mod commands;
+pub mod completion;
+pub mod config;
+pub mod definition;
+pub mod documents;
+pub mod folding;
+pub mod format;
+pub mod hover;
+pub mod in_flight;
+pub mod index;
+pub mod inlay_hints;
+pub mod inline_completion;
+pub mod inline_values;
+pub mod lsif;
+pub mod memory;
+pub mod moniker;
+pub mod notebooks;
+pub mod pull;
+pub mod recheck;
+pub mod refactor;
+pub mod references;
+pub mod rename;
+pub mod scheduler;
+pub mod selection;
+pub mod semantic_tokens;
+pub mod stats;
+pub mod workspace_symbols;
+
+use baseline::{Baseline, DEFAULT_BASELINE_FILE, uri_to_path};
+use cancel::{CancellationToken, Cancelled};
+use config::{ConfigLayers, ConfigUpdate, ServerConfig};
+use diagnostics::Diagnostic;
+use documents::{Document, DocumentStore};
+use in_flight::InFlight;
+use index::WorkspaceIndex;
+pub use mylang_analysis::{
+    FileAnalysis, Timings, analyze, analyze_cancellable, analyze_with, ast, builtins, cancel,
+    diagnostics, docs, document_diagnostics, fuzzy, interpreter, lexer, line_index, messages,
+    parser, resolver, types, workspace,
+};
+use notebooks::{Notebook, NotebookStore};
+use recheck::Recheck;
+use scheduler::Scheduler;
+use semantic_tokens::SemanticTokensCache;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
 
 use serde::Deserialize;
 use serde_json::{Value, json};
@@ -14,9 +61,93 @@ struct DidOpenParams {
 }
 
 #[derive(Deserialize)]
-#[allow(dead_code)]
 struct InitializeParams {
     capabilities: Value,
+    #[serde(rename = "rootUri")]
+    root_uri: Option<String>,
+    /// The client's UI language, used unless the configuration picks one.
+    locale: Option<String>,
+    #[serde(rename = "initializationOptions")]
+    initialization_options: Option<InitializationOptions>,
+}
+
+#[derive(Deserialize)]
+struct InitializationOptions {
+    /// Milliseconds to wait after the last edit before re-analyzing.
+    #[serde(rename = "debounceMs")]
+    debounce_ms: Option<u64>,
+    /// Baseline file to load instead of `mylang-baseline.json` in the root.
+    baseline: Option<PathBuf>,
+    #[serde(flatten)]
+    config: ConfigUpdate,
+}
+
+#[derive(Deserialize)]
+struct DidChangeParams {
+    #[serde(rename = "textDocument")]
+    text_document: VersionedTextDocumentIdentifier,
+    #[serde(rename = "contentChanges")]
+    content_changes: Vec<TextDocumentContentChangeEvent>,
+}
+
+#[derive(Deserialize)]
+struct VersionedTextDocumentIdentifier {
+    uri: String,
+    version: u32,
+}
+
+#[derive(Deserialize)]
+struct TextDocumentContentChangeEvent {
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct DidChangeWatchedFilesParams {
+    changes: Vec<FileEvent>,
+}
+
+#[derive(Deserialize)]
+struct FileEvent {
+    uri: String,
+    #[serde(rename = "type")]
+    change_type: u8,
+}
+
+const FILE_CREATED: u8 = 1;
+const FILE_CHANGED: u8 = 2;
+const FILE_DELETED: u8 = 3;
+
+#[derive(Deserialize)]
+struct DeleteFilesParams {
+    files: Vec<FileDelete>,
+}
+
+#[derive(Deserialize)]
+struct FileDelete {
+    uri: String,
+}
+
+#[derive(Deserialize)]
+struct RenameFilesParams {
+    files: Vec<FileRename>,
+}
+
+#[derive(Deserialize)]
+struct FileRename {
+    #[serde(rename = "oldUri")]
+    old_uri: String,
+}
+
+#[derive(Deserialize)]
+struct DidSaveParams {
+    #[serde(rename = "textDocument")]
+    text_document: pull::TextDocumentIdentifier,
+}
+
+#[derive(Deserialize)]
+struct DidCloseParams {
+    #[serde(rename = "textDocument")]
+    text_document: pull::TextDocumentIdentifier,
 }
 
 #[derive(Deserialize)]
@@ -29,221 +160,1233 @@ struct TextDocument {
     text: String,
 }
 
-pub fn run_analysis(message: String) -> Result<String, Diagnostic> {
-    let value = serde_json::from_str::<Value>(&message).map_err(|e| Diagnostic {
-        range: Range::default(),
-        severity: DiagnosticSeverity::Error,
-        message: Some(format!("Invalid JSON: {}", e)),
-        source: Some("custom-lsp".to_string()),
-    })?;
+/// Everything the main loop waits on: messages from the client, and frames
+/// produced by background work that still have to be written out.
+pub enum Event {
+    Incoming(String),
+    Outgoing(String),
+    Closed,
+}
+
+#[derive(Default)]
+pub struct ServerState {
+    pub documents: DocumentStore,
+    /// Open notebooks, each analyzed as one document made of its cells.
+    pub notebooks: NotebookStore,
+    /// Declarations across the workspace, including files that are not open.
+    pub index: WorkspaceIndex,
+    pub scheduler: Scheduler,
+    pub recheck: Recheck,
+    /// Known problems to hide, loaded from the workspace baseline file.
+    pub baseline: Arc<Baseline>,
+    pub config: Arc<ServerConfig>,
+    /// Counts changes to the settings and baseline every report depends on,
+    /// so pulled result ids from before one no longer match.
+    pub generation: u64,
+    /// The sources `config` is resolved from, kept so any one of them can
+    /// change underneath the others.
+    pub layers: ConfigLayers,
+    /// Where the baseline was loaded from, or would be.
+    pub baseline_path: Option<PathBuf>,
+    /// Lets background work hand its results back to the main loop.
+    pub events: Option<Sender<Event>>,
+    /// The work being done, for the reader thread to cancel.
+    pub in_flight: Arc<InFlight>,
+    /// Runs started by the Run lens that have not finished.
+    pub runs: commands::Runs,
+    /// Whether the client accepts server-initiated progress reporting.
+    pub work_done_progress: bool,
+    /// Whether the client lets us register file watchers after startup.
+    pub watch_files: bool,
+    /// Whether the client renders Markdown in hovers.
+    pub hover_markdown: bool,
+    /// The workspace folder, if the client opened one.
+    pub root: Option<PathBuf>,
+    /// Whether the client can show code actions that do not apply, with why.
+    pub code_action_disabled: bool,
+    /// The `workspace/*/refresh` requests the client supports.
+    pub refresh: Vec<&'static str>,
+    pub semantic_tokens: SemanticTokensCache,
+    /// Whether the client sent `shutdown`; every request after it fails.
+    pub shut_down: bool,
+    /// Set by `exit`: 0 after a `shutdown`, 1 without one.
+    pub exit_code: Option<i32>,
+}
+
+/// Why a message could not be handled, answered as a JSON-RPC error when the
+/// message was a request.
+pub struct ResponseError {
+    pub code: i64,
+    pub message: String,
+}
+
+fn invalid_params(method: &str, e: serde_json::Error) -> ResponseError {
+    ResponseError {
+        code: INVALID_PARAMS,
+        message: format!("Invalid {} params: {}", method, e),
+    }
+}
+
+/// Handles a single client message and returns every message that should be
+/// sent back, in order. Requests produce exactly one response carrying their
+/// id, an error if they could not be handled; notifications and partial
+/// results may add more.
+pub fn run_analysis(message: String, state: &mut ServerState) -> Vec<String> {
+    let value = match serde_json::from_str::<Value>(&message) {
+        Ok(value) => value,
+        Err(e) => {
+            tracing::warn!("rejected a message: {}", e);
+            return vec![parse_error(&format!("Invalid JSON: {e}"))];
+        }
+    };
+
+    match handle_message(&value, state) {
+        Ok(messages) => messages,
+        Err(e) => {
+            tracing::warn!("rejected a message: {}", e.message);
+            // Notifications are never answered, not even with an error.
+            let id = value.get("id").cloned().unwrap_or(Value::Null);
+            if id.is_null() && value.get("method").is_some() {
+                return Vec::new();
+            }
+            let response = error_response(&id, e.code, &e.message);
+            vec![serde_json::to_string(&response).unwrap()]
+        }
+    }
+}
+
+/// The error answering a message that could not be read, which has no id
+/// to answer with.
+pub fn parse_error(message: &str) -> String {
+    serde_json::to_string(&error_response(&Value::Null, PARSE_ERROR, message)).unwrap()
+}
+
+fn handle_message(value: &Value, state: &mut ServerState) -> Result<Vec<String>, ResponseError> {
+    // Replies to our own requests. Only the one creating a progress token
+    // needs handling: the progress begins once the client has accepted it.
+    if value.get("method").is_none()
+        && let Some(id) = value.get("id")
+    {
+        let accepted = value.get("error").is_none();
+        return Ok(state
+            .recheck
+            .created(id, accepted)
+            .iter()
+            .map(|message| serde_json::to_string(message).unwrap())
+            .collect());
+    }
 
     let method = value
         .get("method")
         .and_then(|m| m.as_str())
-        .ok_or_else(|| Diagnostic {
-            range: Range::default(),
-            severity: DiagnosticSeverity::Error,
-            message: Some("Missing 'method' field".to_string()),
-            source: Some("custom-lsp".to_string()),
+        .ok_or_else(|| ResponseError {
+            code: INVALID_REQUEST,
+            message: "Missing 'method' field".to_string(),
         })?;
+    // Requests such as `shutdown` may leave their params out.
+    let params = value.get("params").unwrap_or(&Value::Null);
 
-    let params = value.get("params").ok_or_else(|| Diagnostic {
-        range: Range::default(),
-        severity: DiagnosticSeverity::Error,
-        message: Some("Missing 'params' field".to_string()),
-        source: Some("custom-lsp".to_string()),
-    })?;
-
-    let mut diagnostics = Vec::new();
-    let mut scope_stack = Vec::new();
-    scope_stack.push(generate_globals());
+    let id = value.get("id").cloned().unwrap_or(Value::Null);
+    let uri = params.pointer("/textDocument/uri").and_then(Value::as_str);
+    let span = tracing::info_span!("message", method, %id, uri = tracing::field::Empty);
+    if let Some(uri) = uri {
+        span.record("uri", uri);
+    }
+    let _span = span.entered();
+    tracing::debug!("handling");
+    if state.shut_down && method != "exit" {
+        if id.is_null() {
+            return Ok(Vec::new());
+        }
+        return Err(ResponseError {
+            code: INVALID_REQUEST,
+            message: "The server is shutting down".to_string(),
+        });
+    }
+    let started = Instant::now();
+    let in_flight = state.in_flight.clone();
+    let running = in_flight.begin(Some(&id), uri);
 
-    let mut response = json!(null);
+    // Analyze the document a request is about up front, where a
+    // cancellation can stop it part way; the handler then finds the
+    // analysis cached.
+    let timings = if id.is_null() {
+        None
+    } else {
+        let analyzed = if in_flight.is_cancelled_request(&id) {
+            Err(Cancelled)
+        } else {
+            uri.and_then(|uri| state.documents.get(uri))
+                .map_or(Ok(None), |document| {
+                    analyze_up_front(document, &running.token)
+                })
+        };
+        match analyzed {
+            Ok(timings) => timings,
+            Err(Cancelled) => {
+                let (code, message) = if in_flight.is_cancelled_request(&id) {
+                    (REQUEST_CANCELLED, "The request was cancelled")
+                } else {
+                    (CONTENT_MODIFIED, "The document changed")
+                };
+                tracing::debug!(message);
+                let response = error_response(&id, code, message);
+                return Ok(vec![serde_json::to_string(&response).unwrap()]);
+            }
+        }
+    };
+    let mut outgoing = Vec::new();
 
     match method {
         "initialize" => {
-            let param: InitializeParams =
-                serde_json::from_value(params.clone()).map_err(|e| Diagnostic {
-                    range: Range::default(),
-                    severity: DiagnosticSeverity::Error,
-                    message: Some(format!("Invalid initialize params: {}", e)),
-                    source: Some("custom-lsp".to_string()),
-                })?;
-        }
+            let param: InitializeParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("initialize", e))?;
 
-        "textDocument/didOpen" => {
-            let param: DidOpenParams =
-                serde_json::from_value(params.clone()).map_err(|e| Diagnostic {
-                    range: Range::default(),
-                    severity: DiagnosticSeverity::Error,
-                    message: Some(format!("Invalid didOpen params: {}", e)),
-                    source: Some("custom-lsp".to_string()),
-                })?;
+            state.work_done_progress = param
+                .capabilities
+                .pointer("/window/workDoneProgress")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            state.watch_files = param
+                .capabilities
+                .pointer("/workspace/didChangeWatchedFiles/dynamicRegistration")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            state.hover_markdown = param
+                .capabilities
+                .pointer("/textDocument/hover/contentFormat")
+                .and_then(|v| v.as_array())
+                .is_some_and(|formats| formats.iter().any(|format| format == "markdown"));
+            state.code_action_disabled = param
+                .capabilities
+                .pointer("/textDocument/codeAction/disabledSupport")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            state.refresh = REFRESH_REQUESTS
+                .iter()
+                .filter(|(_, capability)| {
+                    param
+                        .capabilities
+                        .pointer(capability)
+                        .and_then(|v| v.as_bool())
+                        .unwrap_or(false)
+                })
+                .map(|(method, _)| *method)
+                .collect();
+
+            let options = param.initialization_options;
+            if let Some(debounce_ms) = options.as_ref().and_then(|options| options.debounce_ms) {
+                state.scheduler.delay = Duration::from_millis(debounce_ms);
+            }
+
+            let (baseline, config) = match options {
+                Some(options) => (options.baseline, options.config),
+                None => (None, ConfigUpdate::default()),
+            };
+            state.layers.initialization = config;
+            state.layers.client_locale = param.locale;
+
+            state.root = param.root_uri.as_deref().map(uri_to_path);
+            state.baseline_path = baseline.or_else(|| {
+                param
+                    .root_uri
+                    .map(|root| uri_to_path(&root).join(DEFAULT_BASELINE_FILE))
+            });
+            let problems = load_settings(state);
+            if let Some(root) = &state.root {
+                state.index.scan(root, &state.config);
+            }
+
+            let mut capabilities = json!({
+                "textDocumentSync": {
+                    "openClose": true,
+                    "change": 1,
+                    "save": { "includeText": false }
+                },
+                "notebookDocumentSync": {
+                    "notebookSelector": [{
+                        "cells": [{ "language": notebooks::LANGUAGE_ID }]
+                    }]
+                },
+                "workspace": {
+                    "fileOperations": {
+                        "didDelete": { "filters": [source_file_filter()] },
+                        "didRename": { "filters": [source_file_filter()] }
+                    }
+                },
+                "completionProvider": {
+                    "triggerCharacters": completion::TRIGGER_CHARACTERS,
+                    "resolveProvider": true
+                },
+                "hoverProvider": true,
+                "definitionProvider": true,
+                "referencesProvider": true,
+                "documentHighlightProvider": true,
+                "workspaceSymbolProvider": true,
+                "documentFormattingProvider": true,
+                "documentRangeFormattingProvider": true,
+                "documentOnTypeFormattingProvider": {
+                    "firstTriggerCharacter": format::ON_TYPE_TRIGGERS[0],
+                    "moreTriggerCharacter": &format::ON_TYPE_TRIGGERS[1..]
+                },
+                "renameProvider": {
+                    "prepareProvider": true
+                },
+                "linkedEditingRangeProvider": true,
+                "codeActionProvider": {
+                    "codeActionKinds": [
+                        "quickfix",
+                        "refactor.extract",
+                        "refactor.inline",
+                        "refactor.rewrite",
+                        actions::FIX_ALL
+                    ]
+                },
+                "codeLensProvider": {
+                    "resolveProvider": true
+                },
+                "executeCommandProvider": {
+                    "commands": commands::ids()
+                },
+                "monikerProvider": true,
+                "inlayHintProvider": true,
+                "inlineValueProvider": true,
+                "inlineCompletionProvider": true,
+                "foldingRangeProvider": true,
+                "selectionRangeProvider": true,
+                "semanticTokensProvider": {
+                    "legend": semantic_tokens::legend(),
+                    "range": true,
+                    "full": { "delta": true }
+                },
+                "diagnosticProvider": {
+                    "interFileDependencies": false,
+                    "workspaceDiagnostics": true
+                }
+            });
+            // Features the configuration turns off are not advertised.
+            if let Some(capabilities) = capabilities.as_object_mut() {
+                capabilities.retain(|capability, _| {
+                    capability
+                        .strip_suffix("Provider")
+                        .is_none_or(|feature| state.config.enabled(feature))
+                });
+            }
 
-            let text = param.text_document.text;
-            let word_errors = find_unknown_words(&text, &mut scope_stack);
-            diagnostics.extend(word_errors);
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": { "capabilities": capabilities }
+            }));
+            outgoing.extend(problems.iter().map(|problem| show_warning(problem)));
+        }
 
-            response = json!({
+        "initialized" if state.watch_files => {
+            outgoing.push(json!({
                 "jsonrpc": "2.0",
-                "method": "textDocument/publishDiagnostics",
+                "id": "mylang/watch-files",
+                "method": "client/registerCapability",
                 "params": {
-                    "uri": param.text_document.uri,
-                    "diagnostics": diagnostics
+                    "registrations": [{
+                        "id": "mylang/watch-files",
+                        "method": "workspace/didChangeWatchedFiles",
+                        "registerOptions": {
+                            "watchers": [{ "globPattern": "**/*.mylang" }]
+                        }
+                    }]
                 }
-            });
+            }));
         }
 
-        _ => {}
-    };
+        "textDocument/didOpen" => {
+            let param: DidOpenParams =
+                serde_json::from_value(params.clone()).map_err(|e| invalid_params("didOpen", e))?;
 
-    let output = serde_json::to_string(&response).unwrap();
-    Ok(output)
-}
+            let document = param.text_document;
+            state
+                .documents
+                .open(document.uri.clone(), document.version, document.text);
+            let diagnostics = reported_diagnostics(
+                &state.config,
+                &state.baseline,
+                state.documents.get(&document.uri).unwrap(),
+            );
+
+            state.scheduler.cancel(&document.uri);
+            outgoing.push(publish_diagnostics(&document.uri, &diagnostics));
+        }
 
-fn generate_globals() -> HashSet<String> {
-    let mut known_words = HashSet::new();
-    known_words.insert("let".to_string());
-    known_words.insert("if".to_string());
-    known_words.insert("else".to_string());
-    known_words.insert("true".to_string());
-    known_words.insert("false".to_string());
+        "textDocument/didChange" => {
+            let param: DidChangeParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("didChange", e))?;
 
-    known_words
-}
+            // The server only advertises full document sync, so the last
+            // change always carries the complete text.
+            if let Some(change) = param.content_changes.into_iter().last() {
+                let document = param.text_document;
+                state
+                    .documents
+                    .open(document.uri.clone(), document.version, change.text);
+                state.scheduler.schedule(document.uri);
+            }
+        }
 
-fn find_unknown_words(text: &String, scope_stack: &mut Vec<HashSet<String>>) -> Vec<Diagnostic> {
-    let mut diagnostics = Vec::new();
+        "textDocument/didSave" => {
+            let param: DidSaveParams =
+                serde_json::from_value(params.clone()).map_err(|e| invalid_params("didSave", e))?;
 
-    let tokens = lexer::lex(text.to_string());
-    let mut i = 0;
+            outgoing.extend(state.recheck.start(
+                &state.documents,
+                &param.text_document.uri,
+                state.work_done_progress,
+            ));
+        }
 
-    while i < tokens.len() {
-        let token = &tokens[i];
+        "textDocument/didClose" => {
+            let param: DidCloseParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("didClose", e))?;
 
-        match token.token_type {
-            TokenType::LET => {
-                i += 1;
-                if i >= tokens.len() {
-                    let diagnostic = Diagnostic::generate(token, "Unexpected termination");
-                    diagnostics.push(diagnostic);
-                    break;
-                }
+            // The client has dropped its text; the file on disk is what
+            // the workspace holds from now on.
+            let uri = param.text_document.uri;
+            state.documents.remove(&uri);
+            state.scheduler.cancel(&uri);
+            state.semantic_tokens.remove(&uri);
+            outgoing.extend(state.recheck.forget(&uri));
+            state.index.refresh(&uri);
+        }
 
-                if tokens[i].token_type != TokenType::IDENTIFIER {
-                    let diagnostic = Diagnostic::generate(
-                        token,
-                        &format!(
-                            "Expected identifier after 'let', found: {}",
-                            tokens[i].lexeme
-                        ),
-                    );
-                    diagnostics.push(diagnostic);
-                    break;
-                }
+        "notebookDocument/didOpen" => {
+            let param: notebooks::DidOpenNotebookParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("notebookDocument/didOpen", e))?;
 
-                let lexeme = tokens[i].lexeme.clone();
-                if scope_stack.last().unwrap().contains(&lexeme) {
-                    let diagnostic = Diagnostic::generate(
-                        token,
-                        &format!("Duplicate identifier in let statement: {}", lexeme),
-                    );
-                    diagnostics.push(diagnostic);
-                    break;
-                }
+            let notebook = state.notebooks.open(param);
+            state.scheduler.cancel(&notebook.document.uri);
+            outgoing.extend(publish_cell_diagnostics(
+                &state.config,
+                &state.baseline,
+                notebook,
+            ));
+        }
 
-                scope_stack.last_mut().unwrap().insert(lexeme);
-                i += 1;
-                if i >= tokens.len() {
-                    let diagnostic = Diagnostic::generate(
-                        token,
-                        "Unexpected end of input after identifier in let statement",
-                    );
-                    diagnostics.push(diagnostic);
-                    break;
-                }
+        "notebookDocument/didChange" => {
+            let param: notebooks::DidChangeNotebookParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("notebookDocument/didChange", e))?;
 
-                let added_words = handle_let_statement(&tokens[i..], &mut diagnostics);
-                scope_stack.push(added_words);
-
-                while tokens[i].token_type != TokenType::SEMICOLON {
-                    if tokens[i].token_type == TokenType::IDENTIFIER {
-                        let lexeme = tokens[i].lexeme.clone();
-                        if !scope_stack.last().unwrap().contains(&lexeme) {
-                            let diagnostic = Diagnostic::generate(
-                                &tokens[i],
-                                &format!("Unknown identifier: {}", lexeme),
-                            );
-                            diagnostics.push(diagnostic);
-                        }
-                    }
-                    i += 1;
-                }
+            let uri = param.notebook_document.uri.clone();
+            for removed in state.notebooks.change(param) {
+                outgoing.push(publish_diagnostics(&removed, &[]));
+            }
+            state.scheduler.schedule(uri);
+        }
+
+        "notebookDocument/didClose" => {
+            let param: notebooks::DidCloseNotebookParams =
+                serde_json::from_value(params.clone())
+                    .map_err(|e| invalid_params("notebookDocument/didClose", e))?;
 
-                if i > tokens.len() {
-                    let diagnostic =
-                        Diagnostic::generate(token, "Unexpected end of input after let statement");
-                    diagnostics.push(diagnostic);
-                    break;
+            let uri = param.notebook_document.uri;
+            state.scheduler.cancel(&uri);
+            if let Some(notebook) = state.notebooks.close(&uri) {
+                for cell in notebook.cell_uris() {
+                    outgoing.push(publish_diagnostics(cell, &[]));
                 }
+            }
+        }
 
-                scope_stack.pop();
+        "$/cancelRequest" => {
+            if let Some(id) = params.get("id") {
+                state.in_flight.forget(id);
+                state.runs.cancel(id);
             }
+        }
 
-            TokenType::IDENTIFIER => {
-                let lexeme = token.lexeme.clone();
-                if !scope_stack.iter().any(|set| set.contains(&lexeme)) {
-                    let diagnostic =
-                        Diagnostic::generate(token, &format!("Unknown identifier: {}", lexeme));
-                    diagnostics.push(diagnostic);
+        "workspace/didChangeConfiguration" => {
+            let Some(settings) = params.pointer("/settings/mylang") else {
+                return Ok(Vec::new());
+            };
+            let update: ConfigUpdate = serde_json::from_value(settings.clone())
+                .map_err(|e| invalid_params("didChangeConfiguration", e))?;
+            state.layers.pushed.merge(update);
+            set_config(state, state.layers.resolve());
+            republish_all(state, &mut outgoing);
+        }
+
+        "mylang/reloadConfig" => {
+            for problem in load_settings(state) {
+                outgoing.push(show_warning(&problem));
+            }
+            republish_all(state, &mut outgoing);
+        }
+
+        "workspace/didChangeWatchedFiles" => {
+            let param: DidChangeWatchedFilesParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("didChangeWatchedFiles", e))?;
+
+            for change in param.changes {
+                match change.change_type {
+                    FILE_DELETED => forget_documents(state, &change.uri, &mut outgoing),
+                    // Open documents are indexed from their edits instead.
+                    FILE_CREATED | FILE_CHANGED
+                        if state.documents.get(&change.uri).is_none()
+                            && !state.config.is_excluded(&uri_to_path(&change.uri)) =>
+                    {
+                        state.index.refresh(&change.uri)
+                    }
+                    _ => {}
                 }
             }
+        }
 
-            _ => {
-                // Handle other token types if necessary
+        "workspace/didDeleteFiles" => {
+            let param: DeleteFilesParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("didDeleteFiles", e))?;
+
+            for file in param.files {
+                forget_documents(state, &file.uri, &mut outgoing);
             }
         }
 
-        i += 1;
-    }
+        "workspace/didRenameFiles" => {
+            let param: RenameFilesParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("didRenameFiles", e))?;
 
-    diagnostics
-}
+            for file in param.files {
+                forget_documents(state, &file.old_uri, &mut outgoing);
+            }
+        }
+
+        "mylang/stats" => {
+            let param: stats::StatsParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("mylang/stats", e))?;
+
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": stats::stats(&state.documents, &state.config, &state.baseline, param)
+            }));
+        }
+
+        "mylang/memoryUsage" => {
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": memory::memory_usage(state)
+            }));
+        }
+
+        "textDocument/completion" => {
+            let param: completion::CompletionParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("textDocument/completion", e))?;
+
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": completion::completion(&state.documents, param)
+            }));
+        }
+
+        "completionItem/resolve" => {
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": completion::resolve(&state.documents, params.clone())
+            }));
+        }
+
+        "textDocument/hover" => {
+            let param: hover::HoverParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("textDocument/hover", e))?;
+
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": hover::hover(&state.documents, param, state.hover_markdown)
+            }));
+        }
+
+        "textDocument/definition" => {
+            let param: definition::DefinitionParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("textDocument/definition", e))?;
+
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": definition::definition(&state.documents, param)
+            }));
+        }
+
+        "textDocument/references" => {
+            let param: references::ReferenceParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("textDocument/references", e))?;
+
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": references::references(&state.documents, param)
+            }));
+        }
 
-fn handle_let_statement(
-    tokens: &[lexer::Token],
-    diagnostics: &mut Vec<Diagnostic>,
-) -> HashSet<String> {
-    let mut current = 0;
+        "textDocument/documentHighlight" => {
+            let param: references::DocumentHighlightParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("textDocument/documentHighlight", e))?;
 
-    let mut added_words = HashSet::new();
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": references::document_highlight(&state.documents, param)
+            }));
+        }
 
-    while current < tokens.len() {
-        let token = &tokens[current];
+        "workspace/symbol" => {
+            let param: workspace_symbols::WorkspaceSymbolParams =
+                serde_json::from_value(params.clone())
+                    .map_err(|e| invalid_params("workspace/symbol", e))?;
 
-        if token.token_type == TokenType::IDENTIFIER {
-            let lexeme = token.lexeme.clone();
-            if !added_words.contains(&lexeme) {
-                added_words.insert(lexeme);
-            } else {
-                let diagnostic = Diagnostic::generate(
-                    token,
-                    &format!("Duplicate identifier in let statement: {}", token.lexeme),
+            // Open documents may be ahead of the files on disk.
+            for document in state.documents.iter() {
+                state.index.set(
+                    document.uri.clone(),
+                    &document.text,
+                    &document.analysis().symbols,
                 );
-                diagnostics.push(diagnostic);
             }
-        } else if token.token_type == TokenType::ARROW {
-            break;
-        } else {
-            let diagnostic = Diagnostic::generate(
-                token,
-                &format!("Unexpected token in let statement: {}", token.lexeme),
+
+            let result = workspace_symbols::workspace_symbol(&state.index, param, &mut outgoing);
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": result
+            }));
+        }
+
+        "textDocument/rename" => {
+            let param: rename::RenameParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("textDocument/rename", e))?;
+
+            outgoing.push(match rename::rename(&state.documents, param) {
+                Ok(result) => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "result": result
+                }),
+                Err(message) => error_response(&id, REQUEST_FAILED, &message),
+            });
+        }
+
+        "textDocument/prepareRename" => {
+            let param: rename::PrepareRenameParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("textDocument/prepareRename", e))?;
+
+            outgoing.push(match rename::prepare_rename(&state.documents, param) {
+                Ok(result) => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "result": result
+                }),
+                Err(message) => error_response(&id, REQUEST_FAILED, &message),
+            });
+        }
+
+        "textDocument/formatting" => {
+            let param: format::DocumentFormattingParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("textDocument/formatting", e))?;
+
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": format::formatting(&state.documents, &state.config, param)
+            }));
+        }
+
+        "textDocument/rangeFormatting" => {
+            let param: format::DocumentRangeFormattingParams =
+                serde_json::from_value(params.clone())
+                    .map_err(|e| invalid_params("textDocument/rangeFormatting", e))?;
+
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": format::range_formatting(&state.documents, &state.config, param)
+            }));
+        }
+
+        "textDocument/onTypeFormatting" => {
+            let param: format::DocumentOnTypeFormattingParams =
+                serde_json::from_value(params.clone())
+                    .map_err(|e| invalid_params("textDocument/onTypeFormatting", e))?;
+
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": format::on_type_formatting(&state.documents, &state.config, param)
+            }));
+        }
+
+        "textDocument/semanticTokens/full" => {
+            let param: semantic_tokens::SemanticTokensParams =
+                serde_json::from_value(params.clone())
+                    .map_err(|e| invalid_params("textDocument/semanticTokens/full", e))?;
+
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": semantic_tokens::full(&state.documents, &mut state.semantic_tokens, param)
+            }));
+        }
+
+        "textDocument/semanticTokens/full/delta" => {
+            let param: semantic_tokens::SemanticTokensDeltaParams =
+                serde_json::from_value(params.clone())
+                    .map_err(|e| invalid_params("textDocument/semanticTokens/full/delta", e))?;
+
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": semantic_tokens::full_delta(&state.documents, &mut state.semantic_tokens, param)
+            }));
+        }
+
+        "textDocument/semanticTokens/range" => {
+            let param: semantic_tokens::SemanticTokensRangeParams =
+                serde_json::from_value(params.clone())
+                    .map_err(|e| invalid_params("textDocument/semanticTokens/range", e))?;
+
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": semantic_tokens::range(&state.documents, param)
+            }));
+        }
+
+        "textDocument/foldingRange" => {
+            let param: folding::FoldingRangeParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("textDocument/foldingRange", e))?;
+
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": folding::folding_ranges(&state.documents, param)
+            }));
+        }
+
+        "textDocument/selectionRange" => {
+            let param: selection::SelectionRangeParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("textDocument/selectionRange", e))?;
+
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": selection::selection_ranges(&state.documents, param)
+            }));
+        }
+
+        "textDocument/moniker" => {
+            let param: moniker::MonikerParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("textDocument/moniker", e))?;
+
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": moniker::moniker(&state.documents, state.root.as_deref(), param)
+            }));
+        }
+
+        "textDocument/inlineValue" => {
+            let param: inline_values::InlineValueParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("textDocument/inlineValue", e))?;
+
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": inline_values::inline_values(&state.documents, param)
+            }));
+        }
+
+        "textDocument/linkedEditingRange" => {
+            let param: rename::LinkedEditingRangeParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("textDocument/linkedEditingRange", e))?;
+
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": rename::linked_editing_range(&state.documents, param)
+            }));
+        }
+
+        "textDocument/inlineCompletion" => {
+            let param: inline_completion::InlineCompletionParams =
+                serde_json::from_value(params.clone())
+                    .map_err(|e| invalid_params("textDocument/inlineCompletion", e))?;
+
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": inline_completion::inline_completion(&state.documents, param)
+            }));
+        }
+
+        "textDocument/inlayHint" => {
+            let param: inlay_hints::InlayHintParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("textDocument/inlayHint", e))?;
+
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": inlay_hints::inlay_hints(&state.documents, param)
+            }));
+        }
+
+        "textDocument/codeLens" => {
+            let param: code_lens::CodeLensParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("textDocument/codeLens", e))?;
+
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": code_lens::code_lens(&state.documents, param)
+            }));
+        }
+
+        "codeLens/resolve" => {
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": code_lens::resolve(&state.documents, params.clone())
+            }));
+        }
+
+        "workspace/executeCommand" => {
+            let param: commands::ExecuteCommandParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("workspace/executeCommand", e))?;
+
+            let mut context = commands::CommandContext {
+                documents: &state.documents,
+                config: &state.config,
+                baseline: &state.baseline,
+                events: state.events.clone(),
+                id: &id,
+                runs: &state.runs,
+                outgoing: Vec::new(),
+            };
+            outgoing.push(match commands::execute_command(&mut context, param) {
+                Ok(result) => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "result": result
+                }),
+                Err(message) => error_response(&id, REQUEST_FAILED, &message),
+            });
+            outgoing.extend(context.outgoing);
+        }
+
+        "textDocument/codeAction" => {
+            let param: actions::CodeActionParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("textDocument/codeAction", e))?;
+
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": actions::code_actions(
+                    &state.documents,
+                    &state.config,
+                    &state.baseline,
+                    param,
+                    state.code_action_disabled
+                )
+            }));
+        }
+
+        "textDocument/diagnostic" => {
+            let param: pull::DocumentDiagnosticParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("textDocument/diagnostic", e))?;
+
+            let result = pull::document_diagnostic(
+                &state.documents,
+                &state.config,
+                &state.baseline,
+                state.generation,
+                param,
+            );
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": result
+            }));
+        }
+
+        "workspace/diagnostic" => {
+            let param: pull::WorkspaceDiagnosticParams = serde_json::from_value(params.clone())
+                .map_err(|e| invalid_params("workspace/diagnostic", e))?;
+
+            let result = pull::workspace_diagnostic(
+                &state.documents,
+                &state.index,
+                &state.config,
+                &state.baseline,
+                state.generation,
+                param,
+                &mut outgoing,
             );
-            diagnostics.push(diagnostic);
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": result
+            }));
         }
 
-        current += 1;
+        "shutdown" => {
+            state.shut_down = true;
+            state.runs.cancel_all();
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": null
+            }));
+        }
+
+        "exit" => {
+            state.runs.cancel_all();
+            state.exit_code = Some(if state.shut_down { 0 } else { 1 });
+        }
+
+        // Notifications the server does not know, `$/` ones included, may
+        // be ignored; requests may not.
+        _ if id.is_null() => {}
+        _ => {
+            return Err(ResponseError {
+                code: METHOD_NOT_FOUND,
+                message: format!("Unhandled method {}", method),
+            });
+        }
+    };
+
+    let serializing = Instant::now();
+    let mut messages: Vec<String> = outgoing
+        .iter()
+        .map(|message| serde_json::to_string(message).unwrap())
+        .collect();
+    if state.config.profiling && !id.is_null() {
+        let handled = serializing - started;
+        let log = profile_log(method, &id, handled, serializing.elapsed(), timings);
+        messages.push(serde_json::to_string(&log).unwrap());
+    }
+    Ok(messages)
+}
+
+/// Analyzes `document` unless its analysis is cached, saying how long the
+/// stages took if they ran.
+fn analyze_up_front(
+    document: &Document,
+    cancel: &CancellationToken,
+) -> Result<Option<Timings>, Cancelled> {
+    let cached = document.cached_analysis().is_some();
+    let analysis = document.try_analysis(cancel)?;
+    Ok((!cached).then_some(analysis.timings))
+}
+
+/// Analyzes every document whose debounce delay has elapsed, or every pending
+/// document when `flush` is set, and returns the resulting notifications.
+pub fn run_pending(state: &mut ServerState, flush: bool) -> Vec<String> {
+    let due = if flush {
+        state.scheduler.take_all()
+    } else {
+        state.scheduler.take_due(Instant::now())
+    };
+
+    let mut outgoing = Vec::new();
+    for uri in &due {
+        let _span = tracing::info_span!("diagnostics", uri).entered();
+        let running = state.in_flight.begin(None, Some(uri));
+        if let Some(document) = state.documents.get(uri) {
+            // An edit cancels the analysis and schedules another.
+            if document.try_analysis(&running.token).is_err() {
+                tracing::debug!("superseded by an edit");
+                continue;
+            }
+            let diagnostics = reported_diagnostics(&state.config, &state.baseline, document);
+            outgoing.push(publish_diagnostics(&document.uri, &diagnostics));
+        } else if let Some(notebook) = state.notebooks.get(uri) {
+            outgoing.extend(publish_cell_diagnostics(
+                &state.config,
+                &state.baseline,
+                notebook,
+            ));
+        }
     }
+    outgoing
+        .iter()
+        .map(|message| serde_json::to_string(message).unwrap())
+        .collect()
+}
+
+/// Whether background work is waiting for the main loop to be idle.
+pub fn has_background_work(state: &ServerState) -> bool {
+    state.recheck.is_running() || state.index.is_scanning()
+}
+
+/// Does one unit of background work: re-checks one dependent of a saved
+/// file, or else indexes one file of the workspace scan. The main loop
+/// only calls it while no message is waiting, so requests always go first
+/// and wait for at most one unit.
+pub fn run_background(state: &mut ServerState) -> Vec<String> {
+    let outgoing = if state.recheck.is_running() {
+        let _span = tracing::debug_span!("recheck").entered();
+        recheck::step(state)
+    } else {
+        let _span = tracing::debug_span!("index").entered();
+        state.index.scan_next();
+        Vec::new()
+    };
+    outgoing
+        .iter()
+        .map(|message| serde_json::to_string(message).unwrap())
+        .collect()
+}
+
+/// Requests asking the client to re-request data the server cannot push,
+/// with the client capability that allows each.
+const REFRESH_REQUESTS: &[(&str, &str)] = &[
+    (
+        "workspace/semanticTokens/refresh",
+        "/workspace/semanticTokens/refreshSupport",
+    ),
+    (
+        "workspace/inlayHint/refresh",
+        "/workspace/inlayHint/refreshSupport",
+    ),
+    (
+        "workspace/codeLens/refresh",
+        "/workspace/codeLens/refreshSupport",
+    ),
+    (
+        "workspace/diagnostic/refresh",
+        "/workspace/diagnostics/refreshSupport",
+    ),
+];
 
-    added_words
+/// Numbers the refresh requests the server sends.
+static NEXT_REFRESH: AtomicU64 = AtomicU64::new(1);
+
+/// Reads the user and project configuration and the baseline from disk and
+/// resolves the settings again. Returns the problems with the configuration
+/// files, for the user to see.
+fn load_settings(state: &mut ServerState) -> Vec<String> {
+    let problems = state.layers.load_files(state.root.as_deref());
+    set_config(state, state.layers.resolve());
+
+    let baseline = state
+        .baseline_path
+        .as_ref()
+        .and_then(|path| Baseline::load(path).ok());
+    state.baseline = Arc::new(baseline.unwrap_or_default());
+    state.generation += 1;
+    problems
+}
+
+/// Makes `config` the snapshot new work runs with, declaring its globals in
+/// every open document.
+fn set_config(state: &mut ServerState, config: ServerConfig) {
+    state.documents.set_globals(config.globals.clone());
+    state.notebooks.set_globals(config.globals.clone());
+    state.config = Arc::new(config);
+    state.generation += 1;
+}
+
+/// Publishes diagnostics for every open document and notebook again, for
+/// when settings they depend on have changed.
+fn republish_all(state: &mut ServerState, outgoing: &mut Vec<Value>) {
+    state.generation += 1;
+    for document in state.documents.iter() {
+        let diagnostics = reported_diagnostics(&state.config, &state.baseline, document);
+        outgoing.push(publish_diagnostics(&document.uri, &diagnostics));
+    }
+    for notebook in state.notebooks.iter() {
+        outgoing.extend(publish_cell_diagnostics(
+            &state.config,
+            &state.baseline,
+            notebook,
+        ));
+    }
+    // Pulled diagnostics, tokens, hints and lenses may be stale too, but
+    // only the client knows which it is showing.
+    outgoing.extend(
+        state
+            .refresh
+            .iter()
+            .filter(|method| {
+                method
+                    .split('/')
+                    .nth(1)
+                    .is_none_or(|feature| state.config.enabled(feature))
+            })
+            .map(|method| refresh_request(method)),
+    );
+}
+
+fn refresh_request(method: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": format!("mylang/refresh/{}", NEXT_REFRESH.fetch_add(1, Ordering::Relaxed)),
+        "method": method
+    })
+}
+
+fn source_file_filter() -> Value {
+    json!({ "pattern": { "glob": "**/*.mylang" } })
+}
+
+/// Drops a deleted or moved file from every index and clears its published
+/// diagnostics. Folder uris take all documents beneath them along.
+fn forget_documents(state: &mut ServerState, uri: &str, outgoing: &mut Vec<Value>) {
+    let folder = format!("{}/", uri.trim_end_matches('/'));
+    let removed: Vec<String> = state
+        .documents
+        .iter()
+        .into_iter()
+        .filter(|document| document.uri == uri || document.uri.starts_with(&folder))
+        .map(|document| document.uri.clone())
+        .collect();
+
+    state.index.remove(uri);
+    for removed_uri in &removed {
+        state.documents.remove(removed_uri);
+        state.scheduler.cancel(removed_uri);
+        state.semantic_tokens.remove(removed_uri);
+        outgoing.push(publish_diagnostics(removed_uri, &[]));
+    }
+
+    // Diagnostics may have been published for a file that is not open, so
+    // clear the uri itself even when nothing was indexed under it.
+    if removed.is_empty() {
+        outgoing.push(publish_diagnostics(uri, &[]));
+    }
+}
+
+/// `ParseError`: the message was not valid JSON.
+const PARSE_ERROR: i64 = -32700;
+
+/// `InvalidRequest`: the message was JSON but not a request or notification,
+/// or came after `shutdown`.
+const INVALID_REQUEST: i64 = -32600;
+
+/// `MethodNotFound`: the server does not handle the request.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// `InvalidParams`: the request's params did not have the expected shape.
+const INVALID_PARAMS: i64 = -32602;
+
+/// `RequestFailed`: the request was valid but could not be carried out.
+const REQUEST_FAILED: i64 = -32803;
+
+/// `RequestCancelled`: the client cancelled the request.
+const REQUEST_CANCELLED: i64 = -32800;
+
+/// `ContentModified`: the document changed while the request was handled.
+const CONTENT_MODIFIED: i64 = -32801;
+
+fn error_response(id: &Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {
+            "code": code,
+            "message": message
+        }
+    })
+}
+
+/// A `window/logMessage` of type `Log` saying where a request's time went:
+/// the analysis stages, if the request ran them, the handler, and writing
+/// the response as JSON.
+fn profile_log(
+    method: &str,
+    id: &Value,
+    handled: Duration,
+    serialize: Duration,
+    timings: Option<Timings>,
+) -> Value {
+    let ms = |duration: Duration| format!("{:.2} ms", duration.as_secs_f64() * 1000.0);
+    let (mut parts, analysis) = match timings {
+        Some(timings) => (
+            vec![
+                format!("lex {}", ms(timings.lex)),
+                format!("parse {}", ms(timings.parse)),
+                format!("resolve {}", ms(timings.resolve)),
+            ],
+            timings.lex + timings.parse + timings.resolve,
+        ),
+        None => (Vec::new(), Duration::ZERO),
+    };
+    parts.push(format!("handler {}", ms(handled.saturating_sub(analysis))));
+    parts.push(format!("serialize {}", ms(serialize)));
+    json!({
+        "jsonrpc": "2.0",
+        "method": "window/logMessage",
+        "params": {
+            "type": 4,
+            "message": format!("{method} {id}: {} ({})", ms(handled + serialize), parts.join(", "))
+        }
+    })
+}
+
+/// A `window/showMessage` notification of type `Warning`.
+fn show_warning(message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "window/showMessage",
+        "params": { "type": 2, "message": message }
+    })
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: &[Diagnostic]) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": {
+            "uri": uri,
+            "diagnostics": diagnostics
+        }
+    })
+}
+
+/// One `publishDiagnostics` per analyzed cell of a notebook.
+fn publish_cell_diagnostics(
+    config: &ServerConfig,
+    baseline: &Baseline,
+    notebook: &Notebook,
+) -> Vec<Value> {
+    notebook
+        .cell_diagnostics(config, baseline)
+        .iter()
+        .map(|(uri, diagnostics)| publish_diagnostics(uri, diagnostics))
+        .collect()
+}
+
+/// The diagnostics shown to the user for a document, served from its cached
+/// analysis.
+pub fn reported_diagnostics(
+    config: &ServerConfig,
+    baseline: &Baseline,
+    document: &Document,
+) -> Vec<Diagnostic> {
+    let diagnostics = document.analysis().diagnostics.clone();
+    adjust_diagnostics(config, baseline, &document.uri, &document.text, diagnostics)
+}
+
+/// Analysis results minus anything recorded in the baseline, adjusted to the
+/// configuration.
+pub fn adjust_diagnostics(
+    config: &ServerConfig,
+    baseline: &Baseline,
+    uri: &str,
+    text: &str,
+    diagnostics: Vec<Diagnostic>,
+) -> Vec<Diagnostic> {
+    let path = uri_to_path(uri);
+    if config.is_excluded(&path) {
+        return Vec::new();
+    }
+    let diagnostics = baseline.filter(&path, text, diagnostics);
+    config.adjust(diagnostics)
 }
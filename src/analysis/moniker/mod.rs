@@ -0,0 +1,117 @@
+use std::path::{Component, Path};
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::baseline::uri_to_path;
+use super::diagnostics::Position;
+use super::documents::DocumentStore;
+use super::line_index::LineIndex;
+use super::pull::TextDocumentIdentifier;
+use super::resolver::{FILE_SCOPE, SymbolId, SymbolKind, SymbolTable};
+
+pub const SCHEME: &str = "mylang";
+/// Package that builtins are reported under.
+pub const BUILTIN_PACKAGE: &str = "std";
+
+#[derive(Deserialize)]
+pub struct MonikerParams {
+    #[serde(rename = "textDocument")]
+    pub text_document: TextDocumentIdentifier,
+    pub position: Position,
+}
+
+/// Handles `textDocument/moniker`. Top-level declarations are exported as
+/// `package::module::name`, where the package is the workspace folder's
+/// name and the module the file's path inside it. Builtins are imported
+/// from `std`, and anything nested is local to the document.
+pub fn moniker(documents: &DocumentStore, root: Option<&Path>, params: MonikerParams) -> Value {
+    let Some(document) = documents.get(&params.text_document.uri) else {
+        return Value::Null;
+    };
+
+    let index = LineIndex::new(&document.text);
+    let offset = index.offset(&params.position);
+    let symbols = &document.analysis().symbols;
+    let Some((id, _)) = symbols.symbol_at(offset) else {
+        return Value::Null;
+    };
+    json!([describe(symbols, id, root, &document.uri)])
+}
+
+/// The moniker of symbol `id` of the document at `uri`.
+pub fn describe(symbols: &SymbolTable, id: SymbolId, root: Option<&Path>, uri: &str) -> Value {
+    let symbol = &symbols.symbols[id];
+    let (identifier, unique, kind) = match (symbol.kind, symbol.span) {
+        (SymbolKind::Builtin, _) | (_, None) => (
+            format!("{}::{}", BUILTIN_PACKAGE, symbol.name),
+            "global",
+            "import",
+        ),
+        (_, Some(_)) if symbol.scope == FILE_SCOPE => (
+            format!("{}::{}", module_path(root, uri), symbol.name),
+            "scheme",
+            "export",
+        ),
+        (_, Some(span)) => {
+            let mut path = vec![module_path(root, uri)];
+            path.extend(containers(symbols, span.start));
+            path.push(symbol.name.clone());
+            // Inner names can repeat, so the declaration's offset tells
+            // them apart.
+            (
+                format!("{}@{}", path.join("::"), span.start),
+                "document",
+                "local",
+            )
+        }
+    };
+
+    json!({
+        "scheme": SCHEME,
+        "identifier": identifier,
+        "unique": unique,
+        "kind": kind
+    })
+}
+
+/// `package::module` for a document: the workspace folder's name, then the
+/// file's path inside it without the extension.
+pub fn module_path(root: Option<&Path>, uri: &str) -> String {
+    let path = uri_to_path(uri);
+    let relative = root
+        .and_then(|root| path.strip_prefix(root).ok())
+        .unwrap_or(&path);
+    let package = root
+        .and_then(|root| root.file_name())
+        .map(|name| name.to_string_lossy().into_owned());
+
+    let mut segments: Vec<String> = package.into_iter().collect();
+    segments.extend(relative.with_extension("").components().filter_map(
+        |component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+            _ => None,
+        },
+    ));
+    segments.join("::")
+}
+
+/// Names of the functions whose bodies hold `offset`, outermost first.
+fn containers(symbols: &SymbolTable, offset: usize) -> Vec<String> {
+    let mut functions: Vec<(usize, &str)> = symbols
+        .symbols
+        .iter()
+        .filter(|symbol| symbol.kind == SymbolKind::Function)
+        .filter_map(|symbol| {
+            let statement = symbol.statement?;
+            let name = symbol.span?;
+            (name.end <= offset && offset < statement.end)
+                .then_some((statement.start, symbol.name.as_str()))
+        })
+        .collect();
+    functions.sort();
+    functions
+        .into_iter()
+        .map(|(_, name)| name.to_string())
+        .collect()
+}
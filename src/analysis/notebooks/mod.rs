@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use super::baseline::Baseline;
+use super::builtins::Global;
+use super::config::ServerConfig;
+use super::diagnostics::{Diagnostic, Position};
+use super::documents::Document;
+use super::memory::HeapSize;
+use super::reported_diagnostics;
+
+/// `NotebookCellKind.Code` from the protocol; markup cells are skipped.
+const CODE_CELL: u8 = 2;
+/// Cells in other languages are kept out of the virtual document.
+pub const LANGUAGE_ID: &str = "mylang";
+
+#[derive(Deserialize)]
+pub struct DidOpenNotebookParams {
+    #[serde(rename = "notebookDocument")]
+    pub notebook_document: NotebookDocument,
+    #[serde(rename = "cellTextDocuments")]
+    pub cell_text_documents: Vec<CellTextDocument>,
+}
+
+#[derive(Deserialize)]
+pub struct NotebookDocument {
+    pub uri: String,
+    pub version: u32,
+    pub cells: Vec<NotebookCell>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct NotebookCell {
+    pub kind: u8,
+    /// The uri of the cell's text document.
+    pub document: String,
+}
+
+#[derive(Deserialize)]
+pub struct CellTextDocument {
+    pub uri: String,
+    #[serde(rename = "languageId")]
+    pub language_id: String,
+    pub text: String,
+}
+
+#[derive(Deserialize)]
+pub struct DidChangeNotebookParams {
+    #[serde(rename = "notebookDocument")]
+    pub notebook_document: VersionedNotebookIdentifier,
+    pub change: NotebookChange,
+}
+
+#[derive(Deserialize)]
+pub struct VersionedNotebookIdentifier {
+    pub uri: String,
+    pub version: u32,
+}
+
+#[derive(Deserialize)]
+pub struct NotebookChange {
+    pub cells: Option<CellChanges>,
+}
+
+#[derive(Deserialize)]
+pub struct CellChanges {
+    pub structure: Option<StructureChange>,
+    /// Cells whose kind or metadata changed.
+    #[serde(default)]
+    pub data: Vec<NotebookCell>,
+    #[serde(rename = "textContent", default)]
+    pub text_content: Vec<CellTextChange>,
+}
+
+#[derive(Deserialize)]
+pub struct StructureChange {
+    pub array: CellArrayChange,
+    #[serde(rename = "didOpen", default)]
+    pub did_open: Vec<CellTextDocument>,
+}
+
+#[derive(Deserialize)]
+pub struct CellArrayChange {
+    pub start: usize,
+    #[serde(rename = "deleteCount")]
+    pub delete_count: usize,
+    #[serde(default)]
+    pub cells: Vec<NotebookCell>,
+}
+
+#[derive(Deserialize)]
+pub struct CellTextChange {
+    pub document: CellIdentifier,
+    pub changes: Vec<CellContentChange>,
+}
+
+#[derive(Deserialize)]
+pub struct CellIdentifier {
+    pub uri: String,
+}
+
+/// Cells follow the server's full document sync, so each change carries
+/// the whole text of the cell.
+#[derive(Deserialize)]
+pub struct CellContentChange {
+    pub text: String,
+}
+
+#[derive(Deserialize)]
+pub struct DidCloseNotebookParams {
+    #[serde(rename = "notebookDocument")]
+    pub notebook_document: NotebookIdentifier,
+}
+
+#[derive(Deserialize)]
+pub struct NotebookIdentifier {
+    pub uri: String,
+}
+
+struct Cell {
+    uri: String,
+    kind: u8,
+    language_id: String,
+    text: String,
+}
+
+impl Cell {
+    fn is_analyzed(&self) -> bool {
+        self.kind == CODE_CELL && self.language_id == LANGUAGE_ID
+    }
+}
+
+/// A notebook's cells, and the virtual document made of its mylang code
+/// cells one after the other, so later cells see the names declared by
+/// earlier ones.
+pub struct Notebook {
+    cells: Vec<Cell>,
+    /// For each analyzed cell, its uri, first line in the virtual document
+    /// and number of lines.
+    lines: Vec<(String, u32, u32)>,
+    pub document: Document,
+}
+
+impl Notebook {
+    fn new(uri: String, version: u32, cells: Vec<Cell>, globals: Arc<[Global]>) -> Self {
+        let mut notebook = Self {
+            cells,
+            lines: Vec::new(),
+            document: Document::new(uri, version, String::new()).with_globals(globals),
+        };
+        notebook.concatenate(version);
+        notebook
+    }
+
+    /// Rebuilds the virtual document from the cells.
+    fn concatenate(&mut self, version: u32) {
+        let mut text = String::new();
+        let mut line = 0;
+        self.lines.clear();
+        for cell in self.cells.iter().filter(|cell| cell.is_analyzed()) {
+            text.push_str(&cell.text);
+            if !cell.text.ends_with('\n') {
+                text.push('\n');
+            }
+            let count = cell.text.lines().count().max(1) as u32;
+            self.lines.push((cell.uri.clone(), line, count));
+            line += count;
+        }
+        self.document = Document::new(self.document.uri.clone(), version, text)
+            .with_globals(self.document.globals().clone());
+    }
+
+    /// The diagnostics of the virtual document, split up by cell with
+    /// lines counted from the start of each cell. Every analyzed cell gets
+    /// an entry, so cells that became clean are cleared too.
+    pub fn cell_diagnostics(
+        &self,
+        config: &ServerConfig,
+        baseline: &Baseline,
+    ) -> Vec<(String, Vec<Diagnostic>)> {
+        let mut cells: Vec<(String, Vec<Diagnostic>)> = self
+            .lines
+            .iter()
+            .map(|(uri, _, _)| (uri.clone(), Vec::new()))
+            .collect();
+
+        for mut diagnostic in reported_diagnostics(config, baseline, &self.document) {
+            let line = diagnostic.range.start.line;
+            let Some(at) = self
+                .lines
+                .iter()
+                .position(|(_, start, count)| line >= *start && line < start + count)
+            else {
+                continue;
+            };
+            let (_, start, count) = &self.lines[at];
+            diagnostic.range.start.line -= start;
+            diagnostic.range.end = if diagnostic.range.end.line < start + count {
+                Position {
+                    line: diagnostic.range.end.line - start,
+                    character: diagnostic.range.end.character,
+                }
+            } else {
+                // Errors at the end of input point past the cell.
+                Position {
+                    line: *count,
+                    character: 0,
+                }
+            };
+            cells[at].1.push(diagnostic);
+        }
+        cells
+    }
+
+    /// The uris of the analyzed cells.
+    pub fn cell_uris(&self) -> impl Iterator<Item = &str> {
+        self.lines.iter().map(|(uri, _, _)| uri.as_str())
+    }
+}
+
+#[derive(Default)]
+pub struct NotebookStore {
+    notebooks: HashMap<String, Notebook>,
+    globals: Arc<[Global]>,
+}
+
+impl NotebookStore {
+    /// Declares `globals` in every notebook, now and when opened later.
+    pub fn set_globals(&mut self, globals: Arc<[Global]>) {
+        for notebook in self.notebooks.values_mut() {
+            notebook.document.set_globals(globals.clone());
+        }
+        self.globals = globals;
+    }
+
+    pub fn open(&mut self, params: DidOpenNotebookParams) -> &Notebook {
+        let mut texts: HashMap<String, CellTextDocument> = params
+            .cell_text_documents
+            .into_iter()
+            .map(|document| (document.uri.clone(), document))
+            .collect();
+        let notebook = params.notebook_document;
+        let cells = notebook
+            .cells
+            .into_iter()
+            .map(|cell| new_cell(cell, &mut texts))
+            .collect();
+
+        let uri = notebook.uri.clone();
+        self.notebooks.insert(
+            uri.clone(),
+            Notebook::new(uri.clone(), notebook.version, cells, self.globals.clone()),
+        );
+        &self.notebooks[&uri]
+    }
+
+    /// Applies a change and returns the uris of the analyzed cells it
+    /// removed, whose diagnostics have to be cleared.
+    pub fn change(&mut self, params: DidChangeNotebookParams) -> Vec<String> {
+        let identifier = params.notebook_document;
+        let Some(notebook) = self.notebooks.get_mut(&identifier.uri) else {
+            return Vec::new();
+        };
+        let before: Vec<String> = notebook.cell_uris().map(str::to_string).collect();
+
+        if let Some(changes) = params.change.cells {
+            if let Some(structure) = changes.structure {
+                let mut texts: HashMap<String, CellTextDocument> = structure
+                    .did_open
+                    .into_iter()
+                    .map(|document| (document.uri.clone(), document))
+                    .collect();
+                let array = structure.array;
+                let start = array.start.min(notebook.cells.len());
+                let end = (start + array.delete_count).min(notebook.cells.len());
+                let inserted: Vec<Cell> = array
+                    .cells
+                    .into_iter()
+                    .map(|cell| new_cell(cell, &mut texts))
+                    .collect();
+                notebook.cells.splice(start..end, inserted);
+            }
+            for data in changes.data {
+                if let Some(cell) = notebook.cells.iter_mut().find(|c| c.uri == data.document) {
+                    cell.kind = data.kind;
+                }
+            }
+            for content in changes.text_content {
+                let Some(change) = content.changes.into_iter().last() else {
+                    continue;
+                };
+                if let Some(cell) = notebook
+                    .cells
+                    .iter_mut()
+                    .find(|cell| cell.uri == content.document.uri)
+                {
+                    cell.text = change.text;
+                }
+            }
+        }
+        notebook.concatenate(identifier.version);
+
+        let after: Vec<&str> = notebook.cell_uris().collect();
+        before
+            .into_iter()
+            .filter(|uri| !after.contains(&uri.as_str()))
+            .collect()
+    }
+
+    pub fn close(&mut self, uri: &str) -> Option<Notebook> {
+        self.notebooks.remove(uri)
+    }
+
+    pub fn get(&self, uri: &str) -> Option<&Notebook> {
+        self.notebooks.get(uri)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Notebook> {
+        self.notebooks.values()
+    }
+}
+
+impl HeapSize for NotebookStore {
+    fn heap_size(&self) -> usize {
+        self.notebooks.heap_size()
+    }
+}
+
+impl HeapSize for Notebook {
+    fn heap_size(&self) -> usize {
+        self.cells.heap_size() + self.lines.heap_size() + self.document.heap_size()
+    }
+}
+
+impl HeapSize for Cell {
+    fn heap_size(&self) -> usize {
+        self.uri.heap_size() + self.language_id.heap_size() + self.text.heap_size()
+    }
+}
+
+/// A cell with the text the client sent for it, or empty when it sent none.
+fn new_cell(cell: NotebookCell, texts: &mut HashMap<String, CellTextDocument>) -> Cell {
+    let (language_id, text) = match texts.remove(&cell.document) {
+        Some(document) => (document.language_id, document.text),
+        None => (LANGUAGE_ID.to_string(), String::new()),
+    };
+    Cell {
+        uri: cell.document,
+        kind: cell.kind,
+        language_id,
+        text,
+    }
+}
@@ -0,0 +1,161 @@
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::baseline::{Baseline, uri_to_path};
+use super::config::ServerConfig;
+use super::documents::{Document, DocumentStore};
+use super::index::WorkspaceIndex;
+use super::reported_diagnostics;
+
+#[derive(Deserialize)]
+pub struct DocumentDiagnosticParams {
+    #[serde(rename = "textDocument")]
+    pub text_document: TextDocumentIdentifier,
+    #[serde(rename = "previousResultId")]
+    pub previous_result_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TextDocumentIdentifier {
+    pub uri: String,
+}
+
+#[derive(Deserialize)]
+pub struct WorkspaceDiagnosticParams {
+    #[serde(rename = "previousResultIds", default)]
+    pub previous_result_ids: Vec<PreviousResultId>,
+    #[serde(rename = "partialResultToken")]
+    pub partial_result_token: Option<Value>,
+}
+
+#[derive(Deserialize)]
+pub struct PreviousResultId {
+    pub uri: String,
+    pub value: String,
+}
+
+/// Result ids are derived from the document version and the generation of
+/// the settings, so a report is unchanged exactly when the client has already
+/// seen diagnostics for this version under the current configuration.
+fn result_id(generation: u64, document: &Document) -> String {
+    format!("{generation}:{}", document.version)
+}
+
+/// Files that are not open have no version, so their ids stand on a hash of
+/// the text read from disk instead.
+fn disk_result_id(generation: u64, text: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    format!("{generation}:{:x}", hasher.finish())
+}
+
+fn report(
+    config: &ServerConfig,
+    baseline: &Baseline,
+    document: &Document,
+    result_id: String,
+    previous_result_id: Option<&str>,
+) -> Value {
+    if previous_result_id == Some(result_id.as_str()) {
+        return json!({
+            "kind": "unchanged",
+            "resultId": result_id
+        });
+    }
+
+    json!({
+        "kind": "full",
+        "resultId": result_id,
+        "items": reported_diagnostics(config, baseline, document)
+    })
+}
+
+pub fn document_diagnostic(
+    documents: &DocumentStore,
+    config: &ServerConfig,
+    baseline: &Baseline,
+    generation: u64,
+    params: DocumentDiagnosticParams,
+) -> Value {
+    match documents.get(&params.text_document.uri) {
+        Some(document) => report(
+            config,
+            baseline,
+            document,
+            result_id(generation, document),
+            params.previous_result_id.as_deref(),
+        ),
+        None => json!({
+            "kind": "full",
+            "items": []
+        }),
+    }
+}
+
+/// Builds one report per open document, then one per indexed file that is
+/// not open, analyzed from its text on disk. When the client supplied a
+/// partial result token, every report is streamed as its own `$/progress`
+/// notification and the final result is left empty, as the protocol requires.
+pub fn workspace_diagnostic(
+    documents: &DocumentStore,
+    index: &WorkspaceIndex,
+    config: &ServerConfig,
+    baseline: &Baseline,
+    generation: u64,
+    params: WorkspaceDiagnosticParams,
+    outgoing: &mut Vec<Value>,
+) -> Value {
+    let mut items = Vec::new();
+    let previous = |uri: &str| {
+        params
+            .previous_result_ids
+            .iter()
+            .find(|previous| previous.uri == uri)
+            .map(|previous| previous.value.as_str())
+    };
+    let mut send = |item: Value| match &params.partial_result_token {
+        Some(token) => outgoing.push(json!({
+            "jsonrpc": "2.0",
+            "method": "$/progress",
+            "params": {
+                "token": token,
+                "value": { "items": [item] }
+            }
+        })),
+        None => items.push(item),
+    };
+
+    for document in documents.iter() {
+        let result_id = result_id(generation, document);
+        let mut item = report(
+            config,
+            baseline,
+            document,
+            result_id,
+            previous(&document.uri),
+        );
+        item["uri"] = json!(document.uri);
+        item["version"] = json!(document.version);
+        send(item);
+    }
+
+    for (uri, _) in index.files() {
+        if documents.get(uri).is_some() {
+            continue;
+        }
+        let Ok(text) = fs::read_to_string(uri_to_path(uri)) else {
+            continue;
+        };
+        let result_id = disk_result_id(generation, &text);
+        let document = Document::new(uri.clone(), 0, text).with_globals(config.globals.clone());
+        let mut item = report(config, baseline, &document, result_id, previous(uri));
+        item["uri"] = json!(uri);
+        item["version"] = Value::Null;
+        send(item);
+    }
+
+    json!({ "items": items })
+}
@@ -0,0 +1,193 @@
+use std::collections::VecDeque;
+
+use serde_json::{Value, json};
+
+use super::ServerState;
+use super::documents::DocumentStore;
+use super::{publish_diagnostics, reported_diagnostics};
+
+/// Background re-analysis of the documents that depend on a saved file,
+/// one document at a time whenever no message is waiting. Only the most
+/// recent recheck runs; starting a new one ends the old.
+#[derive(Default)]
+pub struct Recheck {
+    current: Option<Run>,
+    started: u64,
+}
+
+struct Run {
+    progress: Progress,
+    remaining: VecDeque<String>,
+    total: usize,
+}
+
+/// Where reporting a run's progress stands. Progress may only begin once
+/// the client has answered the request creating its token.
+enum Progress {
+    Off,
+    Requested(String),
+    Begun(String),
+}
+
+/// mylang has no imports yet, so nothing records which files read which.
+/// Until it does, every other open document is conservatively treated as a
+/// dependent of the saved one.
+pub fn dependents(documents: &DocumentStore, uri: &str) -> Vec<String> {
+    documents
+        .iter()
+        .into_iter()
+        .filter(|document| document.uri != uri)
+        .map(|document| document.uri.clone())
+        .collect()
+}
+
+impl Recheck {
+    /// Queues the dependents of `saved_uri`, returning the messages that
+    /// end the previous recheck and announce this one.
+    pub fn start(
+        &mut self,
+        documents: &DocumentStore,
+        saved_uri: &str,
+        report_progress: bool,
+    ) -> Vec<Value> {
+        let mut outgoing = self.finish();
+
+        let targets: VecDeque<String> = dependents(documents, saved_uri).into();
+        if targets.is_empty() {
+            return outgoing;
+        }
+
+        self.started += 1;
+        let progress = if report_progress {
+            let token = format!("mylang/recheck/{}", self.started);
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": token,
+                "method": "window/workDoneProgress/create",
+                "params": { "token": token }
+            }));
+            Progress::Requested(token)
+        } else {
+            Progress::Off
+        };
+        self.current = Some(Run {
+            progress,
+            total: targets.len(),
+            remaining: targets,
+        });
+        outgoing
+    }
+
+    /// Handles the client's answer to the request creating a progress
+    /// token, returning the message that begins the progress if the token
+    /// is the current run's and the client accepted it. A run that ended
+    /// before the answer came never shows progress.
+    pub fn created(&mut self, id: &Value, accepted: bool) -> Vec<Value> {
+        let Some(run) = &mut self.current else {
+            return Vec::new();
+        };
+        let Progress::Requested(token) = &run.progress else {
+            return Vec::new();
+        };
+        if id.as_str() != Some(token) {
+            return Vec::new();
+        }
+        if !accepted {
+            run.progress = Progress::Off;
+            return Vec::new();
+        }
+
+        let begin = progress(
+            token,
+            json!({
+                "kind": "begin",
+                "title": "Re-checking dependents",
+                "cancellable": false,
+                "percentage": run.percentage()
+            }),
+        );
+        run.progress = Progress::Begun(token.clone());
+        vec![begin]
+    }
+
+    /// Drops `uri` from the documents still to check, once it is closed,
+    /// returning the message that ends the run if nothing is left.
+    pub fn forget(&mut self, uri: &str) -> Vec<Value> {
+        let Some(run) = &mut self.current else {
+            return Vec::new();
+        };
+        let before = run.remaining.len();
+        run.remaining.retain(|remaining| remaining != uri);
+        run.total -= before - run.remaining.len();
+        if run.remaining.is_empty() {
+            return self.finish();
+        }
+        Vec::new()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.current.is_some()
+    }
+
+    fn finish(&mut self) -> Vec<Value> {
+        match self.current.take().map(|run| run.progress) {
+            Some(Progress::Begun(token)) => vec![progress(&token, json!({ "kind": "end" }))],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Run {
+    fn percentage(&self) -> usize {
+        if self.total == 0 {
+            return 100;
+        }
+        (self.total - self.remaining.len()) * 100 / self.total
+    }
+}
+
+/// Re-checks the next dependent with the text and settings current now.
+pub fn step(state: &mut ServerState) -> Vec<Value> {
+    let Some(run) = &mut state.recheck.current else {
+        return Vec::new();
+    };
+    let mut outgoing = Vec::new();
+    if let Some(uri) = run.remaining.pop_front() {
+        // Documents closed since the save are skipped, and so are those
+        // edited while being checked, which get checked again anyway.
+        let running = state.in_flight.begin(None, Some(&uri));
+        if let Some(document) = state
+            .documents
+            .get(&uri)
+            .filter(|document| document.try_analysis(&running.token).is_ok())
+        {
+            let diagnostics = reported_diagnostics(&state.config, &state.baseline, document);
+            outgoing.push(publish_diagnostics(&uri, &diagnostics));
+        }
+        if let Progress::Begun(token) = &run.progress {
+            outgoing.push(progress(
+                token,
+                json!({
+                    "kind": "report",
+                    "message": uri,
+                    "percentage": run.percentage()
+                }),
+            ));
+        }
+    }
+    if run.remaining.is_empty() {
+        outgoing.extend(state.recheck.finish());
+    }
+    outgoing
+}
+
+fn progress(token: &str, value: Value) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "$/progress",
+        "params": {
+            "token": token,
+            "value": value
+        }
+    })
+}
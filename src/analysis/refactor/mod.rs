@@ -0,0 +1,655 @@
+use serde_json::{Value, json};
+
+use super::FileAnalysis;
+use super::ast::{BinaryOp, Expr, ExprKind, LetStmt, Stmt};
+use super::documents::Document;
+use super::lexer::{Span, Token, TokenType};
+use super::line_index::LineIndex;
+use super::resolver::{SymbolId, SymbolKind, SymbolTable, whole_lines};
+
+/// Offers to bind the expression selected by `selection` to a fresh name,
+/// declared just before the statement that holds it.
+pub fn extract_variable(document: &Document, selection: Span) -> Option<Value> {
+    let text = &document.text;
+    let analysis = document.analysis();
+    let selection = trim(text, selection);
+    let find = |span: Span| {
+        analysis
+            .program
+            .statements
+            .iter()
+            .filter_map(Stmt::expr)
+            .find_map(|expr| expression_at(expr, span))
+    };
+    // Parentheses belong to the expression they wrap, so selecting just
+    // inside them still picks it out.
+    let (expr, selection) = match find(selection) {
+        Some(expr) => (expr, selection),
+        None if text[..selection.start].ends_with('(')
+            && text[selection.end..].starts_with(')') =>
+        {
+            let wrapped = Span::new(selection.start - 1, selection.end + 1);
+            (find(wrapped)?, wrapped)
+        }
+        None => return None,
+    };
+    if matches!(expr.kind, ExprKind::Error) {
+        return None;
+    }
+
+    let anchor = anchor(&analysis.program.statements, None, selection)?;
+    // Binding a whole expression statement only to discard the name.
+    if anchor.start == selection.start && text[selection.end..anchor.end].trim() == ";" {
+        return None;
+    }
+
+    let symbols = &analysis.symbols;
+    if !keeps_meaning(symbols, text, selection, &[anchor.start]) {
+        return None;
+    }
+
+    let name = fresh_name(symbols, text, "extracted");
+    let line_start = text[..anchor.start].rfind('\n').map_or(0, |at| at + 1);
+    let indent = &text[line_start..anchor.start];
+    let separator = if indent.trim().is_empty() {
+        format!("\n{indent}")
+    } else {
+        " ".to_string()
+    };
+
+    let index = LineIndex::new(text);
+    Some(json!({
+        "title": "Extract into variable",
+        "kind": "refactor.extract",
+        "edit": {
+            "changes": {
+                document.uri.clone(): [{
+                    "range": index.range(Span::new(anchor.start, selection.end)),
+                    "newText": format!(
+                        "let {} = {};{}{}{}",
+                        name,
+                        &text[selection.start..selection.end],
+                        separator,
+                        &text[anchor.start..selection.start],
+                        name
+                    )
+                }]
+            }
+        }
+    }))
+}
+
+fn covers(outer: Span, inner: Span) -> bool {
+    outer.start <= inner.start && inner.end <= outer.end
+}
+
+fn trim(text: &str, span: Span) -> Span {
+    let selected = &text[span.start..span.end];
+    let start = span.start + (selected.len() - selected.trim_start().len());
+    let end = span.end - (selected.len() - selected.trim_end().len());
+    Span::new(start, end.max(start))
+}
+
+/// The expression spanning exactly `span`.
+fn expression_at(expr: &Expr, span: Span) -> Option<&Expr> {
+    if expr.span == span {
+        return Some(expr);
+    }
+    if !covers(expr.span, span) {
+        return None;
+    }
+    expr.children()
+        .into_iter()
+        .find_map(|child| expression_at(child, span))
+}
+
+/// The statement, or block tail, holding `span` in the innermost block
+/// around it.
+fn anchor(statements: &[Stmt], tail: Option<&Expr>, span: Span) -> Option<Span> {
+    let (outer, expr) = match statements.iter().find(|stmt| covers(stmt.span(), span)) {
+        Some(stmt) => (stmt.span(), stmt.expr()),
+        None => (tail.filter(|tail| covers(tail.span, span))?.span, tail),
+    };
+    Some(
+        expr.and_then(|expr| nested_anchor(expr, span))
+            .unwrap_or(outer),
+    )
+}
+
+fn nested_anchor(expr: &Expr, span: Span) -> Option<Span> {
+    if expr.span == span || !covers(expr.span, span) {
+        return None;
+    }
+    match &expr.kind {
+        ExprKind::Block(block) => anchor(&block.statements, block.tail.as_deref(), span),
+        ExprKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => nested_anchor(condition, span)
+            .or_else(|| {
+                covers(then_branch.span, span)
+                    .then(|| anchor(&then_branch.statements, then_branch.tail.as_deref(), span))
+                    .flatten()
+            })
+            .or_else(|| nested_anchor(else_branch.as_deref()?, span)),
+        _ => expr
+            .children()
+            .into_iter()
+            .find_map(|child| nested_anchor(child, span)),
+    }
+}
+
+/// Whether every name used in `span`, other than those it declares itself,
+/// would still refer to the same symbol if the code moved to each offset in
+/// `to`.
+fn keeps_meaning(symbols: &SymbolTable, text: &str, span: Span, to: &[usize]) -> bool {
+    symbols.references.iter().all(|reference| {
+        let Some(id) = reference.symbol else {
+            return true;
+        };
+        if !covers(span, reference.span)
+            || symbols.symbols[id]
+                .span
+                .is_some_and(|declared| covers(span, declared))
+        {
+            return true;
+        }
+        let name = &text[reference.span.start..reference.span.end];
+        to.iter()
+            .all(|offset| symbols.resolve_at(*offset, name) == Some(id))
+    })
+}
+
+/// `base`, or `base` with the first number that makes it a name the
+/// document neither declares nor uses.
+fn fresh_name(symbols: &SymbolTable, text: &str, base: &str) -> String {
+    let taken = |name: &str| {
+        symbols.symbols.iter().any(|symbol| symbol.name == name)
+            || symbols
+                .references
+                .iter()
+                .any(|reference| &text[reference.span.start..reference.span.end] == name)
+    };
+    let mut name = base.to_string();
+    let mut suffix = 1;
+    while taken(&name) {
+        suffix += 1;
+        name = format!("{base}{suffix}");
+    }
+    name
+}
+
+/// Offers to move the statements selected by `selection` into a new
+/// top-level function. Names the statements use but do not declare become
+/// its parameters, unless they are visible where the function goes.
+pub fn extract_function(document: &Document, selection: Span) -> Option<Value> {
+    let text = &document.text;
+    let analysis = document.analysis();
+    let selection = trim(text, selection);
+    let statements = &analysis.program.statements;
+    let (run, tail) = statement_run(statements, None, selection)?;
+    if run.is_empty() && tail.is_none() {
+        return None;
+    }
+    let insert_at = statements
+        .iter()
+        .find(|stmt| covers(stmt.span(), selection))
+        .map_or(selection.start, |stmt| stmt.span().start);
+
+    let symbols = &analysis.symbols;
+    let mut params: Vec<SymbolId> = Vec::new();
+    let mut outputs: Vec<SymbolId> = Vec::new();
+    for reference in &symbols.references {
+        let Some(id) = reference.symbol else {
+            continue;
+        };
+        let declared_inside = symbols.symbols[id]
+            .span
+            .is_some_and(|span| covers(selection, span));
+        if covers(selection, reference.span) {
+            if !declared_inside
+                && !params.contains(&id)
+                && symbols.resolve_at(insert_at, &symbols.symbols[id].name) != Some(id)
+            {
+                params.push(id);
+            }
+        } else if declared_inside && !outputs.contains(&id) {
+            outputs.push(id);
+        }
+    }
+    // Only one value can come back out of the call.
+    if outputs.len() > 1 {
+        return None;
+    }
+
+    let name = fresh_name(symbols, text, "extracted");
+    let arguments: Vec<&str> = params
+        .iter()
+        .map(|id| symbols.symbols[*id].name.as_str())
+        .collect();
+
+    let line_start = text[..selection.start].rfind('\n').map_or(0, |at| at + 1);
+    let prefix = &text[line_start..selection.start];
+    let dedent = if prefix.trim().is_empty() { prefix } else { "" };
+    let mut body: Vec<String> = text[selection.start..selection.end]
+        .lines()
+        .map(|line| {
+            let line = line.strip_prefix(dedent).unwrap_or(line.trim_start());
+            if line.is_empty() {
+                String::new()
+            } else {
+                format!("    {line}")
+            }
+        })
+        .collect();
+    let call = format!("{}({})", name, arguments.join(", "));
+    let call = match outputs.first() {
+        Some(output) => {
+            let output = &symbols.symbols[*output].name;
+            body.push(format!("    {output}"));
+            format!("let {output} = {call};")
+        }
+        None if tail.is_some() => call,
+        None => format!("{call};"),
+    };
+
+    let mut signature = vec!["let", name.as_str()];
+    signature.extend(&arguments);
+    let function = format!(
+        "{} -> {{\n{}\n}};\n\n",
+        signature.join(" "),
+        body.join("\n")
+    );
+
+    let index = LineIndex::new(text);
+    let edits = if insert_at == selection.start {
+        json!([{
+            "range": index.range(selection),
+            "newText": format!("{function}{call}")
+        }])
+    } else {
+        json!([
+            {
+                "range": index.range(Span::new(insert_at, insert_at)),
+                "newText": function
+            },
+            {
+                "range": index.range(selection),
+                "newText": call
+            }
+        ])
+    };
+    Some(json!({
+        "title": "Extract into function",
+        "kind": "refactor.extract",
+        "edit": {
+            "changes": { document.uri.clone(): edits }
+        }
+    }))
+}
+
+/// The statements, and possibly the tail, that `span` covers exactly in the
+/// innermost block holding it.
+fn statement_run<'a>(
+    statements: &'a [Stmt],
+    tail: Option<&'a Expr>,
+    span: Span,
+) -> Option<(&'a [Stmt], Option<&'a Expr>)> {
+    if let Some(first) = statements
+        .iter()
+        .position(|stmt| stmt.span().start == span.start)
+    {
+        let count = statements[first..]
+            .iter()
+            .take_while(|stmt| stmt.span().end <= span.end)
+            .count();
+        let run = &statements[first..first + count];
+        if run.last().is_some_and(|last| last.span().end == span.end) {
+            return Some((run, None));
+        }
+        if first + count == statements.len() && tail.is_some_and(|tail| tail.span.end == span.end) {
+            return Some((run, tail));
+        }
+        return None;
+    }
+
+    let inner = statements
+        .iter()
+        .filter_map(Stmt::expr)
+        .chain(tail)
+        .find(|expr| covers(expr.span, span))?;
+    nested_run(inner, span)
+}
+
+fn nested_run(expr: &Expr, span: Span) -> Option<(&[Stmt], Option<&Expr>)> {
+    if !covers(expr.span, span) {
+        return None;
+    }
+    match &expr.kind {
+        ExprKind::Block(block) => statement_run(&block.statements, block.tail.as_deref(), span),
+        ExprKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => nested_run(condition, span)
+            .or_else(|| {
+                covers(then_branch.span, span)
+                    .then(|| {
+                        statement_run(&then_branch.statements, then_branch.tail.as_deref(), span)
+                    })
+                    .flatten()
+            })
+            .or_else(|| nested_run(else_branch.as_deref()?, span)),
+        _ => expr
+            .children()
+            .into_iter()
+            .find_map(|child| nested_run(child, span)),
+    }
+}
+
+/// Offers to rewrite nested single-argument calls around `selection`, like
+/// `h(g(f(x)))`, as the pipeline `x |> f |> g |> h`.
+pub fn calls_to_pipeline(document: &Document, selection: Span) -> Option<Value> {
+    let text = &document.text;
+    let analysis = document.analysis();
+    let selection = trim(text, selection);
+    let path = path(&analysis.program.statements, selection);
+
+    let mut top = path
+        .iter()
+        .rposition(|expr| single_argument(expr).is_some())?;
+    while top > 0 && single_argument(path[top - 1]).is_some_and(|arg| std::ptr::eq(arg, path[top]))
+    {
+        top -= 1;
+    }
+
+    let mut stages = Vec::new();
+    let mut value = path[top];
+    while let ExprKind::Call { callee, args } = &value.kind
+        && args.len() == 1
+    {
+        stages.push(&text[callee.span.start..callee.span.end]);
+        value = &args[0];
+    }
+    if stages.len() < 2 {
+        return None;
+    }
+
+    let mut pipeline = wrap(analysis, text, value, PIPE);
+    for stage in stages.iter().rev() {
+        pipeline = format!("{pipeline} |> {stage}");
+    }
+    let parent = top.checked_sub(1).map(|parent| path[parent]);
+    if required_precedence(parent, path[top]) > PIPE {
+        pipeline = format!("({pipeline})");
+    }
+
+    Some(rewrite(
+        document,
+        "Convert to pipeline",
+        path[top].span,
+        pipeline,
+    ))
+}
+
+/// Offers to rewrite the pipeline around `selection`, like `x |> f |> g`,
+/// as the nested calls `g(f(x))`.
+pub fn pipeline_to_calls(document: &Document, selection: Span) -> Option<Value> {
+    let text = &document.text;
+    let analysis = document.analysis();
+    let selection = trim(text, selection);
+    let path = path(&analysis.program.statements, selection);
+
+    let mut top = path
+        .iter()
+        .rposition(|expr| pipe_operands(expr).is_some())?;
+    while top > 0
+        && pipe_operands(path[top - 1]).is_some_and(|(left, _)| std::ptr::eq(left, path[top]))
+    {
+        top -= 1;
+    }
+
+    let mut stages = Vec::new();
+    let mut value = path[top];
+    while let Some((left, right)) = pipe_operands(value) {
+        stages.push(right);
+        value = left;
+    }
+
+    let mut calls = wrap(analysis, text, value, 0);
+    for stage in stages.iter().rev() {
+        calls = format!("{}({})", wrap(analysis, text, stage, POSTFIX), calls);
+    }
+
+    Some(rewrite(
+        document,
+        "Convert to nested calls",
+        path[top].span,
+        calls,
+    ))
+}
+
+/// The argument of a call with exactly one.
+fn single_argument(expr: &Expr) -> Option<&Expr> {
+    match &expr.kind {
+        ExprKind::Call { args, .. } if args.len() == 1 => Some(&args[0]),
+        _ => None,
+    }
+}
+
+fn pipe_operands(expr: &Expr) -> Option<(&Expr, &Expr)> {
+    match &expr.kind {
+        ExprKind::Binary {
+            op: BinaryOp::Pipe,
+            left,
+            right,
+        } => Some((left, right)),
+        _ => None,
+    }
+}
+
+fn rewrite(document: &Document, title: &str, span: Span, new_text: String) -> Value {
+    let index = LineIndex::new(&document.text);
+    json!({
+        "title": title,
+        "kind": "refactor.rewrite",
+        "edit": {
+            "changes": {
+                document.uri.clone(): [{
+                    "range": index.range(span),
+                    "newText": new_text
+                }]
+            }
+        }
+    })
+}
+
+/// The expressions holding `span`, outermost first.
+fn path(statements: &[Stmt], span: Span) -> Vec<&Expr> {
+    let mut path = Vec::new();
+    let mut exprs: Vec<&Expr> = statements.iter().filter_map(Stmt::expr).collect();
+    while let Some(expr) = exprs.into_iter().find(|expr| covers(expr.span, span)) {
+        path.push(expr);
+        exprs = expr.children();
+    }
+    path
+}
+
+const PIPE: u8 = 1;
+const UNARY: u8 = 6;
+const POSTFIX: u8 = 7;
+
+/// How tightly `expr` binds, from `PIPE` up to `POSTFIX` for calls, names
+/// and literals. Lambdas and `if` rank below everything, since their bodies
+/// would swallow an operator that follows them.
+fn precedence(analysis: &FileAnalysis, expr: &Expr) -> u8 {
+    if parenthesized(analysis, expr) {
+        return POSTFIX;
+    }
+    match &expr.kind {
+        ExprKind::Binary { op, .. } => binary_precedence(*op),
+        ExprKind::Unary { .. } => UNARY,
+        ExprKind::Lambda { .. } | ExprKind::If { .. } | ExprKind::Error => 0,
+        _ => POSTFIX,
+    }
+}
+
+fn binary_precedence(op: BinaryOp) -> u8 {
+    match op {
+        BinaryOp::Pipe => PIPE,
+        BinaryOp::Equal
+        | BinaryOp::NotEqual
+        | BinaryOp::Less
+        | BinaryOp::LessEqual
+        | BinaryOp::Greater
+        | BinaryOp::GreaterEqual => 2,
+        BinaryOp::Add | BinaryOp::Subtract => 3,
+        BinaryOp::Multiply | BinaryOp::Divide => 4,
+        BinaryOp::Power => 5,
+    }
+}
+
+/// The precedence an expression needs to stand in for `child` inside
+/// `parent` without parentheses.
+fn required_precedence(parent: Option<&Expr>, child: &Expr) -> u8 {
+    let Some(parent) = parent else {
+        return 0;
+    };
+    match &parent.kind {
+        ExprKind::Binary { op, left, .. } => {
+            let is_left = std::ptr::eq(&**left, child);
+            match op {
+                // `^` is right associative and takes a unary operand on its left.
+                BinaryOp::Power if is_left => UNARY,
+                BinaryOp::Power => binary_precedence(*op),
+                _ => binary_precedence(*op) + u8::from(!is_left),
+            }
+        }
+        ExprKind::Unary { .. } => UNARY,
+        ExprKind::Field { .. } => POSTFIX,
+        ExprKind::Call { callee, .. } if std::ptr::eq(&**callee, child) => POSTFIX,
+        _ => 0,
+    }
+}
+
+/// The source of `expr`, parenthesized unless it binds at least as tightly
+/// as `required`.
+fn wrap(analysis: &FileAnalysis, text: &str, expr: &Expr, required: u8) -> String {
+    let source = &text[expr.span.start..expr.span.end];
+    if precedence(analysis, expr) >= required {
+        source.to_string()
+    } else {
+        format!("({source})")
+    }
+}
+
+/// Whether the source of `expr` is wrapped in a single pair of parentheses.
+fn parenthesized(analysis: &FileAnalysis, expr: &Expr) -> bool {
+    let tokens: Vec<&Token> = analysis
+        .tokens
+        .iter()
+        .filter(|token| covers(expr.span, token.span))
+        .collect();
+    if tokens
+        .first()
+        .is_none_or(|token| token.token_type != TokenType::LeftParen)
+    {
+        return false;
+    }
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token.token_type {
+            TokenType::LeftParen => depth += 1,
+            TokenType::RightParen => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 {
+            return i == tokens.len() - 1;
+        }
+    }
+    false
+}
+
+/// Bindings used more often than this are left alone, as inlining would
+/// repeat the initializer too many times.
+pub const MAX_INLINE_USES: usize = 3;
+
+/// Offers to replace every use of the variable at `selection` with its
+/// initializer and delete the binding.
+pub fn inline_variable(document: &Document, selection: Span) -> Option<Value> {
+    let text = &document.text;
+    let analysis = document.analysis();
+    let symbols = &analysis.symbols;
+    let (id, _) = symbols.symbol_at(selection.start)?;
+    let symbol = &symbols.symbols[id];
+    if symbol.kind != SymbolKind::Variable {
+        return None;
+    }
+    let statement = let_statement(&analysis.program.statements, symbol.statement?)?;
+    let value = statement.value.as_ref()?;
+    let uses: Vec<usize> = symbols.uses_of(id).iter().map(|span| span.start).collect();
+    if uses.is_empty() || uses.len() > MAX_INLINE_USES {
+        return None;
+    }
+
+    if !keeps_meaning(symbols, text, value.span, &uses) {
+        return None;
+    }
+
+    let index = LineIndex::new(text);
+    let mut edits = vec![json!({
+        "range": index.range(whole_lines(text, statement.span)),
+        "newText": ""
+    })];
+    for span in symbols.uses_of(id) {
+        let path = path(&analysis.program.statements, span);
+        let parent = path.len().checked_sub(2).map(|parent| path[parent]);
+        let required = path
+            .last()
+            .map_or(0, |name| required_precedence(parent, name));
+        edits.push(json!({
+            "range": index.range(span),
+            "newText": wrap(analysis, text, value, required)
+        }));
+    }
+
+    Some(json!({
+        "title": format!("Inline '{}'", symbol.name),
+        "kind": "refactor.inline",
+        "edit": {
+            "changes": { document.uri.clone(): edits }
+        }
+    }))
+}
+
+/// The `let` statement spanning exactly `span`, at any depth.
+pub fn let_statement(statements: &[Stmt], span: Span) -> Option<&LetStmt> {
+    let stmt = statements.iter().find(|stmt| covers(stmt.span(), span))?;
+    if let Stmt::Let(stmt) = stmt
+        && stmt.span == span
+    {
+        return Some(stmt);
+    }
+    nested_let_statement(stmt.expr()?, span)
+}
+
+fn nested_let_statement(expr: &Expr, span: Span) -> Option<&LetStmt> {
+    if !covers(expr.span, span) {
+        return None;
+    }
+    match &expr.kind {
+        ExprKind::Block(block) => let_statement(&block.statements, span),
+        ExprKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => nested_let_statement(condition, span)
+            .or_else(|| let_statement(&then_branch.statements, span))
+            .or_else(|| nested_let_statement(else_branch.as_deref()?, span)),
+        _ => expr
+            .children()
+            .into_iter()
+            .find_map(|child| nested_let_statement(child, span)),
+    }
+}
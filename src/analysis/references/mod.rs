@@ -0,0 +1,87 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::diagnostics::Position;
+use super::documents::DocumentStore;
+use super::line_index::LineIndex;
+use super::pull::TextDocumentIdentifier;
+
+#[derive(Deserialize)]
+pub struct ReferenceParams {
+    #[serde(rename = "textDocument")]
+    pub text_document: TextDocumentIdentifier,
+    pub position: Position,
+    pub context: ReferenceContext,
+}
+
+#[derive(Deserialize)]
+pub struct ReferenceContext {
+    #[serde(rename = "includeDeclaration")]
+    pub include_declaration: bool,
+}
+
+/// Handles `textDocument/references`, listing every use of the symbol under
+/// the cursor and, if asked, its declaration. Symbols cannot be shared
+/// between files, so all locations are in the requesting document.
+pub fn references(documents: &DocumentStore, params: ReferenceParams) -> Value {
+    let Some(document) = documents.get(&params.text_document.uri) else {
+        return Value::Null;
+    };
+
+    let index = LineIndex::new(&document.text);
+    let offset = index.offset(&params.position);
+    let symbols = &document.analysis().symbols;
+    let Some((id, _)) = symbols.symbol_at(offset) else {
+        return Value::Null;
+    };
+
+    let mut spans = symbols.uses_of(id);
+    if let Some(declaration) = symbols.symbols[id].span
+        && params.context.include_declaration
+    {
+        spans.insert(0, declaration);
+    }
+
+    json!(
+        spans
+            .into_iter()
+            .map(|span| json!({ "uri": document.uri, "range": index.range(span) }))
+            .collect::<Vec<_>>()
+    )
+}
+
+/// `DocumentHighlightKind` values from the protocol.
+const READ: u8 = 2;
+const WRITE: u8 = 3;
+
+#[derive(Deserialize)]
+pub struct DocumentHighlightParams {
+    #[serde(rename = "textDocument")]
+    pub text_document: TextDocumentIdentifier,
+    pub position: Position,
+}
+
+/// Handles `textDocument/documentHighlight`: the binding site of the symbol
+/// under the cursor is marked as a write, every use as a read.
+pub fn document_highlight(documents: &DocumentStore, params: DocumentHighlightParams) -> Value {
+    let Some(document) = documents.get(&params.text_document.uri) else {
+        return Value::Null;
+    };
+
+    let index = LineIndex::new(&document.text);
+    let offset = index.offset(&params.position);
+    let symbols = &document.analysis().symbols;
+    let Some((id, _)) = symbols.symbol_at(offset) else {
+        return Value::Null;
+    };
+
+    let declaration = symbols.symbols[id].span.map(|span| (span, WRITE));
+    let uses = symbols.uses_of(id).into_iter().map(|span| (span, READ));
+    json!(
+        declaration
+            .into_iter()
+            .chain(uses)
+            .map(|(span, kind)| json!({ "range": index.range(span), "kind": kind }))
+            .collect::<Vec<_>>()
+    )
+}
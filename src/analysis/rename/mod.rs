@@ -0,0 +1,273 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::diagnostics::Position;
+use super::documents::DocumentStore;
+use super::lexer::{self, TokenType};
+use super::line_index::LineIndex;
+use super::pull::TextDocumentIdentifier;
+use super::resolver::{SymbolId, SymbolKind, SymbolTable};
+
+#[derive(Deserialize)]
+pub struct RenameParams {
+    #[serde(rename = "textDocument")]
+    pub text_document: TextDocumentIdentifier,
+    pub position: Position,
+    #[serde(rename = "newName")]
+    pub new_name: String,
+}
+
+/// Handles `textDocument/rename`, replacing the declaration and every use
+/// of the symbol under the cursor. Edits are tied to the document version
+/// they were computed for, so a client that has moved on rejects them
+/// instead of applying them to the wrong text.
+pub fn rename(documents: &DocumentStore, params: RenameParams) -> Result<Value, String> {
+    let Some(document) = documents.get(&params.text_document.uri) else {
+        return Ok(Value::Null);
+    };
+
+    if !is_identifier(&params.new_name) {
+        return Err(format!("'{}' is not a valid identifier", params.new_name));
+    }
+
+    let index = LineIndex::new(&document.text);
+    let offset = index.offset(&params.position);
+    let symbols = &document.analysis().symbols;
+    let Some((id, _)) = symbols.symbol_at(offset) else {
+        return Err("There is no symbol to rename here".to_string());
+    };
+    let symbol = &symbols.symbols[id];
+    if symbol.kind == SymbolKind::Builtin {
+        return Err(format!("The builtin '{}' cannot be renamed", symbol.name));
+    }
+
+    if params.new_name != symbol.name
+        && let Some(conflict) = conflict(symbols, &index, id, &params.new_name)
+    {
+        return Err(conflict);
+    }
+
+    // Hiding an unused builtin breaks nothing today, but is easy to regret.
+    let shadows_builtin = symbols
+        .symbols
+        .iter()
+        .any(|other| other.kind == SymbolKind::Builtin && other.name == params.new_name);
+
+    let edits: Vec<Value> = symbol
+        .span
+        .into_iter()
+        .chain(symbols.uses_of(id))
+        .map(|span| {
+            let mut edit = json!({
+                "range": index.range(span),
+                "newText": params.new_name
+            });
+            if shadows_builtin {
+                edit["annotationId"] = json!(SHADOWS_BUILTIN);
+            }
+            edit
+        })
+        .collect();
+
+    let mut result = json!({
+        "documentChanges": [{
+            "textDocument": {
+                "uri": document.uri,
+                "version": document.version
+            },
+            "edits": edits
+        }]
+    });
+    if shadows_builtin {
+        result["changeAnnotations"] = json!({
+            SHADOWS_BUILTIN: {
+                "label": format!("Shadow the builtin '{}'", params.new_name),
+                "needsConfirmation": true,
+                "description": format!(
+                    "'{}' will no longer refer to the builtin where the renamed symbol is visible.",
+                    params.new_name
+                )
+            }
+        });
+    }
+    Ok(result)
+}
+
+const SHADOWS_BUILTIN: &str = "shadowsBuiltin";
+
+/// Explains why renaming `id` to `new_name` would change what the program
+/// means: a clash with a declaration in the same scope, a use that would
+/// then resolve to a closer declaration, or a use of another `new_name`
+/// that the renamed symbol would hide.
+fn conflict(
+    symbols: &SymbolTable,
+    index: &LineIndex,
+    id: SymbolId,
+    new_name: &str,
+) -> Option<String> {
+    let symbol = &symbols.symbols[id];
+    let line = |offset: usize| index.position(offset).line + 1;
+
+    if let Some(existing) = symbols.scopes[symbol.scope]
+        .symbols
+        .iter()
+        .map(|other| &symbols.symbols[*other])
+        .find(|other| other.name == new_name && other.kind != SymbolKind::Builtin)
+    {
+        let at = existing.span.map_or(0, |span| line(span.start));
+        return Some(format!(
+            "'{}' is already declared in this scope on line {}",
+            new_name, at
+        ));
+    }
+
+    // Uses that would be captured by a closer declaration of `new_name`.
+    for span in symbols.uses_of(id) {
+        let from = symbols.scope_at(span.start);
+        let Some(other) = symbols.resolve_at(span.start, new_name) else {
+            continue;
+        };
+        if symbols.scope_distance(from, symbols.symbols[other].scope)
+            < symbols.scope_distance(from, symbol.scope)
+        {
+            return Some(format!(
+                "The use on line {} would refer to a different '{}'",
+                line(span.start),
+                new_name
+            ));
+        }
+    }
+
+    // Uses of another `new_name` that the renamed symbol would hide.
+    for reference in &symbols.references {
+        let Some(other) = reference.symbol else {
+            continue;
+        };
+        if other == id || symbols.symbols[other].name != new_name {
+            continue;
+        }
+        let from = symbols.scope_at(reference.span.start);
+        let Some(distance) = symbols.scope_distance(from, symbol.scope) else {
+            continue;
+        };
+        if symbol.visible_from <= reference.span.start
+            && symbols
+                .scope_distance(from, symbols.symbols[other].scope)
+                .is_none_or(|other_distance| distance < other_distance)
+        {
+            return Some(format!(
+                "'{}' would hide the '{}' used on line {}",
+                symbol.name,
+                new_name,
+                line(reference.span.start)
+            ));
+        }
+    }
+
+    None
+}
+
+#[derive(Deserialize)]
+pub struct PrepareRenameParams {
+    #[serde(rename = "textDocument")]
+    pub text_document: TextDocumentIdentifier,
+    pub position: Position,
+}
+
+/// Handles `textDocument/prepareRename`, returning the identifier under the
+/// cursor and its current name, or explaining why nothing there can be
+/// renamed.
+pub fn prepare_rename(
+    documents: &DocumentStore,
+    params: PrepareRenameParams,
+) -> Result<Value, String> {
+    let Some(document) = documents.get(&params.text_document.uri) else {
+        return Ok(Value::Null);
+    };
+
+    let index = LineIndex::new(&document.text);
+    let offset = index.offset(&params.position);
+    let analysis = document.analysis();
+    let symbols = &analysis.symbols;
+
+    let Some((id, span)) = symbols.symbol_at(offset) else {
+        let token = analysis
+            .tokens
+            .iter()
+            .find(|token| token.span.start <= offset && offset <= token.span.end);
+        return Err(match token {
+            Some(token) if is_literal(token.token_type) => "Literals cannot be renamed",
+            Some(token) if lexer::keyword(token.lexeme(&document.text)).is_some() => {
+                "Keywords cannot be renamed"
+            }
+            _ => "There is no symbol to rename here",
+        }
+        .to_string());
+    };
+
+    let symbol = &symbols.symbols[id];
+    if symbol.kind == SymbolKind::Builtin {
+        return Err(format!("The builtin '{}' cannot be renamed", symbol.name));
+    }
+
+    Ok(json!({
+        "range": index.range(span),
+        "placeholder": symbol.name
+    }))
+}
+
+fn is_literal(token_type: TokenType) -> bool {
+    matches!(
+        token_type,
+        TokenType::NUMBER | TokenType::STRING | TokenType::TRUE | TokenType::FALSE
+    )
+}
+
+/// Whether `name` would lex as a single identifier rather than a keyword.
+pub fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+        && lexer::keyword(name).is_none()
+}
+
+/// An ASCII identifier, so the editor stops linking the ranges once an edit
+/// turns the name into something else.
+const IDENTIFIER_PATTERN: &str = "[A-Za-z_][A-Za-z0-9_]*";
+
+#[derive(Deserialize)]
+pub struct LinkedEditingRangeParams {
+    #[serde(rename = "textDocument")]
+    pub text_document: TextDocumentIdentifier,
+    pub position: Position,
+}
+
+/// Handles `textDocument/linkedEditingRange`, returning the declaration and
+/// every use of the symbol under the cursor so they can be edited together.
+/// Builtins have no declaration to edit and get nothing.
+pub fn linked_editing_range(documents: &DocumentStore, params: LinkedEditingRangeParams) -> Value {
+    let Some(document) = documents.get(&params.text_document.uri) else {
+        return Value::Null;
+    };
+
+    let index = LineIndex::new(&document.text);
+    let offset = index.offset(&params.position);
+    let symbols = &document.analysis().symbols;
+    let Some((id, _)) = symbols.symbol_at(offset) else {
+        return Value::Null;
+    };
+    let Some(declaration) = symbols.symbols[id].span else {
+        return Value::Null;
+    };
+
+    let ranges: Vec<Value> = std::iter::once(declaration)
+        .chain(symbols.uses_of(id))
+        .map(|span| json!(index.range(span)))
+        .collect();
+    json!({
+        "ranges": ranges,
+        "wordPattern": IDENTIFIER_PATTERN
+    })
+}
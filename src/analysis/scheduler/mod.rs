@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Tracks documents waiting to be re-analyzed. Every change pushes the
+/// document's deadline back, so a burst of edits results in a single run
+/// against whatever text is current once the burst settles.
+pub struct Scheduler {
+    pub delay: Duration,
+    pending: HashMap<String, Instant>,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self {
+            delay: DEFAULT_DEBOUNCE,
+            pending: HashMap::new(),
+        }
+    }
+}
+
+impl Scheduler {
+    pub fn schedule(&mut self, uri: String) {
+        self.pending.insert(uri, Instant::now() + self.delay);
+    }
+
+    pub fn cancel(&mut self, uri: &str) {
+        self.pending.remove(uri);
+    }
+
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.pending.values().min().copied()
+    }
+
+    pub fn take_due(&mut self, now: Instant) -> Vec<String> {
+        let mut due: Vec<String> = self
+            .pending
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(uri, _)| uri.clone())
+            .collect();
+        due.sort();
+
+        for uri in &due {
+            self.pending.remove(uri);
+        }
+        due
+    }
+
+    pub fn take_all(&mut self) -> Vec<String> {
+        let mut all: Vec<String> = self.pending.drain().map(|(uri, _)| uri).collect();
+        all.sort();
+        all
+    }
+}
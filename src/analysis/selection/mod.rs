@@ -0,0 +1,109 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::ast::{Expr, ExprKind, Stmt};
+use super::diagnostics::Position;
+use super::documents::DocumentStore;
+use super::lexer::Span;
+use super::line_index::LineIndex;
+use super::pull::TextDocumentIdentifier;
+
+#[derive(Deserialize)]
+pub struct SelectionRangeParams {
+    #[serde(rename = "textDocument")]
+    pub text_document: TextDocumentIdentifier,
+    pub positions: Vec<Position>,
+}
+
+/// Handles `textDocument/selectionRange`. Each position expands from the
+/// token under it through the enclosing expressions, statements and
+/// blocks out to the whole file.
+pub fn selection_ranges(documents: &DocumentStore, params: SelectionRangeParams) -> Value {
+    let Some(document) = documents.get(&params.text_document.uri) else {
+        return Value::Null;
+    };
+
+    let index = LineIndex::new(&document.text);
+    let analysis = document.analysis();
+    let ranges: Vec<Value> = params
+        .positions
+        .iter()
+        .map(|position| {
+            let offset = index.offset(position);
+            let mut spans = vec![Span::new(0, document.text.len())];
+            statements(&analysis.program.statements, None, offset, &mut spans);
+            if let Some(token) = analysis
+                .tokens
+                .iter()
+                .find(|token| token.span.start <= offset && offset <= token.span.end)
+            {
+                spans.push(token.span);
+            }
+            spans.dedup();
+
+            spans.into_iter().fold(Value::Null, |parent, span| {
+                let mut range = json!({ "range": index.range(span) });
+                if !parent.is_null() {
+                    range["parent"] = parent;
+                }
+                range
+            })
+        })
+        .collect();
+    json!(ranges)
+}
+
+fn contains(span: Span, offset: usize) -> bool {
+    span.start <= offset && offset <= span.end
+}
+
+/// Pushes the span of the statement holding `offset`, then whatever inside
+/// it holds `offset` too.
+fn statements(statements: &[Stmt], tail: Option<&Expr>, offset: usize, out: &mut Vec<Span>) {
+    if let Some(stmt) = statements.iter().find(|stmt| contains(stmt.span(), offset)) {
+        out.push(stmt.span());
+        if let Some(expr) = stmt.expr() {
+            expression(expr, offset, out);
+        }
+    } else if let Some(tail) = tail {
+        expression(tail, offset, out);
+    }
+}
+
+fn expression(expr: &Expr, offset: usize, out: &mut Vec<Span>) -> bool {
+    if !contains(expr.span, offset) {
+        return false;
+    }
+    out.push(expr.span);
+    match &expr.kind {
+        ExprKind::Block(block) => {
+            statements(&block.statements, block.tail.as_deref(), offset, out);
+        }
+        ExprKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            let in_condition = expression(condition, offset, out);
+            if !in_condition && contains(then_branch.span, offset) {
+                out.push(then_branch.span);
+                statements(
+                    &then_branch.statements,
+                    then_branch.tail.as_deref(),
+                    offset,
+                    out,
+                );
+            } else if !in_condition && let Some(else_branch) = else_branch {
+                expression(else_branch, offset, out);
+            }
+        }
+        _ => {
+            for child in expr.children() {
+                if expression(child, offset, out) {
+                    break;
+                }
+            }
+        }
+    }
+    true
+}
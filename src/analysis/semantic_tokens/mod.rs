@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::diagnostics::Range;
+use super::docs;
+use super::documents::{Document, DocumentStore};
+use super::lexer::{self, Span, TokenType};
+use super::line_index::LineIndex;
+use super::memory::HeapSize;
+use super::pull::TextDocumentIdentifier;
+use super::resolver::{SymbolId, SymbolKind};
+use super::types::Type;
+
+/// Token types in legend order; a token's type is its index here.
+pub const TOKEN_TYPES: &[&str] = &[
+    "keyword",
+    "variable",
+    "parameter",
+    "function",
+    "property",
+    "string",
+    "number",
+    "comment",
+    "operator",
+];
+
+/// Token modifiers in legend order; modifier `i` sets bit `1 << i`.
+pub const TOKEN_MODIFIERS: &[&str] = &["declaration", "readonly", "deprecated", "defaultLibrary"];
+
+const KEYWORD: u32 = 0;
+const VARIABLE: u32 = 1;
+const PARAMETER: u32 = 2;
+const FUNCTION: u32 = 3;
+const PROPERTY: u32 = 4;
+const STRING: u32 = 5;
+const NUMBER: u32 = 6;
+const COMMENT: u32 = 7;
+const OPERATOR: u32 = 8;
+
+const DECLARATION: u32 = 1 << 0;
+/// Nothing in mylang can be reassigned, so every variable and parameter
+/// is read-only.
+const READONLY: u32 = 1 << 1;
+const DEPRECATED: u32 = 1 << 2;
+const DEFAULT_LIBRARY: u32 = 1 << 3;
+
+pub fn legend() -> Value {
+    json!({
+        "tokenTypes": TOKEN_TYPES,
+        "tokenModifiers": TOKEN_MODIFIERS
+    })
+}
+
+/// The token arrays last sent for each document, so the next request can
+/// be answered with only what changed.
+#[derive(Default)]
+pub struct SemanticTokensCache {
+    next_id: u64,
+    issued: HashMap<String, (String, Vec<u32>)>,
+}
+
+impl SemanticTokensCache {
+    /// Remembers `data` as sent for `uri`, returning its new result id.
+    fn issue(&mut self, uri: &str, data: Vec<u32>) -> String {
+        self.next_id += 1;
+        let result_id = self.next_id.to_string();
+        self.issued
+            .insert(uri.to_string(), (result_id.clone(), data));
+        result_id
+    }
+
+    pub fn remove(&mut self, uri: &str) {
+        self.issued.remove(uri);
+    }
+}
+
+impl HeapSize for SemanticTokensCache {
+    fn heap_size(&self) -> usize {
+        self.issued.heap_size()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SemanticTokensParams {
+    #[serde(rename = "textDocument")]
+    pub text_document: TextDocumentIdentifier,
+}
+
+/// Handles `textDocument/semanticTokens/full`.
+pub fn full(
+    documents: &DocumentStore,
+    cache: &mut SemanticTokensCache,
+    params: SemanticTokensParams,
+) -> Value {
+    let Some(document) = documents.get(&params.text_document.uri) else {
+        return Value::Null;
+    };
+
+    let data = encode(&document.text, &classify(document));
+    json!({
+        "resultId": cache.issue(&document.uri, data.clone()),
+        "data": data
+    })
+}
+
+#[derive(Deserialize)]
+pub struct SemanticTokensDeltaParams {
+    #[serde(rename = "textDocument")]
+    pub text_document: TextDocumentIdentifier,
+    #[serde(rename = "previousResultId")]
+    pub previous_result_id: String,
+}
+
+/// Handles `textDocument/semanticTokens/full/delta`. Edits replace the one
+/// stretch of the array between what the old and new tokens share at
+/// either end; if the client's result is not the one we last sent, it gets
+/// the full array instead.
+pub fn full_delta(
+    documents: &DocumentStore,
+    cache: &mut SemanticTokensCache,
+    params: SemanticTokensDeltaParams,
+) -> Value {
+    let Some(document) = documents.get(&params.text_document.uri) else {
+        return Value::Null;
+    };
+
+    let data = encode(&document.text, &classify(document));
+    let edits = match cache.issued.get(&document.uri) {
+        Some((result_id, previous)) if *result_id == params.previous_result_id => {
+            let prefix = previous
+                .iter()
+                .zip(&data)
+                .take_while(|(a, b)| a == b)
+                .count();
+            let suffix = previous[prefix..]
+                .iter()
+                .rev()
+                .zip(data[prefix..].iter().rev())
+                .take_while(|(a, b)| a == b)
+                .count();
+            let delete_count = previous.len() - prefix - suffix;
+            let inserted = &data[prefix..data.len() - suffix];
+            if delete_count == 0 && inserted.is_empty() {
+                Some(Vec::new())
+            } else {
+                Some(vec![json!({
+                    "start": prefix,
+                    "deleteCount": delete_count,
+                    "data": inserted
+                })])
+            }
+        }
+        _ => None,
+    };
+
+    match edits {
+        Some(edits) => json!({
+            "resultId": cache.issue(&document.uri, data),
+            "edits": edits
+        }),
+        None => json!({
+            "resultId": cache.issue(&document.uri, data.clone()),
+            "data": data
+        }),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SemanticTokensRangeParams {
+    #[serde(rename = "textDocument")]
+    pub text_document: TextDocumentIdentifier,
+    pub range: Range,
+}
+
+/// Handles `textDocument/semanticTokens/range`, so the client can colour
+/// the visible part of a large file before the whole of it.
+pub fn range(documents: &DocumentStore, params: SemanticTokensRangeParams) -> Value {
+    let Some(document) = documents.get(&params.text_document.uri) else {
+        return Value::Null;
+    };
+
+    let index = LineIndex::new(&document.text);
+    let start = index.offset(&params.range.start);
+    let end = index.offset(&params.range.end);
+    let tokens: Vec<SemanticToken> = classify(document)
+        .into_iter()
+        .filter(|token| token.span.start < end && start < token.span.end)
+        .collect();
+    json!({ "data": encode(&document.text, &tokens) })
+}
+
+/// A stretch of source and how to highlight it.
+struct SemanticToken {
+    span: Span,
+    kind: u32,
+    modifiers: u32,
+}
+
+/// Every highlighted token in the document, in source order. Identifiers
+/// are told apart by what they resolve to.
+fn classify(document: &Document) -> Vec<SemanticToken> {
+    let analysis = document.analysis();
+    let symbols = &analysis.symbols;
+
+    let mut resolved: HashMap<usize, SymbolId> = symbols
+        .references
+        .iter()
+        .filter_map(|reference| Some((reference.span.start, reference.symbol?)))
+        .collect();
+    resolved.extend(
+        symbols
+            .symbols
+            .iter()
+            .enumerate()
+            .filter_map(|(id, symbol)| Some((symbol.span?.start, id))),
+    );
+
+    let mut tokens: Vec<SemanticToken> = lexer::comments(&document.text, &analysis.tokens)
+        .into_iter()
+        .map(|span| SemanticToken {
+            span,
+            kind: COMMENT,
+            modifiers: 0,
+        })
+        .collect();
+
+    let mut deprecated: HashMap<SymbolId, bool> = HashMap::new();
+    let mut previous = None;
+    for token in &analysis.tokens {
+        let mut modifiers = 0;
+        let kind = match token.token_type {
+            TokenType::IDENTIFIER if previous == Some(TokenType::DOT) => Some(PROPERTY),
+            TokenType::IDENTIFIER => {
+                let id = resolved.get(&token.span.start).copied();
+                let symbol = id.map(|id| &symbols.symbols[id]);
+                if let (Some(id), Some(symbol)) = (id, symbol) {
+                    if symbol.span == Some(token.span) {
+                        modifiers |= DECLARATION;
+                    }
+                    if matches!(symbol.kind, SymbolKind::Variable | SymbolKind::Parameter) {
+                        modifiers |= READONLY;
+                    }
+                    if symbol.kind == SymbolKind::Builtin {
+                        modifiers |= DEFAULT_LIBRARY;
+                    }
+                    let tagged = *deprecated.entry(id).or_insert_with(|| {
+                        symbol.statement.is_some_and(|statement| {
+                            docs::doc_comment(&document.text, statement.start)
+                                .is_some_and(|doc| docs::is_deprecated(&doc))
+                        })
+                    });
+                    if tagged {
+                        modifiers |= DEPRECATED;
+                    }
+                }
+                Some(match symbol {
+                    Some(symbol) if symbol.kind == SymbolKind::Parameter => PARAMETER,
+                    // Globals declared with a value type are the only
+                    // builtins that are not functions.
+                    Some(symbol)
+                        if symbol.kind == SymbolKind::Function
+                            || symbol.ty == Type::Function
+                            || (symbol.kind == SymbolKind::Builtin
+                                && symbol.ty == Type::Unknown) =>
+                    {
+                        FUNCTION
+                    }
+                    _ => VARIABLE,
+                })
+            }
+            TokenType::STRING => Some(STRING),
+            TokenType::NUMBER => Some(NUMBER),
+            TokenType::TRUE
+            | TokenType::FALSE
+            | TokenType::IF
+            | TokenType::ELSE
+            | TokenType::LET
+            | TokenType::FN => Some(KEYWORD),
+            TokenType::PLUS
+            | TokenType::MINUS
+            | TokenType::SLASH
+            | TokenType::STAR
+            | TokenType::CARET
+            | TokenType::ARROW
+            | TokenType::PIPE
+            | TokenType::EQUAL
+            | TokenType::BANG
+            | TokenType::GREATER
+            | TokenType::LESS
+            | TokenType::EqualEqual
+            | TokenType::BangEqual
+            | TokenType::LessEqual
+            | TokenType::GreaterEqual => Some(OPERATOR),
+            _ => None,
+        };
+        previous = Some(token.token_type);
+
+        if let Some(kind) = kind {
+            tokens.push(SemanticToken {
+                span: token.span,
+                kind,
+                modifiers,
+            });
+        }
+    }
+
+    tokens.sort_by_key(|token| token.span.start);
+    tokens
+}
+
+/// Encodes `tokens` in the protocol's relative format: five integers per
+/// token, each position counted from the token before. Tokens spanning
+/// lines, such as multi-line strings, are split at the line breaks.
+fn encode(text: &str, tokens: &[SemanticToken]) -> Vec<u32> {
+    let index = LineIndex::new(text);
+    let mut data = Vec::with_capacity(tokens.len() * 5);
+    let (mut line, mut character) = (0, 0);
+
+    for token in tokens {
+        let mut start = token.span.start;
+        for piece in text[token.span.start..token.span.end].split('\n') {
+            let end = start + piece.trim_end_matches('\r').len();
+            if end > start {
+                let position = index.position(start);
+                let delta_line = position.line - line;
+                let delta_start = if delta_line == 0 {
+                    position.character - character
+                } else {
+                    position.character
+                };
+                let length = text[start..end].encode_utf16().count() as u32;
+                data.extend([delta_line, delta_start, length, token.kind, token.modifiers]);
+                line = position.line;
+                character = position.character;
+            }
+            start += piece.len() + 1;
+        }
+    }
+    data
+}
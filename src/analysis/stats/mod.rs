@@ -0,0 +1,70 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::baseline::Baseline;
+use super::config::ServerConfig;
+use super::diagnostics::DiagnosticSeverity;
+use super::documents::{Document, DocumentStore};
+use super::pull::TextDocumentIdentifier;
+use super::reported_diagnostics;
+
+#[derive(Deserialize)]
+pub struct StatsParams {
+    /// Limits the report to one document; all open documents otherwise.
+    #[serde(rename = "textDocument")]
+    pub text_document: Option<TextDocumentIdentifier>,
+}
+
+/// Handles `mylang/stats`, reporting analysis metrics per document.
+pub fn stats(
+    documents: &DocumentStore,
+    config: &ServerConfig,
+    baseline: &Baseline,
+    params: StatsParams,
+) -> Value {
+    match params.text_document {
+        Some(text_document) => match documents.get(&text_document.uri) {
+            Some(document) => document_stats(config, baseline, document),
+            None => Value::Null,
+        },
+        None => json!(
+            documents
+                .iter()
+                .into_iter()
+                .map(|document| document_stats(config, baseline, document))
+                .collect::<Vec<_>>()
+        ),
+    }
+}
+
+fn document_stats(config: &ServerConfig, baseline: &Baseline, document: &Document) -> Value {
+    // Read the counters first so this request's own lookups are not counted.
+    let cache_hits = document.cache_hits.get();
+    let cache_misses = document.cache_misses.get();
+
+    let diagnostics = reported_diagnostics(config, baseline, document);
+    let count = |severity: DiagnosticSeverity| {
+        diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == severity)
+            .count()
+    };
+
+    let analysis = document.analysis();
+    json!({
+        "uri": document.uri,
+        "version": document.version,
+        "tokenCount": analysis.token_count,
+        "parseTimeMs": analysis.duration.as_secs_f64() * 1000.0,
+        "diagnostics": {
+            "error": count(DiagnosticSeverity::Error),
+            "warning": count(DiagnosticSeverity::Warning),
+            "information": count(DiagnosticSeverity::Information),
+            "hint": count(DiagnosticSeverity::Hint)
+        },
+        "cache": {
+            "hits": cache_hits,
+            "misses": cache_misses
+        }
+    })
+}
@@ -0,0 +1,85 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::fuzzy;
+use super::index::{IndexedSymbol, WorkspaceIndex};
+use super::resolver::SymbolKind;
+
+/// The most symbols returned for one query.
+pub const MAX_RESULTS: usize = 256;
+/// How many symbols each partial result carries.
+const BATCH_SIZE: usize = 64;
+
+/// `SymbolKind` values from the protocol.
+const FUNCTION_KIND: u8 = 12;
+const VARIABLE_KIND: u8 = 13;
+
+#[derive(Deserialize)]
+pub struct WorkspaceSymbolParams {
+    pub query: String,
+    #[serde(rename = "partialResultToken")]
+    pub partial_result_token: Option<Value>,
+}
+
+/// Handles `workspace/symbol`, fuzzy matching the query against every
+/// indexed declaration. Results are ordered by match quality and capped at
+/// [`MAX_RESULTS`]. With a partial result token they are streamed in
+/// batches through `$/progress` and the final result is left empty.
+pub fn workspace_symbol(
+    index: &WorkspaceIndex,
+    params: WorkspaceSymbolParams,
+    outgoing: &mut Vec<Value>,
+) -> Value {
+    let mut matches: Vec<(u32, &String, &IndexedSymbol)> = index
+        .files()
+        .flat_map(|(uri, symbols)| symbols.iter().map(move |symbol| (uri, symbol)))
+        .filter_map(|(uri, symbol)| Some((fuzzy::score(&params.query, &symbol.name)?, uri, symbol)))
+        .collect();
+    matches.sort_by(|(a, a_uri, first), (b, b_uri, second)| {
+        b.cmp(a)
+            .then_with(|| first.name.cmp(&second.name))
+            .then_with(|| a_uri.cmp(b_uri))
+    });
+    matches.truncate(MAX_RESULTS);
+
+    let results: Vec<Value> = matches
+        .into_iter()
+        .map(|(_, uri, symbol)| symbol_information(uri, symbol))
+        .collect();
+
+    match params.partial_result_token {
+        Some(token) => {
+            for batch in results.chunks(BATCH_SIZE) {
+                outgoing.push(json!({
+                    "jsonrpc": "2.0",
+                    "method": "$/progress",
+                    "params": {
+                        "token": token,
+                        "value": batch
+                    }
+                }));
+            }
+            json!([])
+        }
+        None => json!(results),
+    }
+}
+
+fn symbol_information(uri: &str, symbol: &IndexedSymbol) -> Value {
+    let kind = match symbol.kind {
+        SymbolKind::Function => FUNCTION_KIND,
+        _ => VARIABLE_KIND,
+    };
+    let mut information = json!({
+        "name": symbol.name,
+        "kind": kind,
+        "location": {
+            "uri": uri,
+            "range": symbol.range
+        }
+    });
+    if let Some(container) = &symbol.container {
+        information["containerName"] = json!(container);
+    }
+    information
+}
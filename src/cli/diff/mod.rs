@@ -0,0 +1,94 @@
+use std::ops::Range;
+
+use crate::analysis::format::{self, Hunk, MAX_DIFF_CELLS};
+
+/// Lines of unchanged text shown around each change.
+const CONTEXT: usize = 3;
+
+/// A unified diff turning `old` into `new`, with `a/` and `b/` prefixed
+/// to `path` so it applies with `patch -p1`. Empty when nothing changed.
+pub fn unified(path: &str, old: &str, new: &str) -> String {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    let changes = changes(&old_lines, &new_lines);
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    let mut out = format!("--- a/{path}\n+++ b/{path}\n");
+    let mut rest = &changes[..];
+    while let Some(first) = rest.first() {
+        // Changes whose context would touch share a hunk.
+        let mut count = 1;
+        while count < rest.len() && rest[count].0.start - rest[count - 1].0.end <= 2 * CONTEXT {
+            count += 1;
+        }
+        let (group, next) = rest.split_at(count);
+        rest = next;
+
+        let start = first.0.start.saturating_sub(CONTEXT);
+        let end = (group[count - 1].0.end + CONTEXT).min(old_lines.len());
+        let new_start = start + first.1.start - first.0.start;
+        let old_count = end - start;
+        let new_count = old_count - group.iter().map(|(old, _)| old.len()).sum::<usize>()
+            + group.iter().map(|(_, new)| new.len()).sum::<usize>();
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            header_range(start, old_count),
+            header_range(new_start, new_count)
+        ));
+
+        let mut at = start;
+        for (old, new) in group {
+            push_lines(&mut out, ' ', &old_lines[at..old.start]);
+            push_lines(&mut out, '-', &old_lines[old.clone()]);
+            push_lines(&mut out, '+', &new_lines[new.clone()]);
+            at = old.end;
+        }
+        push_lines(&mut out, ' ', &old_lines[at..end]);
+    }
+    out
+}
+
+/// The changed line ranges, skipping the common prefix and suffix before
+/// running the full diff on what is left.
+fn changes(old: &[&str], new: &[&str]) -> Vec<Hunk> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_changed = &old[prefix..old.len() - suffix];
+    let new_changed = &new[prefix..new.len() - suffix];
+
+    let hunks = if old_changed.len() * new_changed.len() <= MAX_DIFF_CELLS {
+        format::diff(old_changed, new_changed)
+    } else {
+        vec![(0..old_changed.len(), 0..new_changed.len())]
+    };
+    let shift = |range: Range<usize>| range.start + prefix..range.end + prefix;
+    hunks
+        .into_iter()
+        .filter(|(old, new)| !old.is_empty() || !new.is_empty())
+        .map(|(old, new)| (shift(old), shift(new)))
+        .collect()
+}
+
+/// `start,count` with a one-based start, which for an empty range is the
+/// line before it.
+fn header_range(start: usize, count: usize) -> String {
+    let start = if count == 0 { start } else { start + 1 };
+    format!("{start},{count}")
+}
+
+fn push_lines(out: &mut String, marker: char, lines: &[&str]) {
+    for line in lines {
+        out.push(marker);
+        out.push_str(line);
+        if !line.ends_with('\n') {
+            out.push_str("\n\\ No newline at end of file\n");
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::process::{Command, Stdio};
+
+const CTRL_A: u8 = 0x01;
+const CTRL_B: u8 = 0x02;
+const CTRL_C: u8 = 0x03;
+const CTRL_D: u8 = 0x04;
+const CTRL_E: u8 = 0x05;
+const CTRL_F: u8 = 0x06;
+const CTRL_H: u8 = 0x08;
+const CTRL_K: u8 = 0x0b;
+const CTRL_U: u8 = 0x15;
+const ESCAPE: u8 = 0x1b;
+const BACKSPACE: u8 = 0x7f;
+
+/// What reading a line ended with.
+pub enum Line {
+    Text(String),
+    /// Ctrl-C discarded the line.
+    Interrupted,
+    /// Ctrl-D on an empty line, or the end of input.
+    End,
+}
+
+/// Reads lines from the terminal with cursor movement and history. Input
+/// that is not a terminal is read plainly and without prompts.
+pub struct Editor {
+    history: Vec<String>,
+    terminal: bool,
+}
+
+impl Editor {
+    /// An editor on the process's stdin and stdout.
+    pub fn stdin() -> Self {
+        Self {
+            history: Vec::new(),
+            terminal: io::stdin().is_terminal() && io::stdout().is_terminal(),
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        self.terminal
+    }
+
+    pub fn read_line(&mut self, prompt: &str) -> io::Result<Line> {
+        if self.terminal
+            && let Some(_raw) = RawMode::enable()
+        {
+            return self.edit(prompt);
+        }
+
+        if self.terminal {
+            print!("{prompt}");
+            io::stdout().flush()?;
+        }
+        let mut line = String::new();
+        if io::stdin().lock().read_line(&mut line)? == 0 {
+            return Ok(Line::End);
+        }
+        let trimmed = line.trim_end_matches(['\r', '\n']).len();
+        line.truncate(trimmed);
+        Ok(Line::Text(line))
+    }
+
+    /// Remembers `line` for the up and down keys, unless it is blank or
+    /// repeats the previous entry.
+    pub fn add_history(&mut self, line: &str) {
+        if !line.trim().is_empty() && self.history.last().is_none_or(|last| last != line) {
+            self.history.push(line.to_string());
+        }
+    }
+
+    fn edit(&mut self, prompt: &str) -> io::Result<Line> {
+        let mut input = io::stdin().lock();
+        let mut output = io::stdout().lock();
+        let mut buffer: Vec<char> = Vec::new();
+        let mut cursor = 0;
+        // Where in the history the up and down keys are, with the line being
+        // typed kept aside while browsing.
+        let mut entry = self.history.len();
+        let mut draft = Vec::new();
+
+        loop {
+            write!(output, "\r{prompt}{}\x1b[K", String::from_iter(&buffer))?;
+            if cursor < buffer.len() {
+                write!(output, "\x1b[{}D", buffer.len() - cursor)?;
+            }
+            output.flush()?;
+
+            let Some(byte) = read_byte(&mut input)? else {
+                writeln!(output)?;
+                return Ok(Line::End);
+            };
+            let key = match byte {
+                ESCAPE => escape_sequence(&mut input)?,
+                byte => Key::Byte(byte),
+            };
+            match key {
+                Key::Byte(b'\r' | b'\n') => {
+                    writeln!(output)?;
+                    return Ok(Line::Text(buffer.into_iter().collect()));
+                }
+                Key::Byte(CTRL_C) => {
+                    writeln!(output, "^C")?;
+                    return Ok(Line::Interrupted);
+                }
+                Key::Byte(CTRL_D) if buffer.is_empty() => {
+                    writeln!(output)?;
+                    return Ok(Line::End);
+                }
+                Key::Byte(CTRL_D) | Key::Delete if cursor < buffer.len() => {
+                    buffer.remove(cursor);
+                }
+                Key::Byte(BACKSPACE | CTRL_H) if cursor > 0 => {
+                    cursor -= 1;
+                    buffer.remove(cursor);
+                }
+                Key::Byte(CTRL_A) | Key::Home => cursor = 0,
+                Key::Byte(CTRL_E) | Key::End => cursor = buffer.len(),
+                Key::Byte(CTRL_B) | Key::Left => cursor = cursor.saturating_sub(1),
+                Key::Byte(CTRL_F) | Key::Right => cursor = (cursor + 1).min(buffer.len()),
+                Key::Byte(CTRL_K) => buffer.truncate(cursor),
+                Key::Byte(CTRL_U) => {
+                    buffer.drain(..cursor);
+                    cursor = 0;
+                }
+                Key::Up if entry > 0 => {
+                    if entry == self.history.len() {
+                        draft = buffer;
+                    }
+                    entry -= 1;
+                    buffer = self.history[entry].chars().collect();
+                    cursor = buffer.len();
+                }
+                Key::Down if entry < self.history.len() => {
+                    entry += 1;
+                    buffer = match self.history.get(entry) {
+                        Some(line) => line.chars().collect(),
+                        None => draft.clone(),
+                    };
+                    cursor = buffer.len();
+                }
+                Key::Byte(byte) if byte >= b' ' => {
+                    if let Some(c) = read_char(byte, &mut input)? {
+                        buffer.insert(cursor, c);
+                        cursor += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+enum Key {
+    Byte(u8),
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    Delete,
+    /// An escape sequence the editor does not handle.
+    Unknown,
+}
+
+fn read_byte(input: &mut impl Read) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    Ok((input.read(&mut byte)? == 1).then_some(byte[0]))
+}
+
+/// Reads the rest of an escape sequence, such as `[A` for the up key or
+/// `[3~` for delete.
+fn escape_sequence(input: &mut impl Read) -> io::Result<Key> {
+    if !matches!(read_byte(input)?, Some(b'[' | b'O')) {
+        return Ok(Key::Unknown);
+    }
+    let mut parameter = String::new();
+    loop {
+        let Some(byte) = read_byte(input)? else {
+            return Ok(Key::Unknown);
+        };
+        let key = match byte {
+            b'0'..=b'9' | b';' => {
+                parameter.push(byte as char);
+                continue;
+            }
+            b'A' => Key::Up,
+            b'B' => Key::Down,
+            b'C' => Key::Right,
+            b'D' => Key::Left,
+            b'H' => Key::Home,
+            b'F' => Key::End,
+            b'~' => match parameter.as_str() {
+                "1" | "7" => Key::Home,
+                "4" | "8" => Key::End,
+                "3" => Key::Delete,
+                _ => Key::Unknown,
+            },
+            _ => Key::Unknown,
+        };
+        return Ok(key);
+    }
+}
+
+/// Reads the continuation bytes of the UTF-8 character that starts with
+/// `first`.
+fn read_char(first: u8, input: &mut impl Read) -> io::Result<Option<char>> {
+    let mut bytes = vec![first];
+    for _ in 1..first.leading_ones().max(1) {
+        match read_byte(input)? {
+            Some(byte) => bytes.push(byte),
+            None => break,
+        }
+    }
+    Ok(std::str::from_utf8(&bytes)
+        .ok()
+        .and_then(|text| text.chars().next()))
+}
+
+/// Switches the terminal to reading key by key without echo for as long as
+/// it lives, through `stty` so no terminal library is needed.
+struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    fn enable() -> Option<Self> {
+        let saved = stty(&["-g"])?.trim().to_string();
+        stty(&["-icanon", "-echo", "-isig", "-ixon", "-iexten", "min", "1"])?;
+        Some(Self { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+fn stty(args: &[&str]) -> Option<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
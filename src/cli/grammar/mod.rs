@@ -0,0 +1,294 @@
+use serde_json::{Value, json};
+
+use crate::analysis::builtins::BUILTINS;
+use crate::analysis::index::SOURCE_EXTENSION;
+use crate::analysis::lexer::{KEYWORDS, LINE_COMMENT, SYMBOLS, TokenType};
+use crate::analysis::parser::BINARY_OPERATORS;
+
+const SCOPE_NAME: &str = "source.mylang";
+
+/// The TextMate scope of a keyword, operator or punctuation token, without
+/// the language suffix. The match is exhaustive so a new token type has to
+/// be given a place in the grammar.
+fn scope(token_type: TokenType) -> Option<&'static str> {
+    match token_type {
+        TokenType::PLUS
+        | TokenType::MINUS
+        | TokenType::SLASH
+        | TokenType::STAR
+        | TokenType::CARET => Some("keyword.operator.arithmetic"),
+        TokenType::EqualEqual
+        | TokenType::BangEqual
+        | TokenType::LessEqual
+        | TokenType::GreaterEqual
+        | TokenType::GREATER
+        | TokenType::LESS => Some("keyword.operator.comparison"),
+        TokenType::BANG => Some("keyword.operator.logical"),
+        TokenType::EQUAL => Some("keyword.operator.assignment"),
+        TokenType::ARROW => Some("keyword.operator.arrow"),
+        TokenType::PIPE => Some("keyword.operator.pipe"),
+        TokenType::LeftParen | TokenType::RightParen => Some("punctuation.section.parens"),
+        TokenType::LeftBracket | TokenType::RightBracket => Some("punctuation.section.brackets"),
+        TokenType::LeftBrace | TokenType::RightBrace => Some("punctuation.section.braces"),
+        TokenType::COMMA => Some("punctuation.separator.comma"),
+        TokenType::COLON => Some("punctuation.separator.colon"),
+        TokenType::DOT => Some("punctuation.accessor"),
+        TokenType::SEMICOLON => Some("punctuation.terminator.statement"),
+        TokenType::TRUE | TokenType::FALSE => Some("constant.language.boolean"),
+        TokenType::IF | TokenType::ELSE => Some("keyword.control.conditional"),
+        TokenType::LET => Some("storage.type"),
+        TokenType::FN => Some("storage.type.function"),
+        TokenType::IDENTIFIER | TokenType::STRING | TokenType::NUMBER | TokenType::EOF => None,
+    }
+}
+
+/// A `.tmLanguage.json` grammar built from the lexer's symbol and keyword
+/// tables, so highlighting agrees with how the server splits text.
+pub fn textmate() -> Value {
+    let pattern = |token_type: TokenType, regex: String| {
+        scope(token_type).map(|scope| json!({ "name": format!("{scope}.mylang"), "match": regex }))
+    };
+    let keywords: Vec<Value> = KEYWORDS
+        .iter()
+        .filter_map(|(keyword, token_type)| pattern(*token_type, format!("\\b{keyword}\\b")))
+        .collect();
+    // One pattern per symbol, in the lexer's order, so `->` is not taken
+    // for `-` followed by `>`.
+    let symbols: Vec<Value> = SYMBOLS
+        .iter()
+        .filter_map(|(symbol, token_type)| pattern(*token_type, escape(symbol)))
+        .collect();
+    let builtins: Vec<&str> = BUILTINS.iter().map(|builtin| builtin.name).collect();
+
+    json!({
+        "$schema": "https://raw.githubusercontent.com/martinring/tmlanguage/master/tmlanguage.json",
+        "name": "mylang",
+        "scopeName": SCOPE_NAME,
+        "fileTypes": [SOURCE_EXTENSION],
+        "patterns": [
+            { "include": "#comment" },
+            { "include": "#string" },
+            { "include": "#number" },
+            { "include": "#keyword" },
+            { "include": "#builtin" },
+            { "include": "#symbol" }
+        ],
+        "repository": {
+            "comment": {
+                "name": "comment.line.double-slash.mylang",
+                "match": format!("{}.*$", escape(LINE_COMMENT))
+            },
+            "string": {
+                "name": "string.quoted.double.mylang",
+                "begin": "\"",
+                "end": "\"",
+                "patterns": [{ "name": "constant.character.escape.mylang", "match": "\\\\." }]
+            },
+            "number": {
+                "name": "constant.numeric.integer.mylang",
+                "match": "\\b[0-9]+\\b"
+            },
+            "keyword": { "patterns": keywords },
+            "builtin": {
+                "name": "support.function.builtin.mylang",
+                "match": format!("\\b(?:{})\\b", builtins.join("|"))
+            },
+            "symbol": { "patterns": symbols }
+        }
+    })
+}
+
+/// A tree-sitter `grammar.js` with the parser's rules. Keywords, operators
+/// and operator precedence come from the lexer's and parser's tables.
+pub fn tree_sitter() -> String {
+    let text = |token_type: TokenType| {
+        let symbol = SYMBOLS
+            .iter()
+            .chain(KEYWORDS)
+            .find(|(_, t)| *t == token_type);
+        let (text, _) = symbol.expect("every token type in the grammar has a fixed text");
+        format!("'{text}'")
+    };
+    let choice = |token_types: &mut dyn Iterator<Item = TokenType>| {
+        let texts: Vec<String> = token_types.map(text).collect();
+        match texts.as_slice() {
+            [text] => text.clone(),
+            texts => format!("choice({})", texts.join(", ")),
+        }
+    };
+
+    // Levels count up from the loosest binary operator; `^`, prefix
+    // operators and postfix calls and fields follow in that order.
+    let mut binary = Vec::new();
+    for (level, ops) in BINARY_OPERATORS.iter().enumerate() {
+        let operator = choice(&mut ops.iter().map(|(token_type, _)| *token_type));
+        binary.push(format!(
+            "      prec.left({}, seq(field('left', $._expression), field('operator', {}), field('right', $._expression))),",
+            level + 1,
+            operator
+        ));
+    }
+    let power = BINARY_OPERATORS.len() + 1;
+    binary.push(format!(
+        "      prec.right({}, seq(field('left', $._expression), field('operator', {}), field('right', $._expression))),",
+        power,
+        text(TokenType::CARET)
+    ));
+    let unary_operator = choice(&mut [TokenType::MINUS, TokenType::BANG].into_iter());
+
+    format!(
+        r#"// Generated by `mylang-lsp grammar --tree-sitter`; regenerate rather than edit.
+
+const PREC = {{
+  unary: {unary},
+  postfix: {postfix},
+}};
+
+module.exports = grammar({{
+  name: 'mylang',
+
+  extras: $ => [/\s/, $.comment],
+
+  word: $ => $.identifier,
+
+  rules: {{
+    source_file: $ => repeat($._statement),
+
+    _statement: $ => choice($.let_statement, $.expression_statement),
+
+    let_statement: $ => seq(
+      {let_},
+      field('name', $.identifier),
+      choice(seq(repeat(field('parameter', $.identifier)), {arrow}), {equal}),
+      field('value', $._expression),
+      {semicolon},
+    ),
+
+    expression_statement: $ => seq($._expression, {semicolon}),
+
+    _expression: $ => choice(
+      $.binary_expression,
+      $.unary_expression,
+      $.call_expression,
+      $.field_expression,
+      $.parenthesized_expression,
+      $.list,
+      $.set,
+      $.block,
+      $.if_expression,
+      $.lambda,
+      $.identifier,
+      $.number,
+      $.string,
+      $.boolean,
+    ),
+
+    binary_expression: $ => choice(
+{binary}
+    ),
+
+    unary_expression: $ => prec(PREC.unary, seq(
+      field('operator', {unary_operator}),
+      field('operand', $._expression),
+    )),
+
+    call_expression: $ => prec(PREC.postfix, seq(
+      field('function', $._expression),
+      field('arguments', $.arguments),
+    )),
+
+    arguments: $ => seq({left_paren}, commaSep($._expression), {right_paren}),
+
+    field_expression: $ => prec(PREC.postfix, seq(
+      field('receiver', $._expression),
+      {dot},
+      field('field', $.identifier),
+    )),
+
+    parenthesized_expression: $ => seq({left_paren}, $._expression, {right_paren}),
+
+    list: $ => seq({left_bracket}, commaSep($._expression), {right_bracket}),
+
+    // A brace with a comma after its first expression is a set.
+    set: $ => seq(
+      {left_brace},
+      $._expression,
+      {comma},
+      commaSep($._expression),
+      {right_brace},
+    ),
+
+    block: $ => seq(
+      {left_brace},
+      repeat($._statement),
+      optional(field('tail', $._expression)),
+      {right_brace},
+    ),
+
+    if_expression: $ => prec.right(seq(
+      {if_},
+      field('condition', $._expression),
+      field('consequence', $.block),
+      optional(seq({else_}, field('alternative', choice($.if_expression, $.block)))),
+    )),
+
+    // The body takes in as much as it can.
+    lambda: $ => prec.right(seq(
+      {fn_},
+      repeat(field('parameter', $.identifier)),
+      {arrow},
+      field('body', $._expression),
+    )),
+
+    boolean: $ => choice({true_}, {false_}),
+
+    identifier: $ => /[a-zA-Z_][\p{{L}}\p{{N}}_]*/,
+
+    number: $ => /[0-9]+/,
+
+    string: $ => /"([^"\\]|\\.)*"/,
+
+    comment: $ => token(seq('{comment}', /.*/)),
+  }},
+}});
+
+function commaSep(rule) {{
+  return optional(seq(rule, repeat(seq({comma}, rule))));
+}}
+"#,
+        unary = power + 1,
+        postfix = power + 2,
+        binary = binary.join("\n"),
+        unary_operator = unary_operator,
+        let_ = text(TokenType::LET),
+        arrow = text(TokenType::ARROW),
+        equal = text(TokenType::EQUAL),
+        semicolon = text(TokenType::SEMICOLON),
+        left_paren = text(TokenType::LeftParen),
+        right_paren = text(TokenType::RightParen),
+        left_bracket = text(TokenType::LeftBracket),
+        right_bracket = text(TokenType::RightBracket),
+        left_brace = text(TokenType::LeftBrace),
+        right_brace = text(TokenType::RightBrace),
+        comma = text(TokenType::COMMA),
+        dot = text(TokenType::DOT),
+        if_ = text(TokenType::IF),
+        else_ = text(TokenType::ELSE),
+        fn_ = text(TokenType::FN),
+        true_ = text(TokenType::TRUE),
+        false_ = text(TokenType::FALSE),
+        comment = LINE_COMMENT,
+    )
+}
+
+/// Escapes the characters that mean something in an Oniguruma regex.
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        if "\\.^$|?*+()[]{}/".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
@@ -0,0 +1,836 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use serde_json::{Value, json};
+
+use crate::analysis::actions;
+use crate::analysis::baseline::{Baseline, DEFAULT_BASELINE_FILE, path_to_uri};
+use crate::analysis::config::{ConfigLayers, ServerConfig};
+use crate::analysis::diagnostics::{Category, Diagnostic, DiagnosticSeverity, TextEdit};
+use crate::analysis::documents::{Document, DocumentStore};
+use crate::analysis::format::{self, FormattingOptions, Style};
+use crate::analysis::index::collect_files;
+use crate::analysis::interpreter::{self, Interpreter};
+use crate::analysis::lexer;
+use crate::analysis::line_index::LineIndex;
+use crate::analysis::lsif;
+use crate::analysis::{analyze, reported_diagnostics};
+
+mod diff;
+mod editor;
+mod grammar;
+mod repl;
+
+pub use repl::repl;
+
+/// `mylang-lsp baseline [--output <file>] <paths>...`
+///
+/// Records every diagnostic `check` reports in the given files so the
+/// server stops reporting them.
+pub fn baseline(args: &[String]) -> i32 {
+    let mut output = PathBuf::from(DEFAULT_BASELINE_FILE);
+    let mut paths = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" | "-o" => match args.next() {
+                Some(path) => output = PathBuf::from(path),
+                None => {
+                    eprintln!("Error: --output expects a file path.");
+                    return 2;
+                }
+            },
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    if paths.is_empty() {
+        paths.push(PathBuf::from("."));
+    }
+
+    let root = output
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."));
+    let mut baseline = Baseline::new(root);
+
+    // What `check` would report with no baseline, so the two agree on
+    // severities, disabled lints, excluded files and globals.
+    let config = project_config();
+    let unfiltered = Baseline::default();
+    for file in collect_files(&paths) {
+        match fs::read_to_string(&file) {
+            Ok(text) => {
+                let document =
+                    Document::new(file_uri(&file), 0, text).with_globals(config.globals.clone());
+                let diagnostics = reported_diagnostics(&config, &unfiltered, &document);
+                baseline.record(&file, &document.text, &diagnostics);
+            }
+            Err(e) => {
+                eprintln!("Error reading {}: {}", file.display(), e);
+                return 2;
+            }
+        }
+    }
+
+    if let Err(e) = baseline.save(&output) {
+        eprintln!("Error writing {}: {}", output.display(), e);
+        return 2;
+    }
+
+    eprintln!(
+        "Recorded {} diagnostics in {}",
+        baseline.len(),
+        output.display()
+    );
+    0
+}
+
+/// `path` relative to `root` when it is not absolute, without `.` segments.
+fn absolute(root: &Path, path: &Path) -> PathBuf {
+    root.join(path)
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+/// `mylang-lsp lsif [--output <file>] <paths>...`
+///
+/// Writes an LSIF index of the given files, one JSON object per line, to
+/// the output file or stdout. The current directory is the project root.
+pub fn lsif(args: &[String]) -> i32 {
+    let mut output = None;
+    let mut paths = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" | "-o" => match args.next() {
+                Some(path) => output = Some(PathBuf::from(path)),
+                None => {
+                    eprintln!("Error: --output expects a file path.");
+                    return 2;
+                }
+            },
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    if paths.is_empty() {
+        paths.push(PathBuf::from("."));
+    }
+
+    let root = match std::env::current_dir() {
+        Ok(root) => root,
+        Err(e) => {
+            eprintln!("Error reading the current directory: {}", e);
+            return 2;
+        }
+    };
+    let mut sources = Vec::new();
+    for file in collect_files(&paths) {
+        match fs::read_to_string(&file) {
+            Ok(text) => sources.push((absolute(&root, &file), text)),
+            Err(e) => {
+                eprintln!("Error reading {}: {}", file.display(), e);
+                return 2;
+            }
+        }
+    }
+
+    let mut dump = String::new();
+    for line in lsif::dump(&root, &sources) {
+        dump.push_str(&line.to_string());
+        dump.push('\n');
+    }
+    match output {
+        Some(output) => {
+            if let Err(e) = fs::write(&output, dump) {
+                eprintln!("Error writing {}: {}", output.display(), e);
+                return 2;
+            }
+            eprintln!("Indexed {} files into {}", sources.len(), output.display());
+        }
+        None => print!("{dump}"),
+    }
+    0
+}
+
+/// `mylang-lsp grammar (--textmate | --tree-sitter) [--output <file>]`
+///
+/// Writes a grammar generated from the lexer's and parser's tables to the
+/// output file or stdout: a `.tmLanguage.json` for `--textmate`, or a
+/// tree-sitter `grammar.js` for `--tree-sitter`.
+pub fn grammar(args: &[String]) -> i32 {
+    let mut format = None;
+    let mut output = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--textmate" | "--tree-sitter" => format = Some(arg.as_str()),
+            "--output" | "-o" => match args.next() {
+                Some(path) => output = Some(PathBuf::from(path)),
+                None => {
+                    eprintln!("Error: --output expects a file path.");
+                    return 2;
+                }
+            },
+            _ => {
+                eprintln!("Error: unknown argument '{}'.", arg);
+                return 2;
+            }
+        }
+    }
+
+    let text = match format {
+        Some("--textmate") => match serde_json::to_string_pretty(&grammar::textmate()) {
+            Ok(text) => text + "\n",
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return 2;
+            }
+        },
+        Some(_) => grammar::tree_sitter(),
+        None => {
+            eprintln!("Error: grammar expects --textmate or --tree-sitter.");
+            return 2;
+        }
+    };
+    match output {
+        Some(output) => {
+            if let Err(e) = fs::write(&output, text) {
+                eprintln!("Error writing {}: {}", output.display(), e);
+                return 2;
+            }
+        }
+        None => print!("{text}"),
+    }
+    0
+}
+
+/// How `check` prints diagnostics.
+#[derive(Clone, Copy, PartialEq)]
+enum OutputFormat {
+    /// `file:line:col: severity[code]: message`, one per line.
+    Human,
+    /// One JSON object per diagnostic and line.
+    Json,
+    /// GitHub Actions workflow commands, which annotate pull requests.
+    Github,
+}
+
+/// How often `check --watch` looks for changed files.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// `mylang-lsp check [--format human|json|github] [--deny-warnings]
+/// [--deny <code>]... [--watch] <paths>...`
+///
+/// Reports the diagnostics of the given files, with the project
+/// configuration and baseline of the current directory applied as the
+/// server would. Exits with 1 when any error was found, or any warning
+/// under `--deny-warnings`, or any diagnostic with a denied code; with 0
+/// otherwise and with 2 when the check could not run. `--watch` keeps
+/// running and reports again for each file that changes.
+pub fn check(args: &[String]) -> i32 {
+    let mut checker = Checker {
+        format: OutputFormat::Human,
+        deny_warnings: false,
+        denied: Vec::new(),
+        config: project_config(),
+        baseline: Baseline::load(Path::new(DEFAULT_BASELINE_FILE)).unwrap_or_default(),
+    };
+    let mut watch = false;
+    let mut paths = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => match args.next().map(String::as_str) {
+                Some("human") => checker.format = OutputFormat::Human,
+                Some("json") => checker.format = OutputFormat::Json,
+                Some("github") => checker.format = OutputFormat::Github,
+                _ => {
+                    eprintln!("Error: --format expects human, json or github.");
+                    return 2;
+                }
+            },
+            "--deny-warnings" => checker.deny_warnings = true,
+            "--deny" => match args.next() {
+                Some(code) => checker.denied.push(code.clone()),
+                None => {
+                    eprintln!("Error: --deny expects a diagnostic code.");
+                    return 2;
+                }
+            },
+            "--watch" => watch = true,
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    if paths.is_empty() {
+        paths.push(PathBuf::from("."));
+    }
+    if watch {
+        checker.watch(&paths);
+    }
+
+    let mut total = Findings::default();
+    let files = collect_files(&paths);
+    for file in &files {
+        let text = match fs::read_to_string(file) {
+            Ok(text) => text,
+            Err(e) => {
+                eprintln!("Error reading {}: {}", file.display(), e);
+                return 2;
+            }
+        };
+        let document =
+            Document::new(file_uri(file), 0, text).with_globals(checker.config.globals.clone());
+        total.add(checker.report(file, &document));
+    }
+
+    eprintln!(
+        "Checked {} files: {} errors, {} warnings",
+        files.len(),
+        total.errors,
+        total.warnings
+    );
+    if total.failed { 1 } else { 0 }
+}
+
+/// The options of one `check` run.
+struct Checker {
+    format: OutputFormat,
+    deny_warnings: bool,
+    denied: Vec<String>,
+    config: ServerConfig,
+    baseline: Baseline,
+}
+
+/// What `check` found, in one file or all of them.
+#[derive(Default)]
+struct Findings {
+    errors: usize,
+    warnings: usize,
+    /// Whether anything found fails the check.
+    failed: bool,
+}
+
+impl Findings {
+    fn add(&mut self, other: Findings) {
+        self.errors += other.errors;
+        self.warnings += other.warnings;
+        self.failed |= other.failed;
+    }
+}
+
+impl Checker {
+    /// Prints the diagnostics of `file`, open as `document`, in position
+    /// order.
+    fn report(&self, file: &Path, document: &Document) -> Findings {
+        let mut diagnostics = reported_diagnostics(&self.config, &self.baseline, document);
+        diagnostics.sort_by_key(|d| (d.range.start.line, d.range.start.character));
+        let shown = file.strip_prefix(".").unwrap_or(file);
+
+        let mut findings = Findings::default();
+        for diagnostic in &diagnostics {
+            match diagnostic.severity {
+                DiagnosticSeverity::Error => findings.errors += 1,
+                DiagnosticSeverity::Warning => findings.warnings += 1,
+                _ => {}
+            }
+            findings.failed |= diagnostic.severity == DiagnosticSeverity::Error
+                || (self.deny_warnings && diagnostic.severity == DiagnosticSeverity::Warning)
+                || diagnostic
+                    .code
+                    .as_ref()
+                    .is_some_and(|code| self.denied.contains(code));
+            match self.format {
+                OutputFormat::Human => println!("{}", human_line(shown, diagnostic)),
+                OutputFormat::Json => println!("{}", json_line(shown, diagnostic)),
+                OutputFormat::Github => println!("{}", github_line(shown, diagnostic)),
+            }
+        }
+        findings
+    }
+
+    /// Polls `paths` for files that were added, changed or removed, and
+    /// reports again for those only. Files stay open in a document store
+    /// between rounds, as they would in the server. Runs until killed.
+    fn watch(&self, paths: &[PathBuf]) -> ! {
+        let mut documents = DocumentStore::default();
+        let mut modified: HashMap<PathBuf, Option<SystemTime>> = HashMap::new();
+        let mut version = 0;
+        loop {
+            let files = collect_files(paths);
+            let removed: Vec<PathBuf> = modified
+                .keys()
+                .filter(|file| !files.contains(file))
+                .cloned()
+                .collect();
+            for file in removed {
+                modified.remove(&file);
+                documents.remove(&file_uri(&file));
+                eprintln!(
+                    "{}: removed",
+                    file.strip_prefix(".").unwrap_or(&file).display()
+                );
+            }
+
+            for file in files {
+                let time = fs::metadata(&file).and_then(|m| m.modified()).ok();
+                if modified.get(&file) == Some(&time) {
+                    continue;
+                }
+                modified.insert(file.clone(), time);
+                let text = match fs::read_to_string(&file) {
+                    Ok(text) => text,
+                    Err(e) => {
+                        eprintln!("Error reading {}: {}", file.display(), e);
+                        continue;
+                    }
+                };
+
+                version += 1;
+                let uri = file_uri(&file);
+                documents.open(uri.clone(), version, text);
+                let findings = self.report(&file, documents.get(&uri).unwrap());
+                eprintln!(
+                    "{}: {} errors, {} warnings",
+                    file.strip_prefix(".").unwrap_or(&file).display(),
+                    findings.errors,
+                    findings.warnings
+                );
+            }
+            thread::sleep(WATCH_INTERVAL);
+        }
+    }
+}
+
+fn file_uri(file: &Path) -> String {
+    path_to_uri(&fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf()))
+}
+
+fn human_line(file: &Path, diagnostic: &Diagnostic) -> String {
+    let code = diagnostic
+        .code
+        .as_ref()
+        .map(|code| format!("[{}]", code))
+        .unwrap_or_default();
+    format!(
+        "{}:{}:{}: {}{}: {}",
+        file.display(),
+        diagnostic.range.start.line + 1,
+        diagnostic.range.start.character + 1,
+        diagnostic.severity.label(),
+        code,
+        diagnostic.message.as_deref().unwrap_or_default()
+    )
+}
+
+/// The fields are kept stable for other tools: `range` is zero-based as in
+/// the protocol, and `fix` is the preferred fix, else the first one, else
+/// null.
+fn json_line(file: &Path, diagnostic: &Diagnostic) -> Value {
+    let fixes = diagnostic.data.as_ref().map_or(&[][..], |data| &data.fixes);
+    let fix = fixes
+        .iter()
+        .find(|fix| fix.preferred)
+        .or_else(|| fixes.first())
+        .map(|fix| json!({ "title": fix.title, "edits": fix.edits }));
+    json!({
+        "file": file.to_string_lossy().replace('\\', "/"),
+        "code": diagnostic.code,
+        "severity": diagnostic.severity.label(),
+        "range": diagnostic.range,
+        "message": diagnostic.message,
+        "fix": fix
+    })
+}
+
+/// A `::error file=...,line=...,col=...::message` workflow command. Hints
+/// and information become notices.
+fn github_line(file: &Path, diagnostic: &Diagnostic) -> String {
+    let command = match diagnostic.severity {
+        DiagnosticSeverity::Error => "error",
+        DiagnosticSeverity::Warning => "warning",
+        _ => "notice",
+    };
+    let range = &diagnostic.range;
+    let mut properties = format!(
+        "file={},line={},col={},endLine={},endColumn={}",
+        escape_property(&file.to_string_lossy().replace('\\', "/")),
+        range.start.line + 1,
+        range.start.character + 1,
+        range.end.line + 1,
+        // One-based and inclusive, where the protocol's end is exclusive.
+        range.end.character.max(1)
+    );
+    if let Some(code) = &diagnostic.code {
+        properties.push_str(&format!(",title={}", escape_property(code)));
+    }
+    format!(
+        "::{} {}::{}",
+        command,
+        properties,
+        escape_data(diagnostic.message.as_deref().unwrap_or_default())
+    )
+}
+
+/// Escapes a workflow command's message.
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escapes a workflow command's property value, which also ends at `,`
+/// and `:`.
+fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// `mylang-lsp fmt [--check] [--diff] [--stdin] <paths>...`
+///
+/// Formats the given files in place. With `--check` nothing is written;
+/// files that would change are listed and the exit code is 1. `--diff`
+/// prints what would change as a unified diff instead of writing it. With
+/// `--stdin` the text piped in is formatted to stdout.
+pub fn fmt(args: &[String]) -> i32 {
+    let mut check = false;
+    let mut show_diff = false;
+    let mut stdin = false;
+    let mut paths = Vec::new();
+
+    for arg in args {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--diff" => show_diff = true,
+            "--stdin" => stdin = true,
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    let style = Style::new(&project_config(), FormattingOptions::default());
+
+    if stdin {
+        let mut text = String::new();
+        if let Err(e) = io::stdin().read_to_string(&mut text) {
+            eprintln!("Error reading stdin: {}", e);
+            return 2;
+        }
+        let Some(formatted) = format::format(&text, &style) else {
+            eprintln!("Error: the input contains text that cannot be formatted.");
+            return 2;
+        };
+        if show_diff {
+            print!("{}", diff::unified("<stdin>", &text, &formatted));
+        } else if !check {
+            print!("{formatted}");
+        }
+        return if check && formatted != text { 1 } else { 0 };
+    }
+
+    if paths.is_empty() {
+        paths.push(PathBuf::from("."));
+    }
+    let mut changed = 0;
+    let mut skipped = 0;
+    for file in collect_files(&paths) {
+        let text = match fs::read_to_string(&file) {
+            Ok(text) => text,
+            Err(e) => {
+                eprintln!("Error reading {}: {}", file.display(), e);
+                return 2;
+            }
+        };
+        let Some(formatted) = format::format(&text, &style) else {
+            eprintln!(
+                "Skipping {}: it contains text that cannot be formatted",
+                file.display()
+            );
+            skipped += 1;
+            continue;
+        };
+        if formatted == text {
+            continue;
+        }
+        changed += 1;
+        let shown = file.strip_prefix(".").unwrap_or(&file);
+        if show_diff {
+            print!(
+                "{}",
+                diff::unified(&shown.to_string_lossy(), &text, &formatted)
+            );
+        } else if check {
+            println!("{}", shown.display());
+        } else if let Err(e) = fs::write(&file, formatted) {
+            eprintln!("Error writing {}: {}", file.display(), e);
+            return 2;
+        }
+    }
+
+    if check || show_diff {
+        eprintln!("{} files would be reformatted", changed);
+    } else {
+        eprintln!("Reformatted {} files", changed);
+    }
+    if skipped > 0 || (check && changed > 0) {
+        1
+    } else {
+        0
+    }
+}
+
+/// Rounds of fixing per file; a fix can expose another, such as a binding
+/// that was only used by one just removed.
+const MAX_FIX_PASSES: usize = 10;
+
+/// `mylang-lsp fix [--diff] <paths>...`
+///
+/// Applies the safe automatic fixes to the given files in place and
+/// reports how many edits each file got. `--diff` prints the changes as a
+/// unified diff instead of writing them.
+pub fn fix(args: &[String]) -> i32 {
+    let mut show_diff = false;
+    let mut paths = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--diff" => show_diff = true,
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    if paths.is_empty() {
+        paths.push(PathBuf::from("."));
+    }
+
+    let config = project_config();
+    let baseline = Baseline::load(Path::new(DEFAULT_BASELINE_FILE)).unwrap_or_default();
+    let mut fixed = 0;
+    for file in collect_files(&paths) {
+        let original = match fs::read_to_string(&file) {
+            Ok(text) => text,
+            Err(e) => {
+                eprintln!("Error reading {}: {}", file.display(), e);
+                return 2;
+            }
+        };
+        let uri = file_uri(&file);
+
+        let mut text: Arc<str> = original.as_str().into();
+        let mut edits = 0;
+        for _ in 0..MAX_FIX_PASSES {
+            let document = Document::new(uri.clone(), 0, text).with_globals(config.globals.clone());
+            let diagnostics = reported_diagnostics(&config, &baseline, &document);
+            let fixes = actions::safe_fixes(&document, &diagnostics);
+            text = document.text;
+            if fixes.is_empty() {
+                break;
+            }
+            edits += fixes.len();
+            text = apply_edits(&text, &fixes).into();
+        }
+        if edits == 0 {
+            continue;
+        }
+
+        fixed += 1;
+        let shown = file.strip_prefix(".").unwrap_or(&file);
+        if show_diff {
+            print!(
+                "{}",
+                diff::unified(&shown.to_string_lossy(), &original, &text)
+            );
+            continue;
+        }
+        if let Err(e) = fs::write(&file, text.as_bytes()) {
+            eprintln!("Error writing {}: {}", file.display(), e);
+            return 2;
+        }
+        println!("{}: {} edits", shown.display(), edits);
+    }
+
+    if show_diff {
+        eprintln!("{} files would be fixed", fixed);
+    } else {
+        eprintln!("Fixed {} files", fixed);
+    }
+    0
+}
+
+/// `text` with non-overlapping `edits` applied.
+fn apply_edits(text: &str, edits: &[TextEdit]) -> String {
+    let index = LineIndex::new(text);
+    let mut spans: Vec<(usize, usize, &str)> = edits
+        .iter()
+        .map(|edit| {
+            let start = index.offset(&edit.range.start);
+            let end = index.offset(&edit.range.end);
+            (start, end, edit.new_text.as_str())
+        })
+        .collect();
+    spans.sort_by_key(|(start, end, _)| (*start, *end));
+
+    let mut out = String::with_capacity(text.len());
+    let mut at = 0;
+    for (start, end, new_text) in spans {
+        out.push_str(&text[at..start]);
+        out.push_str(new_text);
+        at = end;
+    }
+    out.push_str(&text[at..]);
+    out
+}
+
+/// `mylang-lsp run <file> [function]`
+///
+/// Runs a file with the built-in interpreter, then calls `function`
+/// without arguments when one is named. Files with errors are reported
+/// instead of run; runtime errors exit with 1.
+pub fn run(args: &[String]) -> i32 {
+    let (path, function) = match args {
+        [path] => (path, None),
+        [path, function] => (path, Some(function.clone())),
+        _ => {
+            eprintln!("Error: run expects a file and optionally a function name.");
+            return 2;
+        }
+    };
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("Error reading {}: {}", path, e);
+            return 2;
+        }
+    };
+
+    let analysis = analyze(&text);
+    let mut errors = analysis
+        .diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == DiagnosticSeverity::Error)
+        .peekable();
+    if errors.peek().is_some() {
+        for diagnostic in errors {
+            eprintln!("{}", human_line(Path::new(path), diagnostic));
+        }
+        return 1;
+    }
+
+    // Deep recursion needs more stack than the main thread has.
+    let run = thread::Builder::new()
+        .stack_size(interpreter::STACK_SIZE)
+        .spawn(move || {
+            let stdout = io::stdout();
+            let mut output = stdout.lock();
+            let mut interpreter = Interpreter::new(&mut output);
+            let result = interpreter
+                .run(&analysis.program)
+                .and_then(|value| match function {
+                    Some(function) => interpreter.call(&function, Vec::new()),
+                    None => Ok(value),
+                });
+            result.map(|_| ())
+        });
+    let result = match run.map(|handle| handle.join()) {
+        Ok(Ok(result)) => result,
+        _ => {
+            eprintln!("Error: the interpreter could not run.");
+            return 2;
+        }
+    };
+    match result {
+        Ok(_) => 0,
+        Err(e) => {
+            let position = LineIndex::new(&text).position(e.span.start);
+            eprintln!(
+                "{}:{}:{}: runtime error: {}",
+                path,
+                position.line + 1,
+                position.character + 1,
+                e.message
+            );
+            1
+        }
+    }
+}
+
+/// `mylang-lsp tokens <file>`
+///
+/// Prints the token stream of a file, one token per line with its
+/// one-based `line:col` range, for reporting lexer problems. `-` reads
+/// stdin.
+pub fn tokens(args: &[String]) -> i32 {
+    let text = match read_source(args, "tokens") {
+        Ok(text) => text,
+        Err(code) => return code,
+    };
+    let index = LineIndex::new(&text);
+    for token in lexer::lex(&text) {
+        let range = index.range(token.span);
+        println!(
+            "{}:{}-{}:{} {:?} {:?}",
+            range.start.line + 1,
+            range.start.character + 1,
+            range.end.line + 1,
+            range.end.character + 1,
+            token.token_type,
+            token.lexeme(&text)
+        );
+    }
+    0
+}
+
+/// `mylang-lsp ast <file>`
+///
+/// Prints the parse tree of a file, as the `mylang.showSyntaxTree` command
+/// shows it, followed by any syntax errors on stderr. `-` reads stdin.
+pub fn ast(args: &[String]) -> i32 {
+    let text = match read_source(args, "ast") {
+        Ok(text) => text,
+        Err(code) => return code,
+    };
+    let analysis = analyze(&text);
+    print!("{}", analysis.program.dump());
+    let shown = Path::new(if args[0] == "-" { "<stdin>" } else { &args[0] });
+    for diagnostic in &analysis.diagnostics {
+        if diagnostic.code.as_deref().map(Category::of) == Some(Category::Syntax) {
+            eprintln!("{}", human_line(shown, diagnostic));
+        }
+    }
+    0
+}
+
+/// The text of the single file named in `args`, or of stdin for `-`.
+fn read_source(args: &[String], command: &str) -> Result<String, i32> {
+    let [path] = args else {
+        eprintln!("Error: {} expects a single file.", command);
+        return Err(2);
+    };
+    let text = if path == "-" {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text).map(|_| text)
+    } else {
+        fs::read_to_string(path)
+    };
+    text.map_err(|e| {
+        eprintln!("Error reading {}: {}", path, e);
+        2
+    })
+}
+
+/// The settings from the user's and the current directory's config files,
+/// or the defaults when there are none. Problems with `mylang-lsp.toml`
+/// files are printed as warnings.
+fn project_config() -> ServerConfig {
+    let root = std::env::current_dir().unwrap_or_default();
+    let mut layers = ConfigLayers::default();
+    for problem in layers.load_files(Some(&root)) {
+        eprintln!("Warning: {problem}");
+    }
+    layers.resolve()
+}
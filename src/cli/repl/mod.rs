@@ -0,0 +1,198 @@
+use std::io;
+use std::thread;
+
+use crate::analysis::analyze;
+use crate::analysis::ast::Stmt;
+use crate::analysis::diagnostics::{DiagnosticSeverity, Range, codes};
+use crate::analysis::interpreter::{self, Interpreter, Value};
+use crate::analysis::lexer::{self, TokenType};
+use crate::analysis::line_index::LineIndex;
+
+use super::editor::{Editor, Line};
+
+const PROMPT: &str = "> ";
+/// Shown while brackets opened on earlier lines are still open.
+const CONTINUATION: &str = ". ";
+
+/// Diagnostics that only make sense for whole files: bindings are used in
+/// later submissions, and redefining a name is how a session changes it.
+const IGNORED: &[&str] = &[codes::UNUSED_VARIABLE, codes::DUPLICATE_IDENTIFIER];
+
+const HELP: &str = "\
+Enter statements or expressions; the trailing ';' is optional.
+Lines with unclosed brackets continue on the next line.
+  :help   show this help
+  :quit   leave the session (also Ctrl-D)";
+
+/// `mylang-lsp repl`
+///
+/// Reads statements and expressions one at a time, shows the diagnostics
+/// the editor would for each, and runs those without errors. Bindings
+/// persist for the rest of the session.
+pub fn repl(args: &[String]) -> i32 {
+    if !args.is_empty() {
+        eprintln!("Error: repl takes no arguments.");
+        return 2;
+    }
+    // Deep recursion needs more stack than the main thread has.
+    let session = thread::Builder::new()
+        .stack_size(interpreter::STACK_SIZE)
+        .spawn(session);
+    match session.map(|handle| handle.join()) {
+        Ok(Ok(code)) => code,
+        _ => {
+            eprintln!("Error: the interpreter could not run.");
+            2
+        }
+    }
+}
+
+fn session() -> i32 {
+    let mut editor = Editor::stdin();
+    let mut output = io::stdout();
+    let mut session = Session {
+        interpreter: Interpreter::new(&mut output),
+        source: String::new(),
+    };
+    if editor.is_terminal() {
+        println!("mylang {}, :help for help", env!("CARGO_PKG_VERSION"));
+    }
+
+    while let Some(input) = read_submission(&mut editor) {
+        match input.trim() {
+            "" => {}
+            ":help" => println!("{HELP}"),
+            ":quit" | ":q" => break,
+            command if command.starts_with(':') => {
+                println!("Unknown command '{command}', :help lists them")
+            }
+            _ => session.submit(&input),
+        }
+    }
+    0
+}
+
+/// Reads lines until the brackets in them are balanced. `None` once input
+/// has ended.
+fn read_submission(editor: &mut Editor) -> Option<String> {
+    let mut input = String::new();
+    loop {
+        let prompt = if input.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION
+        };
+        match editor.read_line(prompt) {
+            Ok(Line::Text(line)) => {
+                editor.add_history(&line);
+                input.push_str(&line);
+                input.push('\n');
+                if !unclosed(&input) {
+                    return Some(input);
+                }
+            }
+            Ok(Line::Interrupted) => input.clear(),
+            // What was typed so far still gets its diagnostics.
+            Ok(Line::End) | Err(_) => return (!input.trim().is_empty()).then_some(input),
+        }
+    }
+}
+
+fn unclosed(text: &str) -> bool {
+    let mut depth = 0;
+    for token in lexer::lex(text) {
+        match token.token_type {
+            TokenType::LeftParen | TokenType::LeftBracket | TokenType::LeftBrace => depth += 1,
+            TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace => depth -= 1,
+            _ => {}
+        }
+    }
+    depth > 0
+}
+
+struct Session<'o> {
+    /// Functions borrow their bodies for the rest of the session, so the
+    /// statements that were run are leaked.
+    interpreter: Interpreter<'static, 'o>,
+    /// Everything submitted without errors, so later submissions resolve
+    /// names against it and runtime errors can point into it.
+    source: String,
+}
+
+impl Session<'_> {
+    fn submit(&mut self, input: &str) {
+        let mut input = input.trim_end().to_string();
+        if !input.ends_with(';') {
+            input.push(';');
+        }
+        let start = self.source.len();
+        let text = format!("{}{}\n", self.source, input);
+        let mut analysis = analyze(&text);
+        let index = LineIndex::new(&text);
+
+        let first_line = index.position(start).line;
+        let mut failed = false;
+        for diagnostic in &analysis.diagnostics {
+            let ignored = diagnostic
+                .code
+                .as_deref()
+                .is_some_and(|code| IGNORED.contains(&code));
+            if ignored || diagnostic.range.start.line < first_line {
+                continue;
+            }
+            failed |= diagnostic.severity == DiagnosticSeverity::Error;
+            let code = diagnostic
+                .code
+                .as_ref()
+                .map(|code| format!("[{}]", code))
+                .unwrap_or_default();
+            let message = format!(
+                "{}{}: {}",
+                diagnostic.severity.label(),
+                code,
+                diagnostic.message.as_deref().unwrap_or_default()
+            );
+            show(&index, &diagnostic.range, &message);
+        }
+        if failed {
+            return;
+        }
+
+        let first = analysis
+            .program
+            .statements
+            .iter()
+            .position(|stmt| stmt.span().start >= start)
+            .unwrap_or(analysis.program.statements.len());
+        let statements: &'static [Stmt] = analysis.program.statements.split_off(first).leak();
+        match self.interpreter.run_statements(statements) {
+            Ok(Value::Unit) => {}
+            Ok(value) => println!("{}", value.nested()),
+            Err(e) => show(
+                &index,
+                &index.range(e.span),
+                &format!("runtime error: {}", e.message),
+            ),
+        }
+        self.source = text;
+    }
+}
+
+/// Prints the line `range` starts on with the range underlined and
+/// `message` after it.
+fn show(index: &LineIndex, range: &Range, message: &str) {
+    let line = index.line_text(range.start.line).trim_end();
+    let start = range.start.character as usize;
+    let end = if range.end.line == range.start.line {
+        range.end.character as usize
+    } else {
+        line.chars().count()
+    };
+    println!("  {line}");
+    println!(
+        "  {}{} {}",
+        " ".repeat(start),
+        "^".repeat(end.saturating_sub(start).max(1)),
+        message
+    );
+}
@@ -0,0 +1,708 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+
+use crate::analysis::analyze;
+use crate::analysis::ast::{Block, Expr, ExprKind, LetKind, Stmt};
+use crate::analysis::diagnostics::DiagnosticSeverity;
+use crate::analysis::interpreter::{self, Debugger, Frame, Interpreter, RuntimeError};
+use crate::analysis::lexer::Span;
+use crate::analysis::line_index::LineIndex;
+
+/// Programs run on a single thread, which is the only one reported.
+const THREAD_ID: u64 = 1;
+
+/// Numbers the messages the adapter sends.
+static NEXT_SEQ: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Deserialize)]
+struct Request {
+    seq: u64,
+    command: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LaunchArguments {
+    program: PathBuf,
+    /// Called after the top level has run.
+    #[serde(default)]
+    function: Option<String>,
+    #[serde(default)]
+    stop_on_entry: bool,
+    #[serde(default)]
+    no_debug: bool,
+}
+
+#[derive(Deserialize)]
+struct SetBreakpointsArguments {
+    source: Source,
+    #[serde(default)]
+    breakpoints: Vec<SourceBreakpoint>,
+}
+
+#[derive(Deserialize)]
+struct Source {
+    path: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct SourceBreakpoint {
+    line: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ScopesArguments {
+    frame_id: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VariablesArguments {
+    variables_reference: usize,
+}
+
+/// A launched program, checked and waiting for `configurationDone`.
+struct Program {
+    path: PathBuf,
+    text: String,
+    function: Option<String>,
+    stop_on_entry: bool,
+    no_debug: bool,
+}
+
+/// What the session asks of a stopped program.
+enum Control {
+    StackTrace(Sender<Value>),
+    Scopes(usize, Sender<Value>),
+    Variables(usize, Sender<Value>),
+    Resume(Step),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Step {
+    Continue,
+    In,
+    Over,
+    Out,
+}
+
+/// What the session and the program's thread both see.
+#[derive(Default)]
+struct Shared {
+    /// 1-based lines, by canonical path.
+    breakpoints: Mutex<HashMap<PathBuf, Vec<u32>>>,
+    stopped: AtomicBool,
+    pause: AtomicBool,
+    terminate: AtomicBool,
+}
+
+#[derive(Default)]
+struct Session {
+    shared: Arc<Shared>,
+    program: Option<Program>,
+    control: Option<Sender<Control>>,
+    thread: Option<JoinHandle<()>>,
+    /// A resume the session answered, held back until the response is out
+    /// so the program cannot report stopping again before it.
+    resuming: Option<Step>,
+}
+
+/// `mylang-lsp --dap`
+///
+/// Serves the Debug Adapter Protocol on stdin and stdout, running programs
+/// with the built-in interpreter.
+pub fn serve() -> i32 {
+    let stdin = io::stdin();
+    let mut reader = BufReader::new(stdin.lock());
+    let mut session = Session::default();
+
+    while let Some(message) = read_message(&mut reader) {
+        let request: Request = match serde_json::from_str(&message) {
+            Ok(request) => request,
+            Err(e) => {
+                tracing::warn!("invalid request: {}", e);
+                continue;
+            }
+        };
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request.seq,
+            "command": request.command,
+        });
+        match session.handle(&request) {
+            Ok(body) => {
+                response["success"] = json!(true);
+                response["body"] = body;
+            }
+            Err(message) => {
+                response["success"] = json!(false);
+                response["message"] = json!(message);
+            }
+        }
+        send(response);
+        session.release();
+
+        match request.command.as_str() {
+            "initialize" => send_event("initialized", json!({})),
+            "disconnect" => break,
+            _ => {}
+        }
+    }
+
+    session.shared.terminate.store(true, Ordering::Relaxed);
+    session.control = None;
+    if let Some(thread) = session.thread.take() {
+        let _ = thread.join();
+    }
+    0
+}
+
+impl Session {
+    fn handle(&mut self, request: &Request) -> Result<Value, String> {
+        match request.command.as_str() {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsTerminateRequest": true
+            })),
+            "launch" => {
+                self.launch(arguments(request)?)?;
+                Ok(Value::Null)
+            }
+            "setBreakpoints" => {
+                let arguments: SetBreakpointsArguments = arguments(request)?;
+                let Some(path) = arguments.source.path else {
+                    return Err("Breakpoints need a source path".to_string());
+                };
+                let stops = fs::read_to_string(&path).map(|text| stop_lines(&text));
+                let mut lines = Vec::new();
+                let breakpoints: Vec<Value> = arguments
+                    .breakpoints
+                    .iter()
+                    .map(|breakpoint| {
+                        let stops = match &stops {
+                            Ok(stops) => stops,
+                            Err(e) => {
+                                return json!({
+                                    "verified": false,
+                                    "line": breakpoint.line,
+                                    "message": format!("Could not read the source: {e}")
+                                });
+                            }
+                        };
+                        // Lines that start no statement stop at the next
+                        // one that does.
+                        match stops.range(breakpoint.line..).next() {
+                            Some(&line) => {
+                                lines.push(line);
+                                json!({ "verified": true, "line": line })
+                            }
+                            None => json!({
+                                "verified": false,
+                                "line": breakpoint.line,
+                                "message": "No statement starts on or after this line"
+                            }),
+                        }
+                    })
+                    .collect();
+                let path = fs::canonicalize(&path).unwrap_or(path);
+                self.shared.breakpoints.lock().unwrap().insert(path, lines);
+                Ok(json!({ "breakpoints": breakpoints }))
+            }
+            "configurationDone" => {
+                self.start()?;
+                Ok(Value::Null)
+            }
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })),
+            "stackTrace" => self.ask(Control::StackTrace),
+            "scopes" => {
+                let arguments: ScopesArguments = arguments(request)?;
+                self.ask(|reply| Control::Scopes(arguments.frame_id, reply))
+            }
+            "variables" => {
+                let arguments: VariablesArguments = arguments(request)?;
+                self.ask(|reply| Control::Variables(arguments.variables_reference, reply))
+            }
+            "continue" => {
+                self.resume(Step::Continue)?;
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            "next" => self.resume(Step::Over).map(|()| Value::Null),
+            "stepIn" => self.resume(Step::In).map(|()| Value::Null),
+            "stepOut" => self.resume(Step::Out).map(|()| Value::Null),
+            "pause" => {
+                self.shared.pause.store(true, Ordering::Relaxed);
+                Ok(Value::Null)
+            }
+            "terminate" | "disconnect" => {
+                self.shared.terminate.store(true, Ordering::Relaxed);
+                let _ = self.resume(Step::Continue);
+                Ok(Value::Null)
+            }
+            command => Err(format!("Unknown command '{command}'")),
+        }
+    }
+
+    /// Checks the program, which runs once configuration is done.
+    fn launch(&mut self, arguments: LaunchArguments) -> Result<(), String> {
+        let path = arguments.program;
+        let text = fs::read_to_string(&path)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        let analysis = analyze(&text);
+        if let Some(error) = analysis
+            .diagnostics
+            .iter()
+            .find(|diagnostic| diagnostic.severity == DiagnosticSeverity::Error)
+        {
+            return Err(format!(
+                "{}:{}:{}: {}",
+                path.display(),
+                error.range.start.line + 1,
+                error.range.start.character + 1,
+                error.message.as_deref().unwrap_or_default()
+            ));
+        }
+        self.program = Some(Program {
+            path: fs::canonicalize(&path).unwrap_or(path),
+            text,
+            function: arguments.function,
+            stop_on_entry: arguments.stop_on_entry,
+            no_debug: arguments.no_debug,
+        });
+        Ok(())
+    }
+
+    fn start(&mut self) -> Result<(), String> {
+        let Some(program) = self.program.take() else {
+            return Err("There is no program to run".to_string());
+        };
+        let (control, commands) = mpsc::channel();
+        let shared = self.shared.clone();
+        let thread = thread::Builder::new()
+            .stack_size(interpreter::STACK_SIZE)
+            .spawn(move || run(program, shared, commands))
+            .map_err(|e| format!("Could not start the program: {e}"))?;
+        self.control = Some(control);
+        self.thread = Some(thread);
+        Ok(())
+    }
+
+    /// Sends a question to the stopped program and waits for the answer.
+    fn ask(&self, question: impl FnOnce(Sender<Value>) -> Control) -> Result<Value, String> {
+        let control = self
+            .control
+            .as_ref()
+            .filter(|_| self.shared.stopped.load(Ordering::Relaxed))
+            .ok_or("The program is not stopped")?;
+        let (reply, answer) = mpsc::channel();
+        control
+            .send(question(reply))
+            .map_err(|_| "The program has ended")?;
+        answer
+            .recv()
+            .map_err(|_| "The program has ended".to_string())
+    }
+
+    /// Lets the stopped program go on once the response has been sent;
+    /// see [`Session::release`].
+    fn resume(&mut self, step: Step) -> Result<(), String> {
+        self.control
+            .as_ref()
+            .filter(|_| self.shared.stopped.swap(false, Ordering::Relaxed))
+            .ok_or("The program is not stopped")?;
+        self.resuming = Some(step);
+        Ok(())
+    }
+
+    /// Sends the program on its way after a resuming request was answered.
+    fn release(&mut self) {
+        if let (Some(step), Some(control)) = (self.resuming.take(), &self.control) {
+            let _ = control.send(Control::Resume(step));
+        }
+    }
+}
+
+fn arguments<T: DeserializeOwned>(request: &Request) -> Result<T, String> {
+    serde_json::from_value(request.arguments.clone())
+        .map_err(|e| format!("Invalid arguments for {}: {}", request.command, e))
+}
+
+/// Runs `program` on the current thread, stopping where the session asks
+/// to, and reports how it ended.
+fn run(program: Program, shared: Arc<Shared>, commands: Receiver<Control>) {
+    let analysis = analyze(&program.text);
+    let index = LineIndex::new(&program.text);
+    let mut output = Output::default();
+    let mut controller = Controller {
+        program: &program,
+        index: &index,
+        shared: &shared,
+        commands: &commands,
+        step: if program.stop_on_entry {
+            Step::In
+        } else {
+            Step::Continue
+        },
+        depth: 0,
+        last: None,
+        references: Vec::new(),
+    };
+
+    let result = {
+        let mut interpreter = Interpreter::new(&mut output);
+        if !program.no_debug {
+            interpreter = interpreter.with_debugger(&mut controller);
+        }
+        interpreter
+            .run(&analysis.program)
+            .and_then(|value| match &program.function {
+                Some(function) => interpreter.call(function, Vec::new()),
+                None => Ok(value),
+            })
+    };
+    let _ = output.flush();
+    shared.stopped.store(false, Ordering::Relaxed);
+
+    let exit_code = match result {
+        Ok(_) => 0,
+        Err(_) if shared.terminate.load(Ordering::Relaxed) => 1,
+        Err(e) => {
+            let position = index.position(e.span.start);
+            send_output(
+                "stderr",
+                &format!(
+                    "{}:{}:{}: runtime error: {}\n",
+                    program.path.display(),
+                    position.line + 1,
+                    position.character + 1,
+                    e.message
+                ),
+            );
+            1
+        }
+    };
+    send_event("exited", json!({ "exitCode": exit_code }));
+    send_event("terminated", json!({}));
+}
+
+/// The 1-based lines the program can stop on: those where a statement, a
+/// block's tail or a function body that is not a block starts, as those
+/// are where the interpreter pauses.
+fn stop_lines(text: &str) -> BTreeSet<u32> {
+    fn statements(statements: &[Stmt], starts: &mut Vec<usize>) {
+        for stmt in statements {
+            starts.push(stmt.span().start);
+            match stmt {
+                Stmt::Let(stmt) => match (&stmt.value, stmt.kind) {
+                    (Some(value), LetKind::Function) => function_body(value, starts),
+                    (Some(value), LetKind::Value) => expr(value, starts),
+                    (None, _) => {}
+                },
+                Stmt::Expr(stmt) => expr(&stmt.expr, starts),
+            }
+        }
+    }
+    fn block(block: &Block, starts: &mut Vec<usize>) {
+        statements(&block.statements, starts);
+        if let Some(tail) = &block.tail {
+            starts.push(tail.span.start);
+            expr(tail, starts);
+        }
+    }
+    fn function_body(body: &Expr, starts: &mut Vec<usize>) {
+        if !matches!(body.kind, ExprKind::Block(_)) {
+            starts.push(body.span.start);
+        }
+        expr(body, starts);
+    }
+    fn expr(node: &Expr, starts: &mut Vec<usize>) {
+        match &node.kind {
+            ExprKind::Block(inner) => block(inner, starts),
+            ExprKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                expr(condition, starts);
+                block(then_branch, starts);
+                if let Some(branch) = else_branch {
+                    expr(branch, starts);
+                }
+            }
+            ExprKind::Lambda { body, .. } => function_body(body, starts),
+            _ => {
+                for child in node.children() {
+                    expr(child, starts);
+                }
+            }
+        }
+    }
+
+    let analysis = analyze(text);
+    let index = LineIndex::new(text);
+    let mut starts = Vec::new();
+    statements(&analysis.program.statements, &mut starts);
+    starts
+        .into_iter()
+        .map(|offset| index.position(offset).line + 1)
+        .collect()
+}
+
+/// What a variables reference handed out while stopped points at.
+enum Reference<'a> {
+    Locals(usize),
+    Globals(usize),
+    Value(interpreter::Value<'a>),
+}
+
+/// Decides where the program stops, and answers the session while it is
+/// stopped.
+struct Controller<'a, 'p> {
+    program: &'p Program,
+    index: &'p LineIndex<'p>,
+    shared: &'p Shared,
+    commands: &'p Receiver<Control>,
+    step: Step,
+    /// How many frames there were when the program last resumed, 0 before
+    /// the first stop.
+    depth: usize,
+    /// The line and frame count of the previous step, so a line made of
+    /// several steps stops only once.
+    last: Option<(u32, usize)>,
+    /// Valid until the program resumes; a reference is its index plus one.
+    references: Vec<Reference<'a>>,
+}
+
+impl<'a> Debugger<'a> for Controller<'a, '_> {
+    fn pause(&mut self, frames: &[Frame<'a>]) -> Result<(), RuntimeError> {
+        let span = frames.last().map_or(0, |frame| frame.span.start);
+        let here = (self.index.position(span).line + 1, frames.len());
+        let moved = self.last != Some(here);
+        self.last = Some(here);
+
+        let reason = if self.shared.terminate.load(Ordering::Relaxed) {
+            return Err(RuntimeError {
+                message: "The debugger ended the run".to_string(),
+                span: frames.last().map_or(Span::new(0, 0), |frame| frame.span),
+            });
+        } else if self.shared.pause.swap(false, Ordering::Relaxed) {
+            "pause"
+        } else if !moved {
+            return Ok(());
+        } else if self.stepped(here.1) {
+            if self.depth == 0 { "entry" } else { "step" }
+        } else if self.breakpoint(here.0) {
+            "breakpoint"
+        } else {
+            return Ok(());
+        };
+        self.stop(reason, frames)
+    }
+}
+
+impl<'a> Controller<'a, '_> {
+    fn stepped(&self, depth: usize) -> bool {
+        match self.step {
+            Step::Continue => false,
+            Step::In => true,
+            Step::Over => depth <= self.depth,
+            Step::Out => depth < self.depth,
+        }
+    }
+
+    fn breakpoint(&self, line: u32) -> bool {
+        self.shared
+            .breakpoints
+            .lock()
+            .unwrap()
+            .get(&self.program.path)
+            .is_some_and(|lines| lines.contains(&line))
+    }
+
+    fn stop(&mut self, reason: &str, frames: &[Frame<'a>]) -> Result<(), RuntimeError> {
+        self.shared.stopped.store(true, Ordering::Relaxed);
+        send_event(
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+        );
+        for command in self.commands.iter() {
+            let (reply, answer) = match command {
+                Control::Resume(step) => {
+                    self.step = step;
+                    self.depth = frames.len();
+                    self.references.clear();
+                    return Ok(());
+                }
+                Control::StackTrace(reply) => (reply, self.stack_trace(frames)),
+                Control::Scopes(frame, reply) => (reply, self.scopes(frame)),
+                Control::Variables(reference, reply) => (reply, self.variables(reference, frames)),
+            };
+            let _ = reply.send(answer);
+        }
+        // The session has ended.
+        Err(RuntimeError {
+            message: "The debugger ended the run".to_string(),
+            span: Span::new(0, 0),
+        })
+    }
+
+    fn stack_trace(&self, frames: &[Frame<'a>]) -> Value {
+        let source = json!({
+            "name": self.program.path.file_name().map(|name| name.to_string_lossy()),
+            "path": self.program.path
+        });
+        let stack_frames: Vec<Value> = frames
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(id, frame)| {
+                let position = self.index.position(frame.span.start);
+                json!({
+                    "id": id,
+                    "name": frame.name(),
+                    "line": position.line + 1,
+                    "column": position.character + 1,
+                    "source": source
+                })
+            })
+            .collect();
+        json!({ "stackFrames": stack_frames, "totalFrames": frames.len() })
+    }
+
+    fn scopes(&mut self, frame: usize) -> Value {
+        json!({ "scopes": [
+            {
+                "name": "Locals",
+                "presentationHint": "locals",
+                "variablesReference": self.reference(Reference::Locals(frame)),
+                "expensive": false
+            },
+            {
+                "name": "Globals",
+                "variablesReference": self.reference(Reference::Globals(frame)),
+                "expensive": false
+            }
+        ] })
+    }
+
+    fn variables(&mut self, reference: usize, frames: &[Frame<'a>]) -> Value {
+        let variables = match reference
+            .checked_sub(1)
+            .and_then(|at| self.references.get(at))
+        {
+            Some(Reference::Locals(frame)) => frames.get(*frame).map(Frame::locals),
+            Some(Reference::Globals(frame)) => frames.get(*frame).map(Frame::globals),
+            Some(Reference::Value(
+                interpreter::Value::List(items) | interpreter::Value::Set(items),
+            )) => Some(
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(at, item)| (at.to_string(), item.clone()))
+                    .collect(),
+            ),
+            _ => None,
+        };
+        let variables: Vec<Value> = variables
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, value)| {
+                let reference = match &value {
+                    interpreter::Value::List(items) | interpreter::Value::Set(items)
+                        if !items.is_empty() =>
+                    {
+                        self.reference(Reference::Value(value.clone()))
+                    }
+                    _ => 0,
+                };
+                json!({
+                    "name": name,
+                    "value": value.nested(),
+                    "type": value.type_name(),
+                    "variablesReference": reference
+                })
+            })
+            .collect();
+        json!({ "variables": variables })
+    }
+
+    fn reference(&mut self, reference: Reference<'a>) -> usize {
+        self.references.push(reference);
+        self.references.len()
+    }
+}
+
+/// Sends what the program prints as `output` events, a line at a time.
+#[derive(Default)]
+struct Output {
+    pending: Vec<u8>,
+}
+
+impl Write for Output {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(bytes);
+        if let Some(end) = self.pending.iter().rposition(|&byte| byte == b'\n') {
+            let lines: Vec<u8> = self.pending.drain(..=end).collect();
+            send_output("stdout", &String::from_utf8_lossy(&lines));
+        }
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            send_output("stdout", &String::from_utf8_lossy(&self.pending));
+            self.pending.clear();
+        }
+        Ok(())
+    }
+}
+
+fn send_output(category: &str, output: &str) {
+    send_event("output", json!({ "category": category, "output": output }));
+}
+
+fn send_event(event: &str, body: Value) {
+    send(json!({ "type": "event", "event": event, "body": body }));
+}
+
+/// Writes a message with the next sequence number. Both the session and
+/// the program's thread send, so each message is written under one lock.
+fn send(mut message: Value) {
+    message["seq"] = json!(NEXT_SEQ.fetch_add(1, Ordering::Relaxed));
+    let text = message.to_string();
+    let mut stdout = io::stdout().lock();
+    let _ = write!(stdout, "Content-Length: {}\r\n\r\n{}", text.len(), text);
+    let _ = stdout.flush();
+}
+
+/// Reads one framed message, or `None` once input has ended.
+fn read_message(reader: &mut impl BufRead) -> Option<String> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim();
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse().ok();
+        } else if line.is_empty() && length.is_some() {
+            break;
+        }
+    }
+    let mut payload = vec![0; length?];
+    reader.read_exact(&mut payload).ok()?;
+    String::from_utf8(payload).ok()
+}
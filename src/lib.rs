@@ -0,0 +1,4 @@
+pub mod analysis;
+pub mod server;
+#[cfg(feature = "wasm")]
+pub mod wasm;
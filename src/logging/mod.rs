@@ -0,0 +1,184 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::format::FmtSpan;
+
+/// Sets the log level when `--log-level` is not given, such as `debug`.
+pub const LOG_LEVEL_ENV: &str = "MYLANG_LSP_LOG";
+
+/// Sets the log file when `--log-file` is not given.
+pub const LOG_FILE_ENV: &str = "MYLANG_LSP_LOG_FILE";
+
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::WARN;
+
+/// Size at which the log file is rotated unless `--log-max-size` says
+/// otherwise.
+const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Rotated files kept next to the log file, as `<file>.1` (the newest) up
+/// to `<file>.3`.
+const KEPT_FILES: usize = 3;
+
+/// Takes the logging options out of `args` and installs the global
+/// subscriber:
+///
+/// - `--log-level <level>`, or the `MYLANG_LSP_LOG` variable
+/// - `--log-file <path>`, or the `MYLANG_LSP_LOG_FILE` variable, appended
+///   to instead of stderr
+/// - `--log-max-size <size>`, such as `512K` or `20M`, after which the file
+///   is rotated
+/// - `--log-max-age <hours>`, after which the file is rotated
+///
+/// Spans log their duration when they close, so at `info` every message
+/// the server handles is logged with the time it took. Nothing is ever
+/// written to stdout, which belongs to the protocol.
+pub fn init(args: &mut Vec<String>) -> Result<(), String> {
+    let level = match take_option(args, "--log-level")? {
+        Some(level) => Some(level),
+        None => std::env::var(LOG_LEVEL_ENV).ok(),
+    };
+    let level = match level {
+        Some(level) => {
+            LevelFilter::from_str(&level).map_err(|_| format!("unknown log level '{level}'"))?
+        }
+        None => DEFAULT_LEVEL,
+    };
+    let max_size = take_option(args, "--log-max-size")?
+        .map(|size| parse_size(&size).ok_or_else(|| format!("invalid log size '{size}'")))
+        .transpose()?
+        .unwrap_or(DEFAULT_MAX_SIZE);
+    let max_age = take_option(args, "--log-max-age")?
+        .map(|hours| {
+            let hours: f64 = hours
+                .parse()
+                .ok()
+                .filter(|hours: &f64| *hours > 0.0)
+                .ok_or_else(|| format!("invalid log age '{hours}'"))?;
+            Ok::<_, String>(Duration::from_secs_f64(hours * 3600.0))
+        })
+        .transpose()?;
+    let file = take_option(args, "--log-file")?
+        .or_else(|| std::env::var(LOG_FILE_ENV).ok())
+        .map(|path| RotatingFile::open(PathBuf::from(path), max_size, max_age))
+        .transpose()?;
+
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_ansi(false);
+    match file {
+        Some(file) => subscriber.with_writer(Mutex::new(file)).init(),
+        None => subscriber.with_writer(io::stderr).init(),
+    }
+    Ok(())
+}
+
+/// Removes `name` and the value after it from `args`.
+fn take_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>, String> {
+    let Some(index) = args.iter().position(|arg| arg == name) else {
+        return Ok(None);
+    };
+    if index + 1 == args.len() {
+        return Err(format!("{name} needs a value"));
+    }
+    let value = args.remove(index + 1);
+    args.remove(index);
+    Ok(Some(value))
+}
+
+/// Bytes, or a number with a `K`, `M` or `G` suffix.
+fn parse_size(size: &str) -> Option<u64> {
+    let (number, unit) = match size.char_indices().last()? {
+        (i, 'K' | 'k') => (&size[..i], 1024),
+        (i, 'M' | 'm') => (&size[..i], 1024 * 1024),
+        (i, 'G' | 'g') => (&size[..i], 1024 * 1024 * 1024),
+        _ => (size, 1),
+    };
+    let number: u64 = number.parse().ok()?;
+    (number > 0).then_some(number * unit)
+}
+
+/// A log file that is moved aside once it grows past `max_size`, or once
+/// it has been written to for longer than `max_age`.
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    opened: Instant,
+    max_size: u64,
+    max_age: Option<Duration>,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf, max_size: u64, max_age: Option<Duration>) -> Result<Self, String> {
+        let file =
+            append(&path).map_err(|e| format!("could not open {}: {}", path.display(), e))?;
+        let size = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+        Ok(Self {
+            path,
+            file,
+            size,
+            opened: Instant::now(),
+            max_size,
+            max_age,
+        })
+    }
+
+    fn due(&self, incoming: usize) -> bool {
+        let full = self.size > 0 && self.size + incoming as u64 > self.max_size;
+        let old = self
+            .max_age
+            .is_some_and(|max_age| self.opened.elapsed() >= max_age);
+        full || old
+    }
+
+    /// Shifts the kept files up by one, dropping the oldest, and starts a
+    /// new file.
+    fn rotate(&mut self) -> io::Result<()> {
+        let _ = fs::remove_file(numbered(&self.path, KEPT_FILES));
+        for n in (1..KEPT_FILES).rev() {
+            let from = numbered(&self.path, n);
+            if from.exists() {
+                fs::rename(&from, numbered(&self.path, n + 1))?;
+            }
+        }
+        fs::rename(&self.path, numbered(&self.path, 1))?;
+        self.file = append(&self.path)?;
+        self.size = 0;
+        self.opened = Instant::now();
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.due(buf.len()) && self.rotate().is_err() {
+            // Try again after another file's worth of logs, not on every
+            // write.
+            self.size = 0;
+            self.opened = Instant::now();
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn numbered(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
+}
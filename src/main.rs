@@ -1,80 +1,33 @@
-pub mod analysis;
+mod cli;
+mod dap;
+mod logging;
 
-use analysis::run_analysis;
-use serde_json::json;
+use mylang_lsp::{analysis, server};
 
-use std::io::{self, BufRead, BufReader, Read};
+use std::env;
+use std::io::{self, BufReader};
+use std::process;
 
 fn main() {
-    let stdin = io::stdin();
-    let mut reader = BufReader::new(stdin.lock());
-    let mut buffer = String::new();
-
-    loop {
-        buffer.clear();
-
-        if reader.read_line(&mut buffer).unwrap_or(0) == 0 {
-            eprintln!("EOF");
-            break;
-        }
-
-        let line = buffer.trim();
-        if line.starts_with("Content-Length: ") {
-            let len = line["Content-Length: ".len()..]
-                .trim()
-                .parse::<usize>()
-                .unwrap();
-
-            buffer.clear();
-            if reader.read_line(&mut buffer).unwrap_or(0) == 0 {
-                eprintln!("Error: Expected blank line after Content-Length header.");
-                break;
-            }
-
-            let mut payload = vec![0; len];
-            let mut total_read = 0;
-
-            while total_read < len {
-                match reader.read(&mut payload[total_read..]) {
-                    Ok(0) => {
-                        eprintln!("Error: Unexpected EOF while reading payload.");
-                        break;
-                    }
-                    Ok(n) => total_read += n,
-                    Err(e) => {
-                        eprintln!("Error reading payload: {}", e);
-                        break;
-                    }
-                }
-            }
-
-            if total_read != len {
-                eprintln!(
-                    "Error: Expected {} bytes, but read {} bytes.",
-                    len, total_read
-                );
-                break;
-            }
-
-            let message = String::from_utf8(payload).unwrap();
-            match run_analysis(message) {
-                Ok(result) => {
-                    println!("Content-Length: {}\r\n\r\n{}", result.len(), result);
-                }
-
-                Err(e) => {
-                    let response = json!({
-                        "jsonrpc": "2.0",
-                        "method": "textDocument/publishDiagnostics",
-                        "params": {
-                            "uri": "file://unknown",
-                            "diagnostics": vec!(e)
-                        }
-                    });
-                    let output = serde_json::to_string(&response).unwrap();
-                    println!("Content-Length: {}\r\n\r\n{}", output.len(), output)
-                }
-            }
-        }
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    if let Err(e) = logging::init(&mut args) {
+        eprintln!("Error: {}", e);
+        process::exit(2);
     }
+    match args.first().map(String::as_str) {
+        Some("--dap") => process::exit(dap::serve()),
+        Some("ast") => process::exit(cli::ast(&args[1..])),
+        Some("baseline") => process::exit(cli::baseline(&args[1..])),
+        Some("check") => process::exit(cli::check(&args[1..])),
+        Some("fix") => process::exit(cli::fix(&args[1..])),
+        Some("fmt") => process::exit(cli::fmt(&args[1..])),
+        Some("grammar") => process::exit(cli::grammar(&args[1..])),
+        Some("lsif") => process::exit(cli::lsif(&args[1..])),
+        Some("repl") => process::exit(cli::repl(&args[1..])),
+        Some("run") => process::exit(cli::run(&args[1..])),
+        Some("tokens") => process::exit(cli::tokens(&args[1..])),
+        _ => {}
+    }
+
+    process::exit(server::serve(BufReader::new(io::stdin()), io::stdout()));
 }
@@ -0,0 +1,261 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::thread;
+use std::time::Instant;
+
+use serde::Deserialize;
+
+use crate::analysis::in_flight::InFlight;
+use crate::analysis::{
+    Event, ServerState, has_background_work, parse_error, run_analysis, run_background, run_pending,
+};
+
+/// Serves the Language Server Protocol until the client sends `exit` or
+/// `input` ends, and returns the exit code. The binary runs it on stdin and
+/// stdout; tests run it on in-memory pipes.
+///
+/// Work comes in two tiers. Messages from the client are handled first, in
+/// the order they arrive, along with the debounced diagnostics of edited
+/// documents. Background work, such as indexing the workspace and
+/// re-checking the dependents of a saved file, runs one small unit at a
+/// time and only while no message is waiting.
+pub fn serve(input: impl BufRead + Send + 'static, mut output: impl Write) -> i32 {
+    let (sender, receiver) = mpsc::channel();
+    let reader_sender = sender.clone();
+    let in_flight = Arc::new(InFlight::default());
+    let reader_in_flight = in_flight.clone();
+    thread::spawn(move || {
+        let _closed = ClosedOnDrop(reader_sender.clone());
+        read_messages(input, &reader_sender, &reader_in_flight);
+    });
+
+    let mut state = ServerState {
+        events: Some(sender),
+        in_flight,
+        ..Default::default()
+    };
+    // Events taken off the channel early, while looking for changes to
+    // coalesce.
+    let mut queued = VecDeque::new();
+
+    loop {
+        let received = match (queued.pop_front(), state.scheduler.next_deadline()) {
+            (Some(event), _) => Ok(event),
+            (None, _) if has_background_work(&state) => match receiver.try_recv() {
+                Ok(event) => Ok(event),
+                Err(TryRecvError::Empty) => {
+                    for result in run_background(&mut state) {
+                        write_message(&mut output, &result);
+                    }
+                    Err(RecvTimeoutError::Timeout)
+                }
+                Err(TryRecvError::Disconnected) => Err(RecvTimeoutError::Disconnected),
+            },
+            (None, Some(deadline)) => {
+                receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            }
+            (None, None) => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        match received {
+            Ok(Event::Incoming(message)) => {
+                for result in run_analysis(coalesce(message, &receiver, &mut queued), &mut state) {
+                    write_message(&mut output, &result);
+                }
+                if let Some(code) = state.exit_code {
+                    return code;
+                }
+            }
+            Ok(Event::Outgoing(result)) => {
+                write_message(&mut output, &result);
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Ok(Event::Closed) | Err(RecvTimeoutError::Disconnected) => {
+                for result in run_pending(&mut state, true) {
+                    write_message(&mut output, &result);
+                }
+                return 0;
+            }
+        }
+
+        for result in run_pending(&mut state, false) {
+            write_message(&mut output, &result);
+        }
+    }
+}
+
+/// The parts of a `didChange` notification that decide whether it can be
+/// coalesced; the content changes are skipped over unread.
+#[derive(Deserialize)]
+struct ChangeNotification {
+    method: String,
+    params: ChangeParams,
+}
+
+#[derive(Deserialize)]
+struct ChangeParams {
+    #[serde(rename = "textDocument")]
+    text_document: ChangedDocument,
+}
+
+#[derive(Deserialize)]
+struct ChangedDocument {
+    uri: String,
+}
+
+fn changed_uri(message: &str) -> Option<String> {
+    let notification: ChangeNotification = serde_json::from_str(message).ok()?;
+    (notification.method == "textDocument/didChange")
+        .then_some(notification.params.text_document.uri)
+}
+
+/// Replaces a `didChange` with the last of the `didChange`s for the same
+/// document queued right behind it, so a burst of typing or a large paste
+/// is applied as one edit. The server only advertises full document sync,
+/// so the last change carries the text all of them add up to. Anything
+/// else in between ends the run, since it may depend on the text at that
+/// point.
+fn coalesce(message: String, receiver: &Receiver<Event>, queued: &mut VecDeque<Event>) -> String {
+    let Some(uri) = changed_uri(&message) else {
+        return message;
+    };
+    queued.extend(receiver.try_iter());
+
+    let mut latest = message;
+    let mut skipped = 0;
+    while let Some(Event::Incoming(next)) = queued.front() {
+        if changed_uri(next).as_ref() != Some(&uri) {
+            break;
+        }
+        if let Some(Event::Incoming(next)) = queued.pop_front() {
+            latest = next;
+            skipped += 1;
+        }
+    }
+    if skipped > 0 {
+        tracing::debug!(uri, skipped, "coalesced didChange notifications");
+    }
+    latest
+}
+
+fn write_message(output: &mut impl Write, message: &str) {
+    let _ = write!(
+        output,
+        "Content-Length: {}\r\n\r\n{}",
+        message.len(),
+        message
+    );
+    let _ = output.flush();
+}
+
+/// Sends `Event::Closed` when dropped, so the main loop learns the reader
+/// has stopped however it stopped, a panic included.
+struct ClosedOnDrop(Sender<Event>);
+
+impl Drop for ClosedOnDrop {
+    fn drop(&mut self) {
+        let _ = self.0.send(Event::Closed);
+    }
+}
+
+/// Reads framed messages on a dedicated thread, so the main loop can wake
+/// up for debounced work while no input is arriving, and so a message can
+/// cancel the work it makes pointless before the main loop gets to it.
+///
+/// Headers that cannot be framed, such as a `Content-Length` that is not a
+/// number or a header block without one, leave no way to find where the
+/// next message starts, so reading stops there. A body that is not UTF-8 is
+/// answered with a parse error and skipped.
+fn read_messages(mut reader: impl BufRead, sender: &Sender<Event>, in_flight: &InFlight) {
+    let mut buffer = String::new();
+
+    loop {
+        let Some(len) = read_headers(&mut reader, &mut buffer) else {
+            return;
+        };
+
+        let mut payload = vec![0; len];
+        let mut total_read = 0;
+
+        while total_read < len {
+            match reader.read(&mut payload[total_read..]) {
+                Ok(0) => {
+                    tracing::error!("unexpected end of input while reading a payload");
+                    break;
+                }
+                Ok(n) => total_read += n,
+                Err(e) => {
+                    tracing::error!("could not read a payload: {}", e);
+                    break;
+                }
+            }
+        }
+
+        if total_read != len {
+            tracing::error!("expected {} bytes, but read {}", len, total_read);
+            return;
+        }
+
+        let event = match String::from_utf8(payload) {
+            Ok(message) => {
+                in_flight.observe(&message);
+                Event::Incoming(message)
+            }
+            Err(e) => {
+                tracing::error!("a message body is not UTF-8: {}", e);
+                Event::Outgoing(parse_error(&format!("The message is not UTF-8: {e}")))
+            }
+        };
+        if sender.send(event).is_err() {
+            return;
+        }
+    }
+}
+
+/// Reads one header block and returns its `Content-Length`, or `None` when
+/// the input ended or the headers cannot be framed. Blank lines before a
+/// block are skipped.
+fn read_headers(reader: &mut impl BufRead, buffer: &mut String) -> Option<usize> {
+    let mut length = None;
+    let mut seen_header = false;
+    loop {
+        buffer.clear();
+        if reader.read_line(buffer).unwrap_or(0) == 0 {
+            if seen_header {
+                tracing::error!("unexpected end of input in the headers");
+            } else {
+                tracing::info!("input closed");
+            }
+            return None;
+        }
+
+        let line = buffer.trim();
+        if line.is_empty() {
+            if !seen_header {
+                continue;
+            }
+            if length.is_none() {
+                tracing::error!("a header block has no Content-Length");
+            }
+            return length;
+        }
+        seen_header = true;
+
+        // Other headers, such as Content-Type, are allowed and ignored.
+        let Some((name, value)) = line.split_once(':') else {
+            tracing::error!("malformed header line: {:?}", line);
+            return None;
+        };
+        if name.trim().eq_ignore_ascii_case("Content-Length") {
+            match value.trim().parse::<usize>() {
+                Ok(value) => length = Some(value),
+                Err(_) => {
+                    tracing::error!("invalid Content-Length: {:?}", value.trim());
+                    return None;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use wasm_bindgen::prelude::*;
+
+use crate::analysis::config::ServerConfig;
+use crate::analysis::document_diagnostics;
+use crate::analysis::format::{self, FormattingOptions, Style};
+
+/// The diagnostics for `text`, as a JSON array of the protocol's
+/// `Diagnostic` objects.
+#[wasm_bindgen]
+pub fn analyze(text: &str) -> String {
+    serde_json::to_string(&document_diagnostics(text)).unwrap_or_else(|_| "[]".to_string())
+}
+
+/// `text` formatted with the default style, or `undefined` when it does not
+/// parse.
+#[wasm_bindgen]
+pub fn format(text: &str) -> Option<String> {
+    let style = Style::new(&ServerConfig::default(), FormattingOptions::default());
+    format::format(text, &style)
+}
@@ -0,0 +1,101 @@
+//! Runs the command-line subcommands of the binary against files on disk.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+fn project(name: &str) -> PathBuf {
+    let directory =
+        std::env::temp_dir().join(format!("mylang-lsp-cli-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+fn run(directory: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mylang-lsp"))
+        .args(args)
+        .current_dir(directory)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn fix_keeps_unused_bindings_whose_value_has_effects() {
+    let directory = project("fix");
+    let source = "let x = print(2);\nlet y = 1;\nlet f a -> print(a);\nlet g a -> a;\n";
+    fs::write(directory.join("main.mylang"), source).unwrap();
+
+    let output = run(&directory, &["fix", "--diff"]);
+    assert!(output.status.success());
+    let diff = String::from_utf8(output.stdout).unwrap();
+    let added: Vec<&str> = diff
+        .lines()
+        .filter(|line| line.starts_with('+') && !line.starts_with("+++"))
+        .collect();
+    assert_eq!(added, ["+let _x = print(2);", "+let _f a -> print(a);"]);
+
+    run(&directory, &["fix"]);
+    assert_eq!(
+        fs::read_to_string(directory.join("main.mylang")).unwrap(),
+        "let _x = print(2);\nlet _f a -> print(a);\n"
+    );
+    let _ = fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn check_reports_nothing_a_fresh_baseline_recorded() {
+    let directory = project("baseline");
+    fs::write(
+        directory.join("mylang-lsp.toml"),
+        "[lints]\nunused-variable = false\n",
+    )
+    .unwrap();
+    fs::write(
+        directory.join("my file ü.mylang"),
+        "let unused = 1;\nprint(missing);\n",
+    )
+    .unwrap();
+
+    let output = run(&directory, &["baseline"]);
+    assert!(output.status.success());
+    // The disabled lint is not recorded, as `check` would not report it.
+    let recorded = fs::read_to_string(directory.join("mylang-baseline.json")).unwrap();
+    assert!(recorded.contains("unknown-identifier"), "{recorded}");
+    assert!(!recorded.contains("unused-variable"), "{recorded}");
+
+    let output = run(&directory, &["check"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+    let _ = fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn the_server_exits_with_zero_only_after_shutdown() {
+    let exit_code = |messages: &[&str]| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_mylang-lsp"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+        let mut input = child.stdin.take().unwrap();
+        for message in messages {
+            write!(
+                input,
+                "Content-Length: {}\r\n\r\n{}",
+                message.len(),
+                message
+            )
+            .unwrap();
+        }
+        // Kept open, so only `exit` can stop the server.
+        let status = child.wait().unwrap();
+        drop(input);
+        status.code()
+    };
+    let shutdown = r#"{"jsonrpc": "2.0", "id": 1, "method": "shutdown"}"#;
+    let exit = r#"{"jsonrpc": "2.0", "method": "exit"}"#;
+    assert_eq!(exit_code(&[shutdown, exit]), Some(0));
+    assert_eq!(exit_code(&[exit]), Some(1));
+}
@@ -0,0 +1,123 @@
+//! Replays the transcripts in `tests/conformance` against the server.
+//!
+//! A transcript is JSON Lines, one step per line; blank lines and lines
+//! starting with `#` are skipped. The steps are:
+//!
+//! - `{"send": message}` sends a message with a Content-Length header.
+//! - `{"send_raw": "text"}` sends the text as is, for framing cases.
+//! - `{"send_body": [byte, ...]}` sends the bytes with a Content-Length
+//!   header, for bodies that are not UTF-8.
+//! - `{"expect": pattern}` takes the next message from the server, which
+//!   must match the pattern. Objects match when every key in the pattern
+//!   matches, so only the fields a transcript cares about need writing.
+//!   Arrays match element by element and must have the same length; other
+//!   values must be equal.
+//! - `{"expect_exit": true}` waits for the server to exit without its input
+//!   being closed, as it must when it cannot read the input any further.
+//!
+//! Every wait gives up after a few seconds, so a server that hangs fails the
+//! transcript instead of the run.
+//!
+//! Once the steps have run the server's input is closed, and anything it
+//! sent that no step expected fails the transcript.
+
+mod support;
+
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+use serde_json::Value;
+use support::Client;
+
+#[test]
+fn conformance() {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance");
+    let mut paths: Vec<_> = fs::read_dir(&directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "jsonl")
+        })
+        .collect();
+    paths.sort();
+    assert!(
+        !paths.is_empty(),
+        "no transcripts in {}",
+        directory.display()
+    );
+
+    let mut failures = Vec::new();
+    for path in &paths {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let text = fs::read_to_string(path).unwrap();
+        if let Err(cause) = panic::catch_unwind(AssertUnwindSafe(|| replay(&text))) {
+            let message = cause
+                .downcast_ref::<String>()
+                .cloned()
+                .or_else(|| cause.downcast_ref::<&str>().map(|s| s.to_string()))
+                .unwrap_or_default();
+            failures.push(format!("{name}: {message}"));
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+fn replay(text: &str) {
+    let mut client = Client::start();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let step: Value = serde_json::from_str(line)
+            .unwrap_or_else(|e| panic!("line {}: invalid step: {e}", number + 1));
+        if let Some(message) = step.get("send") {
+            client.send(message.clone());
+        } else if let Some(bytes) = step.get("send_raw").and_then(Value::as_str) {
+            client.send_raw(bytes);
+        } else if let Some(body) = step.get("send_body").and_then(Value::as_array) {
+            let body: Vec<u8> = body
+                .iter()
+                .map(|byte| {
+                    byte.as_u64()
+                        .and_then(|byte| u8::try_from(byte).ok())
+                        .unwrap_or_else(|| panic!("line {}: {byte} is not a byte", number + 1))
+                })
+                .collect();
+            client.send_bytes(format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes());
+            client.send_bytes(&body);
+        } else if step.get("expect_exit").is_some() {
+            client.expect_exit();
+        } else if let Some(pattern) = step.get("expect") {
+            let message = client.next_message();
+            assert!(
+                matches(pattern, &message),
+                "line {}: expected {pattern}, got {message}",
+                number + 1
+            );
+        } else {
+            panic!("line {}: unknown step {step}", number + 1);
+        }
+    }
+
+    let unexpected = client.shutdown();
+    assert!(
+        unexpected.is_empty(),
+        "unexpected messages: {}",
+        Value::Array(unexpected)
+    );
+}
+
+fn matches(pattern: &Value, actual: &Value) -> bool {
+    match (pattern, actual) {
+        (Value::Object(pattern), Value::Object(actual)) => pattern
+            .iter()
+            .all(|(key, value)| actual.get(key).is_some_and(|actual| matches(value, actual))),
+        (Value::Array(pattern), Value::Array(actual)) => {
+            pattern.len() == actual.len() && pattern.iter().zip(actual).all(|(p, a)| matches(p, a))
+        }
+        _ => pattern == actual,
+    }
+}
@@ -0,0 +1,113 @@
+//! Scripted sessions with the debug adapter, `mylang-lsp --dap`.
+
+mod support;
+
+use std::fs;
+use std::path::PathBuf;
+
+use serde_json::{Value, json};
+use support::Client;
+
+const PROGRAM: &str = "\
+let double x -> x * 2;
+let a = double(1);
+
+let b = double(a);
+print(b);
+";
+
+fn program(name: &str) -> PathBuf {
+    let directory =
+        std::env::temp_dir().join(format!("mylang-lsp-dap-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    let path = directory.join("main.mylang");
+    fs::write(&path, PROGRAM).unwrap();
+    path
+}
+
+/// Sends a request and returns its response, which must have succeeded.
+fn request(client: &mut Client, seq: u64, command: &str, arguments: Value) -> Value {
+    client.send(json!({
+        "seq": seq,
+        "type": "request",
+        "command": command,
+        "arguments": arguments
+    }));
+    let response = client.expect(command, |message| {
+        message["type"] == "response" && message["request_seq"] == seq
+    });
+    assert_eq!(response["success"], true, "{response}");
+    response["body"].clone()
+}
+
+/// The line of the innermost frame of the stopped program.
+fn stopped_line(client: &mut Client, seq: u64) -> Value {
+    let trace = request(client, seq, "stackTrace", json!({ "threadId": 1 }));
+    trace["stackFrames"][0]["line"].clone()
+}
+
+#[test]
+fn breakpoints_stop_only_where_statements_start() {
+    let path = program("breakpoints");
+    let mut client = Client::debug_adapter();
+    request(
+        &mut client,
+        1,
+        "initialize",
+        json!({ "adapterID": "mylang" }),
+    );
+    client.expect("the initialized event", |message| {
+        message["event"] == "initialized"
+    });
+    request(&mut client, 2, "launch", json!({ "program": path }));
+
+    let body = request(
+        &mut client,
+        3,
+        "setBreakpoints",
+        json!({
+            "source": { "path": path },
+            "breakpoints": [{ "line": 3 }, { "line": 2 }, { "line": 99 }]
+        }),
+    );
+    let breakpoints = body["breakpoints"].as_array().unwrap();
+    // The blank line moves to the statement after it.
+    assert_eq!(breakpoints[0]["verified"], true);
+    assert_eq!(breakpoints[0]["line"], 4);
+    assert_eq!(breakpoints[1]["verified"], true);
+    assert_eq!(breakpoints[1]["line"], 2);
+    assert_eq!(breakpoints[2]["verified"], false);
+    assert!(breakpoints[2]["message"].is_string());
+
+    request(&mut client, 4, "configurationDone", json!({}));
+    let stopped = client.expect("a stop", |message| message["event"] == "stopped");
+    assert_eq!(stopped["body"]["reason"], "breakpoint");
+    assert_eq!(stopped_line(&mut client, 5), 2);
+
+    // Each step is answered before the program stops again.
+    for (seq, command, line) in [(6, "next", 4), (8, "stepIn", 1)] {
+        client.send(json!({
+            "seq": seq,
+            "type": "request",
+            "command": command,
+            "arguments": { "threadId": 1 }
+        }));
+        let response = client.next_message();
+        assert_eq!(response["request_seq"], seq, "{response}");
+        let stopped = client.next_message();
+        assert_eq!(stopped["event"], "stopped", "{stopped}");
+        assert_eq!(stopped["body"]["reason"], "step");
+        assert_eq!(stopped_line(&mut client, seq + 1), line);
+    }
+
+    request(&mut client, 10, "continue", json!({ "threadId": 1 }));
+    let output = client.expect("the output", |message| message["event"] == "output");
+    assert_eq!(output["body"]["output"], "4\n");
+    let exited = client.expect("the exit", |message| message["event"] == "exited");
+    assert_eq!(exited["body"]["exitCode"], 0);
+    client.expect("the end", |message| message["event"] == "terminated");
+    request(&mut client, 11, "disconnect", json!({}));
+    client.shutdown();
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
@@ -0,0 +1,234 @@
+//! The editor features that read a document without changing it, and the
+//! refactorings, called directly on a document store.
+
+mod support;
+
+use mylang_lsp::analysis::documents::{Document, DocumentStore};
+use mylang_lsp::analysis::lexer::Span;
+use mylang_lsp::analysis::semantic_tokens::{self, SemanticTokensCache, TOKEN_TYPES};
+use mylang_lsp::analysis::{completion, folding, refactor, selection};
+use serde_json::{Value, json};
+use support::apply;
+
+const URI: &str = "file:///main.mylang";
+
+const SOURCE: &str = "\
+// region setup
+let double x -> {
+    x * 2
+};
+// endregion
+let xs = [1, 2]
+    |> map(double);
+print(xs);
+";
+
+fn documents() -> DocumentStore {
+    let mut documents = DocumentStore::default();
+    documents.open(URI.to_string(), 1, SOURCE);
+    documents
+}
+
+fn params(extra: Value) -> Value {
+    let mut params = json!({ "textDocument": { "uri": URI } });
+    params
+        .as_object_mut()
+        .unwrap()
+        .extend(extra.as_object().unwrap().clone());
+    params
+}
+
+/// The semantic tokens as (line, character, length, type, modifiers).
+fn decode(data: &Value) -> Vec<(u64, u64, u64, &'static str, u64)> {
+    let data: Vec<u64> = serde_json::from_value(data.clone()).unwrap();
+    let (mut line, mut character) = (0, 0);
+    data.chunks(5)
+        .map(|token| {
+            if token[0] > 0 {
+                character = 0;
+            }
+            line += token[0];
+            character += token[1];
+            (
+                line,
+                character,
+                token[2],
+                TOKEN_TYPES[token[3] as usize],
+                token[4],
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn semantic_tokens_tell_declarations_and_builtins_apart() {
+    let mut cache = SemanticTokensCache::default();
+    let result = semantic_tokens::full(
+        &documents(),
+        &mut cache,
+        serde_json::from_value(params(json!({}))).unwrap(),
+    );
+    let tokens = decode(&result["data"]);
+    // `double` is declared, `x` is a parameter, and `map` is a builtin.
+    assert!(tokens.contains(&(1, 4, 6, "function", 0b1)), "{tokens:?}");
+    assert!(
+        tokens.contains(&(1, 11, 1, "parameter", 0b11)),
+        "{tokens:?}"
+    );
+    assert!(
+        tokens.contains(&(6, 7, 3, "function", 0b1000)),
+        "{tokens:?}"
+    );
+    assert!(tokens.contains(&(0, 0, 15, "comment", 0)), "{tokens:?}");
+}
+
+#[test]
+fn folding_covers_blocks_pipelines_and_regions() {
+    let ranges = folding::folding_ranges(
+        &documents(),
+        serde_json::from_value(params(json!({}))).unwrap(),
+    );
+    assert_eq!(
+        ranges,
+        json!([
+            { "startLine": 1, "endLine": 2 },
+            { "startLine": 5, "endLine": 6 },
+            { "startLine": 0, "endLine": 4, "kind": "region" }
+        ])
+    );
+}
+
+#[test]
+fn selection_grows_from_the_token_to_the_file() {
+    let result = selection::selection_ranges(
+        &documents(),
+        serde_json::from_value(params(json!({
+            "positions": [{ "line": 2, "character": 4 }]
+        })))
+        .unwrap(),
+    );
+    let mut ranges = Vec::new();
+    let mut range = &result[0];
+    while !range.is_null() {
+        let (start, end) = (&range["range"]["start"], &range["range"]["end"]);
+        ranges.push([
+            start["line"].as_u64().unwrap(),
+            start["character"].as_u64().unwrap(),
+            end["line"].as_u64().unwrap(),
+            end["character"].as_u64().unwrap(),
+        ]);
+        range = &range["parent"];
+    }
+    // `x`, `x * 2`, the block, the statement, the file.
+    assert_eq!(
+        ranges,
+        [
+            [2, 4, 2, 5],
+            [2, 4, 2, 9],
+            [1, 16, 3, 1],
+            [1, 0, 3, 2],
+            [0, 0, 8, 0]
+        ]
+    );
+}
+
+#[test]
+fn completion_offers_visible_names_then_keywords_then_builtins() {
+    let result = completion::completion(
+        &documents(),
+        serde_json::from_value(params(json!({
+            "position": { "line": 7, "character": 6 }
+        })))
+        .unwrap(),
+    );
+    let labels: Vec<&str> = result["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["label"].as_str().unwrap())
+        .collect();
+    assert_eq!(labels[..6], ["double", "xs", "false", "fn", "if", "true"]);
+    assert!(labels.contains(&"map"));
+}
+
+#[test]
+fn completion_after_a_pipe_offers_only_functions() {
+    let result = completion::completion(
+        &documents(),
+        serde_json::from_value(params(json!({
+            "position": { "line": 6, "character": 7 },
+            "context": { "triggerCharacter": ">" }
+        })))
+        .unwrap(),
+    );
+    let items = result["items"].as_array().unwrap();
+    assert!(items.iter().all(|item| item["kind"] == 3), "{result}");
+    assert!(items.iter().any(|item| item["label"] == "double"));
+    assert!(!items.iter().any(|item| item["label"] == "xs"));
+}
+
+/// The text after applying the edit of a refactoring's code action.
+fn refactored(text: &str, action: Option<Value>) -> String {
+    let action = action.expect("the refactoring is offered");
+    apply(text, &action["edit"]["changes"][URI])
+}
+
+fn document(text: &str) -> Document {
+    Document::new(URI.to_string(), 1, text)
+}
+
+#[test]
+fn extract_variable_binds_the_selection_before_its_statement() {
+    let text = "let y = print(1 + 2);\n";
+    assert_eq!(
+        refactored(
+            text,
+            refactor::extract_variable(&document(text), Span::new(14, 19))
+        ),
+        "let extracted = 1 + 2;\nlet y = print(extracted);\n"
+    );
+}
+
+#[test]
+fn extract_function_passes_the_names_the_statements_use() {
+    let text = "let f a -> {\n    let b = a + 1;\n    print(b);\n    b\n};\n";
+    let start = text.find("let b").unwrap();
+    let end = text.find("print(b);").unwrap() + "print(b);".len();
+    assert_eq!(
+        refactored(
+            text,
+            refactor::extract_function(&document(text), Span::new(start, end))
+        ),
+        "let extracted a -> {\n    let b = a + 1;\n    print(b);\n    b\n};\n\n\
+         let f a -> {\n    let b = extracted(a);\n    b\n};\n"
+    );
+}
+
+#[test]
+fn inline_variable_replaces_each_use_with_the_value() {
+    let text = "let a = 1;\nprint(a + a);\n";
+    assert_eq!(
+        refactored(
+            text,
+            refactor::inline_variable(&document(text), Span::new(4, 5))
+        ),
+        "print(1 + 1);\n"
+    );
+}
+
+#[test]
+fn nested_calls_and_pipelines_convert_both_ways() {
+    let text = "print(str(1));\n";
+    let piped = refactored(
+        text,
+        refactor::calls_to_pipeline(&document(text), Span::new(0, 13)),
+    );
+    assert_eq!(piped, "1 |> str |> print;\n");
+    assert_eq!(
+        refactored(
+            &piped,
+            refactor::pipeline_to_calls(&document(&piped), Span::new(0, 17))
+        ),
+        text
+    );
+}
@@ -0,0 +1,129 @@
+//! The formatter: whole documents, ranges, and statements as they are
+//! typed.
+
+mod support;
+
+use mylang_lsp::analysis::config::ServerConfig;
+use mylang_lsp::analysis::documents::DocumentStore;
+use mylang_lsp::analysis::format::{
+    FormattingOptions, Style, format, on_type_formatting, range_formatting,
+};
+use serde_json::json;
+use support::apply;
+
+const URI: &str = "file:///main.mylang";
+
+/// Inputs and how the canonical style lays them out.
+const GOLDEN: &[(&str, &str)] = &[
+    ("let   x=1+2 ;print( x ) ;", "let x = 1 + 2;\nprint(x);\n"),
+    (
+        "let f a -> {\nlet b = a*2;\n\n\n\nb}\n\n\n",
+        "let f a -> {\n    let b = a * 2;\n\n    b\n}\n",
+    ),
+    (
+        "if x {print(1);} else {print(2);}",
+        "if x {\n    print(1);\n} else {\n    print(2);\n}\n",
+    ),
+    ("let z = -1 - -2;", "let z = -1 - -2;\n"),
+    (
+        "// leading\nlet x = 1; // trailing\n  // own line\nprint(x);",
+        "// leading\nlet x = 1; // trailing\n// own line\nprint(x);\n",
+    ),
+    // Syntax errors are laid out as far as the tokens go.
+    (
+        "let x = (1 + ;\nprint( x );",
+        "let x = (1 +;\n    print(x);\n",
+    ),
+];
+
+fn style() -> Style {
+    Style::new(&ServerConfig::default(), FormattingOptions::default())
+}
+
+fn documents(text: &str) -> DocumentStore {
+    let mut documents = DocumentStore::default();
+    documents.open(URI.to_string(), 1, text);
+    documents
+}
+
+#[test]
+fn documents_format_to_the_golden_output() {
+    for (input, expected) in GOLDEN {
+        assert_eq!(
+            format(input, &style()).as_deref(),
+            Some(*expected),
+            "{input:?}"
+        );
+    }
+}
+
+#[test]
+fn formatting_twice_changes_nothing_more() {
+    for (input, _) in GOLDEN {
+        let once = format(input, &style()).unwrap();
+        assert_eq!(format(&once, &style()).unwrap(), once, "{input:?}");
+    }
+}
+
+#[test]
+fn text_the_lexer_rejects_is_left_alone() {
+    assert_eq!(format("let x = 1 # 2;", &style()), None);
+}
+
+#[test]
+fn long_or_broken_pipelines_put_each_stage_on_its_own_line() {
+    assert_eq!(
+        format(
+            "let y = [1,2,3] |> map(fn x -> x*2) |> filter(fn x -> x > 2) |> sum;",
+            &style()
+        )
+        .unwrap(),
+        "let y = [1, 2, 3]\n        |> map(fn x -> x * 2)\n        |> filter(fn x -> x > 2)\n        |> sum;\n"
+    );
+    assert_eq!(
+        format("let y = 1 |> str;", &style()).unwrap(),
+        "let y = 1 |> str;\n"
+    );
+    assert_eq!(
+        format("let y = 1\n|> str;", &style()).unwrap(),
+        "let y = 1\n        |> str;\n"
+    );
+}
+
+#[test]
+fn range_formatting_only_touches_the_selected_statements() {
+    let text = "let a=1;\nlet b=2;\nlet c=3;\n";
+    let edits = range_formatting(
+        &documents(text),
+        &ServerConfig::default(),
+        serde_json::from_value(json!({
+            "textDocument": { "uri": URI },
+            "range": {
+                "start": { "line": 1, "character": 0 },
+                "end": { "line": 1, "character": 8 }
+            }
+        }))
+        .unwrap(),
+    );
+    assert_eq!(apply(text, &edits), "let a=1;\nlet b = 2;\nlet c=3;\n");
+}
+
+#[test]
+fn typing_a_semicolon_formats_the_statement_it_ends() {
+    let text = "let a=1;\nprint( a+1 );\n";
+    let on_type = |character: u32, ch: &str| {
+        on_type_formatting(
+            &documents(text),
+            &ServerConfig::default(),
+            serde_json::from_value(json!({
+                "textDocument": { "uri": URI },
+                "position": { "line": 1, "character": character },
+                "ch": ch
+            }))
+            .unwrap(),
+        )
+    };
+    assert_eq!(apply(text, &on_type(13, ";")), "let a=1;\nprint(a + 1);\n");
+    // A `}` that was not typed as a token completes nothing.
+    assert_eq!(on_type(13, "}"), json!([]));
+}
@@ -0,0 +1,695 @@
+mod support;
+
+use std::fs;
+use std::path::PathBuf;
+
+use serde_json::{Value, json};
+use support::Client;
+
+const URI: &str = "file:///main.mylang";
+
+#[test]
+fn initialize_advertises_capabilities() {
+    let mut client = Client::start();
+    let result = client.request("initialize", json!({ "capabilities": {} }));
+    assert!(
+        result["capabilities"]["hoverProvider"]
+            .as_bool()
+            .unwrap_or(false)
+    );
+}
+
+#[test]
+fn did_open_publishes_diagnostics() {
+    let mut client = Client::initialized(json!({}));
+    client.open(URI, "let x = 1;\nprint(x + y);\n");
+    let diagnostics = client.diagnostics(URI);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["code"], "unknown-identifier");
+    assert_eq!(
+        diagnostics[0]["range"]["start"],
+        json!({ "line": 1, "character": 10 })
+    );
+}
+
+#[test]
+fn did_change_republishes_diagnostics() {
+    let mut client = Client::initialized(json!({}));
+    client.open(URI, "print(y);\n");
+    assert_eq!(client.diagnostics(URI).len(), 1);
+
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": URI, "version": 2 },
+            "contentChanges": [{ "text": "let y = 1;\nprint(y);\n" }]
+        }),
+    );
+    assert!(client.diagnostics(URI).is_empty());
+}
+
+#[test]
+fn hover_describes_the_symbol() {
+    let mut client = Client::initialized(json!({}));
+    client.open(URI, "let answer = 42;\nprint(answer);\n");
+    client.diagnostics(URI);
+    let hover = client.request(
+        "textDocument/hover",
+        json!({
+            "textDocument": { "uri": URI },
+            "position": { "line": 1, "character": 8 }
+        }),
+    );
+    assert!(hover["contents"].to_string().contains("answer"));
+}
+
+#[test]
+fn server_exits_when_input_ends() {
+    let client = Client::initialized(json!({}));
+    client.shutdown();
+}
+
+/// A fresh directory with a `project` folder inside, for tests that need
+/// files on disk.
+fn workspace(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("mylang-lsp-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(directory.join("project")).unwrap();
+    directory
+}
+
+fn initialize_in(root: &std::path::Path, options: Value) -> Client {
+    let mut client = Client::start();
+    client.request(
+        "initialize",
+        json!({
+            "capabilities": {},
+            "rootUri": format!("file://{}", root.display()),
+            "initializationOptions": options
+        }),
+    );
+    client.notify("initialized", json!({}));
+    client
+}
+
+#[test]
+fn workspace_config_is_found_above_the_root() {
+    let directory = workspace("config");
+    fs::write(
+        directory.join("mylang-lsp.toml"),
+        r#"
+builtins = ["host"]
+exclude = ["project/generated/**"]
+
+[lints]
+unused-variable = false
+
+[severities]
+unknown-identifier = "warning"
+"#,
+    )
+    .unwrap();
+    let project = directory.join("project");
+    let mut client = initialize_in(&project, Value::Null);
+
+    let uri = format!("file://{}/main.mylang", project.display());
+    client.open(&uri, "let unused = 1;\nprint(host + missing);\n");
+    let diagnostics = client.diagnostics(&uri);
+    assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+    assert_eq!(diagnostics[0]["code"], "unknown-identifier");
+    assert_eq!(diagnostics[0]["severity"], 2);
+
+    let generated = format!("file://{}/generated/out.mylang", project.display());
+    client.open(&generated, "print(missing);\n");
+    assert!(client.diagnostics(&generated).is_empty());
+    let _ = fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn invalid_workspace_config_is_shown_as_a_warning() {
+    let directory = workspace("invalid");
+    fs::write(directory.join("mylang-lsp.toml"), "exclude = [\n").unwrap();
+    let mut client = initialize_in(&directory.join("project"), Value::Null);
+
+    let message = client.notification("window/showMessage");
+    assert_eq!(message["type"], 2);
+    assert!(
+        message["message"]
+            .as_str()
+            .unwrap()
+            .contains("mylang-lsp.toml")
+    );
+    let _ = fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn invalid_project_json_is_shown_as_a_warning() {
+    let directory = workspace("invalid-json");
+    fs::write(directory.join("project/mylang.json"), "{ \"profile\": 1 }").unwrap();
+    let mut client = initialize_in(&directory.join("project"), Value::Null);
+
+    let message = client.notification("window/showMessage");
+    assert_eq!(message["type"], 2);
+    assert!(message["message"].as_str().unwrap().contains("mylang.json"));
+    let _ = fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn client_settings_override_the_workspace_config() {
+    let directory = workspace("layers");
+    fs::write(
+        directory.join("mylang-lsp.toml"),
+        "[severities]\nunknown-identifier = \"warning\"\nunused-variable = \"error\"\n",
+    )
+    .unwrap();
+    let project = directory.join("project");
+    let mut client = initialize_in(
+        &project,
+        json!({ "severities": { "unknown-identifier": "information" } }),
+    );
+
+    let uri = format!("file://{}/main.mylang", project.display());
+    client.open(&uri, "let unused = 1;\nprint(missing);\n");
+    let severities = |diagnostics: Vec<Value>| -> Vec<Value> {
+        diagnostics.iter().map(|d| d["severity"].clone()).collect()
+    };
+    assert_eq!(severities(client.diagnostics(&uri)), [json!(3), json!(1)]);
+
+    let push = |client: &mut Client, settings: Value| {
+        client.notify(
+            "workspace/didChangeConfiguration",
+            json!({ "settings": { "mylang": settings } }),
+        );
+    };
+    push(
+        &mut client,
+        json!({ "severities": { "unknown-identifier": "hint" } }),
+    );
+    assert_eq!(severities(client.diagnostics(&uri)), [json!(4), json!(1)]);
+
+    // A later push keeps what earlier ones set.
+    push(
+        &mut client,
+        json!({ "lints": { "unused-variable": false } }),
+    );
+    assert_eq!(severities(client.diagnostics(&uri)), [json!(4)]);
+    let _ = fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn configured_globals_are_known_and_checked() {
+    let directory = workspace("globals");
+    fs::write(
+        directory.join("mylang-lsp.toml"),
+        "builtins = [\"host\", \"fetch(url: string) -> string\"]\n",
+    )
+    .unwrap();
+    let project = directory.join("project");
+    let mut client = initialize_in(&project, Value::Null);
+
+    let uri = format!("file://{}/main.mylang", project.display());
+    client.open(&uri, "print(host);\nprint(fetch(\"a\", 2));\n");
+    let diagnostics = client.diagnostics(&uri);
+    assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+    assert_eq!(diagnostics[0]["code"], "wrong-argument-count");
+    assert_eq!(diagnostics[0]["range"]["start"]["line"], 1);
+
+    let hover = client.request(
+        "textDocument/hover",
+        json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": 1, "character": 7 }
+        }),
+    );
+    assert!(
+        hover["contents"]
+            .to_string()
+            .contains("fetch(url: string) -> string"),
+        "{hover}"
+    );
+    let _ = fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn environment_variables_configure_the_server() {
+    let directory = workspace("environment");
+    let user_config = directory.join("user.toml");
+    fs::write(
+        &user_config,
+        "[severities]\nunknown-identifier = \"hint\"\n",
+    )
+    .unwrap();
+    let mut client = Client::spawn(&[
+        ("MYLANG_LSP_CONFIG", user_config.to_str().unwrap()),
+        ("MYLANG_LSP_FEATURES", "-semanticTokens,-inlayHint"),
+        ("MYLANG_LSP_PROFILE", "lenient"),
+    ]);
+
+    let result = client.request("initialize", json!({ "capabilities": {} }));
+    let capabilities = &result["capabilities"];
+    assert!(capabilities.get("semanticTokensProvider").is_none());
+    assert!(capabilities.get("inlayHintProvider").is_none());
+    assert_eq!(capabilities["hoverProvider"], true);
+    let message = client.notification("window/showMessage");
+    assert!(
+        message["message"]
+            .as_str()
+            .unwrap()
+            .contains("MYLANG_LSP_PROFILE")
+    );
+    client.notify("initialized", json!({}));
+
+    client.open(URI, "print(missing);\n");
+    let diagnostics = client.diagnostics(URI);
+    assert_eq!(diagnostics[0]["severity"], 4);
+    client.shutdown();
+    let _ = fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn a_burst_of_changes_ends_with_the_latest_text() {
+    let mut client = Client::initialized(json!({}));
+    let other = "file:///other.mylang";
+    client.open(URI, "print(a);\n");
+    client.open(other, "print(b);\n");
+    assert_eq!(client.diagnostics(URI).len(), 1);
+    assert_eq!(client.diagnostics(other).len(), 1);
+
+    let change = |client: &mut Client, uri: &str, version: u32, text: &str| {
+        client.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": uri, "version": version },
+                "contentChanges": [{ "text": text }]
+            }),
+        );
+    };
+    for version in 2..20 {
+        change(
+            &mut client,
+            URI,
+            version,
+            &"print(a);\n".repeat(version as usize),
+        );
+    }
+    change(&mut client, other, 2, "let b = 1;\nprint(b);\n");
+    change(&mut client, URI, 20, "let a = 1;\nprint(a);\n");
+
+    assert!(client.diagnostics(URI).is_empty());
+    assert!(client.diagnostics(other).is_empty());
+}
+
+#[test]
+fn requests_are_answered_while_the_workspace_is_indexed() {
+    let directory = workspace("indexing");
+    let project = directory.join("project");
+    for n in 0..200 {
+        fs::write(
+            project.join(format!("file{n}.mylang")),
+            format!("let value{n} = {n};\n").repeat(50),
+        )
+        .unwrap();
+    }
+    let mut client = initialize_in(&project, Value::Null);
+    client.open(URI, "let answer = 42;\nprint(answer);\n");
+    let hover = client.request(
+        "textDocument/hover",
+        json!({
+            "textDocument": { "uri": URI },
+            "position": { "line": 1, "character": 8 }
+        }),
+    );
+    assert!(hover["contents"].to_string().contains("answer"));
+
+    // The index fills in between requests.
+    let deadline = std::time::Instant::now() + support::TIMEOUT;
+    loop {
+        let symbols = client.request("workspace/symbol", json!({ "query": "value199" }));
+        if symbols
+            .as_array()
+            .is_some_and(|symbols| !symbols.is_empty())
+        {
+            break;
+        }
+        assert!(
+            std::time::Instant::now() < deadline,
+            "file199 was never indexed"
+        );
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    let _ = fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn a_cancelled_request_stops_and_answers_request_cancelled() {
+    let mut client = Client::initialized(json!({}));
+    let text: String = (0..5_000).map(|n| format!("let x{n} = {n};\n")).collect();
+    client.open(URI, &text);
+    let hover = json!({
+        "textDocument": { "uri": URI },
+        "position": { "line": 0, "character": 4 }
+    });
+    client.send(
+        json!({ "jsonrpc": "2.0", "id": 100, "method": "textDocument/hover", "params": hover }),
+    );
+    client.notify("$/cancelRequest", json!({ "id": 100 }));
+
+    let response = client.expect("a response to the hover", |message| message["id"] == 100);
+    assert_eq!(response["error"]["code"], -32800);
+
+    // Nothing half-done was kept.
+    let result = client.request("textDocument/hover", hover);
+    assert!(result["contents"].to_string().contains("x0"));
+}
+
+#[test]
+fn a_request_on_an_edited_document_can_be_cancelled() {
+    let mut client = Client::initialized(json!({}));
+    let text = |prefix: &str| -> String {
+        (0..5_000)
+            .map(|n| format!("let {prefix}{n} = {n};\n"))
+            .collect()
+    };
+    client.open(URI, &text("x"));
+    client.diagnostics(URI);
+    // The new text is not analyzed until a request or the debounce needs
+    // it, so the hover has to run the whole analysis.
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": URI, "version": 2 },
+            "contentChanges": [{ "text": text("y") }]
+        }),
+    );
+    let hover = json!({
+        "textDocument": { "uri": URI },
+        "position": { "line": 0, "character": 4 }
+    });
+    client.send(
+        json!({ "jsonrpc": "2.0", "id": 100, "method": "textDocument/hover", "params": hover }),
+    );
+    client.notify("$/cancelRequest", json!({ "id": 100 }));
+
+    let response = client.expect("a response to the hover", |message| message["id"] == 100);
+    assert_eq!(response["error"]["code"], -32800);
+
+    let result = client.request("textDocument/hover", hover);
+    assert!(result["contents"].to_string().contains("y0"));
+}
+
+#[test]
+fn profiling_logs_where_the_time_of_each_request_went() {
+    let mut client = Client::start();
+    client.request(
+        "initialize",
+        json!({ "capabilities": {}, "initializationOptions": { "profiling": true } }),
+    );
+    client.notify("initialized", json!({}));
+    client.open(URI, "let answer = 42;\n");
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": URI, "version": 2 },
+            "contentChanges": [{ "text": "let answer = 42;\nprint(answer);\n" }]
+        }),
+    );
+    client.request(
+        "textDocument/hover",
+        json!({
+            "textDocument": { "uri": URI },
+            "position": { "line": 1, "character": 8 }
+        }),
+    );
+
+    let log = client.expect("the hover's timings", |message| {
+        message["method"] == "window/logMessage"
+            && message["params"]["message"]
+                .as_str()
+                .is_some_and(|message| message.starts_with("textDocument/hover 2: "))
+    });
+    let message = log["params"]["message"].as_str().unwrap();
+    for stage in ["lex", "parse", "resolve", "handler", "serialize"] {
+        assert!(message.contains(stage), "{message}");
+    }
+}
+
+#[test]
+fn a_closed_document_is_no_longer_checked() {
+    let mut client = Client::initialized(json!({}));
+    let closed = "file:///closed.mylang";
+    client.open(closed, "print(missing);\n");
+    client.open(URI, "let answer = 42;\nprint(answer);\n");
+    assert_eq!(client.diagnostics(closed).len(), 1);
+    client.diagnostics(URI);
+
+    client.notify(
+        "textDocument/didClose",
+        json!({ "textDocument": { "uri": closed } }),
+    );
+    client.notify(
+        "textDocument/didSave",
+        json!({ "textDocument": { "uri": URI } }),
+    );
+
+    let stats = client.request("mylang/stats", json!({}));
+    let uris: Vec<&Value> = stats
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|document| &document["uri"])
+        .collect();
+    assert_eq!(uris, [URI]);
+    let unexpected = client.shutdown();
+    assert!(
+        !unexpected
+            .iter()
+            .any(|message| message["params"]["uri"] == closed),
+        "{unexpected:?}"
+    );
+}
+
+#[test]
+fn pulled_diagnostics_change_with_the_settings() {
+    let mut client = Client::initialized(json!({}));
+    client.open(URI, "let unused = 1;\n");
+    client.diagnostics(URI);
+    let pull = |client: &mut Client, previous: &Value| {
+        client.request(
+            "textDocument/diagnostic",
+            json!({ "textDocument": { "uri": URI }, "previousResultId": previous }),
+        )
+    };
+    let first = pull(&mut client, &Value::Null);
+    assert_eq!(first["kind"], "full");
+    assert_eq!(pull(&mut client, &first["resultId"])["kind"], "unchanged");
+
+    client.notify(
+        "workspace/didChangeConfiguration",
+        json!({ "settings": { "mylang": { "lints": { "unused-variable": false } } } }),
+    );
+    let second = pull(&mut client, &first["resultId"]);
+    assert_eq!(second["kind"], "full");
+    assert_eq!(second["items"], json!([]));
+}
+
+#[test]
+fn workspace_diagnostics_cover_files_that_are_not_open() {
+    let directory = workspace("pull");
+    let project = directory.join("project");
+    fs::write(project.join("closed.mylang"), "print(missing);\n").unwrap();
+    let mut client = initialize_in(&project, Value::Null);
+    let open = format!("file://{}/open.mylang", project.display());
+    client.open(&open, "print(1);\n");
+
+    let closed = format!("file://{}/closed.mylang", project.display());
+    let deadline = std::time::Instant::now() + support::TIMEOUT;
+    let items = loop {
+        let result = client.request("workspace/diagnostic", json!({ "previousResultIds": [] }));
+        let items = result["items"].as_array().unwrap().clone();
+        if items.iter().any(|item| item["uri"] == closed) {
+            break items;
+        }
+        assert!(
+            std::time::Instant::now() < deadline,
+            "closed.mylang was never reported"
+        );
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    };
+    let report = |uri: &str| items.iter().find(|item| item["uri"] == uri).unwrap();
+    assert_eq!(report(&open)["version"], 1);
+    assert_eq!(report(&open)["items"], json!([]));
+    assert_eq!(report(&closed)["version"], Value::Null);
+    assert_eq!(report(&closed)["items"].as_array().unwrap().len(), 1);
+    let _ = fs::remove_dir_all(&directory);
+}
+
+/// A client that shows progress, with ten open documents whose latest text
+/// is not analyzed yet, and a save that starts re-checking them.
+fn recheck_started() -> (Client, Value) {
+    let mut client = Client::start();
+    client.request(
+        "initialize",
+        json!({
+            "capabilities": { "window": { "workDoneProgress": true } },
+            "initializationOptions": { "debounceMs": 60_000 }
+        }),
+    );
+    client.notify("initialized", json!({}));
+    for n in 0..10 {
+        let uri = format!("file:///dependent{n}.mylang");
+        let text = |prefix: &str| -> String {
+            (0..300)
+                .map(|n| format!("let {prefix}{n} = {n};\n"))
+                .collect()
+        };
+        client.open(&uri, &text("x"));
+        client.diagnostics(&uri);
+        client.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": uri, "version": 2 },
+                "contentChanges": [{ "text": text("y") }]
+            }),
+        );
+    }
+    client.open(URI, "print(1);\n");
+    client.notify(
+        "textDocument/didSave",
+        json!({ "textDocument": { "uri": URI } }),
+    );
+    let create = client.expect("a progress token", |message| {
+        message["method"] == "window/workDoneProgress/create"
+    });
+    (client, create)
+}
+
+#[test]
+fn recheck_progress_begins_once_the_client_accepts_the_token() {
+    let (mut client, create) = recheck_started();
+    let token = create["params"]["token"].clone();
+    assert!(
+        !client
+            .received()
+            .iter()
+            .any(|message| message["method"] == "$/progress"),
+        "progress began before the token was accepted"
+    );
+    client.send(json!({ "jsonrpc": "2.0", "id": create["id"], "result": null }));
+
+    let progress = |message: &Value, kind: &str| {
+        message["method"] == "$/progress"
+            && message["params"]["token"] == token
+            && message["params"]["value"]["kind"] == kind
+    };
+    client.expect("the progress to begin", |message| {
+        progress(message, "begin")
+    });
+    client.expect("the progress to end", |message| progress(message, "end"));
+}
+
+#[test]
+fn recheck_progress_is_skipped_when_the_client_refuses_the_token() {
+    let (mut client, create) = recheck_started();
+    client.send(json!({
+        "jsonrpc": "2.0",
+        "id": create["id"],
+        "error": { "code": -32603, "message": "no progress here" }
+    }));
+    for n in 0..10 {
+        client.diagnostics(&format!("file:///dependent{n}.mylang"));
+    }
+    let unexpected = client.shutdown();
+    assert!(
+        !unexpected
+            .iter()
+            .any(|message| message["method"] == "$/progress"),
+        "{unexpected:?}"
+    );
+}
+
+#[test]
+fn closing_the_last_dependent_ends_the_recheck() {
+    let mut client = Client::start();
+    client.request(
+        "initialize",
+        json!({ "capabilities": { "window": { "workDoneProgress": true } } }),
+    );
+    client.notify("initialized", json!({}));
+    let dependent = "file:///dependent.mylang";
+    client.open(URI, "print(1);\n");
+    client.open(dependent, "print(2);\n");
+    client.diagnostics(URI);
+    client.diagnostics(dependent);
+
+    // In one write, so the close is waiting before the recheck gets to
+    // the dependent.
+    let frame = |message: Value| {
+        let body = message.to_string();
+        format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+    };
+    client.send_raw(&format!(
+        "{}{}",
+        frame(json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didSave",
+            "params": { "textDocument": { "uri": URI } }
+        })),
+        frame(json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didClose",
+            "params": { "textDocument": { "uri": dependent } }
+        }))
+    ));
+    let create = client.expect("a progress token", |message| {
+        message["method"] == "window/workDoneProgress/create"
+    });
+    client.send(json!({ "jsonrpc": "2.0", "id": create["id"], "result": null }));
+
+    // The server is still answering, and never began progress for a run
+    // that had already ended.
+    client.request("mylang/stats", json!({}));
+    let unexpected = client.shutdown();
+    assert!(
+        !unexpected
+            .iter()
+            .any(|message| message["params"]["value"]["kind"] == "begin"),
+        "{unexpected:?}"
+    );
+}
+
+#[test]
+fn cancelling_a_run_kills_its_process() {
+    let directory = workspace("run");
+    let path = directory.join("project/spin.mylang");
+    fs::write(
+        &path,
+        "let main -> map(range(0, 100000), fn x -> len(range(0, 100000)));\n",
+    )
+    .unwrap();
+    let uri = format!("file://{}", path.display());
+
+    let mut client = Client::spawn(&[]);
+    client.request("initialize", json!({ "capabilities": {} }));
+    client.notify("initialized", json!({}));
+    let log = |message: &Value, text: &str| {
+        message["method"] == "window/logMessage"
+            && message["params"]["message"]
+                .as_str()
+                .is_some_and(|message| message.ends_with(text))
+    };
+
+    client.send(json!({
+        "jsonrpc": "2.0",
+        "id": "run",
+        "method": "workspace/executeCommand",
+        "params": { "command": "mylang.run", "arguments": [uri, "main"] }
+    }));
+    client.expect("the run's response", |message| message["id"] == "run");
+    client.expect("the run to start", |message| {
+        log(message, "spin.mylang main")
+    });
+    client.notify("$/cancelRequest", json!({ "id": "run" }));
+    client.expect("the run to stop", |message| log(message, "was cancelled"));
+    client.shutdown();
+    let _ = fs::remove_dir_all(&directory);
+}
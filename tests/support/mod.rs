@@ -0,0 +1,288 @@
+//! A client that talks to the server over in-memory pipes, or to the server
+//! binary over its stdio, for end-to-end tests that need no editor. It also
+//! drives the debug adapter, which uses the same framing.
+
+#![allow(dead_code)]
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use mylang_lsp::analysis::diagnostics::Range;
+use mylang_lsp::analysis::line_index::LineIndex;
+use mylang_lsp::server;
+use serde_json::{Value, json};
+
+/// How long to wait for a message before failing the test.
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct Client {
+    input: Option<Box<dyn Write + Send>>,
+    messages: Receiver<Value>,
+    /// Messages that arrived while waiting for a different one.
+    backlog: VecDeque<Value>,
+    next_id: u64,
+    server: Option<JoinHandle<()>>,
+}
+
+impl Client {
+    /// Starts a server on its own thread.
+    pub fn start() -> Self {
+        let (server_input, input) = io::pipe().unwrap();
+        let (output, server_output) = io::pipe().unwrap();
+        let server = thread::spawn(move || {
+            server::serve(BufReader::new(server_input), server_output);
+        });
+        Self::connect(Box::new(input), output, server)
+    }
+
+    /// Starts the server binary with the environment variables `vars` set,
+    /// for settings only read from the process environment.
+    pub fn spawn(vars: &[(&str, &str)]) -> Self {
+        Self::spawn_with(&[], vars)
+    }
+
+    /// Starts the debug adapter, `mylang-lsp --dap`, which frames its
+    /// messages the same way.
+    pub fn debug_adapter() -> Self {
+        Self::spawn_with(&["--dap"], &[])
+    }
+
+    fn spawn_with(args: &[&str], vars: &[(&str, &str)]) -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_mylang-lsp"))
+            .args(args)
+            .envs(vars.iter().copied())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let input = child.stdin.take().unwrap();
+        let output = child.stdout.take().unwrap();
+        let server = thread::spawn(move || {
+            child.wait().unwrap();
+        });
+        Self::connect(Box::new(input), output, server)
+    }
+
+    fn connect(
+        input: Box<dyn Write + Send>,
+        output: impl Read + Send + 'static,
+        server: JoinHandle<()>,
+    ) -> Self {
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(output);
+            while let Some(message) = read_message(&mut reader) {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            input: Some(input),
+            messages,
+            backlog: VecDeque::new(),
+            next_id: 1,
+            server: Some(server),
+        }
+    }
+
+    /// Starts a server and completes the `initialize` handshake.
+    pub fn initialized(capabilities: Value) -> Self {
+        let mut client = Self::start();
+        client.request("initialize", json!({ "capabilities": capabilities }));
+        client.notify("initialized", json!({}));
+        client
+    }
+
+    pub fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    /// Sends a request and returns its result, failing the test if the
+    /// response is an error or does not arrive in time.
+    pub fn request(&mut self, method: &str, params: Value) -> Value {
+        let response = self.request_response(method, params);
+        if let Some(error) = response.get("error") {
+            panic!("{method} failed: {error}");
+        }
+        response["result"].clone()
+    }
+
+    /// Sends a request and returns the whole response.
+    pub fn request_response(&mut self, method: &str, params: Value) -> Value {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        self.expect(&format!("a response to {method}"), |message| {
+            message["id"] == id && message.get("method").is_none()
+        })
+    }
+
+    /// Waits for a notification with `method`.
+    pub fn notification(&mut self, method: &str) -> Value {
+        self.expect(method, |message| {
+            message["method"] == method && message.get("id").is_none()
+        })["params"]
+            .clone()
+    }
+
+    /// Waits for the diagnostics published for `uri`.
+    pub fn diagnostics(&mut self, uri: &str) -> Vec<Value> {
+        let params = self.expect(&format!("diagnostics for {uri}"), |message| {
+            message["method"] == "textDocument/publishDiagnostics"
+                && message["params"]["uri"] == uri
+        })["params"]
+            .clone();
+        params["diagnostics"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Opens `uri` with `text` as version 1.
+    pub fn open(&mut self, uri: &str, text: &str) {
+        self.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": { "uri": uri, "languageId": "mylang", "version": 1, "text": text }
+            }),
+        );
+    }
+
+    /// The next message from the server.
+    pub fn next_message(&mut self) -> Value {
+        self.expect("a message", |_| true)
+    }
+
+    /// The first message from the server that `matches`, keeping the others
+    /// for later calls.
+    pub fn expect(&mut self, what: &str, matches: impl Fn(&Value) -> bool) -> Value {
+        if let Some(position) = self.backlog.iter().position(&matches) {
+            return self.backlog.remove(position).unwrap();
+        }
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match self.messages.recv_timeout(timeout) {
+                Ok(message) if matches(&message) => return message,
+                Ok(message) => self.backlog.push_back(message),
+                Err(RecvTimeoutError::Timeout) => {
+                    panic!("timed out waiting for {what}; received {:?}", self.backlog)
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    panic!("the server exited before sending {what}")
+                }
+            }
+        }
+    }
+
+    /// What the server has sent so far that no call consumed, without
+    /// waiting for more.
+    pub fn received(&mut self) -> Vec<Value> {
+        self.backlog.extend(self.messages.try_iter());
+        self.backlog.iter().cloned().collect()
+    }
+
+    /// Closes the server's input and waits for it to exit, returning what it
+    /// sent that no call consumed.
+    pub fn shutdown(mut self) -> Vec<Value> {
+        self.input = None;
+        if let Some(server) = self.server.take() {
+            server.join().expect("the server panicked");
+        }
+        let mut rest: Vec<Value> = self.backlog.drain(..).collect();
+        rest.extend(self.messages.try_iter());
+        rest
+    }
+
+    /// Sends `message` with a Content-Length header.
+    pub fn send(&mut self, message: Value) {
+        let body = message.to_string();
+        self.send_raw(&format!("Content-Length: {}\r\n\r\n{}", body.len(), body));
+    }
+
+    /// Sends `bytes` as they are, framing included.
+    pub fn send_raw(&mut self, bytes: &str) {
+        self.send_bytes(bytes.as_bytes());
+    }
+
+    /// Sends `bytes` as they are, for messages that are not UTF-8.
+    pub fn send_bytes(&mut self, bytes: &[u8]) {
+        let input = self.input.as_mut().expect("the client was shut down");
+        input.write_all(bytes).unwrap();
+        input.flush().unwrap();
+    }
+
+    /// Waits for the server to exit with its input still open, as it does
+    /// once the input can no longer be read. Messages sent before then are
+    /// kept for later calls.
+    pub fn expect_exit(&mut self) {
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match self.messages.recv_timeout(timeout) {
+                Ok(message) => self.backlog.push_back(message),
+                Err(RecvTimeoutError::Timeout) => {
+                    panic!("timed out waiting for the server to exit")
+                }
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        // Ending the input is what stops the server.
+        self.input = None;
+    }
+}
+
+/// Applies `edits`, an array of `TextEdit`s that do not overlap, to `text`.
+pub fn apply(text: &str, edits: &Value) -> String {
+    let index = LineIndex::new(text);
+    let mut edits: Vec<(usize, usize, &str)> = edits
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|edit| {
+            let range: Range = serde_json::from_value(edit["range"].clone()).unwrap();
+            (
+                index.offset(&range.start),
+                index.offset(&range.end),
+                edit["newText"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    edits.sort_by_key(|(start, _, _)| std::cmp::Reverse(*start));
+    let mut text = text.to_string();
+    for (start, end, new_text) in edits {
+        text.replace_range(start..end, new_text);
+    }
+    text
+}
+
+fn read_message(reader: &mut impl BufRead) -> Option<Value> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim();
+        if let Some(value) = line.strip_prefix("Content-Length: ") {
+            length = value.parse().ok();
+        } else if line.is_empty() && length.is_some() {
+            break;
+        }
+    }
+    let mut body = vec![0; length?];
+    reader.read_exact(&mut body).ok()?;
+    serde_json::from_slice(&body).ok()
+}